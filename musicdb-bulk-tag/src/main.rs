@@ -0,0 +1,166 @@
+use std::{
+    io::Write,
+    net::{SocketAddr, TcpStream},
+};
+
+use clap::{Parser, Subcommand};
+use musicdb_lib::{
+    data::{
+        database::Database,
+        filter::{Filter, FilterType},
+        song::Song,
+    },
+    load::ToFromBytes,
+    server::{Action, Command},
+};
+
+/// Changes the tags of all songs matching a filter, without the gui.
+#[derive(Parser)]
+struct Args {
+    addr: SocketAddr,
+    /// only change songs whose tags match this. if used multiple times, all have to match.
+    ///
+    /// `Fav` (has the tag `Fav`), `Genre=*` (has a tag starting with `Genre=`),
+    /// `Year>=2000`, `Year<1990`, `Year=1990..1999` (numbers, both ends inclusive),
+    /// `!...` (doesn't match ...)
+    #[arg(long = "where", value_name = "FILTER", value_parser = parse_filter)]
+    filters: Vec<FilterType>,
+    /// only print the ids of the songs which would be changed
+    #[arg(long)]
+    dry_run: bool,
+    #[command(subcommand)]
+    op: Op,
+}
+#[derive(Subcommand)]
+enum Op {
+    SetFlag {
+        tag: String,
+    },
+    UnsetFlag {
+        tag: String,
+    },
+    /// sets `KEY=VALUE`, replacing any other `KEY=` tag
+    SetProperty {
+        key: String,
+        value: String,
+    },
+    /// removes all `KEY=` tags
+    UnsetProperty {
+        key: String,
+    },
+}
+
+fn main() {
+    let args = Args::parse();
+    let filter = Filter {
+        and: true,
+        filters: args.filters,
+    };
+    let mut con = TcpStream::connect(args.addr).unwrap();
+    writeln!(con, "main").unwrap();
+    let mut db = Database::new_clientside();
+    while !db.is_client_init() {
+        let action = db.seq.recv(Command::from_bytes(&mut con).unwrap());
+        db.apply_action_unchecked_seq(action, None);
+    }
+    let mut songs = db
+        .songs()
+        .values()
+        .filter(|song| filter.passes(&song.general))
+        .filter_map(|song| args.op.action(song).map(|action| (song, action)))
+        .collect::<Vec<_>>();
+    songs.sort_by_key(|(song, _)| song.id);
+    if args.dry_run {
+        for (song, _) in &songs {
+            println!("{}", song.id);
+        }
+        eprintln!("Would change {} songs.", songs.len());
+        return;
+    }
+    eprintln!("Changing {} songs.", songs.len());
+    if !songs.is_empty() {
+        Action::Multiple(songs.into_iter().map(|(_, action)| action).collect())
+            .cmd(0xFFu8)
+            .to_bytes(&mut con)
+            .unwrap();
+    }
+}
+
+impl Op {
+    /// the action to perform on this song, or `None` if it wouldn't change anything
+    fn action(&self, song: &Song) -> Option<Action> {
+        let tags = &song.general.tags;
+        match self {
+            Self::SetFlag { tag } => {
+                (!tags.contains(tag)).then(|| Action::TagSongFlagSet(song.id, tag.clone()))
+            }
+            Self::UnsetFlag { tag } => tags
+                .contains(tag)
+                .then(|| Action::TagSongFlagUnset(song.id, tag.clone())),
+            Self::SetProperty { key, value } => {
+                let key = property_key(key);
+                let tag = format!("{key}{value}");
+                tags.iter()
+                    .filter(|t| t.starts_with(&key))
+                    .ne(std::iter::once(&tag))
+                    .then(|| Action::TagSongPropertySet(song.id, key, value.clone()))
+            }
+            Self::UnsetProperty { key } => {
+                let key = property_key(key);
+                tags.iter()
+                    .any(|t| t.starts_with(&key))
+                    .then(|| Action::TagSongPropertyUnset(song.id, key))
+            }
+        }
+    }
+}
+
+/// `Year` and `Year=` both mean the `Year=` property
+fn property_key(key: &str) -> String {
+    if key.ends_with('=') {
+        key.to_owned()
+    } else {
+        format!("{key}=")
+    }
+}
+
+fn parse_filter(s: &str) -> Result<FilterType, String> {
+    if let Some(s) = s.strip_prefix('!') {
+        return Ok(FilterType::Not(Filter {
+            and: true,
+            filters: vec![parse_filter(s)?],
+        }));
+    }
+    let int = |v: &str| {
+        v.trim()
+            .parse::<i32>()
+            .map_err(|e| format!("{v:?} isn't a number: {e}"))
+    };
+    for op in [">=", "<=", ">", "<"] {
+        if let Some((key, val)) = s.split_once(op) {
+            let key = property_key(key);
+            let val = int(val)?;
+            return Ok(match op {
+                ">=" => FilterType::TagWithValueInt(key, val, i32::MAX),
+                "<=" => FilterType::TagWithValueInt(key, i32::MIN, val),
+                ">" => FilterType::TagWithValueInt(key, val.saturating_add(1), i32::MAX),
+                _ => FilterType::TagWithValueInt(key, i32::MIN, val.saturating_sub(1)),
+            });
+        }
+    }
+    if let Some((key, (min, max))) = s
+        .split_once('=')
+        .and_then(|(key, range)| Some((key, range.split_once("..")?)))
+    {
+        return Ok(FilterType::TagWithValueInt(
+            property_key(key),
+            int(min)?,
+            int(max)?,
+        ));
+    }
+    if let Some(prefix) = s.strip_suffix('*') {
+        Ok(FilterType::TagStartsWith(prefix.to_owned()))
+    } else {
+        Ok(FilterType::TagEq(s.to_owned()))
+    }
+}
@@ -1,7 +1,7 @@
 use std::{
     any::Any,
     collections::{BTreeMap, HashMap},
-    io::Cursor,
+    io::{Cursor, Write},
     net::TcpStream,
     sync::{mpsc::Sender, Arc, Mutex},
     thread::JoinHandle,
@@ -40,6 +40,7 @@ use crate::{
     gui_screen::GuiScreen,
     gui_song_adder::SongAdder,
     gui_text::Label,
+    gui_undo::QueueUndo,
     textcfg,
 };
 
@@ -49,38 +50,11 @@ pub enum GuiEvent {
     RefreshMers,
     UpdatedQueue,
     UpdatedLibrary,
+    /// like `UpdatedLibrary`, but the entire database was replaced
+    SyncedDatabase,
     Exit,
 }
 
-pub fn hotkey_deselect_all(modifiers: &ModifiersState, key: Option<VirtualKeyCode>) -> bool {
-    !modifiers.logo()
-        && !modifiers.alt()
-        && modifiers.ctrl()
-        && !modifiers.shift()
-        && matches!(key, Some(VirtualKeyCode::S))
-}
-pub fn hotkey_select_all(modifiers: &ModifiersState, key: Option<VirtualKeyCode>) -> bool {
-    !modifiers.logo()
-        && !modifiers.alt()
-        && modifiers.ctrl()
-        && !modifiers.shift()
-        && matches!(key, Some(VirtualKeyCode::A))
-}
-pub fn hotkey_select_albums(modifiers: &ModifiersState, key: Option<VirtualKeyCode>) -> bool {
-    !modifiers.logo()
-        && !modifiers.alt()
-        && modifiers.ctrl()
-        && modifiers.shift()
-        && matches!(key, Some(VirtualKeyCode::A))
-}
-pub fn hotkey_select_songs(modifiers: &ModifiersState, key: Option<VirtualKeyCode>) -> bool {
-    !modifiers.logo()
-        && !modifiers.alt()
-        && modifiers.ctrl()
-        && modifiers.shift()
-        && matches!(key, Some(VirtualKeyCode::S))
-}
-
 pub fn main(
     database: Arc<Mutex<Database>>,
     connection: TcpStream,
@@ -97,10 +71,28 @@ pub fn main(
     let mut scroll_pixels_multiplier = 1.0;
     let mut scroll_lines_multiplier = 3.0;
     let mut scroll_pages_multiplier = 0.75;
+    let mut idle_fullscreen_cover = false;
     let status_bar_text;
     let idle_top_text;
     let idle_side1_text;
     let idle_side2_text;
+    let mut hotkeys = Hotkeys::default();
+    let mut colors = Colors::default();
+    let default_filter_presets = || {
+        vec![
+            (
+                "Fav".to_owned(),
+                crate::gui_library::FilterType::TagEq("Fav".to_owned()),
+            ),
+            (
+                "Year".to_owned(),
+                crate::gui_library::FilterType::TagWithValueInt("Year=".to_owned(), 1990, 2000),
+            ),
+        ]
+    };
+    let mut filter_presets_song = default_filter_presets();
+    let mut filter_presets_album = default_filter_presets();
+    let mut filter_presets_artist = default_filter_presets();
     match std::fs::read_to_string(&config_file) {
         Ok(cfg) => {
             if let Ok(table) = cfg.parse::<toml::Table>() {
@@ -136,6 +128,9 @@ pub fn main(
                 {
                     scroll_pages_multiplier = v;
                 }
+                if let Some(v) = table.get("idle_fullscreen_cover").and_then(|v| v.as_bool()) {
+                    idle_fullscreen_cover = v;
+                }
                 if let Some(t) = table.get("text").and_then(|v| v.as_table()) {
                     if let Some(v) = t.get("status_bar").and_then(|v| v.as_str()) {
                         match v.parse() {
@@ -189,6 +184,23 @@ pub fn main(
                     eprintln!("[toml] missing the required `[text]` section!");
                     std::process::exit(30);
                 }
+                if let Some(t) = table.get("hotkeys").and_then(|v| v.as_table()) {
+                    hotkeys.load(t);
+                }
+                if let Some(t) = table.get("colors").and_then(|v| v.as_table()) {
+                    colors.load(t);
+                }
+                if let Some(t) = table.get("filter_presets").and_then(|v| v.as_table()) {
+                    for (name, presets) in [
+                        ("song", &mut filter_presets_song),
+                        ("album", &mut filter_presets_album),
+                        ("artist", &mut filter_presets_artist),
+                    ] {
+                        if let Some(v) = t.get(name) {
+                            load_filter_presets(name, v, presets);
+                        }
+                    }
+                }
             } else {
                 eprintln!("Couldn't parse config file {config_file:?} as toml!");
                 std::process::exit(30);
@@ -215,7 +227,11 @@ pub fn main(
     let window = speedy2d::Window::<GuiEvent>::new_with_user_events(
         "MusicDB Client",
         WindowCreationOptions::new_windowed(
-            speedy2d::window::WindowSize::MarginPhysicalPixels(0),
+            if let Some(size) = load_window_size() {
+                speedy2d::window::WindowSize::PhysicalPixels(size)
+            } else {
+                speedy2d::window::WindowSize::MarginPhysicalPixels(0)
+            },
             None,
         ),
     )
@@ -238,36 +254,12 @@ pub fn main(
             idle_top_text,
             idle_side1_text,
             idle_side2_text,
-            filter_presets_song: vec![
-                (
-                    "Fav".to_owned(),
-                    crate::gui_library::FilterType::TagEq("Fav".to_owned()),
-                ),
-                (
-                    "Year".to_owned(),
-                    crate::gui_library::FilterType::TagWithValueInt("Year=".to_owned(), 1990, 2000),
-                ),
-            ],
-            filter_presets_album: vec![
-                (
-                    "Fav".to_owned(),
-                    crate::gui_library::FilterType::TagEq("Fav".to_owned()),
-                ),
-                (
-                    "Year".to_owned(),
-                    crate::gui_library::FilterType::TagWithValueInt("Year=".to_owned(), 1990, 2000),
-                ),
-            ],
-            filter_presets_artist: vec![
-                (
-                    "Fav".to_owned(),
-                    crate::gui_library::FilterType::TagEq("Fav".to_owned()),
-                ),
-                (
-                    "Year".to_owned(),
-                    crate::gui_library::FilterType::TagWithValueInt("Year=".to_owned(), 1990, 2000),
-                ),
-            ],
+            idle_fullscreen_cover,
+            filter_presets_song,
+            filter_presets_album,
+            filter_presets_artist,
+            hotkeys,
+            colors,
             #[cfg(feature = "merscfg")]
             merscfg: crate::merscfg::MersCfg::new(config_dir.join("dynamic_config.mers"), database),
         },
@@ -276,17 +268,96 @@ pub fn main(
     ));
 }
 
+/// `[[filter_presets.<song|album|artist>]]` entries with a `name` and a `filter`,
+/// see `gui_library::filter_type_from_toml` for the filter syntax.
+fn load_filter_presets(
+    kind: &str,
+    value: &toml::Value,
+    presets: &mut Vec<(String, crate::gui_library::FilterType)>,
+) {
+    let Some(list) = value.as_array() else {
+        eprintln!("[toml] `filter_presets.{kind}` must be an array of tables");
+        return;
+    };
+    for preset in list {
+        let name = preset.get("name").and_then(|v| v.as_str());
+        let filter = preset
+            .get("filter")
+            .ok_or_else(|| "missing `filter`".to_owned())
+            .and_then(crate::gui_library::filter_type_from_toml);
+        match (name, filter) {
+            (Some(name), Ok(filter)) => presets.push((name.to_owned(), filter)),
+            (None, _) => eprintln!("[toml] `filter_presets.{kind}`: preset without a `name`"),
+            (Some(name), Err(e)) => eprintln!("[toml] `filter_presets.{kind}` `{name}`: {e}"),
+        }
+    }
+}
+
+fn window_size_file() -> std::path::PathBuf {
+    super::get_config_file_path().join("window_size")
+}
+/// the window size saved by `save_window_size`, `<width>x<height>`
+fn load_window_size() -> Option<UVec2> {
+    let s = std::fs::read_to_string(window_size_file()).ok()?;
+    let (w, h) = s.trim().split_once('x')?;
+    let (w, h) = (w.parse().ok()?, h.parse().ok()?);
+    if w > 0 && h > 0 {
+        Some(UVec2::new(w, h))
+    } else {
+        None
+    }
+}
+fn save_window_size(size: UVec2) {
+    if let Err(e) = std::fs::write(window_size_file(), format!("{}x{}", size.x, size.y)) {
+        eprintln!("[warn] couldn't save window size: {e}");
+    }
+}
+
 pub struct GuiConfig {
     pub status_bar_text: textcfg::TextBuilder,
     pub idle_top_text: textcfg::TextBuilder,
     pub idle_side1_text: textcfg::TextBuilder,
     pub idle_side2_text: textcfg::TextBuilder,
+    /// show the cover as the background of the idle display
+    pub idle_fullscreen_cover: bool,
     pub filter_presets_song: Vec<(String, crate::gui_library::FilterType)>,
     pub filter_presets_album: Vec<(String, crate::gui_library::FilterType)>,
     pub filter_presets_artist: Vec<(String, crate::gui_library::FilterType)>,
+    pub hotkeys: Hotkeys,
+    pub colors: Colors,
     #[cfg(feature = "merscfg")]
     pub merscfg: crate::merscfg::MersCfg,
 }
+impl GuiConfig {
+    /// adds a filter preset for songs (0), albums (1) or artists (2)
+    /// and appends it to `config_gui.toml` so it is still there after a restart.
+    pub fn add_filter_preset(
+        &mut self,
+        tab: usize,
+        name: String,
+        filter: crate::gui_library::FilterType,
+    ) {
+        let (kind, presets) = match tab {
+            2 => ("artist", &mut self.filter_presets_artist),
+            1 => ("album", &mut self.filter_presets_album),
+            _ => ("song", &mut self.filter_presets_song),
+        };
+        let entry = format!(
+            "\n[[filter_presets.{kind}]]\nname = {}\nfilter = {}\n",
+            toml::Value::String(name.clone()),
+            crate::gui_library::filter_type_to_toml(&filter),
+        );
+        presets.push((name, filter));
+        let config_file = super::get_config_file_path().join("config_gui.toml");
+        if let Err(e) = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&config_file)
+            .and_then(|mut file| file.write_all(entry.as_bytes()))
+        {
+            eprintln!("[warn] couldn't save filter preset to {config_file:?}: {e}");
+        }
+    }
+}
 
 pub struct Gui {
     pub event_sender: Arc<UserEventSender<GuiEvent>>,
@@ -302,6 +373,7 @@ pub struct Gui {
     pub keybinds: BTreeMap<KeyBinding, KeyActionRef>,
     pub key_actions: KeyActions,
     pub covers: Option<HashMap<CoverId, GuiServerImage>>,
+    pub thumbnails: Option<HashMap<CoverId, GuiServerImage>>,
     pub custom_images: Option<HashMap<String, GuiServerImage>>,
     pub modifiers: ModifiersState,
     pub dragging: Option<(
@@ -316,6 +388,13 @@ pub struct Gui {
     pub scroll_pages_multiplier: f64,
     pub gui_config: Option<GuiConfig>,
     last_performance_check: Instant,
+    /// set on resize, the size is saved once the window hasn't been resized for a second
+    window_resized: Option<Instant>,
+    pub queue_undo: QueueUndo,
+    #[cfg(feature = "mpris")]
+    mpris: Option<crate::mpris::Mpris>,
+    #[cfg(feature = "tray")]
+    tray: Option<crate::tray::Tray>,
     average_frame_time_ms: u32,
     frames_drawn: u32,
 }
@@ -381,25 +460,44 @@ impl Gui {
                             Action::Resume
                             | Action::Pause
                             | Action::Stop
+                            | Action::StopAfterCurrentSong(_)
+                            | Action::SetVolume(_)
+                            | Action::SetMuted(_)
+                            | Action::SetCurrentSongPosition(_)
+                            | Action::PlaybackPosition(_)
+                            | Action::SetCacheConfig { .. }
+                            | Action::SetCachePinned(..)
                             | Action::Save
+                            | Action::ReloadDatabase
                             | Action::InitComplete => {}
                             Action::NextSong
+                            | Action::PreviousSong
                             | Action::QueueUpdate(..)
                             | Action::QueueAdd(..)
+                            | Action::QueueReplace(..)
                             | Action::QueueInsert(..)
+                            | Action::QueueInsertAfterCurrent(..)
                             | Action::QueueRemove(..)
                             | Action::QueueMove(..)
                             | Action::QueueMoveInto(..)
+                            | Action::QueueMoveMultiple(..)
                             | Action::QueueGoto(..)
+                            | Action::PlayFrom(..)
                             | Action::QueueShuffle(..)
                             | Action::QueueSetShuffle(..)
-                            | Action::QueueUnshuffle(..) => {
+                            | Action::QueueUnshuffle(..)
+                            | Action::QueueSetRepeat(..)
+                            | Action::SetCurrentSongFormat(_) => {
                                 if let Some(s) = &*event_sender_arc.lock().unwrap() {
                                     _ = s.send_event(GuiEvent::UpdatedQueue);
                                 }
                             }
-                            Action::SyncDatabase(..)
-                            | Action::AddSong(_, _)
+                            Action::SyncDatabase(..) => {
+                                if let Some(s) = &*event_sender_arc.lock().unwrap() {
+                                    _ = s.send_event(GuiEvent::SyncedDatabase);
+                                }
+                            }
+                            Action::AddSong(_, _)
                             | Action::AddAlbum(_, _)
                             | Action::AddArtist(_, _)
                             | Action::AddCover(_, _)
@@ -421,7 +519,14 @@ impl Gui {
                             | Action::TagAlbumPropertyUnset(..)
                             | Action::TagArtistPropertySet(..)
                             | Action::TagArtistPropertyUnset(..)
-                            | Action::SetSongDuration(..) => {
+                            | Action::SetSongRating(..)
+                            | Action::SetSongGain(..)
+                            | Action::SetArtistAliases(..)
+                            | Action::SavePlaylist(..)
+                            | Action::RenamePlaylist(..)
+                            | Action::RemovePlaylist(..)
+                            | Action::SetSongDuration(..)
+                            | Action::SongPlayed(..) => {
                                 if let Some(s) = &*event_sender_arc.lock().unwrap() {
                                     _ = s.send_event(GuiEvent::UpdatedLibrary);
                                 }
@@ -495,6 +600,18 @@ impl Gui {
             ));
         }
         let no_animations = false;
+        #[cfg(feature = "mpris")]
+        let mpris = crate::mpris::Mpris::start(
+            Arc::clone(&database),
+            &connection,
+            Arc::clone(&event_sender),
+        );
+        #[cfg(feature = "tray")]
+        let tray = crate::tray::Tray::start(
+            Arc::clone(&database),
+            &connection,
+            Arc::clone(&event_sender),
+        );
         Gui {
             event_sender,
             database,
@@ -516,6 +633,7 @@ impl Gui {
             keybinds: BTreeMap::new(),
             key_actions: KeyActions::default(),
             covers: Some(HashMap::new()),
+            thumbnails: Some(HashMap::new()),
             custom_images: Some(HashMap::new()),
             // font: Font::new(include_bytes!("/usr/share/fonts/TTF/FiraSans-Regular.ttf")).unwrap(),
             modifiers: ModifiersState::default(),
@@ -528,11 +646,29 @@ impl Gui {
             scroll_pages_multiplier,
             gui_config: Some(gui_config),
             last_performance_check: Instant::now(),
+            window_resized: None,
+            queue_undo: QueueUndo::default(),
+            #[cfg(feature = "mpris")]
+            mpris,
+            #[cfg(feature = "tray")]
+            tray,
             average_frame_time_ms: 0,
             frames_drawn: 0,
         }
     }
 
+    /// sends the inverse of the last queue action sent by the gui, if possible
+    pub fn undo_queue_action(&mut self) {
+        let db = self.database.lock().unwrap();
+        if let Some(action) = self.queue_undo.undo(&db) {
+            let command = db.seq.pack(action);
+            drop(db);
+            if let Err(e) = command.to_bytes(&mut self.connection) {
+                eprintln!("Error sending command to server: {e}");
+            }
+        }
+    }
+
     fn get_specific_gui_elem_config(&mut self, elem: SpecificGuiElem) -> &mut GuiElemCfg {
         match elem {
             SpecificGuiElem::SearchArtist => self
@@ -1126,6 +1262,26 @@ impl<A: GuiElem, B: GuiElem, C: GuiElem, D: GuiElem, E: GuiElem> GuiElemChildren
         5
     }
 }
+impl<A: GuiElem, B: GuiElem, C: GuiElem, D: GuiElem, E: GuiElem, F: GuiElem> GuiElemChildren
+    for (A, B, C, D, E, F)
+{
+    fn iter(&mut self) -> Box<dyn Iterator<Item = &mut dyn GuiElem> + '_> {
+        Box::new(
+            [
+                self.0.elem_mut(),
+                self.1.elem_mut(),
+                self.2.elem_mut(),
+                self.3.elem_mut(),
+                self.4.elem_mut(),
+                self.5.elem_mut(),
+            ]
+            .into_iter(),
+        )
+    }
+    fn len(&self) -> usize {
+        6
+    }
+}
 
 #[derive(Debug, Clone)]
 /// The config for any gui element.
@@ -1292,6 +1448,8 @@ pub struct DrawInfo<'a> {
     pub helper: Option<&'a mut WindowHelper<GuiEvent>>,
     pub get_con: Arc<Mutex<get::Client<Box<dyn ClientIo + 'static>>>>,
     pub covers: &'a mut HashMap<CoverId, GuiServerImage>,
+    /// small versions of the covers, for places where covers are only shown at the size of a few lines of text
+    pub thumbnails: &'a mut HashMap<CoverId, GuiServerImage>,
     pub custom_images: &'a mut HashMap<String, GuiServerImage>,
     pub has_keyboard_focus: bool,
     pub child_has_keyboard_focus: bool,
@@ -1311,6 +1469,20 @@ pub fn adjust_area(outer: &Rectangle, rel_area: &Rectangle) -> Rectangle {
         adjust_pos(outer, rel_area.bottom_right()),
     )
 }
+/// formats a duration in milliseconds as `m:ss` or `h:mm:ss`
+pub fn format_duration(millis: u64) -> String {
+    let secs = millis / 1000;
+    if secs >= 60 * 60 {
+        format!(
+            "{}:{:0>2}:{:0>2}",
+            secs / (60 * 60),
+            (secs / 60) % 60,
+            secs % 60
+        )
+    } else {
+        format!("{}:{:0>2}", secs / 60, secs % 60)
+    }
+}
 pub fn adjust_pos(outer: &Rectangle, rel_pos: &Vec2) -> Vec2 {
     Vec2::new(
         outer.top_left().x + outer.width() * rel_pos.x,
@@ -1355,7 +1527,10 @@ impl Gui {
                 }
             }
             GuiAction::SendToServer(action) => {
-                let command = self.database.lock().unwrap().seq.pack(action);
+                let db = self.database.lock().unwrap();
+                self.queue_undo.record(&action, &db);
+                let command = db.seq.pack(action);
+                drop(db);
                 #[cfg(debug_assertions)]
                 eprintln!("[DEBUG] Sending command to server: {command:?}");
                 if let Err(e) = command.to_bytes(&mut self.connection) {
@@ -1462,17 +1637,18 @@ impl Gui {
 impl WindowHandler<GuiEvent> for Gui {
     fn on_draw(&mut self, helper: &mut WindowHelper<GuiEvent>, graphics: &mut Graphics2D) {
         let draw_start_time = Instant::now();
+        let mut cfg = self.gui_config.take().unwrap();
         graphics.draw_rectangle(
             Rectangle::new(Vec2::ZERO, self.size.into_f32()),
-            Color::BLACK,
+            cfg.colors.background,
         );
-        let mut cfg = self.gui_config.take().unwrap();
         // before the db is locked!
         #[cfg(feature = "merscfg")]
         MersCfg::run(&mut cfg, self, |m| &m.func_before_draw);
         let dblock = Arc::clone(&self.database);
         let mut dblock = dblock.lock().unwrap();
         let mut covers = self.covers.take().unwrap();
+        let mut thumbnails = self.thumbnails.take().unwrap();
         let mut custom_images = self.custom_images.take().unwrap();
         let mut info = DrawInfo {
             time: draw_start_time,
@@ -1484,6 +1660,7 @@ impl WindowHandler<GuiEvent> for Gui {
             mouse_pos_in_bounds: false,
             get_con: Arc::clone(&self.get_con),
             covers: &mut covers,
+            thumbnails: &mut thumbnails,
             custom_images: &mut custom_images,
             helper: Some(helper),
             has_keyboard_focus: false,
@@ -1533,6 +1710,7 @@ impl WindowHandler<GuiEvent> for Gui {
         drop(info);
         self.gui_config = Some(cfg);
         self.covers = Some(covers);
+        self.thumbnails = Some(thumbnails);
         self.custom_images = Some(custom_images);
         drop(dblock);
         for a in actions {
@@ -1554,6 +1732,13 @@ impl WindowHandler<GuiEvent> for Gui {
                 .content
                 .text() = "On due to\nbad performance".to_string();
         }
+        if self
+            .window_resized
+            .is_some_and(|t| draw_start_time.duration_since(t).as_secs() >= 1)
+        {
+            self.window_resized = None;
+            save_window_size(self.size);
+        }
         #[cfg(debug_assertions)]
         {
             self.frames_drawn += 1;
@@ -1777,7 +1962,17 @@ impl WindowHandler<GuiEvent> for Gui {
     }
     fn on_user_event(&mut self, helper: &mut WindowHelper<GuiEvent>, user_event: GuiEvent) {
         match user_event {
-            GuiEvent::Refresh => helper.request_redraw(),
+            GuiEvent::Refresh => {
+                #[cfg(feature = "mpris")]
+                if let Some(mpris) = &mut self.mpris {
+                    mpris.update();
+                }
+                #[cfg(feature = "tray")]
+                if let Some(tray) = &mut self.tray {
+                    tray.update();
+                }
+                helper.request_redraw()
+            }
             #[cfg(feature = "merscfg")]
             GuiEvent::RefreshMers => {
                 if let Some(mut cfg) = self.gui_config.take() {
@@ -1804,10 +1999,28 @@ impl WindowHandler<GuiEvent> for Gui {
                 } else {
                     eprintln!("WARN: Skipping call to merscfg's queue_updated because gui_config is not available");
                 }
+                #[cfg(feature = "mpris")]
+                if let Some(mpris) = &mut self.mpris {
+                    mpris.update();
+                }
                 self.gui._recursive_all(true, &mut |e| e.updated_queue());
                 helper.request_redraw();
             }
-            GuiEvent::Exit => helper.terminate_loop(),
+            GuiEvent::SyncedDatabase => {
+                self.queue_undo.clear();
+                self.on_user_event(helper, GuiEvent::UpdatedLibrary);
+            }
+            GuiEvent::Exit => {
+                if self.window_resized.take().is_some() {
+                    save_window_size(self.size);
+                }
+                self.gui
+                    .c_main_view
+                    .children
+                    .library_browser
+                    .save_search_state();
+                helper.terminate_loop()
+            }
         }
     }
     fn on_mouse_move(&mut self, helper: &mut WindowHelper<GuiEvent>, position: Vec2) {
@@ -1815,6 +2028,10 @@ impl WindowHandler<GuiEvent> for Gui {
         helper.request_redraw();
     }
     fn on_resize(&mut self, _helper: &mut WindowHelper<GuiEvent>, size_pixels: UVec2) {
+        // don't save the compact mode's window size
+        if self.size != size_pixels && self.size != UVec2::ZERO && self.gui.compact.is_none() {
+            self.window_resized = Some(Instant::now());
+        }
         self.size = size_pixels;
         self.gui
             ._recursive_all(true, &mut |e| e.config_mut().redraw = true);
@@ -1841,6 +2058,20 @@ impl GuiServerImage {
                 .and_then(|v| v.ok())
         }))
     }
+    /// like `new_cover`, but loads the cover's thumbnail, or the full cover if the server can't create thumbnails
+    pub fn new_cover_thumbnail<T: ClientIo + 'static>(
+        id: CoverId,
+        get_con: Arc<Mutex<get::Client<T>>>,
+    ) -> Self {
+        Self::Loading(std::thread::spawn(move || {
+            let mut get_con = get_con.lock().unwrap();
+            match get_con.cover_thumbnail(id) {
+                Ok(Ok(bytes)) => Some(bytes),
+                Ok(Err(_)) => get_con.cover_bytes(id).ok().and_then(|v| v.ok()),
+                Err(_) => None,
+            }
+        }))
+    }
     pub fn new_custom_file<T: ClientIo + 'static>(
         file: String,
         get_con: Arc<Mutex<get::Client<T>>>,
@@ -1919,6 +2150,9 @@ impl KeyBinding {
             key,
         }
     }
+    pub fn key(key: VirtualKeyCode) -> Self {
+        Self { modifiers: 0, key }
+    }
     pub fn ctrl(key: VirtualKeyCode) -> Self {
         Self {
             modifiers: Self::CTRL,
@@ -1943,6 +2177,206 @@ impl KeyBinding {
     pub fn get_meta(&self) -> bool {
         self.modifiers & Self::META != 0
     }
+    pub fn matches(&self, modifiers: &ModifiersState, key: Option<VirtualKeyCode>) -> bool {
+        key.is_some_and(|key| Self::new(modifiers, key) == *self)
+    }
+    /// Parses something like `ctrl+shift+A` or `PageDown`. Key names are the names of the `VirtualKeyCode` variants.
+    /// An empty string or `none` means no keybinding.
+    pub fn parse(s: &str) -> Result<Option<Self>, String> {
+        let s = s.trim();
+        if s.is_empty() || s.eq_ignore_ascii_case("none") {
+            return Ok(None);
+        }
+        let mut parts = s.split('+').map(|v| v.trim()).collect::<Vec<_>>();
+        let key = parts.pop().unwrap_or_default();
+        let mut modifiers = 0;
+        for m in parts {
+            modifiers |= match m.to_lowercase().as_str() {
+                "ctrl" | "control" => Self::CTRL,
+                "alt" => Self::ALT,
+                "shift" => Self::SHIFT,
+                "meta" | "super" | "logo" => Self::META,
+                _ => return Err(format!("unknown modifier `{m}`")),
+            };
+        }
+        match parse_key(key) {
+            Some(key) => Ok(Some(Self { modifiers, key })),
+            None => Err(format!("unknown key `{key}`")),
+        }
+    }
+}
+fn parse_key(key: &str) -> Option<VirtualKeyCode> {
+    macro_rules! keys {
+        ($($k:ident)*) => {
+            match key {
+                $(stringify!($k) => Some(VirtualKeyCode::$k),)*
+                _ => None,
+            }
+        };
+    }
+    if let [c @ b'0'..=b'9'] = key.as_bytes() {
+        return parse_key(&format!("Key{}", *c as char));
+    }
+    if let [c @ b'a'..=b'z'] = key.as_bytes() {
+        return parse_key(&c.to_ascii_uppercase().to_string());
+    }
+    keys!(
+        Key1 Key2 Key3 Key4 Key5 Key6 Key7 Key8 Key9 Key0
+        A B C D E F G H I J K L M N O P Q R S T U V W X Y Z
+        F1 F2 F3 F4 F5 F6 F7 F8 F9 F10 F11 F12
+        Escape Insert Home Delete End PageDown PageUp Left Up Right Down
+        Backspace Return Space Tab
+        Numpad0 Numpad1 Numpad2 Numpad3 Numpad4 Numpad5 Numpad6 Numpad7 Numpad8 Numpad9
+        NumpadAdd NumpadSubtract NumpadMultiply NumpadDivide NumpadEnter
+        Apostrophe Backslash Comma Equals Grave LBracket RBracket Minus Period Semicolon Slash Plus
+        PlayPause MediaStop NextTrack PrevTrack Mute VolumeUp VolumeDown
+    )
+}
+/// Colors which can be changed in the `[colors]` section of `config_gui.toml`.
+#[derive(Clone, Copy)]
+pub struct Colors {
+    /// the window's background
+    pub background: Color,
+    pub artist: Color,
+    pub album: Color,
+    pub song: Color,
+    /// drawn over selected artists, albums and songs in the library
+    pub selection: Color,
+}
+impl Default for Colors {
+    fn default() -> Self {
+        Self {
+            background: Color::BLACK,
+            artist: Color::from_int_rgb(81, 24, 125),
+            album: Color::from_int_rgb(8, 61, 47),
+            song: Color::from_int_rgb(175, 175, 175),
+            selection: Color::from_rgba(1.0, 1.0, 1.0, 0.2),
+        }
+    }
+}
+impl Colors {
+    /// overwrites the colors which are set in the table, invalid entries are ignored (with a warning)
+    pub fn load(&mut self, table: &toml::Table) {
+        for (name, value) in table {
+            let color = match name.as_str() {
+                "background" => &mut self.background,
+                "artist" => &mut self.artist,
+                "album" => &mut self.album,
+                "song" => &mut self.song,
+                "selection" => &mut self.selection,
+                _ => {
+                    eprintln!("[toml] unknown color `colors.{name}`");
+                    continue;
+                }
+            };
+            match value.as_str().map(parse_color) {
+                Some(Some(v)) => *color = v,
+                Some(None) => eprintln!(
+                    "[toml] `colors.{name}` must be a hex color like 'RRGGBB' or 'RRGGBBAA'"
+                ),
+                None => eprintln!("[toml] `colors.{name}` must be a string"),
+            }
+        }
+    }
+}
+/// `RRGGBB` or `RRGGBBAA`, optionally starting with a `#`
+fn parse_color(s: &str) -> Option<Color> {
+    let s = s.trim().trim_start_matches('#');
+    if !s.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let v = u32::from_str_radix(s, 16).ok()?;
+    match s.len() {
+        6 => Some(Color::from_hex_rgb(v)),
+        8 => Some(Color::from_hex_argb(v.rotate_right(8))),
+        _ => None,
+    }
+}
+/// Keybindings which can be changed in the `[hotkeys]` section of `config_gui.toml`.
+/// `None` means the action has no keybinding.
+#[derive(Clone, Copy)]
+pub struct Hotkeys {
+    pub quit: Option<KeyBinding>,
+    pub idle: Option<KeyBinding>,
+    pub compact: Option<KeyBinding>,
+    pub repeat: Option<KeyBinding>,
+    pub search_songs: Option<KeyBinding>,
+    pub undo: Option<KeyBinding>,
+    pub play_pause: Option<KeyBinding>,
+    pub stop: Option<KeyBinding>,
+    pub next_song: Option<KeyBinding>,
+    pub next_song_alt: Option<KeyBinding>,
+    pub restart_song: Option<KeyBinding>,
+    pub restart_song_alt: Option<KeyBinding>,
+    pub seek_forward: Option<KeyBinding>,
+    pub seek_backward: Option<KeyBinding>,
+    pub deselect_all: Option<KeyBinding>,
+    pub select_all: Option<KeyBinding>,
+    pub select_albums: Option<KeyBinding>,
+    pub select_songs: Option<KeyBinding>,
+}
+impl Default for Hotkeys {
+    fn default() -> Self {
+        Self {
+            quit: Some(KeyBinding::ctrl(VirtualKeyCode::Q)),
+            idle: Some(KeyBinding::ctrl(VirtualKeyCode::I)),
+            compact: Some(KeyBinding::ctrl(VirtualKeyCode::M)),
+            repeat: Some(KeyBinding::ctrl(VirtualKeyCode::R)),
+            search_songs: Some(KeyBinding::ctrl(VirtualKeyCode::F)),
+            undo: Some(KeyBinding::ctrl(VirtualKeyCode::Z)),
+            play_pause: Some(KeyBinding::key(VirtualKeyCode::PlayPause)),
+            stop: Some(KeyBinding::key(VirtualKeyCode::MediaStop)),
+            next_song: Some(KeyBinding::key(VirtualKeyCode::NextTrack)),
+            next_song_alt: Some(KeyBinding::key(VirtualKeyCode::PageDown)),
+            restart_song: Some(KeyBinding::key(VirtualKeyCode::PrevTrack)),
+            restart_song_alt: Some(KeyBinding::key(VirtualKeyCode::PageUp)),
+            seek_forward: Some(KeyBinding::key(VirtualKeyCode::Right)),
+            seek_backward: Some(KeyBinding::key(VirtualKeyCode::Left)),
+            deselect_all: Some(KeyBinding::ctrl(VirtualKeyCode::S)),
+            select_all: Some(KeyBinding::ctrl(VirtualKeyCode::A)),
+            select_albums: Some(KeyBinding::ctrl_shift(VirtualKeyCode::A)),
+            select_songs: Some(KeyBinding::ctrl_shift(VirtualKeyCode::S)),
+        }
+    }
+}
+impl Hotkeys {
+    /// overwrites the hotkeys which are set in the table, invalid entries are ignored (with a warning)
+    pub fn load(&mut self, table: &toml::Table) {
+        for (name, value) in table {
+            let Some(hotkey) = self.get_mut(name) else {
+                eprintln!("[toml] unknown hotkey `hotkeys.{name}`");
+                continue;
+            };
+            match value.as_str().map(KeyBinding::parse) {
+                Some(Ok(v)) => *hotkey = v,
+                Some(Err(e)) => eprintln!("[toml] `hotkeys.{name}`: {e}"),
+                None => eprintln!("[toml] `hotkeys.{name}` must be a string"),
+            }
+        }
+    }
+    fn get_mut(&mut self, name: &str) -> Option<&mut Option<KeyBinding>> {
+        Some(match name {
+            "quit" => &mut self.quit,
+            "idle" => &mut self.idle,
+            "compact" => &mut self.compact,
+            "repeat" => &mut self.repeat,
+            "search_songs" => &mut self.search_songs,
+            "undo" => &mut self.undo,
+            "play_pause" => &mut self.play_pause,
+            "stop" => &mut self.stop,
+            "next_song" => &mut self.next_song,
+            "next_song_alt" => &mut self.next_song_alt,
+            "restart_song" => &mut self.restart_song,
+            "restart_song_alt" => &mut self.restart_song_alt,
+            "seek_forward" => &mut self.seek_forward,
+            "seek_backward" => &mut self.seek_backward,
+            "deselect_all" => &mut self.deselect_all,
+            "select_all" => &mut self.select_all,
+            "select_albums" => &mut self.select_albums,
+            "select_songs" => &mut self.select_songs,
+            _ => return None,
+        })
+    }
 }
 pub struct KeyAction {
     pub category: String,
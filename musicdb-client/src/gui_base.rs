@@ -165,6 +165,14 @@ impl<C: GuiElemChildren> ScrollBox<C> {
             mouse_scroll_margin_right: 0.0,
         }
     }
+    /// scrolls so that the child at `index` is at the top (or as far down as possible)
+    pub fn scroll_to(&mut self, index: usize) {
+        self.scroll_target = self
+            .children_heights
+            .iter()
+            .take(index)
+            .fold(0.0, |a, b| a + b);
+    }
 }
 impl<C: GuiElemChildren + 'static> GuiElem for ScrollBox<C> {
     fn config(&self) -> &GuiElemCfg {
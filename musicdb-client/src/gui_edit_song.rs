@@ -8,7 +8,7 @@ use speedy2d::{color::Color, dimen::Vec2, shape::Rectangle};
 
 use crate::{
     color_scale,
-    gui::{GuiAction, GuiElem, GuiElemCfg, GuiElemChildren},
+    gui::{format_duration, GuiAction, GuiElem, GuiElemCfg, GuiElemChildren},
     gui_anim::AnimationController,
     gui_base::{Button, Panel, ScrollBox},
     gui_text::{Label, TextField},
@@ -21,6 +21,10 @@ const ELEM_HEIGHT: f32 = 32.0;
 pub struct EditorForSongs {
     config: GuiElemCfg,
     songs: Vec<Song>,
+    /// `Some` if the user chose a new rating
+    rating: Option<Option<u8>>,
+    /// the gain which was shown when the editor was opened, only changes to it are applied
+    gain: String,
     c_title: Label,
     c_scrollbox: ScrollBox<EditorForSongElems>,
     c_buttons: Panel<[Button<[Label; 1]>; 2]>,
@@ -33,11 +37,17 @@ pub enum Event {
     Close,
     Apply,
     SetArtist(String, Option<ArtistId>),
+    SetRating(Option<u8>),
 }
 pub struct EditorForSongElems {
     c_title: TextField,
     c_artist: EditorForSongArtistChooser,
     c_album: Label,
+    /// no rating, then 1 to 5 stars
+    c_rating: Panel<[Button<[Label; 1]>; 6]>,
+    /// `Gain=` in dB, empty to remove it
+    c_gain: TextField,
+    c_info: Label,
 }
 impl GuiElemChildren for EditorForSongElems {
     fn iter(&mut self) -> Box<dyn Iterator<Item = &mut dyn crate::gui::GuiElem> + '_> {
@@ -46,18 +56,36 @@ impl GuiElemChildren for EditorForSongElems {
                 self.c_title.elem_mut(),
                 self.c_artist.elem_mut(),
                 self.c_album.elem_mut(),
+                self.c_rating.elem_mut(),
+                self.c_gain.elem_mut(),
+                self.c_info.elem_mut(),
             ]
             .into_iter(),
         )
     }
     fn len(&self) -> usize {
-        3
+        6
     }
 }
 
 impl EditorForSongs {
     pub fn new(songs: Vec<Song>) -> Self {
         let (sender, recv) = std::sync::mpsc::channel();
+        // only show a rating if all songs have the same one
+        let rating = songs.first().and_then(|first| {
+            let rating = first.rating();
+            songs.iter().all(|s| s.rating() == rating).then_some(rating)
+        });
+        let mut c_rating = rating_buttons(&sender);
+        set_rating_colors(&mut c_rating, rating.flatten());
+        // same for the gain
+        let gain = songs
+            .first()
+            .map(|first| first.gain_db())
+            .filter(|gain| songs.iter().all(|s| s.gain_db() == *gain))
+            .flatten()
+            .map(|gain| gain.to_string())
+            .unwrap_or_default();
         Self {
             config: GuiElemCfg::at(Rectangle::from_tuples((0.0, 1.0), (1.0, 2.0))),
             c_title: Label::new(
@@ -96,6 +124,21 @@ impl EditorForSongs {
                         None,
                         Vec2::new(0.0, 0.5),
                     ),
+                    c_rating,
+                    c_gain: TextField::new_adv(
+                        GuiElemCfg::default(),
+                        gain.clone(),
+                        "gain in dB (for songs which are too loud or too quiet)".to_owned(),
+                        Color::DARK_GRAY,
+                        Color::WHITE,
+                    ),
+                    c_info: Label::new(
+                        GuiElemCfg::default(),
+                        technical_info(&songs),
+                        Color::GRAY,
+                        None,
+                        Vec2::new(0.0, 0.5),
+                    ),
                 },
                 vec![],
                 ELEM_HEIGHT,
@@ -142,12 +185,92 @@ impl EditorForSongs {
             c_background: Panel::with_background(GuiElemCfg::default(), (), Color::BLACK),
             created: Some(Instant::now()),
             songs,
+            rating: None,
+            gain,
             event_sender: sender,
             event_recv: recv,
         }
     }
 }
 
+fn rating_buttons(sender: &std::sync::mpsc::Sender<Event>) -> Panel<[Button<[Label; 1]>; 6]> {
+    let button = |i: u8| {
+        let sender = sender.clone();
+        let x = i as f32 / 6.0;
+        Button::new(
+            GuiElemCfg::at(Rectangle::from_tuples((x, 0.0), (x + 1.0 / 6.0, 1.0))),
+            move |_| {
+                sender
+                    .send(Event::SetRating(if i == 0 { None } else { Some(i) }))
+                    .unwrap();
+                vec![]
+            },
+            [Label::new(
+                GuiElemCfg::default(),
+                if i == 0 { "×" } else { "★" }.to_owned(),
+                Color::GRAY,
+                None,
+                Vec2::new(0.5, 0.5),
+            )],
+        )
+    };
+    Panel::new(
+        GuiElemCfg::default(),
+        std::array::from_fn(|i| button(i as u8)),
+    )
+}
+fn set_rating_colors(c_rating: &mut Panel<[Button<[Label; 1]>; 6]>, rating: Option<u8>) {
+    for (i, button) in c_rating.children.iter_mut().enumerate() {
+        let active = match rating {
+            None => i == 0,
+            Some(rating) => i > 0 && i <= rating as usize,
+        };
+        *button.children[0].content.color() = match (i, active) {
+            (0, true) => Color::WHITE,
+            (_, true) => Color::from_rgb(1.0, 0.8, 0.2),
+            (_, false) => Color::DARK_GRAY,
+        };
+    }
+}
+
+/// format, file size, duration and bitrate (from the song, or calculated from size and duration if the song doesn't have one).
+/// for multiple songs, this shows the total size and duration and the range of bitrates.
+fn technical_info(songs: &[Song]) -> String {
+    let mut formats = songs
+        .iter()
+        .map(|s| {
+            s.codec.clone().unwrap_or_else(|| {
+                s.location
+                    .rel_path
+                    .extension()
+                    .map(|v| v.to_string_lossy().to_uppercase())
+                    .unwrap_or_else(|| "?".to_owned())
+            })
+        })
+        .collect::<Vec<_>>();
+    formats.sort();
+    formats.dedup();
+    let size = songs.iter().map(|s| s.file_size).sum::<u64>();
+    let duration = songs.iter().map(|s| s.duration_millis).sum::<u64>();
+    // bytes per millisecond * 8 = kbit/s
+    let bitrates = songs.iter().filter_map(|s| {
+        s.bitrate
+            .map(|v| v as u64)
+            .or_else(|| (s.duration_millis > 0).then(|| s.file_size * 8 / s.duration_millis))
+    });
+    let bitrate = match (bitrates.clone().min(), bitrates.max()) {
+        (Some(min), Some(max)) if min == max => format!(" · {min} kbit/s"),
+        (Some(min), Some(max)) => format!(" · {min}-{max} kbit/s"),
+        _ => String::new(),
+    };
+    format!(
+        "{} · {:.1} MiB · {}{bitrate}",
+        formats.join(", "),
+        size as f64 / (1024.0 * 1024.0),
+        format_duration(duration),
+    )
+}
+
 impl GuiElem for EditorForSongs {
     fn children(&mut self) -> Box<dyn Iterator<Item = &mut dyn GuiElem> + '_> {
         Box::new(
@@ -169,6 +292,27 @@ impl GuiElem for EditorForSongs {
                         gui.gui.set_normal_ui_enabled(true);
                     }))),
                     Event::Apply => {
+                        let gain = self
+                            .c_scrollbox
+                            .children
+                            .c_gain
+                            .c_input
+                            .content
+                            .get_text()
+                            .trim()
+                            .to_owned();
+                        // empty removes the gain, invalid values are ignored
+                        let gain = if gain == self.gain {
+                            None
+                        } else if gain.is_empty() {
+                            Some(0.0)
+                        } else {
+                            gain.trim_end_matches("dB")
+                                .trim()
+                                .parse::<f32>()
+                                .ok()
+                                .filter(|v| v.is_finite())
+                        };
                         for song in &self.songs {
                             let mut song = song.clone();
 
@@ -188,11 +332,22 @@ impl GuiElem for EditorForSongs {
                                 song.artist = artist_id;
                                 song.album = None;
                             }
+                            let id = song.id;
                             info.actions
                                 .push(GuiAction::SendToServer(Action::ModifySong(
                                     song,
                                     Req::none(),
                                 )));
+                            if let Some(rating) = self.rating {
+                                info.actions
+                                    .push(GuiAction::SendToServer(Action::SetSongRating(
+                                        id, rating,
+                                    )));
+                            }
+                            if let Some(gain) = gain {
+                                info.actions
+                                    .push(GuiAction::SendToServer(Action::SetSongGain(id, gain)));
+                            }
                         }
                     }
                     Event::SetArtist(name, id) => {
@@ -209,6 +364,10 @@ impl GuiElem for EditorForSongs {
                             .text() = name;
                         self.c_scrollbox.children.c_artist.config_mut().redraw = true;
                     }
+                    Event::SetRating(rating) => {
+                        self.rating = Some(rating);
+                        set_rating_colors(&mut self.c_scrollbox.children.c_rating, rating);
+                    }
                 },
                 Err(_) => break,
             }
@@ -4,7 +4,12 @@ use std::{
 };
 
 use musicdb_lib::data::ArtistId;
-use speedy2d::{color::Color, dimen::Vec2, image::ImageHandle, shape::Rectangle};
+use speedy2d::{
+    color::Color,
+    dimen::{UVec2, Vec2},
+    image::ImageHandle,
+    shape::Rectangle,
+};
 
 use crate::{
     gui::{rect_from_rel, DrawInfo, GuiAction, GuiElem, GuiElemCfg, GuiServerImage},
@@ -229,13 +234,28 @@ impl GuiElem for IdleDisplay {
                 }
             }
         }
-        // draw cover
-        if let Some(Some(cover)) = self
+        let cover = self
             .current_info
             .current_cover
             .as_ref()
-            .map(|v| v.1.as_ref())
-        {
+            .and_then(|v| v.1.as_ref());
+        let fullscreen_cover =
+            info.gui_config.idle_fullscreen_cover && cover.is_some_and(|v| v.is_some());
+        // draw fullscreen cover (darkened so the text is readable),
+        // the cover and artist image aren't shown next to the text then, so the text can use the whole width.
+        if fullscreen_cover {
+            if let Some(Some(cover)) = cover {
+                let brightness = 0.35;
+                g.draw_rectangle_image_subset_tinted(
+                    info.pos.clone(),
+                    Color::from_rgba(brightness, brightness, brightness, self.idle_mode),
+                    cover_crop(*cover.size(), info.pos.size()),
+                    cover,
+                );
+            }
+            self.cover_aspect_ratio.target = 0.0;
+            self.artist_image_aspect_ratio.target = 0.0;
+        } else if let Some(cover) = cover {
             image_display(
                 g,
                 cover.as_ref(),
@@ -247,7 +267,9 @@ impl GuiElem for IdleDisplay {
             );
         }
         // draw artist image
-        if let Some((_, Some((_, Some(img))))) = &self.current_artist_image {
+        if let (false, Some((_, Some((_, Some(img)))))) =
+            (fullscreen_cover, &self.current_artist_image)
+        {
             let top = info.pos.top_left().y + info.pos.height() * self.cover_top;
             let bottom = info.pos.top_left().y + info.pos.height() * self.cover_bottom;
             image_display(
@@ -336,3 +358,17 @@ impl GuiElem for IdleDisplay {
         self.current_info.update = true;
     }
 }
+
+/// the part of the image (in normalized coordinates) which fills the area without stretching the image
+fn cover_crop(image: UVec2, area: Vec2) -> Rectangle {
+    let image_ar = image.x.max(1) as f32 / image.y.max(1) as f32;
+    let area_ar = area.x.max(1.0) / area.y.max(1.0);
+    if image_ar > area_ar {
+        // image is wider, cut off left and right
+        let w = area_ar / image_ar;
+        Rectangle::from_tuples(((1.0 - w) / 2.0, 0.0), ((1.0 + w) / 2.0, 1.0))
+    } else {
+        let h = image_ar / area_ar;
+        Rectangle::from_tuples((0.0, (1.0 - h) / 2.0), (1.0, (1.0 + h) / 2.0))
+    }
+}
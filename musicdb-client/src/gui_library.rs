@@ -1,21 +1,27 @@
 use std::{
     cmp::Ordering,
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     sync::Arc,
     sync::{
         atomic::{AtomicBool, AtomicUsize},
         mpsc, Mutex,
     },
-    time::Instant,
+    time::{Duration, Instant},
 };
 
-use musicdb_lib::data::{
-    album::Album,
-    artist::Artist,
-    database::Database,
-    queue::{Queue, QueueContent},
-    song::Song,
-    AlbumId, ArtistId, GeneralData, SongId,
+use musicdb_lib::{
+    data::{
+        album::Album,
+        artist::Artist,
+        database::Database,
+        m3u::to_m3u,
+        queue::{Queue, QueueContent},
+        search_index::SearchIndex,
+        song::Song,
+        AlbumId, ArtistId, GeneralData, SongId,
+    },
+    load::ToFromBytes,
+    server::{Action, Req},
 };
 use regex::{Regex, RegexBuilder};
 use speedy2d::{
@@ -27,14 +33,18 @@ use speedy2d::{
 
 use crate::{
     gui::{
-        Dragging, DrawInfo, EventInfo, GuiAction, GuiConfig, GuiElem, GuiElemCfg, GuiElemChildren,
-        GuiElemWrapper,
+        format_duration, Colors, Dragging, DrawInfo, EventInfo, GuiAction, GuiConfig, GuiElem,
+        GuiElemCfg, GuiElemChildren, GuiElemWrapper, Hotkeys,
     },
     gui_anim::AnimationController,
     gui_base::{Button, Panel, ScrollBox},
+    gui_notif::NotifInfo,
+    gui_queue::{add_to_queue_album_by_id, add_to_queue_artist_by_id},
     gui_text::{self, AdvancedLabel, Label, TextField},
 };
 
+pub use musicdb_lib::data::filter::{Filter, FilterType};
+
 use self::selected::Selected;
 
 /*
@@ -69,6 +79,13 @@ pub struct LibraryBrowser {
     search_album_regex: Option<Regex>,
     search_song: String,
     search_song_regex: Option<Regex>,
+    /// used to skip names which can't match the search before running the regex
+    search_index_artists: SearchIndex,
+    search_index_albums: SearchIndex,
+    search_index_songs: SearchIndex,
+    search_candidates_artists: Option<HashSet<u64>>,
+    search_candidates_albums: Option<HashSet<u64>>,
+    search_candidates_songs: Option<HashSet<u64>>,
     filter_target_state: Arc<AtomicBool>,
     filter_state: AnimationController<f32>,
     library_updated: bool,
@@ -77,11 +94,82 @@ pub struct LibraryBrowser {
     search_was_case_sensitive: bool,
     search_prefer_start_matches: Arc<AtomicBool>,
     search_prefers_start_matches: bool,
+    sort_mode: Arc<AtomicUsize>,
+    sort_mode_current: SortMode,
     filter_songs: Arc<Mutex<Filter>>,
     filter_albums: Arc<Mutex<Filter>>,
     filter_artists: Arc<Mutex<Filter>>,
+    tag_suggestions: TagSuggestions,
     do_something_receiver: mpsc::Receiver<Box<dyn FnOnce(&mut Self)>>,
     selected_popup_state: (f32, usize, usize, usize),
+    jump_to_current: bool,
+    follow_current: Arc<AtomicBool>,
+    followed_song: Option<SongId>,
+    hotkeys: Hotkeys,
+    /// when the search or filters were last changed, if they haven't been saved yet
+    search_state_changed: Option<Instant>,
+}
+/// The search texts, search settings and filters, which are saved to a file in the config dir
+/// so they can be restored when the client is started again.
+struct SearchState {
+    artist: String,
+    album: String,
+    song: String,
+    case_sensitive: bool,
+    prefer_start_matches: bool,
+    filter_songs: Filter,
+    filter_albums: Filter,
+    filter_artists: Filter,
+}
+impl SearchState {
+    fn file() -> std::path::PathBuf {
+        crate::get_config_file_path().join("library_search")
+    }
+    fn load() -> Option<Self> {
+        let bytes = std::fs::read(Self::file()).ok()?;
+        match Self::from_bytes(&mut bytes.as_slice()) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                eprintln!("[warn] couldn't load saved search: {e}");
+                None
+            }
+        }
+    }
+}
+impl ToFromBytes for SearchState {
+    fn to_bytes<T>(&self, s: &mut T) -> Result<(), std::io::Error>
+    where
+        T: std::io::Write,
+    {
+        self.artist.to_bytes(s)?;
+        self.album.to_bytes(s)?;
+        self.song.to_bytes(s)?;
+        self.case_sensitive.to_bytes(s)?;
+        self.prefer_start_matches.to_bytes(s)?;
+        self.filter_songs.to_bytes(s)?;
+        self.filter_albums.to_bytes(s)?;
+        self.filter_artists.to_bytes(s)?;
+        Ok(())
+    }
+    fn from_bytes<T>(s: &mut T) -> Result<Self, std::io::Error>
+    where
+        T: std::io::Read,
+    {
+        Ok(Self {
+            artist: ToFromBytes::from_bytes(s)?,
+            album: ToFromBytes::from_bytes(s)?,
+            song: ToFromBytes::from_bytes(s)?,
+            case_sensitive: ToFromBytes::from_bytes(s)?,
+            prefer_start_matches: ToFromBytes::from_bytes(s)?,
+            filter_songs: ToFromBytes::from_bytes(s)?,
+            filter_albums: ToFromBytes::from_bytes(s)?,
+            filter_artists: ToFromBytes::from_bytes(s)?,
+        })
+    }
+}
+/// `None` means the search index couldn't rule anything out
+fn is_candidate(candidates: &Option<HashSet<u64>>, id: u64) -> bool {
+    candidates.as_ref().is_none_or(|c| c.contains(&id))
 }
 fn search_regex_new(pat: &str, case_insensitive: bool) -> Result<Option<Regex>, regex::Error> {
     if pat.is_empty() {
@@ -95,25 +183,164 @@ fn search_regex_new(pat: &str, case_insensitive: bool) -> Result<Option<Regex>,
         ))
     }
 }
+/// How artists, albums and singles are ordered in the library (before search scores are applied)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortMode {
+    Name,
+    Year,
+    Duration,
+    RecentlyAdded,
+}
+impl SortMode {
+    const ALL: [Self; 4] = [Self::Name, Self::Year, Self::Duration, Self::RecentlyAdded];
+    fn from_index(i: usize) -> Self {
+        Self::ALL[i % Self::ALL.len()]
+    }
+    fn index(self) -> usize {
+        Self::ALL.iter().position(|v| *v == self).unwrap_or(0)
+    }
+    fn label(self) -> &'static str {
+        match self {
+            Self::Name => "sort by name",
+            Self::Year => "sort by year",
+            Self::Duration => "sort by duration",
+            Self::RecentlyAdded => "sort by recently added",
+        }
+    }
+    fn cmp(self, a: &SortInfo, b: &SortInfo) -> Ordering {
+        match self {
+            Self::Name => a.name.cmp(b.name),
+            // unknown years go last
+            Self::Year => match (a.year, b.year) {
+                (Some(x), Some(y)) => x.cmp(&y),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            }
+            .then_with(|| a.name.cmp(b.name)),
+            // longest first
+            Self::Duration => b
+                .duration_millis
+                .cmp(&a.duration_millis)
+                .then_with(|| a.name.cmp(b.name)),
+            // ids are assigned in increasing order, so newer entries have higher ids
+            Self::RecentlyAdded => b.id.cmp(&a.id),
+        }
+    }
+}
+/// What `SortMode::cmp` looks at. For albums and artists, `year` is the earliest year and `duration_millis` the total duration.
+struct SortInfo<'a> {
+    name: &'a str,
+    year: Option<u32>,
+    duration_millis: u64,
+    id: u64,
+}
+impl<'a> SortInfo<'a> {
+    fn song(song: &'a Song) -> Self {
+        Self {
+            name: &song.title,
+            year: year_tag(&song.general),
+            duration_millis: song.duration_millis,
+            id: song.id,
+        }
+    }
+    fn album(album: &'a Album, db: &Database) -> Self {
+        Self {
+            name: &album.name,
+            year: album
+                .songs
+                .iter()
+                .filter_map(|id| db.get_song(id))
+                .filter_map(|s| year_tag(&s.general))
+                .min()
+                .or_else(|| year_tag(&album.general)),
+            duration_millis: album_duration_millis(album, db),
+            id: album.id,
+        }
+    }
+    fn artist<'b>(artist: &'a Artist, children: impl Iterator<Item = &'b SortInfo<'a>>) -> Self
+    where
+        'a: 'b,
+    {
+        let mut year = year_tag(&artist.general);
+        let mut duration_millis = 0u64;
+        for child in children {
+            if let Some(y) = child.year {
+                year = Some(year.map_or(y, |v| v.min(y)));
+            }
+            duration_millis = duration_millis.saturating_add(child.duration_millis);
+        }
+        Self {
+            name: &artist.name,
+            year,
+            duration_millis,
+            id: artist.id,
+        }
+    }
+    fn missing(id: u64) -> Self {
+        Self {
+            name: "",
+            year: None,
+            duration_millis: 0,
+            id,
+        }
+    }
+}
+/// the value of the `Year=` tag, ignoring anything after the year (like in `Year=2004-05-12`)
+fn year_tag(general: &GeneralData) -> Option<u32> {
+    let v = general.tags.iter().find_map(|t| t.strip_prefix("Year="))?;
+    let end = v.find(|c: char| !c.is_ascii_digit()).unwrap_or(v.len());
+    v[..end].parse().ok()
+}
+fn album_duration_millis(album: &Album, db: &Database) -> u64 {
+    album
+        .songs
+        .iter()
+        .filter_map(|id| db.get_song(id))
+        .map(|s| s.duration_millis)
+        .fold(0, u64::saturating_add)
+}
 const LP_LIB1: f32 = 0.1;
 const LP_LIB2: f32 = 1.0;
 const LP_LIB1S: f32 = 0.4;
 impl LibraryBrowser {
     pub fn new(config: GuiElemCfg) -> Self {
-        let c_search_artist = TextField::new(
+        let state = SearchState::load().unwrap_or_else(|| SearchState {
+            artist: String::new(),
+            album: String::new(),
+            song: String::new(),
+            case_sensitive: false,
+            prefer_start_matches: true,
+            filter_songs: Filter {
+                and: true,
+                filters: vec![],
+            },
+            filter_albums: Filter {
+                and: true,
+                filters: vec![],
+            },
+            filter_artists: Filter {
+                and: true,
+                filters: vec![],
+            },
+        });
+        let c_search_artist = TextField::new_adv(
             GuiElemCfg::at(Rectangle::from_tuples((0.01, 0.01), (0.45, 0.05))),
+            state.artist.clone(),
             "artist".to_string(),
             Color::GRAY,
             Color::WHITE,
         );
-        let c_search_album = TextField::new(
+        let c_search_album = TextField::new_adv(
             GuiElemCfg::at(Rectangle::from_tuples((0.55, 0.01), (0.99, 0.05))),
+            state.album.clone(),
             "album".to_string(),
             Color::GRAY,
             Color::WHITE,
         );
-        let c_search_song = TextField::new(
+        let c_search_song = TextField::new_adv(
             GuiElemCfg::at(Rectangle::from_tuples((0.01, 0.06), (0.99, 0.1))),
+            state.song.clone(),
             "song".to_string(),
             Color::GRAY,
             Color::WHITE,
@@ -127,10 +354,13 @@ impl LibraryBrowser {
         );
         let (do_something_sender, do_something_receiver) = mpsc::channel();
         let search_settings_changed = Arc::new(AtomicBool::new(false));
-        let search_was_case_sensitive = false;
+        let search_was_case_sensitive = state.case_sensitive;
         let search_is_case_sensitive = Arc::new(AtomicBool::new(search_was_case_sensitive));
-        let search_prefers_start_matches = true;
+        let search_prefers_start_matches = state.prefer_start_matches;
         let search_prefer_start_matches = Arc::new(AtomicBool::new(search_prefers_start_matches));
+        let follow_current = Arc::new(AtomicBool::new(false));
+        let sort_mode_current = SortMode::Name;
+        let sort_mode = Arc::new(AtomicUsize::new(sort_mode_current.index()));
         let filter_target_state = Arc::new(AtomicBool::new(false));
         let fts = Arc::clone(&filter_target_state);
         let c_filter_button = Button::new(
@@ -150,19 +380,16 @@ impl LibraryBrowser {
                 Vec2::new(0.5, 0.5),
             )],
         );
-        let filter_songs = Arc::new(Mutex::new(Filter {
-            and: true,
-            filters: vec![],
-        }));
-        let filter_albums = Arc::new(Mutex::new(Filter {
-            and: true,
-            filters: vec![],
-        }));
-        let filter_artists = Arc::new(Mutex::new(Filter {
-            and: true,
-            filters: vec![],
-        }));
+        let filter_songs = Arc::new(Mutex::new(state.filter_songs));
+        let filter_albums = Arc::new(Mutex::new(state.filter_albums));
+        let filter_artists = Arc::new(Mutex::new(state.filter_artists));
+        let search_regex = |pat: &str| {
+            search_regex_new(pat, !search_was_case_sensitive)
+                .ok()
+                .flatten()
+        };
         let selected = Selected::new(Arc::clone(&search_settings_changed));
+        let tag_suggestions = TagSuggestions::default();
         Self {
             config: config.w_keyboard_watch(),
             c_search_artist,
@@ -174,9 +401,12 @@ impl LibraryBrowser {
                 Arc::clone(&search_settings_changed),
                 Arc::clone(&search_is_case_sensitive),
                 Arc::clone(&search_prefer_start_matches),
+                Arc::clone(&sort_mode),
+                Arc::clone(&follow_current),
                 Arc::clone(&filter_songs),
                 Arc::clone(&filter_albums),
                 Arc::clone(&filter_artists),
+                tag_suggestions.clone(),
                 selected.clone(),
                 do_something_sender.clone(),
             ),
@@ -196,12 +426,18 @@ impl LibraryBrowser {
             library_filtered: vec![],
             selected,
             // - - -
-            search_artist: String::new(),
-            search_artist_regex: None,
-            search_album: String::new(),
-            search_album_regex: None,
-            search_song: String::new(),
-            search_song_regex: None,
+            search_artist_regex: search_regex(&state.artist),
+            search_artist: state.artist,
+            search_album_regex: search_regex(&state.album),
+            search_album: state.album,
+            search_song_regex: search_regex(&state.song),
+            search_song: state.song,
+            search_index_artists: SearchIndex::new(),
+            search_index_albums: SearchIndex::new(),
+            search_index_songs: SearchIndex::new(),
+            search_candidates_artists: None,
+            search_candidates_albums: None,
+            search_candidates_songs: None,
             filter_target_state,
             filter_state: AnimationController::new(0.0, 0.0, 0.25, 25.0, 0.1, 0.2, Instant::now()),
             library_updated: true,
@@ -210,11 +446,38 @@ impl LibraryBrowser {
             search_was_case_sensitive,
             search_prefer_start_matches,
             search_prefers_start_matches,
+            sort_mode,
+            sort_mode_current,
             filter_songs,
             filter_albums,
             filter_artists,
+            tag_suggestions,
             do_something_receiver,
             selected_popup_state: (0.0, 0, 0, 0),
+            jump_to_current: false,
+            follow_current,
+            followed_song: None,
+            hotkeys: Hotkeys::default(),
+            search_state_changed: None,
+        }
+    }
+    /// saves the search and filters if they were changed, see `SearchState`
+    pub fn save_search_state(&mut self) {
+        if self.search_state_changed.take().is_none() {
+            return;
+        }
+        let state = SearchState {
+            artist: self.search_artist.clone(),
+            album: self.search_album.clone(),
+            song: self.search_song.clone(),
+            case_sensitive: self.search_was_case_sensitive,
+            prefer_start_matches: self.search_prefers_start_matches,
+            filter_songs: self.filter_songs.lock().unwrap().clone(),
+            filter_albums: self.filter_albums.lock().unwrap().clone(),
+            filter_artists: self.filter_artists.lock().unwrap().clone(),
+        };
+        if let Err(e) = std::fs::write(SearchState::file(), state.to_bytes_vec()) {
+            eprintln!("[warn] couldn't save search: {e}");
         }
     }
     pub fn selected_add_all(&self) {
@@ -297,6 +560,7 @@ impl GuiElem for LibraryBrowser {
         false
     }
     fn draw(&mut self, info: &mut DrawInfo, _g: &mut speedy2d::Graphics2D) {
+        self.hotkeys = info.gui_config.hotkeys;
         loop {
             if let Ok(action) = self.do_something_receiver.try_recv() {
                 action(self);
@@ -376,6 +640,14 @@ impl GuiElem for LibraryBrowser {
                 };
             }
         }
+        if search_changed {
+            self.search_state_changed = Some(info.time);
+        } else if self
+            .search_state_changed
+            .is_some_and(|t| info.time.duration_since(t).as_secs() >= 1)
+        {
+            self.save_search_state();
+        }
         // filter panel
         let filter_target_state = self
             .filter_target_state
@@ -394,20 +666,36 @@ impl GuiElem for LibraryBrowser {
             filter_panel.config.enabled = self.filter_state.value > 0.0;
         }
         // -
+        let sort_mode =
+            SortMode::from_index(self.sort_mode.load(std::sync::atomic::Ordering::Relaxed));
+        if self.sort_mode_current != sort_mode {
+            self.sort_mode_current = sort_mode;
+            self.library_updated = true;
+        }
         if self.library_updated {
             self.library_updated = false;
-            self.update_local_library(&info.database, |(_, a), (_, b)| a.name.cmp(&b.name));
+            self.update_local_library(&info.database, self.sort_mode_current);
+            self.update_search_index(info.database);
+            self.tag_suggestions.update(info.database);
             search_changed = true;
         }
         if search_changed {
+            self.search_candidates_artists =
+                self.search_index_artists.candidates(&self.search_artist);
+            self.search_candidates_albums = self.search_index_albums.candidates(&self.search_album);
+            self.search_candidates_songs = self.search_index_songs.candidates(&self.search_song);
             fn filter(
                 s: &LibraryBrowser,
+                is_candidate: bool,
                 pat: &str,
                 regex: &Option<Regex>,
                 search_text: &String,
                 filter: &Filter,
                 search_gd: &GeneralData,
             ) -> f32 {
+                if !is_candidate {
+                    return 0.0;
+                }
                 if !filter.passes(search_gd) {
                     return 0.0;
                 };
@@ -450,18 +738,28 @@ impl GuiElem for LibraryBrowser {
             self.filter_local_library(
                 &info.database,
                 |s, artist| {
-                    filter(
-                        s,
-                        &artist.name,
-                        &s.search_artist_regex,
-                        &s.search_artist,
-                        &s.filter_artists.lock().unwrap(),
-                        &artist.general,
-                    )
+                    // an artist matches if its name or any of its aliases does
+                    let is_candidate = is_candidate(&s.search_candidates_artists, artist.id);
+                    let filter_artists = s.filter_artists.lock().unwrap();
+                    std::iter::once(&artist.name)
+                        .chain(&artist.aliases)
+                        .map(|name| {
+                            filter(
+                                s,
+                                is_candidate,
+                                name,
+                                &s.search_artist_regex,
+                                &s.search_artist,
+                                &filter_artists,
+                                &artist.general,
+                            )
+                        })
+                        .fold(0.0, f32::max)
                 },
                 |s, album| {
                     filter(
                         s,
+                        is_candidate(&s.search_candidates_albums, album.id),
                         &album.name,
                         &s.search_album_regex,
                         &s.search_album,
@@ -473,6 +771,7 @@ impl GuiElem for LibraryBrowser {
                     if song.album.is_some() || allow_singles {
                         filter(
                             s,
+                            is_candidate(&s.search_candidates_songs, song.id),
                             &song.title,
                             &s.search_song_regex,
                             &s.search_song,
@@ -590,7 +889,23 @@ impl GuiElem for LibraryBrowser {
         }
         if self.config.redraw || info.pos.size() != self.config.pixel_pos.size() {
             self.config.redraw = false;
-            self.update_ui(&info.database, info.line_height);
+            self.update_ui(&info.database, info.line_height, &info.gui_config.colors);
+        }
+        // jump to (or follow) the current song
+        let current = info.database.queue.get_current_song().copied();
+        if self.jump_to_current
+            || (current != self.followed_song
+                && self
+                    .follow_current
+                    .load(std::sync::atomic::Ordering::Relaxed))
+        {
+            self.jump_to_current = false;
+            self.followed_song = current;
+            if let Some(song) = current.and_then(|id| info.database.get_song(&id)) {
+                if let Some(index) = self.index_of_song(song) {
+                    self.c_scroll_box.scroll_to(index);
+                }
+            }
         }
     }
     fn updated_library(&mut self) {
@@ -604,16 +919,40 @@ impl GuiElem for LibraryBrowser {
         key: Option<VirtualKeyCode>,
         _scan: speedy2d::window::KeyScancode,
     ) -> Vec<GuiAction> {
-        if down && crate::gui::hotkey_deselect_all(&modifiers, key) && e.take() {
+        if down
+            && self
+                .hotkeys
+                .deselect_all
+                .is_some_and(|h| h.matches(&modifiers, key))
+            && e.take()
+        {
             self.selected.clear();
         }
-        if down && crate::gui::hotkey_select_all(&modifiers, key) && e.take() {
+        if down
+            && self
+                .hotkeys
+                .select_all
+                .is_some_and(|h| h.matches(&modifiers, key))
+            && e.take()
+        {
             self.selected_add_all();
         }
-        if down && crate::gui::hotkey_select_albums(&modifiers, key) && e.take() {
+        if down
+            && self
+                .hotkeys
+                .select_albums
+                .is_some_and(|h| h.matches(&modifiers, key))
+            && e.take()
+        {
             self.selected_add_albums();
         }
-        if down && crate::gui::hotkey_select_songs(&modifiers, key) && e.take() {
+        if down
+            && self
+                .hotkeys
+                .select_songs
+                .is_some_and(|h| h.matches(&modifiers, key))
+            && e.take()
+        {
             self.selected_add_songs();
         }
         vec![]
@@ -621,33 +960,54 @@ impl GuiElem for LibraryBrowser {
 }
 impl LibraryBrowser {
     /// Sets `self.library_sorted` based on the contents of the `Database`.
-    fn update_local_library(
-        &mut self,
-        db: &Database,
-        sort_artists: impl FnMut(&(&ArtistId, &Artist), &(&ArtistId, &Artist)) -> Ordering,
-    ) {
-        let mut artists = db.artists().iter().collect::<Vec<_>>();
-        artists.sort_unstable_by(sort_artists);
-        self.library_sorted = artists
-            .into_iter()
+    /// Artists, albums and singles are ordered using `sort_mode`, songs in an album keep their order.
+    fn update_local_library(&mut self, db: &Database, sort_mode: SortMode) {
+        let mut artists = db
+            .artists()
+            .iter()
             .map(|(ar_id, artist)| {
-                let singles = artist.singles.iter().map(|id| *id).collect();
-                let albums = artist
+                let mut singles = artist
+                    .singles
+                    .iter()
+                    .filter_map(|id| db.songs().get(id))
+                    .map(|song| (song.id, SortInfo::song(song)))
+                    .collect::<Vec<_>>();
+                singles.sort_by(|(_, a), (_, b)| sort_mode.cmp(a, b));
+                let mut albums = artist
                     .albums
                     .iter()
                     .map(|id| {
-                        let songs = if let Some(album) = db.albums().get(id) {
-                            album.songs.iter().map(|id| *id).collect()
+                        if let Some(album) = db.albums().get(id) {
+                            (*id, album.songs.clone(), SortInfo::album(album, db))
                         } else {
                             eprintln!("[warn] No album with id {id} found in db!");
-                            vec![]
-                        };
-                        (*id, songs)
+                            (*id, vec![], SortInfo::missing(*id))
+                        }
                     })
-                    .collect();
-                (*ar_id, singles, albums)
+                    .collect::<Vec<_>>();
+                albums.sort_by(|(.., a), (.., b)| sort_mode.cmp(a, b));
+                let info = SortInfo::artist(
+                    artist,
+                    singles
+                        .iter()
+                        .map(|(_, i)| i)
+                        .chain(albums.iter().map(|(.., i)| i)),
+                );
+                (
+                    info,
+                    (
+                        *ar_id,
+                        singles.into_iter().map(|(id, _)| id).collect(),
+                        albums
+                            .into_iter()
+                            .map(|(id, songs, _)| (id, songs))
+                            .collect(),
+                    ),
+                )
             })
-            .collect();
+            .collect::<Vec<_>>();
+        artists.sort_by(|(a, _), (b, _)| sort_mode.cmp(a, b));
+        self.library_sorted = artists.into_iter().map(|(_, v)| v).collect();
     }
     /// Sets `self.library_filtered` using the value of `self.library_sorted` and filter functions.
     /// Return values of the filter functions:
@@ -656,6 +1016,34 @@ impl LibraryBrowser {
     /// anything else -> priority (determines how things will be sorted)
     /// Album Value = max(Song Values) * AlbumFilterVal
     /// Artist Value = max(Album Values) * ArtistFilterVal
+    /// only changes the names which were added, changed, or removed
+    fn update_search_index(&mut self, db: &Database) {
+        self.search_index_artists
+            .retain(|id| db.artists().contains_key(&id));
+        for artist in db.artists().values() {
+            if artist.aliases.is_empty() {
+                self.search_index_artists.set(artist.id, &artist.name);
+            } else {
+                // one name per line, trigrams containing the newline never match a search
+                let names = std::iter::once(&artist.name)
+                    .chain(&artist.aliases)
+                    .map(|v| v.as_str())
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                self.search_index_artists.set(artist.id, &names);
+            }
+        }
+        self.search_index_albums
+            .retain(|id| db.albums().contains_key(&id));
+        for album in db.albums().values() {
+            self.search_index_albums.set(album.id, &album.name);
+        }
+        self.search_index_songs
+            .retain(|id| db.songs().contains_key(&id));
+        for song in db.songs().values() {
+            self.search_index_songs.set(song.id, &song.title);
+        }
+    }
     fn filter_local_library(
         &mut self,
         db: &Database,
@@ -735,25 +1123,72 @@ impl LibraryBrowser {
         a.sort_by(|(.., a), (.., b)| b.partial_cmp(a).unwrap_or(Ordering::Equal));
         self.library_filtered = a;
     }
+    /// The index of the song in the `ScrollBox`.
+    /// If the song isn't shown (because of the search/filters), its album or artist is used instead.
+    fn index_of_song(&self, song: &Song) -> Option<usize> {
+        let children = self.c_scroll_box.children.as_slice();
+        children
+            .iter()
+            .position(|e| matches!(e, ListElement::Song(v) if v.id == song.id))
+            .or_else(|| {
+                children
+                    .iter()
+                    .position(|e| matches!(e, ListElement::Album(v) if Some(v.id) == song.album))
+            })
+            .or_else(|| {
+                children
+                    .iter()
+                    .position(|e| matches!(e, ListElement::Artist(v) if v.id == song.artist))
+            })
+    }
     /// Sets the contents of the `ScrollBox` based on `self.library_filtered`.
-    fn update_ui(&mut self, db: &Database, line_height: f32) {
+    fn update_ui(&mut self, db: &Database, line_height: f32, colors: &Colors) {
         let mut elems = vec![];
         let mut elemh = vec![];
         for (artist_id, singles, albums, _artist_filterscore) in self.library_filtered.iter() {
-            let (e, h) = self.build_ui_element_artist(*artist_id, db, line_height);
+            let (e, h) = self.build_ui_element_artist(colors, *artist_id, db, line_height);
             elems.push(e);
             elemh.push(h);
             for (song_id, _song_filterscore) in singles {
-                let (e, h) = self.build_ui_element_song(*song_id, db, line_height);
+                let (e, h) = self.build_ui_element_song(colors, *song_id, db, line_height);
                 elems.push(e);
                 elemh.push(h);
             }
             for (album_id, songs, _album_filterscore) in albums {
-                let (e, h) = self.build_ui_element_album(*album_id, db, line_height);
+                let (e, h) = self.build_ui_element_album(colors, *album_id, db, line_height);
                 elems.push(e);
                 elemh.push(h);
-                for (song_id, _song_filterscore) in songs {
-                    let (e, h) = self.build_ui_element_song(*song_id, db, line_height);
+                // albums with multiple discs show their songs grouped by disc, with a header for each disc
+                let disc = |id: &SongId| db.get_song(id).and_then(|song| song.disc());
+                let mut songs = songs.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+                let multiple_discs = db
+                    .albums()
+                    .get(album_id)
+                    .is_some_and(|album| album.disc_count(db) > 1);
+                if multiple_discs {
+                    // stable, so songs on the same disc stay in the same order
+                    songs.sort_by_key(disc);
+                }
+                let mut prev_disc = None;
+                for (i, song_id) in songs.iter().enumerate() {
+                    if multiple_discs {
+                        let disc = disc(song_id);
+                        if i == 0 || disc != prev_disc {
+                            elems.push(ListElement::Disc(Label::new(
+                                GuiElemCfg::default(),
+                                match disc {
+                                    Some(disc) => format!("Disc {disc}"),
+                                    None => "Disc ?".to_owned(),
+                                },
+                                Color::GRAY,
+                                None,
+                                Vec2::new(0.0, 0.5),
+                            )));
+                            elemh.push(line_height);
+                            prev_disc = disc;
+                        }
+                    }
+                    let (e, h) = self.build_ui_element_song(colors, *song_id, db, line_height);
                     elems.push(e);
                     elemh.push(h);
                 }
@@ -764,7 +1199,13 @@ impl LibraryBrowser {
         library_scroll_box.children_heights = elemh;
         library_scroll_box.config_mut().redraw = true;
     }
-    fn build_ui_element_artist(&self, id: ArtistId, db: &Database, h: f32) -> (ListElement, f32) {
+    fn build_ui_element_artist(
+        &self,
+        colors: &Colors,
+        id: ArtistId,
+        db: &Database,
+        h: f32,
+    ) -> (ListElement, f32) {
         (
             ListElement::Artist(ListArtist::new(
                 GuiElemCfg::default(),
@@ -774,32 +1215,23 @@ impl LibraryBrowser {
                 } else {
                     format!("[ Artist #{id} ]")
                 },
+                colors.artist,
                 self.selected.clone(),
             )),
             h * 2.5,
         )
     }
-    fn build_ui_element_album(&self, id: ArtistId, db: &Database, h: f32) -> (ListElement, f32) {
+    fn build_ui_element_album(
+        &self,
+        colors: &Colors,
+        id: ArtistId,
+        db: &Database,
+        h: f32,
+    ) -> (ListElement, f32) {
         let (name, duration) = if let Some(v) = db.albums().get(&id) {
-            let duration = v
-                .songs
-                .iter()
-                .filter_map(|id| db.get_song(id))
-                .map(|s| s.duration_millis)
-                .fold(0, u64::saturating_add)
-                / 1000;
             (
                 v.name.to_owned(),
-                if duration >= 60 * 60 {
-                    format!(
-                        "  {}:{:0>2}:{:0>2}",
-                        duration / (60 * 60),
-                        (duration / 60) % 60,
-                        duration % 60
-                    )
-                } else {
-                    format!("  {}:{:0>2}", duration / 60, duration % 60)
-                },
+                format!("  {}", format_duration(album_duration_millis(v, db))),
             )
         } else {
             (format!("[ Album #{id} ]"), String::new())
@@ -810,17 +1242,23 @@ impl LibraryBrowser {
                 id,
                 name,
                 duration,
+                colors.album,
                 self.selected.clone(),
             )),
             h * 1.5,
         )
     }
-    fn build_ui_element_song(&self, id: ArtistId, db: &Database, h: f32) -> (ListElement, f32) {
+    fn build_ui_element_song(
+        &self,
+        colors: &Colors,
+        id: ArtistId,
+        db: &Database,
+        h: f32,
+    ) -> (ListElement, f32) {
         let (name, duration) = if let Some(v) = db.songs().get(&id) {
-            let duration = v.duration_millis / 1000;
             (
                 v.title.to_owned(),
-                format!("  {}:{:0>2}", duration / 60, duration % 60),
+                format!("  {}", format_duration(v.duration_millis)),
             )
         } else {
             (format!("[ Song #{id} ]"), String::new())
@@ -831,6 +1269,7 @@ impl LibraryBrowser {
                 id,
                 name,
                 duration,
+                colors.song,
                 self.selected.clone(),
             )),
             h,
@@ -842,6 +1281,8 @@ pub enum ListElement {
     Artist(ListArtist),
     Album(ListAlbum),
     Song(ListSong),
+    /// the header above the songs on one disc of an album with multiple discs
+    Disc(Label),
 }
 impl GuiElemWrapper for ListElement {
     fn as_elem(&self) -> &dyn GuiElem {
@@ -849,6 +1290,7 @@ impl GuiElemWrapper for ListElement {
             Self::Artist(v) => v,
             Self::Album(v) => v,
             Self::Song(v) => v,
+            Self::Disc(v) => v,
         }
     }
     fn as_elem_mut(&mut self) -> &mut dyn GuiElem {
@@ -856,6 +1298,7 @@ impl GuiElemWrapper for ListElement {
             Self::Artist(v) => v,
             Self::Album(v) => v,
             Self::Song(v) => v,
+            Self::Disc(v) => v,
         }
     }
 }
@@ -870,11 +1313,17 @@ pub struct ListArtist {
     sel: bool,
 }
 impl ListArtist {
-    pub fn new(mut config: GuiElemCfg, id: ArtistId, name: String, selected: Selected) -> Self {
+    pub fn new(
+        mut config: GuiElemCfg,
+        id: ArtistId,
+        name: String,
+        color: Color,
+        selected: Selected,
+    ) -> Self {
         let label = Label::new(
             GuiElemCfg::default(),
             name,
-            Color::from_int_rgb(81, 24, 125),
+            color,
             None,
             Vec2::new(0.0, 0.5),
         );
@@ -922,7 +1371,7 @@ impl GuiElem for ListArtist {
                     self.children.push(Box::new(Panel::with_background(
                         GuiElemCfg::default(),
                         (),
-                        Color::from_rgba(1.0, 1.0, 1.0, 0.2),
+                        info.gui_config.colors.selection,
                     )));
                 } else {
                     self.children.pop();
@@ -983,6 +1432,33 @@ impl GuiElem for ListArtist {
         }
         vec![]
     }
+    fn mouse_pressed(&mut self, e: &mut EventInfo, button: MouseButton) -> Vec<GuiAction> {
+        if button == MouseButton::Right && e.take() {
+            let id = self.id;
+            let selected = self.selected.clone();
+            vec![GuiAction::Build(Box::new(move |db| {
+                if let Some(artist) = db.artists().get(&id) {
+                    let songs = artist
+                        .singles
+                        .iter()
+                        .chain(
+                            artist
+                                .albums
+                                .iter()
+                                .filter_map(|id| db.albums().get(id))
+                                .flat_map(|album| album.songs.iter()),
+                        )
+                        .filter_map(|id| db.get_song(id).cloned())
+                        .collect();
+                    context_menu_songs(songs, selected, move |db| add_to_queue_artist_by_id(id, db))
+                } else {
+                    vec![]
+                }
+            }))]
+        } else {
+            vec![]
+        }
+    }
 }
 
 pub struct ListAlbum {
@@ -1000,6 +1476,7 @@ impl ListAlbum {
         id: AlbumId,
         name: String,
         half_sized_info: String,
+        color: Color,
         selected: Selected,
     ) -> Self {
         let label = AdvancedLabel::new(
@@ -1007,10 +1484,7 @@ impl ListAlbum {
             Vec2::new(0.0, 0.5),
             vec![vec![
                 (
-                    gui_text::AdvancedContent::Text(gui_text::Content::new(
-                        name,
-                        Color::from_int_rgb(8, 61, 47),
-                    )),
+                    gui_text::AdvancedContent::Text(gui_text::Content::new(name, color)),
                     1.0,
                     1.0,
                 ),
@@ -1068,7 +1542,7 @@ impl GuiElem for ListAlbum {
                     self.children.push(Box::new(Panel::with_background(
                         GuiElemCfg::default(),
                         (),
-                        Color::from_rgba(1.0, 1.0, 1.0, 0.2),
+                        info.gui_config.colors.selection,
                     )));
                 } else {
                     self.children.pop();
@@ -1129,6 +1603,26 @@ impl GuiElem for ListAlbum {
         }
         vec![]
     }
+    fn mouse_pressed(&mut self, e: &mut EventInfo, button: MouseButton) -> Vec<GuiAction> {
+        if button == MouseButton::Right && e.take() {
+            let id = self.id;
+            let selected = self.selected.clone();
+            vec![GuiAction::Build(Box::new(move |db| {
+                if let Some(album) = db.albums().get(&id) {
+                    let songs = album
+                        .songs
+                        .iter()
+                        .filter_map(|id| db.get_song(id).cloned())
+                        .collect();
+                    context_menu_songs(songs, selected, move |db| add_to_queue_album_by_id(id, db))
+                } else {
+                    vec![]
+                }
+            }))]
+        } else {
+            vec![]
+        }
+    }
 }
 
 pub struct ListSong {
@@ -1139,13 +1633,17 @@ pub struct ListSong {
     mouse_pos: Vec2,
     selected: Selected,
     sel: bool,
+    last_click: Option<Instant>,
 }
+/// clicking a song twice within this time plays it
+const DOUBLE_CLICK_TIME: Duration = Duration::from_millis(400);
 impl ListSong {
     pub fn new(
         mut config: GuiElemCfg,
         id: SongId,
         name: String,
         duration: String,
+        color: Color,
         selected: Selected,
     ) -> Self {
         let label = AdvancedLabel::new(
@@ -1153,10 +1651,7 @@ impl ListSong {
             Vec2::new(0.0, 0.5),
             vec![vec![
                 (
-                    gui_text::AdvancedContent::Text(gui_text::Content::new(
-                        name,
-                        Color::from_int_rgb(175, 175, 175),
-                    )),
+                    gui_text::AdvancedContent::Text(gui_text::Content::new(name, color)),
                     1.0,
                     1.0,
                 ),
@@ -1176,6 +1671,7 @@ impl ListSong {
             mouse_pos: Vec2::ZERO,
             selected,
             sel: false,
+            last_click: None,
         }
     }
 }
@@ -1211,7 +1707,7 @@ impl GuiElem for ListSong {
                     self.children.push(Box::new(Panel::with_background(
                         GuiElemCfg::default(),
                         (),
-                        Color::from_rgba(1.0, 1.0, 1.0, 0.2),
+                        info.gui_config.colors.selection,
                     )));
                 } else {
                     self.children.pop();
@@ -1263,11 +1759,23 @@ impl GuiElem for ListSong {
             self.mouse = false;
             self.config.redraw = true;
             if e.take() {
+                // the first click of a double-click selected/deselected the song,
+                // so toggling again restores the previous selection
                 if !self.sel {
                     self.selected.insert_song(self.id);
                 } else {
                     self.selected.remove_song(&self.id);
                 }
+                let now = Instant::now();
+                if self
+                    .last_click
+                    .is_some_and(|t| now.duration_since(t) < DOUBLE_CLICK_TIME)
+                {
+                    self.last_click = None;
+                    let id = self.id;
+                    return vec![play_now(move |_| Some(QueueContent::Song(id).into()))];
+                }
+                self.last_click = Some(now);
             }
         }
         vec![]
@@ -1275,28 +1783,203 @@ impl GuiElem for ListSong {
     fn mouse_pressed(&mut self, e: &mut EventInfo, button: MouseButton) -> Vec<GuiAction> {
         if button == MouseButton::Right && e.take() {
             let id = self.id;
+            let selected = self.selected.clone();
             vec![GuiAction::Build(Box::new(move |db| {
                 if let Some(me) = db.songs().get(&id) {
                     let me = me.clone();
-                    vec![GuiAction::ContextMenu(Some(vec![Box::new(Button::new(
+                    vec![GuiAction::ContextMenu(Some(vec![
+                        context_menu_button("Edit", move || {
+                            vec![GuiAction::EditSongs(vec![me.clone()])]
+                        }),
+                        context_menu_button("Play now", move || {
+                            vec![play_now(move |_| Some(QueueContent::Song(id).into()))]
+                        }),
+                        context_menu_button("Play next", move || {
+                            vec![play_next(move |_| Some(QueueContent::Song(id).into()))]
+                        }),
+                        context_menu_button("Export selection as M3U", move || {
+                            vec![export_selection_m3u(selected.clone())]
+                        }),
+                    ]))]
+                } else {
+                    vec![]
+                }
+            }))]
+        } else {
+            vec![]
+        }
+    }
+}
+
+/// adds the queue element to the end of the queue and starts playing it
+fn play_now(queue: impl FnOnce(&Database) -> Option<Queue> + 'static) -> GuiAction {
+    GuiAction::Build(Box::new(move |db| {
+        if let Some(q) = queue(db) {
+            let index = match db.queue.content() {
+                QueueContent::Folder(folder) => folder.content.len(),
+                _ => 0,
+            };
+            vec![GuiAction::SendToServer(Action::Multiple(vec![
+                Action::QueueAdd(vec![], vec![q], Req::none()),
+                Action::PlayFrom(vec![index]),
+            ]))]
+        } else {
+            vec![]
+        }
+    }))
+}
+/// inserts the queue element right after the current one, or adds it to the end of the queue if nothing is playing
+fn play_next(queue: impl FnOnce(&Database) -> Option<Queue> + 'static) -> GuiAction {
+    GuiAction::Build(Box::new(move |db| {
+        if let Some(q) = queue(db) {
+            vec![GuiAction::SendToServer(Action::QueueInsertAfterCurrent(
+                vec![q],
+            ))]
+        } else {
+            vec![]
+        }
+    }))
+}
+/// asks where to save the selected songs as an M3U playlist, then writes it.
+/// this happens in a new thread because the dialog blocks. the result is shown as a notification.
+fn export_selection_m3u(selected: Selected) -> GuiAction {
+    GuiAction::Do(Box::new(move |gui| {
+        let db = gui.database.lock().unwrap();
+        let queues = selected.as_queue(&gui.gui.c_main_view.children.library_browser, &db);
+        let (m3u, unresolved) = to_m3u(&queues, &db);
+        drop(db);
+        let songs = m3u.lines().filter(|l| !l.starts_with('#')).count();
+        let notif_sender = gui.notif_sender.clone();
+        let notify = move |text: String| {
+            _ = notif_sender.send(Box::new(move |_| {
+                (
+                    Box::new(Panel::with_background(
                         GuiElemCfg::default(),
-                        move |_| vec![GuiAction::EditSongs(vec![me.clone()])],
                         [Label::new(
                             GuiElemCfg::default(),
-                            format!("Edit"),
+                            text,
                             Color::WHITE,
                             None,
-                            Vec2::new_y(0.5),
+                            Vec2::new(0.5, 0.5),
                         )],
-                    ))]))]
+                        Color::from_rgba(0.0, 0.0, 0.0, 0.8),
+                    )),
+                    NotifInfo::new(Duration::from_secs(3)),
+                )
+            }));
+        };
+        if songs == 0 && unresolved.is_empty() {
+            notify("No songs selected".to_owned());
+            return;
+        }
+        std::thread::spawn(move || {
+            let Some(path) = save_file_dialog("selection.m3u8") else {
+                return;
+            };
+            if let Err(e) = std::fs::write(&path, m3u) {
+                eprintln!("[warn] couldn't write {path:?}: {e}");
+                notify(format!("Couldn't export the selection: {e}"));
+                return;
+            }
+            for id in &unresolved {
+                eprintln!("[warn] m3u export: song #{id} isn't in the database");
+            }
+            notify(if unresolved.is_empty() {
+                format!("Exported {songs} songs to {}", path.to_string_lossy())
+            } else {
+                format!(
+                    "Exported {songs} songs to {}, {} songs couldn't be found",
+                    path.to_string_lossy(),
+                    unresolved.len()
+                )
+            });
+        });
+    }))
+}
+/// shows a save dialog using `zenity` or `kdialog`. if neither is installed, a file in the home directory is used.
+/// returns `None` if the user cancelled.
+fn save_file_dialog(file_name: &str) -> Option<std::path::PathBuf> {
+    let dialogs = [
+        (
+            "zenity",
+            vec![
+                "--file-selection".to_owned(),
+                "--save".to_owned(),
+                "--confirm-overwrite".to_owned(),
+                format!("--filename={file_name}"),
+            ],
+        ),
+        (
+            "kdialog",
+            vec!["--getsavefilename".to_owned(), file_name.to_owned()],
+        ),
+    ];
+    for (program, args) in dialogs {
+        if let Ok(out) = std::process::Command::new(program).args(args).output() {
+            let path = String::from_utf8_lossy(&out.stdout).trim().to_owned();
+            return if out.status.success() && !path.is_empty() {
+                Some(path.into())
+            } else {
+                None
+            };
+        }
+    }
+    directories::UserDirs::new().map(|dirs| dirs.home_dir().join(file_name))
+}
+fn context_menu_button(
+    text: &str,
+    action: impl Fn() -> Vec<GuiAction> + 'static,
+) -> Box<dyn GuiElem> {
+    Box::new(Button::new(
+        GuiElemCfg::default(),
+        move |_| action(),
+        [Label::new(
+            GuiElemCfg::default(),
+            text.to_owned(),
+            Color::WHITE,
+            None,
+            Vec2::new_y(0.5),
+        )],
+    ))
+}
+/// context menu for albums and artists, `queue` creates the element for "Play now", "Play next" and "Add to queue"
+fn context_menu_songs(
+    songs: Vec<Song>,
+    selected: Selected,
+    queue: impl Fn(&Database) -> Option<Queue> + Clone + 'static,
+) -> Vec<GuiAction> {
+    let ids = songs.iter().map(|s| s.id).collect::<Vec<_>>();
+    let q1 = queue.clone();
+    let q2 = queue.clone();
+    vec![GuiAction::ContextMenu(Some(vec![
+        context_menu_button("Edit", move || vec![GuiAction::EditSongs(songs.clone())]),
+        context_menu_button("Play now", move || vec![play_now(q1.clone())]),
+        context_menu_button("Play next", move || vec![play_next(q2.clone())]),
+        context_menu_button("Add to queue", move || {
+            let queue = queue.clone();
+            vec![GuiAction::Build(Box::new(move |db| {
+                if let Some(q) = queue(db) {
+                    vec![GuiAction::SendToServer(Action::QueueAdd(
+                        vec![],
+                        vec![q],
+                        Req::none(),
+                    ))]
                 } else {
                     vec![]
                 }
             }))]
-        } else {
+        }),
+        context_menu_button("Export selection as M3U", {
+            let selected = selected.clone();
+            move || vec![export_selection_m3u(selected.clone())]
+        }),
+        context_menu_button("Select all songs", move || {
+            for id in &ids {
+                selected.insert_song(*id);
+            }
             vec![]
-        }
-    }
+        }),
+    ]))]
 }
 
 pub struct FilterPanel {
@@ -1305,6 +1988,8 @@ pub struct FilterPanel {
         Button<[Label; 1]>,
         Button<[Label; 1]>,
         Button<[Label; 1]>,
+        Panel<[Button<[Label; 1]>; 2]>,
+        Button<[Label; 1]>,
         Panel<[Button<[Label; 1]>; 3]>,
     )>,
     c_tab_filters_songs: ScrollBox<FilterTab>,
@@ -1318,10 +2003,13 @@ pub struct FilterPanel {
     filter_songs: Arc<Mutex<Filter>>,
     filter_albums: Arc<Mutex<Filter>>,
     filter_artists: Arc<Mutex<Filter>>,
+    tag_suggestions: TagSuggestions,
 }
 #[derive(Default)]
 struct FilterTab {
     buttons: Vec<Button<[Label; 1]>>,
+    /// name + "save as preset"
+    save_preset: Option<Panel<(TextField, Button<[Label; 1]>)>>,
     filters: Vec<FilterLine>,
 }
 enum FilterLine {
@@ -1357,31 +2045,42 @@ impl GuiElemChildren for FilterTab {
             self.buttons
                 .iter_mut()
                 .map(|v| v.elem_mut())
+                .chain(self.save_preset.iter_mut().map(|v| v.elem_mut()))
                 .chain(self.filters.iter_mut().map(|v| v.elem_mut())),
         )
     }
     fn len(&self) -> usize {
-        self.buttons.len() + self.filters.len()
+        self.buttons.len() + self.save_preset.iter().len() + self.filters.len()
     }
 }
 const FP_CASESENS_N: &'static str = "search is case-insensitive";
 const FP_CASESENS_Y: &'static str = "search is case-sensitive!";
 const FP_PREFSTART_N: &'static str = "simple search";
 const FP_PREFSTART_Y: &'static str = "will prefer matches at the start of a word";
+const FP_FOLLOW_N: &'static str = "don't follow";
+const FP_FOLLOW_Y: &'static str = "follow current song";
+/// how much nested filters are indented in the filter editor
+const FP_INDENT_BY: f32 = 0.05;
 impl FilterPanel {
     pub fn new(
         search_settings_changed: Arc<AtomicBool>,
         search_is_case_sensitive: Arc<AtomicBool>,
         search_prefer_start_matches: Arc<AtomicBool>,
+        sort_mode: Arc<AtomicUsize>,
+        follow_current: Arc<AtomicBool>,
         filter_songs: Arc<Mutex<Filter>>,
         filter_albums: Arc<Mutex<Filter>>,
         filter_artists: Arc<Mutex<Filter>>,
+        tag_suggestions: TagSuggestions,
         selected: Selected,
         do_something_sender: mpsc::Sender<Box<dyn FnOnce(&mut LibraryBrowser)>>,
     ) -> Self {
         let is_case_sensitive = search_is_case_sensitive.load(std::sync::atomic::Ordering::Relaxed);
         let prefer_start_matches =
             search_prefer_start_matches.load(std::sync::atomic::Ordering::Relaxed);
+        let is_following_current = follow_current.load(std::sync::atomic::Ordering::Relaxed);
+        let initial_sort_mode =
+            SortMode::from_index(sort_mode.load(std::sync::atomic::Ordering::Relaxed));
         let ssc1 = Arc::clone(&search_settings_changed);
         let ssc2 = Arc::clone(&search_settings_changed);
         let sel3 = selected.clone();
@@ -1458,6 +2157,86 @@ impl FilterPanel {
                         Vec2::new(0.5, 0.5),
                     )],
                 ),
+                Button::new(
+                    GuiElemCfg::default(),
+                    move |button| {
+                        let v = SortMode::from_index(
+                            sort_mode.load(std::sync::atomic::Ordering::Relaxed) + 1,
+                        );
+                        sort_mode.store(v.index(), std::sync::atomic::Ordering::Relaxed);
+                        *button
+                            .children()
+                            .next()
+                            .unwrap()
+                            .any_mut()
+                            .downcast_mut::<Label>()
+                            .unwrap()
+                            .content
+                            .text() = v.label().to_owned();
+                        vec![]
+                    },
+                    [Label::new(
+                        GuiElemCfg::default(),
+                        initial_sort_mode.label().to_owned(),
+                        Color::GRAY,
+                        None,
+                        Vec2::new(0.5, 0.5),
+                    )],
+                ),
+                Panel::new(
+                    GuiElemCfg::default(),
+                    [
+                        Button::new(
+                            GuiElemCfg::at(Rectangle::from_tuples((0.0, 0.0), (0.5, 1.0))),
+                            {
+                                let dss = do_something_sender.clone();
+                                move |_| {
+                                    dss.send(Box::new(|s| s.jump_to_current = true)).unwrap();
+                                    vec![]
+                                }
+                            },
+                            [Label::new(
+                                GuiElemCfg::default(),
+                                "jump to current".to_owned(),
+                                Color::GRAY,
+                                None,
+                                Vec2::new(0.5, 0.5),
+                            )],
+                        ),
+                        Button::new(
+                            GuiElemCfg::at(Rectangle::from_tuples((0.55, 0.0), (1.0, 1.0))),
+                            move |button| {
+                                let v = !follow_current.load(std::sync::atomic::Ordering::Relaxed);
+                                follow_current.store(v, std::sync::atomic::Ordering::Relaxed);
+                                *button
+                                    .children()
+                                    .next()
+                                    .unwrap()
+                                    .any_mut()
+                                    .downcast_mut::<Label>()
+                                    .unwrap()
+                                    .content
+                                    .text() = if v {
+                                    FP_FOLLOW_Y.to_owned()
+                                } else {
+                                    FP_FOLLOW_N.to_owned()
+                                };
+                                vec![]
+                            },
+                            [Label::new(
+                                GuiElemCfg::default(),
+                                if is_following_current {
+                                    FP_FOLLOW_Y.to_owned()
+                                } else {
+                                    FP_FOLLOW_N.to_owned()
+                                },
+                                Color::GRAY,
+                                None,
+                                Vec2::new(0.5, 0.5),
+                            )],
+                        ),
+                    ],
+                ),
                 Button::new(
                     GuiElemCfg::default(),
                     move |_| {
@@ -1619,6 +2398,7 @@ impl FilterPanel {
             filter_songs,
             filter_albums,
             filter_artists,
+            tag_suggestions,
         }
     }
     fn build_filter(
@@ -1628,6 +2408,7 @@ impl FilterPanel {
         on_change: &Arc<impl Fn(bool) + 'static>,
         path: Vec<usize>,
         gui_cfg: &GuiConfig,
+        tag_suggestions: &TagSuggestions,
     ) -> (FilterTab, Vec<f32>) {
         let f0 = Arc::clone(filter);
         let oc0 = Arc::clone(on_change);
@@ -1637,9 +2418,9 @@ impl FilterPanel {
             filter,
             &mut filters,
             0.0,
-            0.05,
             on_change,
             path,
+            tag_suggestions,
         );
         let ft = FilterTab {
             buttons: [Button::new(
@@ -1687,19 +2468,77 @@ impl FilterPanel {
                 }),
             )
             .collect(),
+            save_preset: Some(Self::build_save_preset(tab, filter, on_change)),
             filters,
         };
         let r = 0..ft.len();
         (ft, r.map(|_| line_height).collect())
     }
+    fn build_save_preset(
+        tab: usize,
+        filter: &Arc<Mutex<Filter>>,
+        on_change: &Arc<impl Fn(bool) + 'static>,
+    ) -> Panel<(TextField, Button<[Label; 1]>)> {
+        let name = Arc::new(Mutex::new(String::new()));
+        let mut tf = TextField::new(
+            GuiElemCfg::at(Rectangle::from_tuples((0.0, 0.0), (0.6, 1.0))),
+            "preset name".to_owned(),
+            Color::GRAY,
+            Color::WHITE,
+        );
+        {
+            let name = Arc::clone(&name);
+            tf.on_changed = Some(Box::new(move |text| {
+                *name.lock().unwrap() = text.trim().to_owned();
+            }));
+        }
+        let f = Arc::clone(filter);
+        let oc = Arc::clone(on_change);
+        Panel::new(
+            GuiElemCfg::default(),
+            (
+                tf,
+                Button::new(
+                    GuiElemCfg::at(Rectangle::from_tuples((0.65, 0.0), (1.0, 1.0))),
+                    move |_| {
+                        let name = name.lock().unwrap().clone();
+                        let mut filter = f.lock().unwrap().clone();
+                        if name.is_empty() || filter.filters.is_empty() {
+                            return vec![];
+                        }
+                        let preset = if filter.filters.len() == 1 {
+                            filter.filters.pop().unwrap()
+                        } else {
+                            FilterType::Nested(filter)
+                        };
+                        let oc = Arc::clone(&oc);
+                        vec![GuiAction::Do(Box::new(move |gui| {
+                            if let Some(cfg) = &mut gui.gui_config {
+                                cfg.add_filter_preset(tab, name, preset);
+                                // rebuild the tab to show the new preset
+                                oc(true);
+                            }
+                        }))]
+                    },
+                    [Label::new(
+                        GuiElemCfg::default(),
+                        "save as preset".to_owned(),
+                        Color::GRAY,
+                        None,
+                        Vec2::new(0.5, 0.5),
+                    )],
+                ),
+            ),
+        )
+    }
     fn build_filter_editor(
         filter: &Filter,
         mutex: &Arc<Mutex<Filter>>,
         children: &mut Vec<FilterLine>,
         mut indent: f32,
-        indent_by: f32,
         on_change: &Arc<impl Fn(bool) + 'static>,
         path: Vec<usize>,
+        tag_suggestions: &TagSuggestions,
     ) {
         if filter.filters.len() > 1 {
             let mx = Arc::clone(mutex);
@@ -1727,13 +2566,19 @@ impl FilterPanel {
                 )],
             )));
         }
-        indent += indent_by;
+        indent += FP_INDENT_BY;
         for (i, f) in filter.filters.iter().enumerate() {
             let mut path = path.clone();
             path.push(i);
             match f {
                 FilterType::Nested(f) => Self::build_filter_editor(
-                    f, mutex, children, indent, indent_by, on_change, path,
+                    f,
+                    mutex,
+                    children,
+                    indent,
+                    on_change,
+                    path,
+                    tag_suggestions,
                 ),
                 FilterType::Not(f) => {
                     children.push(FilterLine::Not(Label::new(
@@ -1744,7 +2589,13 @@ impl FilterPanel {
                         Vec2::new(0.0, 0.5),
                     )));
                     Self::build_filter_editor(
-                        f, mutex, children, indent, indent_by, on_change, path,
+                        f,
+                        mutex,
+                        children,
+                        indent,
+                        on_change,
+                        path,
+                        tag_suggestions,
                     )
                 }
                 FilterType::TagEq(v) => {
@@ -1754,7 +2605,8 @@ impl FilterPanel {
                         "tag value".to_owned(),
                         Color::GRAY,
                         Color::WHITE,
-                    );
+                    )
+                    .with_suggestions(Arc::clone(&tag_suggestions.tags));
                     let mx = Arc::clone(mutex);
                     let oc = Arc::clone(on_change);
                     tf.on_changed = Some(Box::new(move |text| {
@@ -1784,7 +2636,8 @@ impl FilterPanel {
                         "tag value".to_owned(),
                         Color::GRAY,
                         Color::WHITE,
-                    );
+                    )
+                    .with_suggestions(Arc::clone(&tag_suggestions.tags));
                     let mx = Arc::clone(mutex);
                     let oc = Arc::clone(on_change);
                     tf.on_changed = Some(Box::new(move |text| {
@@ -1816,7 +2669,8 @@ impl FilterPanel {
                         "tag value".to_owned(),
                         Color::GRAY,
                         Color::WHITE,
-                    );
+                    )
+                    .with_suggestions(Arc::clone(&tag_suggestions.keys));
                     let mx = Arc::clone(mutex);
                     let oc = Arc::clone(on_change);
                     let p = path.clone();
@@ -1888,6 +2742,121 @@ impl FilterPanel {
         }
     }
 }
+/// Filters in `config_gui.toml`'s `[[filter_presets.<song|album|artist>]]` look like this:
+/// `{ eq = "Fav" }`, `{ starts_with = "Genre=" }`, `{ int = "Year=", min = 1990, max = 1999 }`,
+/// `{ not = <filter> }`, and `{ and = [<filters>] }` or `{ or = [<filters>] }` to combine filters.
+pub fn filter_type_from_toml(v: &toml::Value) -> Result<FilterType, String> {
+    let t = v
+        .as_table()
+        .ok_or_else(|| format!("expected a table, found `{v}`"))?;
+    let str = |key: &str| {
+        t[key]
+            .as_str()
+            .map(|v| v.to_owned())
+            .ok_or_else(|| format!("`{key}` must be a string"))
+    };
+    let int = |key: &str| {
+        t.get(key)
+            .and_then(|v| v.as_integer())
+            .and_then(|v| v.try_into().ok())
+            .ok_or_else(|| format!("`{key}` must be a (32-bit) integer"))
+    };
+    if t.contains_key("eq") {
+        Ok(FilterType::TagEq(str("eq")?))
+    } else if t.contains_key("starts_with") {
+        Ok(FilterType::TagStartsWith(str("starts_with")?))
+    } else if t.contains_key("int") {
+        Ok(FilterType::TagWithValueInt(
+            str("int")?,
+            int("min")?,
+            int("max")?,
+        ))
+    } else if let Some(v) = t.get("not") {
+        Ok(FilterType::Not(filter_from_toml(v)?))
+    } else {
+        Ok(FilterType::Nested(filter_from_toml(v)?))
+    }
+}
+fn filter_from_toml(v: &toml::Value) -> Result<Filter, String> {
+    let (and, filters) = match (v.get("and"), v.get("or")) {
+        (Some(filters), None) => (true, filters),
+        (None, Some(filters)) => (false, filters),
+        _ => return Err(format!("expected exactly one of `and` or `or` in `{v}`")),
+    };
+    Ok(Filter {
+        and,
+        filters: filters
+            .as_array()
+            .ok_or_else(|| format!("`{filters}` must be an array"))?
+            .iter()
+            .map(filter_type_from_toml)
+            .collect::<Result<_, _>>()?,
+    })
+}
+pub fn filter_type_to_toml(f: &FilterType) -> toml::Value {
+    let mut t = toml::Table::new();
+    match f {
+        FilterType::Nested(f) => return filter_to_toml(f),
+        FilterType::Not(f) => {
+            t.insert("not".to_owned(), filter_to_toml(f));
+        }
+        FilterType::TagEq(v) => {
+            t.insert("eq".to_owned(), toml::Value::String(v.clone()));
+        }
+        FilterType::TagStartsWith(v) => {
+            t.insert("starts_with".to_owned(), toml::Value::String(v.clone()));
+        }
+        FilterType::TagWithValueInt(v, min, max) => {
+            t.insert("int".to_owned(), toml::Value::String(v.clone()));
+            t.insert("min".to_owned(), toml::Value::Integer(*min as _));
+            t.insert("max".to_owned(), toml::Value::Integer(*max as _));
+        }
+    }
+    toml::Value::Table(t)
+}
+fn filter_to_toml(f: &Filter) -> toml::Value {
+    let mut t = toml::Table::new();
+    t.insert(
+        if f.and { "and" } else { "or" }.to_owned(),
+        toml::Value::Array(f.filters.iter().map(filter_type_to_toml).collect()),
+    );
+    toml::Value::Table(t)
+}
+
+/// Existing tags, used to autocomplete tag values in the filter editor.
+/// Both lists are ordered by how often the tag is used (most common first).
+#[derive(Clone, Default)]
+pub struct TagSuggestions {
+    /// all tags
+    tags: Arc<Mutex<Vec<String>>>,
+    /// the `Key=` part of `Key=Value` tags, for `TagWithValueInt`
+    keys: Arc<Mutex<Vec<String>>>,
+}
+impl TagSuggestions {
+    fn update(&self, db: &Database) {
+        let mut tags = HashMap::<&str, usize>::new();
+        let mut keys = HashMap::<&str, usize>::new();
+        let general = db
+            .songs()
+            .values()
+            .map(|v| &v.general)
+            .chain(db.albums().values().map(|v| &v.general))
+            .chain(db.artists().values().map(|v| &v.general));
+        for tag in general.flat_map(|v| &v.tags) {
+            *tags.entry(tag).or_default() += 1;
+            if let Some(i) = tag.find('=') {
+                *keys.entry(&tag[..=i]).or_default() += 1;
+            }
+        }
+        fn by_count(map: HashMap<&str, usize>) -> Vec<String> {
+            let mut list = map.into_iter().collect::<Vec<_>>();
+            list.sort_unstable_by(|(a, an), (b, bn)| bn.cmp(an).then_with(|| a.cmp(b)));
+            list.into_iter().map(|(v, _)| v.to_owned()).collect()
+        }
+        *self.tags.lock().unwrap() = by_count(tags);
+        *self.keys.lock().unwrap() = by_count(keys);
+    }
+}
 impl GuiElem for FilterPanel {
     fn draw(&mut self, info: &mut DrawInfo, _g: &mut speedy2d::Graphics2D) {
         // set line height
@@ -1968,6 +2937,7 @@ impl GuiElem for FilterPanel {
                         }),
                         vec![],
                         info.gui_config,
+                        &self.tag_suggestions,
                     );
                     sb.children = ft;
                     sb.children_heights = heights;
@@ -2008,88 +2978,8 @@ impl GuiElem for FilterPanel {
         self
     }
 }
-#[derive(Clone)]
-pub struct Filter {
-    and: bool,
-    filters: Vec<FilterType>,
-}
-#[derive(Clone)]
-#[allow(unused)]
-pub enum FilterType {
-    Nested(Filter),
-    Not(Filter),
-    TagEq(String),
-    TagStartsWith(String),
-    /// true if the tag is '<String><Integer>' and Integer is between min and max (both inclusive)
-    /// note: <String> usually ends with '='.
-    TagWithValueInt(String, i32, i32),
-}
-impl Filter {
-    pub fn passes(&self, gd: &GeneralData) -> bool {
-        if self.filters.is_empty() {
-            return true;
-        }
-        let mut iter = self.filters.iter().map(|v| v.passes(gd));
-        if self.and {
-            iter.all(|v| v)
-        } else {
-            iter.any(|v| v)
-        }
-    }
-    pub fn get_mut(&mut self, path: &[usize]) -> Option<Result<&mut FilterType, &mut Self>> {
-        if let Some(i) = path.first() {
-            let p = &path[1..];
-            if let Some(f) = self.filters.get_mut(*i) {
-                f.get_mut(p)
-            } else {
-                None
-            }
-        } else {
-            Some(Err(self))
-        }
-    }
-}
-impl FilterType {
-    pub fn passes(&self, gd: &GeneralData) -> bool {
-        match self {
-            Self::Nested(f) => f.passes(gd),
-            Self::Not(f) => !f.passes(gd),
-            Self::TagEq(v) => gd.tags.iter().any(|t| t == v),
-            Self::TagStartsWith(v) => gd.tags.iter().any(|t| t.starts_with(v)),
-            Self::TagWithValueInt(v, min, max) => gd.tags.iter().any(|t| {
-                if t.starts_with(v) {
-                    if let Ok(val) = t[v.len()..].parse() {
-                        *min <= val && val <= *max
-                    } else {
-                        false
-                    }
-                } else {
-                    false
-                }
-            }),
-        }
-    }
-    pub fn get_mut(&mut self, path: &[usize]) -> Option<Result<&mut Self, &mut Filter>> {
-        if path.is_empty() {
-            Some(Ok(self))
-        } else {
-            if let Some(f) = self.inner_filter() {
-                f.get_mut(path)
-            } else {
-                None
-            }
-        }
-    }
-    pub fn inner_filter(&mut self) -> Option<&mut Filter> {
-        match self {
-            Self::Nested(f) | Self::Not(f) => Some(f),
-            Self::TagEq(_) | Self::TagStartsWith(_) | Self::TagWithValueInt(..) => None,
-        }
-    }
-}
-
 mod selected {
-    use musicdb_lib::data::queue::QueueFolder;
+    use musicdb_lib::data::queue::{QueueFolder, ShuffleWeight};
 
     use super::*;
     #[derive(Clone)]
@@ -2204,6 +3094,8 @@ mod selected {
                                     None => "< unknown album >".to_owned(),
                                 },
                                 order: None,
+                                seed: None,
+                                weight: ShuffleWeight::Uniform,
                             })
                             .into(),
                         );
@@ -2219,6 +3111,8 @@ mod selected {
                                 None => "< unknown artist >".to_owned(),
                             },
                             order: None,
+                            seed: None,
+                            weight: ShuffleWeight::Uniform,
                         })
                         .into(),
                     );
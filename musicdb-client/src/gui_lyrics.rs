@@ -0,0 +1,193 @@
+use std::{
+    sync::{mpsc, Arc},
+    time::Instant,
+};
+
+use musicdb_lib::data::{lyrics::Lyrics, SongId};
+use speedy2d::{color::Color, dimen::Vec2};
+
+use crate::{
+    gui::{DrawInfo, GuiElem, GuiElemCfg},
+    gui_base::{ScrollBox, ScrollBoxSizeUnit},
+    gui_text::Label,
+};
+
+/*
+
+Shows the lyrics of the current song, from its `Lyrics=` tag or from a `.lrc` file next to the song file.
+For timed lyrics, the current line is highlighted and scrolled into view.
+
+*/
+
+pub struct LyricsPanel {
+    config: GuiElemCfg,
+    c_scroll_box: ScrollBox<Vec<Label>>,
+    song: Option<SongId>,
+    lyrics: Option<Lyrics>,
+    current_line: Option<usize>,
+    /// the song's tags may have changed, load the lyrics again
+    reload: bool,
+    /// playback position (ms) at the given time, like in `PlaybackBar`
+    position: (u64, Instant),
+    playing: bool,
+    db_position: Option<(u64, Instant)>,
+    sender: mpsc::Sender<(SongId, Option<Lyrics>)>,
+    recv: mpsc::Receiver<(SongId, Option<Lyrics>)>,
+}
+const COLOR_LINE: Color = Color::from_rgb(0.6, 0.6, 0.6);
+const COLOR_CURRENT_LINE: Color = Color::WHITE;
+impl LyricsPanel {
+    pub fn new(config: GuiElemCfg) -> Self {
+        let (sender, recv) = mpsc::channel();
+        Self {
+            config,
+            c_scroll_box: ScrollBox::new(
+                GuiElemCfg::default(),
+                ScrollBoxSizeUnit::Pixels,
+                vec![],
+                vec![],
+                0.0,
+            ),
+            song: None,
+            lyrics: None,
+            current_line: None,
+            reload: false,
+            position: (0, Instant::now()),
+            playing: false,
+            db_position: None,
+            sender,
+            recv,
+        }
+    }
+    fn set_lyrics(&mut self, lyrics: Option<Lyrics>, line_height: f32) {
+        let lines = match &lyrics {
+            Some(lyrics) => lyrics.lines.iter().map(|(_, line)| line.clone()).collect(),
+            None if self.song.is_some() => vec!["no lyrics".to_owned()],
+            None => vec![],
+        };
+        self.c_scroll_box.children = lines
+            .into_iter()
+            .map(|line| {
+                Label::new(
+                    GuiElemCfg::default(),
+                    line,
+                    COLOR_LINE,
+                    None,
+                    Vec2::new(0.5, 0.5),
+                )
+            })
+            .collect();
+        self.c_scroll_box.children_heights = vec![line_height; self.c_scroll_box.children.len()];
+        self.c_scroll_box.scroll_to(0);
+        self.c_scroll_box.config_mut().redraw = true;
+        self.lyrics = lyrics;
+        self.current_line = None;
+    }
+    fn position_at(&self, now: Instant) -> u64 {
+        if self.playing {
+            self.position.0 + now.saturating_duration_since(self.position.1).as_millis() as u64
+        } else {
+            self.position.0
+        }
+    }
+}
+impl GuiElem for LyricsPanel {
+    fn config(&self) -> &GuiElemCfg {
+        &self.config
+    }
+    fn config_mut(&mut self) -> &mut GuiElemCfg {
+        &mut self.config
+    }
+    fn children(&mut self) -> Box<dyn Iterator<Item = &mut dyn GuiElem> + '_> {
+        Box::new([self.c_scroll_box.elem_mut()].into_iter())
+    }
+    fn any(&self) -> &dyn std::any::Any {
+        self
+    }
+    fn any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+    fn elem(&self) -> &dyn GuiElem {
+        self
+    }
+    fn elem_mut(&mut self) -> &mut dyn GuiElem {
+        self
+    }
+    fn draw(&mut self, info: &mut DrawInfo, _g: &mut speedy2d::Graphics2D) {
+        let now = info.time;
+        let song = info.database.queue.get_current_song().copied();
+        if self.config.redraw {
+            self.config.redraw = false;
+            self.c_scroll_box.children_heights =
+                vec![info.line_height; self.c_scroll_box.children.len()];
+        }
+        if song != self.song || self.reload {
+            if song != self.song {
+                self.song = song;
+                self.position = (0, now);
+            }
+            self.reload = false;
+            let song = song.and_then(|id| info.database.get_song(&id));
+            if let Some(lyrics) = song.and_then(|song| song.lyrics_tag()) {
+                let lyrics = Lyrics::parse(lyrics);
+                self.set_lyrics(Some(lyrics), info.line_height);
+            } else {
+                self.set_lyrics(None, info.line_height);
+                if let Some(song) = song {
+                    // try to load a .lrc file from the server
+                    let (id, path) = (song.id, song.lyrics_sidecar_path());
+                    let get_con = Arc::clone(&info.get_con);
+                    let sender = self.sender.clone();
+                    std::thread::spawn(move || {
+                        let lyrics = get_con
+                            .lock()
+                            .unwrap()
+                            .song_file_by_path(&path.to_string_lossy())
+                            .ok()
+                            .and_then(Result::ok)
+                            .map(|bytes| Lyrics::parse(&String::from_utf8_lossy(&bytes)));
+                        _ = sender.send((id, lyrics));
+                    });
+                }
+            }
+        }
+        while let Ok((id, lyrics)) = self.recv.try_recv() {
+            // ignore lyrics of songs which are no longer playing
+            if Some(id) == self.song && lyrics.is_some() {
+                self.set_lyrics(lyrics, info.line_height);
+            }
+        }
+        if info.database.current_song_position != self.db_position {
+            self.db_position = info.database.current_song_position;
+            if let Some(position) = self.db_position {
+                self.position = position;
+            }
+        }
+        if info.database.playing != self.playing {
+            self.position = (self.position_at(now), now);
+            self.playing = info.database.playing;
+        }
+        // highlight the current line
+        if let Some(lyrics) = self.lyrics.as_ref().filter(|v| v.is_timed()) {
+            let line = lyrics.current_line(self.position_at(now));
+            if line != self.current_line {
+                if let Some(label) = self
+                    .current_line
+                    .and_then(|i| self.c_scroll_box.children.get_mut(i))
+                {
+                    *label.content.color() = COLOR_LINE;
+                }
+                if let Some(label) = line.and_then(|i| self.c_scroll_box.children.get_mut(i)) {
+                    *label.content.color() = COLOR_CURRENT_LINE;
+                }
+                // keep a few lines above the current one visible
+                self.c_scroll_box
+                    .scroll_to(line.unwrap_or(0).saturating_sub(3));
+                self.current_line = line;
+            }
+        }
+    }
+    fn updated_library(&mut self) {
+        self.reload = true;
+    }
+}
@@ -1,10 +1,18 @@
-use std::{sync::Arc, time::Duration};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use musicdb_lib::data::{CoverId, SongId};
-use speedy2d::{color::Color, dimen::Vec2, image::ImageHandle, shape::Rectangle};
+use musicdb_lib::{
+    data::{CoverId, SongId},
+    server::Action,
+};
+use speedy2d::{
+    color::Color, dimen::Vec2, image::ImageHandle, shape::Rectangle, window::MouseButton,
+};
 
 use crate::{
-    gui::{DrawInfo, GuiAction, GuiElemCfg, GuiServerImage},
+    gui::{DrawInfo, EventInfo, GuiAction, GuiElem, GuiElemCfg, GuiServerImage},
     gui_anim::AnimationController,
     gui_base::Panel,
     gui_notif::NotifInfo,
@@ -175,3 +183,157 @@ pub fn image_display(
 pub fn get_right_x(left: f32, top: f32, bottom: f32, aspect_ratio: f32) -> f32 {
     left + aspect_ratio * (bottom - top)
 }
+
+/// Shows how much of the current song has been played. Click (or drag and release) to seek.
+/// Clients don't know the exact playback position, so it is estimated from the time the song started playing.
+pub struct PlaybackBar {
+    config: GuiElemCfg,
+    c_label: Label,
+    song: Option<SongId>,
+    duration: u64,
+    /// playback position (ms) at the given time. while paused, the position is not changing.
+    position: (u64, Instant),
+    playing: bool,
+    /// the last `db.current_song_position` we have seen
+    db_position: Option<(u64, Instant)>,
+    /// where the mouse is on the bar (0.0 to 1.0), if it is on the bar (or the bar is being dragged)
+    hover: Option<f32>,
+}
+impl PlaybackBar {
+    pub fn new(config: GuiElemCfg) -> Self {
+        Self {
+            config: config.w_mouse(),
+            c_label: Label::new(
+                GuiElemCfg::default(),
+                String::new(),
+                Color::WHITE,
+                Some(Color::from_rgba(0.0, 0.0, 0.0, 0.6)),
+                Vec2::new(0.0, 0.5),
+            ),
+            song: None,
+            duration: 0,
+            position: (0, Instant::now()),
+            playing: false,
+            db_position: None,
+            hover: None,
+        }
+    }
+    /// The estimated playback position and the duration of the current song, in milliseconds
+    pub fn playback_position(&self) -> Option<(u64, u64)> {
+        if self.song.is_some() && self.duration > 0 {
+            Some((self.position_at(Instant::now()), self.duration))
+        } else {
+            None
+        }
+    }
+    fn position_at(&self, now: Instant) -> u64 {
+        if self.playing {
+            self.position.0 + now.saturating_duration_since(self.position.1).as_millis() as u64
+        } else {
+            self.position.0
+        }
+    }
+}
+impl GuiElem for PlaybackBar {
+    fn config(&self) -> &GuiElemCfg {
+        &self.config
+    }
+    fn config_mut(&mut self) -> &mut GuiElemCfg {
+        &mut self.config
+    }
+    fn children(&mut self) -> Box<dyn Iterator<Item = &mut dyn GuiElem> + '_> {
+        Box::new([self.c_label.elem_mut()].into_iter())
+    }
+    fn any(&self) -> &dyn std::any::Any {
+        self
+    }
+    fn any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+    fn elem(&self) -> &dyn GuiElem {
+        self
+    }
+    fn elem_mut(&mut self) -> &mut dyn GuiElem {
+        self
+    }
+    fn draw(&mut self, info: &mut DrawInfo, g: &mut speedy2d::Graphics2D) {
+        let now = info.time;
+        let song = info.database.queue.get_current_song().copied();
+        if song != self.song {
+            self.song = song;
+            self.position = (0, now);
+        }
+        self.duration = song
+            .and_then(|id| info.database.get_song(&id))
+            .map_or(0, |song| song.duration_millis);
+        if info.database.current_song_position != self.db_position {
+            self.db_position = info.database.current_song_position;
+            if let Some(position) = self.db_position {
+                self.position = position;
+            }
+        }
+        if info.database.playing != self.playing {
+            self.position = (self.position_at(now), now);
+            self.playing = info.database.playing;
+        }
+        self.hover = if self.duration > 0
+            && (self.config.mouse_pressed.0 || info.pos.contains(info.mouse_pos))
+        {
+            Some(((info.mouse_pos.x - info.pos.top_left().x) / info.pos.width()).clamp(0.0, 1.0))
+        } else {
+            None
+        };
+        // draw the bar
+        let y_mid_line = 0.5 * (info.pos.top_left().y + info.pos.bottom_right().y);
+        let line_radius = info.pos.height() * 0.15;
+        g.draw_rectangle(
+            Rectangle::from_tuples(
+                (info.pos.top_left().x, y_mid_line - line_radius),
+                (info.pos.bottom_right().x, y_mid_line + line_radius),
+            ),
+            Color::from_int_rgb(50, 50, 100),
+        );
+        if self.duration > 0 {
+            let progress = (self.position_at(now) as f32 / self.duration as f32).min(1.0);
+            g.draw_rectangle(
+                Rectangle::from_tuples(
+                    (info.pos.top_left().x, y_mid_line - line_radius),
+                    (
+                        info.pos.top_left().x + info.pos.width() * progress,
+                        y_mid_line + line_radius,
+                    ),
+                ),
+                Color::CYAN,
+            );
+        }
+        // preview the time we would seek to
+        if let Some(hover) = self.hover {
+            let x = info.pos.top_left().x + info.pos.width() * hover;
+            g.draw_rectangle(
+                Rectangle::from_tuples(
+                    (x - 1.0, info.pos.top_left().y),
+                    (x + 1.0, info.pos.bottom_right().y),
+                ),
+                Color::WHITE,
+            );
+            let text = crate::gui::format_duration((self.duration as f64 * hover as f64) as u64);
+            if *self.c_label.content.get_text() != text {
+                *self.c_label.content.text() = text;
+            }
+            self.c_label.pos.x = hover;
+            self.c_label.config_mut().enabled = true;
+        } else {
+            self.c_label.config_mut().enabled = false;
+        }
+    }
+    fn mouse_up(&mut self, _e: &mut EventInfo, button: MouseButton) -> Vec<GuiAction> {
+        if button == MouseButton::Left {
+            if let Some(hover) = self.hover {
+                return vec![GuiAction::SendToServer(Action::SetCurrentSongPosition(
+                    (self.duration as f64 * hover as f64) as u64,
+                ))];
+            }
+        }
+        vec![]
+    }
+}
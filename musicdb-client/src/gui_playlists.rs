@@ -0,0 +1,223 @@
+use std::sync::mpsc;
+
+use musicdb_lib::{
+    data::queue::{QueueContent, QueueFolder},
+    server::{Action, Req},
+};
+use speedy2d::{color::Color, dimen::Vec2, shape::Rectangle};
+
+use crate::{
+    gui::{DrawInfo, GuiAction, GuiElem, GuiElemCfg},
+    gui_base::{Button, Panel, ScrollBox, ScrollBoxSizeUnit},
+    gui_text::{Label, TextField},
+};
+
+/*
+
+Lists the playlists saved on the server (see `Action::SavePlaylist`).
+Clicking a playlist adds it to the end of the queue, `play` replaces the queue with it.
+The text field's content is used as the name when saving the queue or renaming a playlist.
+
+*/
+
+pub struct PlaylistsSidebar {
+    config: GuiElemCfg,
+    c_name: TextField,
+    c_save: Button<[Label; 1]>,
+    c_scroll_box: ScrollBox<Vec<Box<dyn GuiElem>>>,
+    sender: mpsc::Sender<PlMsg>,
+    recv: mpsc::Receiver<PlMsg>,
+}
+#[derive(Clone)]
+enum PlMsg {
+    Save,
+    Load(String),
+    Play(String),
+    Rename(String),
+    Remove(String),
+}
+type PlaylistRow = Panel<(
+    Button<[Label; 1]>,
+    Button<[Label; 1]>,
+    Button<[Label; 1]>,
+    Button<[Label; 1]>,
+)>;
+impl PlaylistsSidebar {
+    pub fn new(config: GuiElemCfg) -> Self {
+        let (sender, recv) = mpsc::channel();
+        Self {
+            config,
+            c_name: TextField::new(
+                GuiElemCfg::at(Rectangle::from_tuples((0.0, 0.0), (1.0, 0.04))),
+                "playlist name".to_owned(),
+                Color::GRAY,
+                Color::WHITE,
+            ),
+            c_save: {
+                let sender = sender.clone();
+                Button::new(
+                    GuiElemCfg::at(Rectangle::from_tuples((0.0, 0.04), (1.0, 0.08))),
+                    move |_| {
+                        _ = sender.send(PlMsg::Save);
+                        vec![]
+                    },
+                    [Label::new(
+                        GuiElemCfg::default(),
+                        "Save queue as playlist".to_owned(),
+                        Color::WHITE,
+                        None,
+                        Vec2::new(0.5, 0.5),
+                    )],
+                )
+            },
+            c_scroll_box: ScrollBox::new(
+                GuiElemCfg::at(Rectangle::from_tuples((0.0, 0.09), (1.0, 1.0))),
+                ScrollBoxSizeUnit::Pixels,
+                vec![],
+                vec![],
+                0.0,
+            ),
+            sender,
+            recv,
+        }
+    }
+    fn row(&self, name: &str, songs: usize) -> PlaylistRow {
+        let button = |pos: Rectangle, text: String, msg: PlMsg| {
+            let sender = self.sender.clone();
+            Button::new(
+                GuiElemCfg::at(pos),
+                move |_| {
+                    _ = sender.send(msg.clone());
+                    vec![]
+                },
+                [Label::new(
+                    GuiElemCfg::default(),
+                    text,
+                    Color::WHITE,
+                    None,
+                    Vec2::new(0.0, 0.5),
+                )],
+            )
+        };
+        Panel::new(
+            GuiElemCfg::default(),
+            (
+                button(
+                    Rectangle::from_tuples((0.0, 0.0), (0.55, 1.0)),
+                    format!("{name} ({songs})"),
+                    PlMsg::Load(name.to_owned()),
+                ),
+                button(
+                    Rectangle::from_tuples((0.55, 0.0), (0.7, 1.0)),
+                    "play".to_owned(),
+                    PlMsg::Play(name.to_owned()),
+                ),
+                button(
+                    Rectangle::from_tuples((0.7, 0.0), (0.9, 1.0)),
+                    "rename".to_owned(),
+                    PlMsg::Rename(name.to_owned()),
+                ),
+                button(
+                    Rectangle::from_tuples((0.9, 0.0), (1.0, 1.0)),
+                    "×".to_owned(),
+                    PlMsg::Remove(name.to_owned()),
+                ),
+            ),
+        )
+    }
+}
+impl GuiElem for PlaylistsSidebar {
+    fn config(&self) -> &GuiElemCfg {
+        &self.config
+    }
+    fn config_mut(&mut self) -> &mut GuiElemCfg {
+        &mut self.config
+    }
+    fn children(&mut self) -> Box<dyn Iterator<Item = &mut dyn GuiElem> + '_> {
+        Box::new(
+            [
+                self.c_name.elem_mut(),
+                self.c_save.elem_mut(),
+                self.c_scroll_box.elem_mut(),
+            ]
+            .into_iter(),
+        )
+    }
+    fn any(&self) -> &dyn std::any::Any {
+        self
+    }
+    fn any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+    fn elem(&self) -> &dyn GuiElem {
+        self
+    }
+    fn elem_mut(&mut self) -> &mut dyn GuiElem {
+        self
+    }
+    fn draw(&mut self, info: &mut DrawInfo, _g: &mut speedy2d::Graphics2D) {
+        while let Ok(msg) = self.recv.try_recv() {
+            let name = self.c_name.c_input.content.get_text().trim().to_owned();
+            match msg {
+                PlMsg::Save => {
+                    if !name.is_empty() {
+                        info.actions
+                            .push(GuiAction::SendToServer(Action::SavePlaylist(
+                                name,
+                                info.database.queue.clone(),
+                            )));
+                    }
+                }
+                PlMsg::Load(playlist) => {
+                    if let Some(queue) = info.database.playlists().get(&playlist) {
+                        info.actions.push(GuiAction::SendToServer(Action::QueueAdd(
+                            vec![],
+                            vec![queue.clone()],
+                            Req::none(),
+                        )));
+                    }
+                }
+                PlMsg::Play(playlist) => {
+                    if let Some(queue) = info.database.playlists().get(&playlist) {
+                        let queue = QueueContent::Folder(QueueFolder {
+                            content: vec![queue.clone()],
+                            ..Default::default()
+                        });
+                        info.actions
+                            .push(GuiAction::SendToServer(Action::Multiple(vec![
+                                Action::QueueReplace(queue.into(), false),
+                                Action::Resume,
+                            ])));
+                    }
+                }
+                PlMsg::Rename(playlist) => {
+                    if !name.is_empty() && name != playlist {
+                        info.actions
+                            .push(GuiAction::SendToServer(Action::RenamePlaylist(
+                                playlist, name,
+                            )));
+                    }
+                }
+                PlMsg::Remove(playlist) => {
+                    info.actions
+                        .push(GuiAction::SendToServer(Action::RemovePlaylist(playlist)));
+                }
+            }
+        }
+        if self.config.redraw || info.pos.size() != self.config.pixel_pos.size() {
+            self.config.redraw = false;
+            let mut playlists = info.database.playlists().iter().collect::<Vec<_>>();
+            playlists.sort_unstable_by(|a, b| a.0.cmp(b.0));
+            let rows = playlists
+                .into_iter()
+                .map(|(name, queue)| Box::new(self.row(name, queue.len())) as Box<dyn GuiElem>)
+                .collect::<Vec<_>>();
+            self.c_scroll_box.children_heights = vec![info.line_height; rows.len()];
+            self.c_scroll_box.children = rows;
+            self.c_scroll_box.config_mut().redraw = true;
+        }
+    }
+    fn updated_library(&mut self) {
+        self.config.redraw = true;
+    }
+}
@@ -1,19 +1,25 @@
 use std::sync::{atomic::AtomicBool, Arc};
 
-use musicdb_lib::server::Action;
+use musicdb_lib::{
+    data::queue::{QueueContent, RepeatMode, ShuffleWeight},
+    server::Action,
+};
 use speedy2d::{color::Color, dimen::Vec2, shape::Rectangle, Graphics2D};
 
 use crate::{
     gui::{DrawInfo, GuiAction, GuiElem, GuiElemCfg},
-    gui_base::{Button, Panel},
+    gui_base::{Button, Panel, Slider},
 };
 
 pub struct PlayPause {
     config: GuiElemCfg,
     set_fav: Button<[FavIcon; 1]>,
     to_zero: Button<[Panel<()>; 1]>,
+    to_prev: Button<[SkipShape; 1]>,
     play_pause: Button<[PlayPauseDisplay; 1]>,
-    to_end: Button<[NextSongShape; 1]>,
+    to_end: Button<[SkipShape; 1]>,
+    repeat: Button<[RepeatIcon; 1]>,
+    shuffle: Button<[ShuffleIcon; 1]>,
 }
 
 impl PlayPause {
@@ -21,7 +27,7 @@ impl PlayPause {
         Self {
             config,
             set_fav: Button::new(
-                GuiElemCfg::at(Rectangle::from_tuples((0.01, 0.01), (0.24, 0.99))),
+                GuiElemCfg::at(Rectangle::from_tuples((0.005, 0.01), (0.136, 0.99))),
                 |_| {
                     vec![GuiAction::Build(Box::new(|db| {
                         if let Some(song_id) = db.queue.get_current_song() {
@@ -47,7 +53,7 @@ impl PlayPause {
                 )],
             ),
             to_zero: Button::new(
-                GuiElemCfg::at(Rectangle::from_tuples((0.26, 0.01), (0.49, 0.99))),
+                GuiElemCfg::at(Rectangle::from_tuples((0.147, 0.01), (0.278, 0.99))),
                 |_| vec![GuiAction::SendToServer(Action::Stop)],
                 [Panel::with_background(
                     GuiElemCfg::at(Rectangle::from_tuples((0.2, 0.2), (0.8, 0.8))),
@@ -55,8 +61,16 @@ impl PlayPause {
                     Color::MAGENTA,
                 )],
             ),
+            to_prev: Button::new(
+                GuiElemCfg::at(Rectangle::from_tuples((0.289, 0.01), (0.42, 0.99))),
+                |_| vec![GuiAction::SendToServer(Action::PreviousSong)],
+                [SkipShape::new(
+                    GuiElemCfg::at(Rectangle::from_tuples((0.2, 0.2), (0.8, 0.8))),
+                    true,
+                )],
+            ),
             play_pause: Button::new(
-                GuiElemCfg::at(Rectangle::from_tuples((0.51, 0.01), (0.74, 0.99))),
+                GuiElemCfg::at(Rectangle::from_tuples((0.431, 0.01), (0.562, 0.99))),
                 |btn| {
                     vec![GuiAction::SendToServer(if btn.children[0].is_playing {
                         Action::Pause
@@ -69,9 +83,43 @@ impl PlayPause {
                 ))],
             ),
             to_end: Button::new(
-                GuiElemCfg::at(Rectangle::from_tuples((0.76, 0.01), (0.99, 0.99))),
+                GuiElemCfg::at(Rectangle::from_tuples((0.573, 0.01), (0.704, 0.99))),
                 |_| vec![GuiAction::SendToServer(Action::NextSong)],
-                [NextSongShape::new(GuiElemCfg::at(Rectangle::from_tuples(
+                [SkipShape::new(
+                    GuiElemCfg::at(Rectangle::from_tuples((0.2, 0.2), (0.8, 0.8))),
+                    false,
+                )],
+            ),
+            repeat: Button::new(
+                GuiElemCfg::at(Rectangle::from_tuples((0.715, 0.01), (0.846, 0.99))),
+                |_| {
+                    vec![GuiAction::Build(Box::new(|db| {
+                        vec![GuiAction::SendToServer(Action::QueueSetRepeat(
+                            match db.repeat {
+                                RepeatMode::Off => RepeatMode::All,
+                                RepeatMode::All => RepeatMode::One,
+                                RepeatMode::One => RepeatMode::Off,
+                            },
+                        ))]
+                    }))]
+                },
+                [RepeatIcon::new(GuiElemCfg::at(Rectangle::from_tuples(
+                    (0.2, 0.2),
+                    (0.8, 0.8),
+                )))],
+            ),
+            shuffle: Button::new(
+                GuiElemCfg::at(Rectangle::from_tuples((0.857, 0.01), (0.988, 0.99))),
+                |_| {
+                    vec![GuiAction::Build(Box::new(|db| {
+                        vec![GuiAction::SendToServer(if is_shuffled(db) {
+                            Action::QueueUnshuffle(vec![])
+                        } else {
+                            Action::QueueShuffle(vec![], ShuffleWeight::Uniform, None)
+                        })]
+                    }))]
+                },
+                [ShuffleIcon::new(GuiElemCfg::at(Rectangle::from_tuples(
                     (0.2, 0.2),
                     (0.8, 0.8),
                 )))],
@@ -80,6 +128,194 @@ impl PlayPause {
     }
 }
 
+/// true if the queue's root folder is shuffled
+fn is_shuffled(db: &musicdb_lib::data::database::Database) -> bool {
+    matches!(db.queue.content(), QueueContent::Folder(folder) if folder.order.is_some())
+}
+
+/// A mute button and a volume slider.
+/// The last volume set using the slider is saved to the `volume` file in the config directory and restored on launch.
+pub struct VolumeControl {
+    config: GuiElemCfg,
+    mute: Button<[MuteIcon; 1]>,
+    slider: Slider,
+    /// was the slider being dragged during the last draw?
+    dragging: bool,
+    /// the saved volume, which will be sent to the server once the database is initialized
+    restore: Option<f32>,
+}
+impl VolumeControl {
+    pub fn new(config: GuiElemCfg) -> Self {
+        Self {
+            config,
+            mute: Button::new(
+                GuiElemCfg::at(Rectangle::from_tuples((0.0, 0.0), (0.3, 1.0))),
+                |_| {
+                    vec![GuiAction::Build(Box::new(|db| {
+                        vec![GuiAction::SendToServer(Action::SetMuted(!db.muted))]
+                    }))]
+                },
+                [MuteIcon::new(GuiElemCfg::at(Rectangle::from_tuples(
+                    (0.2, 0.2),
+                    (0.8, 0.8),
+                )))],
+            ),
+            slider: Slider::new_labeled(
+                GuiElemCfg::at(Rectangle::from_tuples((0.3, 0.0), (1.0, 1.0))),
+                0.0,
+                1.0,
+                1.0,
+                |slider, label, info| {
+                    if slider.val_changed() {
+                        *label.content.text() = format!("{:.0}%", slider.val * 100.0);
+                        // only send the volume if the user changed it (the value is also updated from the db)
+                        let volume = slider.val as f32;
+                        if slider.config.mouse_pressed.0
+                            && (volume - info.database.volume).abs() > 0.001
+                        {
+                            info.actions
+                                .push(GuiAction::SendToServer(Action::SetVolume(volume)));
+                        }
+                    }
+                },
+            ),
+            dragging: false,
+            restore: std::fs::read_to_string(Self::volume_file())
+                .ok()
+                .and_then(|v| v.trim().parse().ok()),
+        }
+    }
+    fn volume_file() -> std::path::PathBuf {
+        crate::get_config_file_path().join("volume")
+    }
+}
+impl GuiElem for VolumeControl {
+    fn draw(&mut self, info: &mut DrawInfo, _g: &mut Graphics2D) {
+        if info.database.is_client_init() {
+            if let Some(volume) = self.restore.take() {
+                if volume != info.database.volume {
+                    info.actions
+                        .push(GuiAction::SendToServer(Action::SetVolume(volume)));
+                }
+            }
+        }
+        let dragging = self.slider.config.mouse_pressed.0;
+        if !dragging && self.slider.val as f32 != info.database.volume {
+            self.slider.val = info.database.volume as _;
+            self.slider.config.redraw = true;
+        }
+        if self.dragging && !dragging {
+            // done changing the volume, save it
+            if let Err(e) = std::fs::write(Self::volume_file(), self.slider.val.to_string()) {
+                eprintln!("[warn] couldn't save volume: {e}");
+            }
+        }
+        self.dragging = dragging;
+    }
+    fn config(&self) -> &GuiElemCfg {
+        &self.config
+    }
+    fn config_mut(&mut self) -> &mut GuiElemCfg {
+        &mut self.config
+    }
+    fn children(&mut self) -> Box<dyn Iterator<Item = &mut dyn GuiElem> + '_> {
+        Box::new([self.mute.elem_mut(), self.slider.elem_mut()].into_iter())
+    }
+    fn any(&self) -> &dyn std::any::Any {
+        self
+    }
+    fn any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+    fn elem(&self) -> &dyn GuiElem {
+        self
+    }
+    fn elem_mut(&mut self) -> &mut dyn GuiElem {
+        self
+    }
+}
+
+struct MuteIcon {
+    config: GuiElemCfg,
+}
+impl MuteIcon {
+    pub fn new(config: GuiElemCfg) -> Self {
+        Self { config }
+    }
+}
+impl GuiElem for MuteIcon {
+    fn draw(&mut self, info: &mut DrawInfo, g: &mut Graphics2D) {
+        let clr = if info.database.muted {
+            Color::GRAY
+        } else {
+            Color::CYAN
+        };
+        let (l, t) = (info.pos.top_left().x, info.pos.top_left().y);
+        let (w, h) = (info.pos.width(), info.pos.height());
+        // speaker
+        g.draw_rectangle(
+            Rectangle::from_tuples((l, t + h * 0.3), (l + w * 0.3, t + h * 0.7)),
+            clr,
+        );
+        g.draw_triangle(
+            [
+                Vec2::new(l + w * 0.15, t + h * 0.5),
+                Vec2::new(l + w * 0.6, t),
+                Vec2::new(l + w * 0.6, t + h),
+            ],
+            clr,
+        );
+        if info.database.muted {
+            g.draw_line(
+                Vec2::new(l + w * 0.7, t + h * 0.3),
+                Vec2::new(l + w, t + h * 0.7),
+                w * 0.08,
+                Color::RED,
+            );
+            g.draw_line(
+                Vec2::new(l + w * 0.7, t + h * 0.7),
+                Vec2::new(l + w, t + h * 0.3),
+                w * 0.08,
+                Color::RED,
+            );
+        } else {
+            g.draw_line(
+                Vec2::new(l + w * 0.75, t + h * 0.3),
+                Vec2::new(l + w * 0.75, t + h * 0.7),
+                w * 0.08,
+                clr,
+            );
+            g.draw_line(
+                Vec2::new(l + w * 0.9, t + h * 0.15),
+                Vec2::new(l + w * 0.9, t + h * 0.85),
+                w * 0.08,
+                clr,
+            );
+        }
+    }
+    fn config(&self) -> &GuiElemCfg {
+        &self.config
+    }
+    fn config_mut(&mut self) -> &mut GuiElemCfg {
+        &mut self.config
+    }
+    fn children(&mut self) -> Box<dyn Iterator<Item = &mut dyn GuiElem> + '_> {
+        Box::new([].into_iter())
+    }
+    fn any(&self) -> &dyn std::any::Any {
+        self
+    }
+    fn any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+    fn elem(&self) -> &dyn GuiElem {
+        self
+    }
+    fn elem_mut(&mut self) -> &mut dyn GuiElem {
+        self
+    }
+}
+
 struct PlayPauseDisplay {
     config: GuiElemCfg,
     is_playing: bool,
@@ -159,27 +395,175 @@ impl GuiElem for PlayPauseDisplay {
     }
 }
 
-struct NextSongShape {
+/// a triangle with a line at its tip (next song), mirrored if `backwards` (previous song)
+struct SkipShape {
     config: GuiElemCfg,
+    backwards: bool,
 }
-impl NextSongShape {
+impl SkipShape {
+    pub fn new(config: GuiElemCfg, backwards: bool) -> Self {
+        Self { config, backwards }
+    }
+}
+impl GuiElem for SkipShape {
+    fn draw(&mut self, info: &mut DrawInfo, g: &mut Graphics2D) {
+        let (base_x, tip_x) = if self.backwards {
+            (info.pos.top_right().x, info.pos.top_left().x)
+        } else {
+            (info.pos.top_left().x, info.pos.top_right().x)
+        };
+        let (top_y, bottom_y) = (info.pos.top_left().y, info.pos.bottom_left().y);
+        let top = Vec2::new(base_x, top_y);
+        let bottom = Vec2::new(base_x, bottom_y);
+        let tip = Vec2::new(tip_x, (top_y + bottom_y) / 2.0);
+        g.draw_triangle([top, tip, bottom], Color::CYAN);
+        let half_width = info.pos.width() * 0.04;
+        let line_x = if self.backwards {
+            tip_x + half_width
+        } else {
+            tip_x - half_width
+        };
+        g.draw_line(
+            Vec2::new(line_x, top_y),
+            Vec2::new(line_x, bottom_y),
+            2.0 * half_width,
+            Color::CYAN,
+        );
+    }
+    fn config(&self) -> &GuiElemCfg {
+        &self.config
+    }
+    fn config_mut(&mut self) -> &mut GuiElemCfg {
+        &mut self.config
+    }
+    fn children(&mut self) -> Box<dyn Iterator<Item = &mut dyn GuiElem> + '_> {
+        Box::new([].into_iter())
+    }
+    fn any(&self) -> &dyn std::any::Any {
+        self
+    }
+    fn any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+    fn elem(&self) -> &dyn GuiElem {
+        self
+    }
+    fn elem_mut(&mut self) -> &mut dyn GuiElem {
+        self
+    }
+}
+
+/// a loop (rectangle with an arrow), with a line in the middle for repeat one.
+/// gray if repeat is off.
+struct RepeatIcon {
+    config: GuiElemCfg,
+}
+impl RepeatIcon {
     pub fn new(config: GuiElemCfg) -> Self {
         Self { config }
     }
 }
-impl GuiElem for NextSongShape {
+impl GuiElem for RepeatIcon {
     fn draw(&mut self, info: &mut DrawInfo, g: &mut Graphics2D) {
-        let top = *info.pos.top_left();
-        let bottom = info.pos.bottom_left();
-        let right = Vec2::new(info.pos.bottom_right().x, (top.y + bottom.y) / 2.0);
-        g.draw_triangle([top, right, bottom], Color::CYAN);
-        let half_width = info.pos.width() * 0.04;
-        let top_right = Vec2::new(info.pos.top_right().x - half_width, info.pos.top_left().y);
-        let bottom_right = Vec2::new(
-            info.pos.top_right().x - half_width,
-            info.pos.bottom_right().y,
+        let clr = if info.database.repeat == RepeatMode::Off {
+            Color::GRAY
+        } else {
+            Color::CYAN
+        };
+        let (l, t) = (info.pos.top_left().x, info.pos.top_left().y);
+        let (r, b) = (info.pos.bottom_right().x, info.pos.bottom_right().y);
+        let (w, h) = (info.pos.width(), info.pos.height());
+        let thickness = w * 0.08;
+        let (t, b) = (t + h * 0.2, b - h * 0.2);
+        g.draw_line(Vec2::new(l, t), Vec2::new(r - w * 0.2, t), thickness, clr);
+        g.draw_line(Vec2::new(r, t), Vec2::new(r, b), thickness, clr);
+        g.draw_line(Vec2::new(r, b), Vec2::new(l + w * 0.2, b), thickness, clr);
+        g.draw_line(Vec2::new(l, b), Vec2::new(l, t), thickness, clr);
+        // arrow heads
+        g.draw_triangle(
+            [
+                Vec2::new(r - w * 0.3, t - h * 0.15),
+                Vec2::new(r - w * 0.05, t),
+                Vec2::new(r - w * 0.3, t + h * 0.15),
+            ],
+            clr,
         );
-        g.draw_line(top_right, bottom_right, 2.0 * half_width, Color::CYAN);
+        g.draw_triangle(
+            [
+                Vec2::new(l + w * 0.3, b - h * 0.15),
+                Vec2::new(l + w * 0.05, b),
+                Vec2::new(l + w * 0.3, b + h * 0.15),
+            ],
+            clr,
+        );
+        if info.database.repeat == RepeatMode::One {
+            let x = l + w * 0.5;
+            g.draw_line(
+                Vec2::new(x, t + h * 0.15),
+                Vec2::new(x, b - h * 0.15),
+                thickness,
+                clr,
+            );
+        }
+    }
+    fn config(&self) -> &GuiElemCfg {
+        &self.config
+    }
+    fn config_mut(&mut self) -> &mut GuiElemCfg {
+        &mut self.config
+    }
+    fn children(&mut self) -> Box<dyn Iterator<Item = &mut dyn GuiElem> + '_> {
+        Box::new([].into_iter())
+    }
+    fn any(&self) -> &dyn std::any::Any {
+        self
+    }
+    fn any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+    fn elem(&self) -> &dyn GuiElem {
+        self
+    }
+    fn elem_mut(&mut self) -> &mut dyn GuiElem {
+        self
+    }
+}
+
+/// two crossing arrows, gray if the queue isn't shuffled
+struct ShuffleIcon {
+    config: GuiElemCfg,
+}
+impl ShuffleIcon {
+    pub fn new(config: GuiElemCfg) -> Self {
+        Self { config }
+    }
+}
+impl GuiElem for ShuffleIcon {
+    fn draw(&mut self, info: &mut DrawInfo, g: &mut Graphics2D) {
+        let clr = if is_shuffled(info.database) {
+            Color::CYAN
+        } else {
+            Color::GRAY
+        };
+        let (l, t) = (info.pos.top_left().x, info.pos.top_left().y);
+        let (w, h) = (info.pos.width(), info.pos.height());
+        let thickness = w * 0.08;
+        for (y1, y2) in [(0.2, 0.8), (0.8, 0.2)] {
+            g.draw_line(
+                Vec2::new(l, t + h * y1),
+                Vec2::new(l + w * 0.8, t + h * y2),
+                thickness,
+                clr,
+            );
+            g.draw_triangle(
+                [
+                    Vec2::new(l + w * 0.75, t + h * (y2 - 0.15)),
+                    Vec2::new(l + w, t + h * y2),
+                    Vec2::new(l + w * 0.75, t + h * (y2 + 0.15)),
+                ],
+                clr,
+            );
+        }
     }
     fn config(&self) -> &GuiElemCfg {
         &self.config
@@ -300,8 +684,11 @@ impl GuiElem for PlayPause {
             [
                 self.set_fav.elem_mut(),
                 self.to_zero.elem_mut(),
+                self.to_prev.elem_mut(),
                 self.play_pause.elem_mut(),
                 self.to_end.elem_mut(),
+                self.repeat.elem_mut(),
+                self.shuffle.elem_mut(),
             ]
             .into_iter(),
         )
@@ -1,7 +1,7 @@
 use musicdb_lib::{
     data::{
         database::Database,
-        queue::{Queue, QueueContent, QueueDuration},
+        queue::{Queue, QueueContent, QueueDuration, RepeatMode, ShuffleWeight},
         song::Song,
         AlbumId, ArtistId,
     },
@@ -15,7 +15,7 @@ use speedy2d::{
 };
 
 use crate::{
-    gui::{Dragging, DrawInfo, EventInfo, GuiAction, GuiElem, GuiElemCfg},
+    gui::{format_duration, Dragging, DrawInfo, EventInfo, GuiAction, GuiElem, GuiElemCfg},
     gui_base::{Panel, ScrollBox},
     gui_text::{self, AdvancedLabel, Label, TextField},
 };
@@ -35,6 +35,7 @@ pub struct QueueViewer {
     c_empty_space_drag_handler: QueueEmptySpaceDragHandler,
     c_control_flow_elements: Panel<(QueueLoop, QueueLoop, QueueFolder, TextField)>,
     c_duration: AdvancedLabel,
+    c_play_next_drag_handler: QueuePlayNextDragHandler,
     recv: std::sync::mpsc::Receiver<QVMsg>,
     queue_updated: bool,
 }
@@ -61,6 +62,8 @@ impl QueueViewer {
                             content: vec![],
                             name: "in loop".to_string(),
                             order: None,
+                            seed: None,
+                            weight: ShuffleWeight::Uniform,
                         })
                         .into(),
                     ),
@@ -81,6 +84,8 @@ impl QueueViewer {
                             content: vec![],
                             name: "in loop".to_string(),
                             order: None,
+                            seed: None,
+                            weight: ShuffleWeight::Uniform,
                         })
                         .into(),
                     ),
@@ -97,6 +102,8 @@ impl QueueViewer {
                     content: vec![],
                     name: format!("folder name"),
                     order: None,
+                    seed: None,
+                    weight: ShuffleWeight::Uniform,
                 },
                 false,
             )
@@ -137,6 +144,9 @@ impl QueueViewer {
                 Vec2::new(0.0, 0.5),
                 vec![],
             ),
+            c_play_next_drag_handler: QueuePlayNextDragHandler::new(GuiElemCfg::at(
+                Rectangle::from_tuples((0.5, QP_INV1), (1.0, QP_INV2)),
+            )),
             queue_updated: false,
             recv,
         }
@@ -156,6 +166,7 @@ impl GuiElem for QueueViewer {
                 self.c_empty_space_drag_handler.elem_mut(),
                 self.c_control_flow_elements.elem_mut(),
                 self.c_duration.elem_mut(),
+                self.c_play_next_drag_handler.elem_mut(),
             ]
             .into_iter(),
         )
@@ -193,38 +204,28 @@ impl GuiElem for QueueViewer {
             fn fmt_dur(dur: QueueDuration) -> String {
                 if dur.infinite {
                     "∞".to_owned()
+                } else if dur.random_counter == 0 {
+                    format_duration(dur.millis)
                 } else {
-                    let seconds = dur.millis / 1000;
-                    let minutes = seconds / 60;
-                    let h = minutes / 60;
-                    let m = minutes % 60;
-                    let s = seconds % 60;
-                    if dur.random_counter == 0 {
-                        if h > 0 {
-                            format!("{h}:{m:0>2}:{s:0>2}")
-                        } else {
-                            format!("{m:0>2}:{s:0>2}")
-                        }
+                    let r = dur.random_counter;
+                    if dur.millis > 0 {
+                        format!("{} + {r} random songs", format_duration(dur.millis))
                     } else {
-                        let r = dur.random_counter;
-                        if dur.millis > 0 {
-                            if h > 0 {
-                                format!("{h}:{m:0>2}:{s:0>2} + {r} random songs")
-                            } else {
-                                format!("{m:0>2}:{s:0>2} + {r} random songs")
-                            }
-                        } else {
-                            format!("{r} random songs")
-                        }
+                        format!("{r} random songs")
                     }
                 }
             }
-            let dt = fmt_dur(info.database.queue.duration_total(&info.database));
-            let dr = fmt_dur(info.database.queue.duration_remaining(&info.database));
+            let total = info.database.queue.duration_total(&info.database);
+            let remaining = info.database.queue.duration_remaining(&info.database);
             label.content = vec![
                 vec![(
                     gui_text::AdvancedContent::Text(gui_text::Content::new(
-                        format!("Total: {dt}"),
+                        format!(
+                            "{} song{} · {}",
+                            total.songs,
+                            if total.songs == 1 { "" } else { "s" },
+                            fmt_dur(total)
+                        ),
                         Color::GRAY,
                     )),
                     1.0,
@@ -232,7 +233,15 @@ impl GuiElem for QueueViewer {
                 )],
                 vec![(
                     gui_text::AdvancedContent::Text(gui_text::Content::new(
-                        format!("Remaining: {dr}"),
+                        format!(
+                            "{} left{}",
+                            fmt_dur(remaining),
+                            match info.database.repeat {
+                                RepeatMode::Off => "",
+                                RepeatMode::All => " (repeat all)",
+                                RepeatMode::One => " (repeat one)",
+                            }
+                        ),
                         Color::GRAY,
                     )),
                     1.0,
@@ -302,6 +311,8 @@ fn queue_gui(
                 content: _,
                 name: _,
                 order: _,
+                seed: _,
+                weight: _,
             } = qf;
             if !skip_folder {
                 target.push(Box::new(QueueFolder::new(
@@ -362,6 +373,46 @@ fn queue_gui(
             target.push(Box::new(QueueIndentEnd::new(cfg, (p1, p2))));
             target_h.push(line_height * 0.4);
         }
+        QueueContent::Playlist(playlist) => {
+            target.push(Box::new(QueuePlaylist::new(
+                cfg.clone(),
+                path.clone(),
+                playlist.clone(),
+                current,
+            )));
+            target_h.push(line_height * 0.8);
+            for (i, id) in playlist.songs.iter().enumerate() {
+                if let Some(s) = db.songs().get(id) {
+                    let mut p = path.clone();
+                    p.push(i);
+                    target.push(Box::new(QueueSong::new(
+                        GuiElemCfg::at(Rectangle::from_tuples(
+                            (depth + depth_inc_by, 0.0),
+                            (1.0, 1.0),
+                        )),
+                        p,
+                        s.clone(),
+                        current && playlist.index == i,
+                        db,
+                        depth_inc_by * 0.33,
+                    )));
+                    target_h.push(line_height * 1.75);
+                }
+            }
+            let mut p1 = path;
+            let p2 = p1.pop().unwrap_or(0) + 1;
+            target.push(Box::new(QueueIndentEnd::new(cfg, (p1, p2))));
+            target_h.push(line_height * 0.4);
+        }
+        QueueContent::Conditional(conditional) => {
+            target.push(Box::new(QueueConditional::new(
+                cfg,
+                path,
+                conditional.clone(),
+                current,
+            )));
+            target_h.push(line_height * 0.8);
+        }
     }
 }
 
@@ -410,6 +461,93 @@ impl GuiElem for QueueEmptySpaceDragHandler {
     }
 }
 
+/// only visible while dragging, inserts the dragged element right after the current one ("play next")
+struct QueuePlayNextDragHandler {
+    config: GuiElemCfg,
+    c_label: Label,
+    /// see `Queue::get_insert_after_current`, updated while dragging
+    insert_at: Option<(Vec<usize>, usize)>,
+}
+impl QueuePlayNextDragHandler {
+    pub fn new(config: GuiElemCfg) -> Self {
+        Self {
+            config: config.w_drag_target(),
+            c_label: Label::new(
+                GuiElemCfg::default().disabled(),
+                "play next".to_owned(),
+                Color::WHITE,
+                None,
+                Vec2::new(0.5, 0.5),
+            ),
+            insert_at: None,
+        }
+    }
+}
+impl GuiElem for QueuePlayNextDragHandler {
+    fn config(&self) -> &GuiElemCfg {
+        &self.config
+    }
+    fn config_mut(&mut self) -> &mut GuiElemCfg {
+        &mut self.config
+    }
+    fn children(&mut self) -> Box<dyn Iterator<Item = &mut dyn GuiElem> + '_> {
+        Box::new([self.c_label.elem_mut()].into_iter())
+    }
+    fn any(&self) -> &dyn std::any::Any {
+        self
+    }
+    fn any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+    fn elem(&self) -> &dyn GuiElem {
+        self
+    }
+    fn elem_mut(&mut self) -> &mut dyn GuiElem {
+        self
+    }
+    fn draw(&mut self, info: &mut DrawInfo, g: &mut speedy2d::Graphics2D) {
+        let dragging = info.dragging.is_some();
+        self.c_label.config_mut().enabled = dragging;
+        if dragging {
+            self.insert_at = info.database.queue.get_insert_after_current();
+            g.draw_rectangle(
+                info.pos.clone(),
+                Color::from_rgba(
+                    0.1,
+                    0.1,
+                    0.1,
+                    if info.pos.contains(info.mouse_pos) {
+                        1.0
+                    } else {
+                        0.9
+                    },
+                ),
+            );
+        }
+    }
+    fn dragged(&mut self, e: &mut EventInfo, dragged: Dragging) -> Vec<GuiAction> {
+        e.take();
+        match self.insert_at.take() {
+            Some(insert_at) => dragged_add_to_queue(
+                dragged,
+                insert_at,
+                |_, q| Action::QueueInsertAfterCurrent(q),
+                |(mut p, j), q| {
+                    p.push(j);
+                    Action::QueueMove(q, p)
+                },
+            ),
+            // nothing is playing, so add to the end of the queue
+            None => dragged_add_to_queue(
+                dragged,
+                (),
+                |_, q| Action::QueueInsertAfterCurrent(q),
+                |_, q| Action::QueueMoveInto(q, vec![]),
+            ),
+        }
+    }
+}
+
 fn generic_queue_draw(
     info: &mut DrawInfo,
     path: &Vec<usize>,
@@ -474,10 +612,7 @@ impl QueueSong {
                         ),
                         (
                             gui_text::AdvancedContent::Text(gui_text::Content::new(
-                                {
-                                    let duration = song.duration_millis / 1000;
-                                    format!("  {}:{:0>2}", duration / 60, duration % 60)
-                                },
+                                format!("  {}", format_duration(song.duration_millis)),
                                 if current {
                                     Color::GRAY
                                 } else {
@@ -661,6 +796,8 @@ struct QueueFolder {
     mouse: bool,
     mouse_pos: Vec2,
     copy: bool,
+    /// right-click shuffles weighted by rating if shift is held, or by least recently played if alt is held
+    weight: ShuffleWeight,
     always_copy: bool,
     copy_on_mouse_down: bool,
 }
@@ -676,6 +813,8 @@ impl QueueFolder {
             content,
             name,
             order,
+            seed: _,
+            weight: _,
         } = &queue;
         Self {
             config: if path.is_empty() {
@@ -707,6 +846,7 @@ impl QueueFolder {
             mouse: false,
             mouse_pos: Vec2::ZERO,
             copy: false,
+            weight: ShuffleWeight::Uniform,
             always_copy: false,
             copy_on_mouse_down: false,
         }
@@ -789,7 +929,7 @@ impl GuiElem for QueueFolder {
             return vec![GuiAction::SendToServer(if self.queue.order.is_some() {
                 Action::QueueUnshuffle(self.path.clone())
             } else {
-                Action::QueueShuffle(self.path.clone())
+                Action::QueueShuffle(self.path.clone(), self.weight.clone(), None)
             })];
         }
         vec![]
@@ -817,6 +957,13 @@ impl GuiElem for QueueFolder {
         _scan: speedy2d::window::KeyScancode,
     ) -> Vec<GuiAction> {
         self.copy = modifiers.ctrl();
+        self.weight = if modifiers.shift() {
+            ShuffleWeight::Tag("Rating=".to_owned())
+        } else if modifiers.alt() {
+            ShuffleWeight::LeastRecentlyPlayed
+        } else {
+            ShuffleWeight::Uniform
+        };
         vec![]
     }
     fn dragged(&mut self, e: &mut EventInfo, dragged: Dragging) -> Vec<GuiAction> {
@@ -1075,6 +1222,248 @@ impl GuiElem for QueueLoop {
     }
 }
 
+struct QueuePlaylist {
+    config: GuiElemCfg,
+    c_name: Label,
+    path: Vec<usize>,
+    playlist: musicdb_lib::data::queue::QueuePlaylist,
+    current: bool,
+    mouse: bool,
+    mouse_pos: Vec2,
+    copy: bool,
+    copy_on_mouse_down: bool,
+}
+impl QueuePlaylist {
+    pub fn new(
+        config: GuiElemCfg,
+        path: Vec<usize>,
+        playlist: musicdb_lib::data::queue::QueuePlaylist,
+        current: bool,
+    ) -> Self {
+        Self {
+            config: config.w_mouse().w_keyboard_watch().w_drag_target(),
+            c_name: Label::new(
+                GuiElemCfg::default(),
+                format!("{}  ({}) [playlist]", playlist.name, playlist.songs.len()),
+                Color::from_int_rgb(52, 132, 50),
+                None,
+                Vec2::new(0.0, 0.5),
+            ),
+            path,
+            playlist,
+            current,
+            mouse: false,
+            mouse_pos: Vec2::ZERO,
+            copy: false,
+            copy_on_mouse_down: false,
+        }
+    }
+}
+impl GuiElem for QueuePlaylist {
+    fn config(&self) -> &GuiElemCfg {
+        &self.config
+    }
+    fn config_mut(&mut self) -> &mut GuiElemCfg {
+        &mut self.config
+    }
+    fn children(&mut self) -> Box<dyn Iterator<Item = &mut dyn GuiElem> + '_> {
+        Box::new([self.c_name.elem_mut()].into_iter())
+    }
+    fn any(&self) -> &dyn std::any::Any {
+        self
+    }
+    fn any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+    fn elem(&self) -> &dyn GuiElem {
+        self
+    }
+    fn elem_mut(&mut self) -> &mut dyn GuiElem {
+        self
+    }
+    fn draw(&mut self, info: &mut DrawInfo, g: &mut speedy2d::Graphics2D) {
+        if self.current {
+            g.draw_rectangle(info.pos.clone(), Color::from_rgba(1.0, 1.0, 1.0, 0.05));
+        }
+        if info.dragging.is_some() && info.pos.contains(info.mouse_pos) {
+            g.draw_rectangle(info.pos.clone(), Color::from_rgba(1.0, 1.0, 1.0, 0.25));
+        }
+        if !self.mouse {
+            self.mouse_pos = Vec2::new(
+                info.mouse_pos.x - self.config.pixel_pos.top_left().x,
+                info.mouse_pos.y - self.config.pixel_pos.top_left().y,
+            );
+        }
+        generic_queue_draw(
+            info,
+            &self.path,
+            || QueueContent::Playlist(self.playlist.clone()).into(),
+            &mut self.mouse,
+            self.copy_on_mouse_down,
+        );
+    }
+    fn mouse_down(&mut self, e: &mut EventInfo, button: MouseButton) -> Vec<GuiAction> {
+        if button == MouseButton::Left && e.take() {
+            self.mouse = true;
+            self.copy_on_mouse_down = self.copy;
+        }
+        vec![]
+    }
+    fn mouse_up(&mut self, e: &mut EventInfo, button: MouseButton) -> Vec<GuiAction> {
+        if self.mouse && button == MouseButton::Left {
+            self.mouse = false;
+            if e.take() {
+                let mut p = self.path.clone();
+                p.push(0);
+                vec![GuiAction::SendToServer(Action::QueueGoto(p))]
+            } else {
+                vec![]
+            }
+        } else {
+            vec![]
+        }
+    }
+    fn key_watch(
+        &mut self,
+        _e: &mut EventInfo,
+        modifiers: ModifiersState,
+        _down: bool,
+        _key: Option<VirtualKeyCode>,
+        _scan: speedy2d::window::KeyScancode,
+    ) -> Vec<GuiAction> {
+        self.copy = modifiers.ctrl();
+        vec![]
+    }
+    fn dragged(&mut self, e: &mut EventInfo, dragged: Dragging) -> Vec<GuiAction> {
+        // songs dropped onto a playlist are added to its end
+        e.take();
+        dragged_add_to_queue(
+            dragged,
+            self.path.clone(),
+            |p, q| Action::QueueAdd(p, q, Req::none()),
+            |p, q| Action::QueueMoveInto(q, p),
+        )
+    }
+}
+
+/// the server replaces it with a playlist once it becomes the current element, so this only shows the filter's name.
+struct QueueConditional {
+    config: GuiElemCfg,
+    c_name: Label,
+    path: Vec<usize>,
+    conditional: musicdb_lib::data::queue::QueueConditional,
+    current: bool,
+    mouse: bool,
+    mouse_pos: Vec2,
+    copy: bool,
+    copy_on_mouse_down: bool,
+}
+impl QueueConditional {
+    pub fn new(
+        config: GuiElemCfg,
+        path: Vec<usize>,
+        conditional: musicdb_lib::data::queue::QueueConditional,
+        current: bool,
+    ) -> Self {
+        let count = if conditional.count == 0 {
+            "all".to_owned()
+        } else {
+            conditional.count.to_string()
+        };
+        let random = if conditional.random { ", random" } else { "" };
+        Self {
+            config: config.w_mouse().w_keyboard_watch(),
+            c_name: Label::new(
+                GuiElemCfg::default(),
+                format!("{}  ({count}{random}) [conditional]", conditional.name),
+                Color::from_int_rgb(132, 96, 50),
+                None,
+                Vec2::new(0.0, 0.5),
+            ),
+            path,
+            conditional,
+            current,
+            mouse: false,
+            mouse_pos: Vec2::ZERO,
+            copy: false,
+            copy_on_mouse_down: false,
+        }
+    }
+}
+impl GuiElem for QueueConditional {
+    fn config(&self) -> &GuiElemCfg {
+        &self.config
+    }
+    fn config_mut(&mut self) -> &mut GuiElemCfg {
+        &mut self.config
+    }
+    fn children(&mut self) -> Box<dyn Iterator<Item = &mut dyn GuiElem> + '_> {
+        Box::new([self.c_name.elem_mut()].into_iter())
+    }
+    fn any(&self) -> &dyn std::any::Any {
+        self
+    }
+    fn any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+    fn elem(&self) -> &dyn GuiElem {
+        self
+    }
+    fn elem_mut(&mut self) -> &mut dyn GuiElem {
+        self
+    }
+    fn draw(&mut self, info: &mut DrawInfo, g: &mut speedy2d::Graphics2D) {
+        if self.current {
+            g.draw_rectangle(info.pos.clone(), Color::from_rgba(1.0, 1.0, 1.0, 0.05));
+        }
+        if !self.mouse {
+            self.mouse_pos = Vec2::new(
+                info.mouse_pos.x - self.config.pixel_pos.top_left().x,
+                info.mouse_pos.y - self.config.pixel_pos.top_left().y,
+            );
+        }
+        generic_queue_draw(
+            info,
+            &self.path,
+            || QueueContent::Conditional(self.conditional.clone()).into(),
+            &mut self.mouse,
+            self.copy_on_mouse_down,
+        );
+    }
+    fn mouse_down(&mut self, e: &mut EventInfo, button: MouseButton) -> Vec<GuiAction> {
+        if button == MouseButton::Left && e.take() {
+            self.mouse = true;
+            self.copy_on_mouse_down = self.copy;
+        }
+        vec![]
+    }
+    fn mouse_up(&mut self, e: &mut EventInfo, button: MouseButton) -> Vec<GuiAction> {
+        if self.mouse && button == MouseButton::Left {
+            self.mouse = false;
+            if e.take() {
+                vec![GuiAction::SendToServer(Action::QueueGoto(
+                    self.path.clone(),
+                ))]
+            } else {
+                vec![]
+            }
+        } else {
+            vec![]
+        }
+    }
+    fn key_watch(
+        &mut self,
+        _e: &mut EventInfo,
+        modifiers: ModifiersState,
+        _down: bool,
+        _key: Option<VirtualKeyCode>,
+        _scan: speedy2d::window::KeyScancode,
+    ) -> Vec<GuiAction> {
+        self.copy = modifiers.ctrl();
+        vec![]
+    }
+}
+
 fn dragged_add_to_queue<T: 'static>(
     dragged: Dragging,
     data: T,
@@ -1112,7 +1501,7 @@ fn dragged_add_to_queue<T: 'static>(
     }
 }
 
-fn add_to_queue_album_by_id(id: AlbumId, db: &Database) -> Option<Queue> {
+pub fn add_to_queue_album_by_id(id: AlbumId, db: &Database) -> Option<Queue> {
     if let Some(album) = db.albums().get(&id) {
         Some(
             QueueContent::Folder(musicdb_lib::data::queue::QueueFolder {
@@ -1124,6 +1513,8 @@ fn add_to_queue_album_by_id(id: AlbumId, db: &Database) -> Option<Queue> {
                     .collect(),
                 name: album.name.clone(),
                 order: None,
+                seed: None,
+                weight: ShuffleWeight::Uniform,
             })
             .into(),
         )
@@ -1131,7 +1522,7 @@ fn add_to_queue_album_by_id(id: AlbumId, db: &Database) -> Option<Queue> {
         None
     }
 }
-fn add_to_queue_artist_by_id(id: ArtistId, db: &Database) -> Option<Queue> {
+pub fn add_to_queue_artist_by_id(id: ArtistId, db: &Database) -> Option<Queue> {
     if let Some(artist) = db.artists().get(&id) {
         Some(
             QueueContent::Folder(musicdb_lib::data::queue::QueueFolder {
@@ -1149,6 +1540,8 @@ fn add_to_queue_artist_by_id(id: ArtistId, db: &Database) -> Option<Queue> {
                     .collect(),
                 name: artist.name.clone(),
                 order: None,
+                seed: None,
+                weight: ShuffleWeight::Uniform,
             })
             .into(),
         )
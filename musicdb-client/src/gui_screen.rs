@@ -1,10 +1,16 @@
 use std::time::Instant;
 
 use musicdb_lib::{
-    data::queue::{QueueContent, QueueFolder},
+    data::queue::{QueueContent, QueueFolder, RepeatMode},
     server::{Action, Req},
 };
-use speedy2d::{color::Color, dimen::Vec2, shape::Rectangle, window::VirtualKeyCode, Graphics2D};
+use speedy2d::{
+    color::Color,
+    dimen::{UVec2, Vec2},
+    shape::Rectangle,
+    window::VirtualKeyCode,
+    Graphics2D,
+};
 use uianimator::{default_animator_f64_quadratic::DefaultAnimatorF64Quadratic, Animator};
 
 use crate::{
@@ -16,7 +22,9 @@ use crate::{
     gui_edit_song::EditorForSongs,
     gui_idle_display::IdleDisplay,
     gui_library::LibraryBrowser,
+    gui_lyrics::LyricsPanel,
     gui_notif::NotifOverlay,
+    gui_playlists::PlaylistsSidebar,
     gui_queue::QueueViewer,
     gui_settings::Settings,
     gui_song_adder::SongAdder,
@@ -30,6 +38,7 @@ use crate::{
 The root gui element.
 Contains the Library, Queue, StatusBar, and sometimes Settings elements.
 Resizes these elements to show/hide the settings menu and to smoothly switch to/from idle mode.
+In compact mode, only the StatusBar (cover, title/artist, progress bar and playback controls) is shown in a small window.
 
 */
 
@@ -61,29 +70,73 @@ pub struct GuiScreen {
     idle_timeout: Option<f64>,
     pub prev_mouse_pos: Vec2,
     pub hotkey: Hotkey,
+    /// leaves compact mode, only shown in compact mode
+    pub c_button_full: Button<[Label; 1]>,
+    /// `Some(size of the window before it was made smaller)` in compact mode
+    pub compact: Option<Vec2>,
+    /// set by `toggle_compact`, the window is resized in `draw`
+    compact_toggled: bool,
 }
+/// the window size in compact mode
+const COMPACT_SIZE: (u32, u32) = (600, 100);
 pub struct MainView {
     pub button_clear_queue: Button<[Label; 1]>,
+    pub button_playlists: Button<[Label; 1]>,
+    pub button_lyrics: Button<[Label; 1]>,
     pub button_settings: Button<[Label; 1]>,
+    pub button_compact: Button<[Label; 1]>,
     pub button_exit: Button<[Label; 1]>,
     pub library_browser: LibraryBrowser,
     pub queue_viewer: QueueViewer,
+    pub playlists: PlaylistsSidebar,
+    pub lyrics: LyricsPanel,
+}
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Sidebar {
+    Playlists,
+    Lyrics,
+}
+impl MainView {
+    /// shows the sidebar (hiding the other one), or hides it if it is already shown.
+    /// the sidebar takes up the right part of the queue's space.
+    pub fn toggle_sidebar(&mut self, sidebar: Sidebar) {
+        let show_playlists = sidebar == Sidebar::Playlists && !self.playlists.config().enabled;
+        let show_lyrics = sidebar == Sidebar::Lyrics && !self.lyrics.config().enabled;
+        self.playlists.config_mut().enabled = show_playlists;
+        self.lyrics.config_mut().enabled = show_lyrics;
+        self.queue_viewer.config_mut().pos = Rectangle::from_tuples(
+            (0.5, 0.03),
+            (
+                if show_playlists || show_lyrics {
+                    0.75
+                } else {
+                    1.0
+                },
+                1.0,
+            ),
+        );
+    }
 }
 impl GuiElemChildren for MainView {
     fn iter(&mut self) -> Box<dyn Iterator<Item = &mut dyn GuiElem> + '_> {
         Box::new(
             [
                 self.button_clear_queue.elem_mut(),
+                self.button_playlists.elem_mut(),
+                self.button_lyrics.elem_mut(),
                 self.button_settings.elem_mut(),
+                self.button_compact.elem_mut(),
                 self.button_exit.elem_mut(),
                 self.library_browser.elem_mut(),
                 self.queue_viewer.elem_mut(),
+                self.playlists.elem_mut(),
+                self.lyrics.elem_mut(),
             ]
             .into_iter(),
         )
     }
     fn len(&self) -> usize {
-        5
+        10
     }
 }
 impl GuiScreen {
@@ -118,7 +171,7 @@ impl GuiScreen {
                 GuiElemCfg::at(Rectangle::from_tuples((0.0, 0.0), (1.0, 0.9))),
                 MainView {
                     button_clear_queue: Button::new(
-                        GuiElemCfg::at(Rectangle::from_tuples((0.5, 0.0), (0.75, 0.03))),
+                        GuiElemCfg::at(Rectangle::from_tuples((0.5, 0.0), (0.6, 0.03))),
                         |_| {
                             vec![GuiAction::SendToServer(Action::QueueUpdate(
                                 vec![],
@@ -137,8 +190,41 @@ impl GuiScreen {
                             Vec2::new(0.5, 0.5),
                         )],
                     ),
+                    button_playlists: Button::new(
+                        GuiElemCfg::at(Rectangle::from_tuples((0.6, 0.0), (0.675, 0.03))),
+                        |_| {
+                            vec![GuiAction::Do(Box::new(|gui| {
+                                gui.gui
+                                    .c_main_view
+                                    .children
+                                    .toggle_sidebar(Sidebar::Playlists)
+                            }))]
+                        },
+                        [Label::new(
+                            GuiElemCfg::default(),
+                            "Playlists".to_string(),
+                            Color::WHITE,
+                            None,
+                            Vec2::new(0.5, 0.5),
+                        )],
+                    ),
+                    button_lyrics: Button::new(
+                        GuiElemCfg::at(Rectangle::from_tuples((0.675, 0.0), (0.75, 0.03))),
+                        |_| {
+                            vec![GuiAction::Do(Box::new(|gui| {
+                                gui.gui.c_main_view.children.toggle_sidebar(Sidebar::Lyrics)
+                            }))]
+                        },
+                        [Label::new(
+                            GuiElemCfg::default(),
+                            "Lyrics".to_string(),
+                            Color::WHITE,
+                            None,
+                            Vec2::new(0.5, 0.5),
+                        )],
+                    ),
                     button_settings: Button::new(
-                        GuiElemCfg::at(Rectangle::from_tuples((0.75, 0.0), (0.875, 0.03))),
+                        GuiElemCfg::at(Rectangle::from_tuples((0.75, 0.0), (0.825, 0.03))),
                         |_| vec![GuiAction::OpenSettings(true)],
                         [Label::new(
                             GuiElemCfg::default(),
@@ -148,8 +234,19 @@ impl GuiScreen {
                             Vec2::new(0.5, 0.5),
                         )],
                     ),
+                    button_compact: Button::new(
+                        GuiElemCfg::at(Rectangle::from_tuples((0.825, 0.0), (0.9, 0.03))),
+                        |_| vec![GuiAction::Do(Box::new(|gui| gui.gui.toggle_compact()))],
+                        [Label::new(
+                            GuiElemCfg::default(),
+                            "Compact".to_string(),
+                            Color::WHITE,
+                            None,
+                            Vec2::new(0.5, 0.5),
+                        )],
+                    ),
                     button_exit: Button::new(
-                        GuiElemCfg::at(Rectangle::from_tuples((0.875, 0.0), (1.0, 0.03))),
+                        GuiElemCfg::at(Rectangle::from_tuples((0.9, 0.0), (1.0, 0.03))),
                         |_| vec![GuiAction::Exit],
                         [Label::new(
                             GuiElemCfg::default(),
@@ -167,6 +264,12 @@ impl GuiScreen {
                         (0.5, 0.03),
                         (1.0, 1.0),
                     ))),
+                    playlists: PlaylistsSidebar::new(
+                        GuiElemCfg::at(Rectangle::from_tuples((0.75, 0.03), (1.0, 1.0))).disabled(),
+                    ),
+                    lyrics: LyricsPanel::new(
+                        GuiElemCfg::at(Rectangle::from_tuples((0.75, 0.03), (1.0, 1.0))).disabled(),
+                    ),
                 },
             ),
             c_context_menu: None,
@@ -177,8 +280,25 @@ impl GuiScreen {
             last_interaction: Instant::now(),
             idle_timeout: Some(60.0),
             prev_mouse_pos: Vec2::ZERO,
+            c_button_full: Button::new(
+                GuiElemCfg::at(Rectangle::from_tuples((0.92, 0.0), (1.0, 1.0))).disabled(),
+                |_| vec![GuiAction::Do(Box::new(|gui| gui.gui.toggle_compact()))],
+                [Label::new(
+                    GuiElemCfg::default(),
+                    "full".to_string(),
+                    Color::GRAY,
+                    None,
+                    Vec2::new(0.5, 0.5),
+                )],
+            ),
+            compact: None,
+            compact_toggled: false,
         }
     }
+    /// switches between the normal layout and compact mode
+    pub fn toggle_compact(&mut self) {
+        self.compact_toggled = true;
+    }
     fn get_prog(v: &mut (bool, Option<Instant>), seconds: f32) -> f32 {
         if let Some(since) = &mut v.1 {
             let prog = since.elapsed().as_secs_f32() / seconds;
@@ -234,7 +354,8 @@ impl GuiScreen {
     pub fn set_normal_ui_enabled(&mut self, enabled: bool) {
         self.c_status_bar.config_mut().enabled = enabled;
         // self.c_settings.config_mut().enabled = enabled;
-        self.c_main_view.config_mut().enabled = enabled;
+        self.c_main_view.config_mut().enabled = enabled && self.compact.is_none();
+        self.c_button_full.config_mut().enabled = enabled && self.compact.is_some();
     }
 }
 impl GuiElem for GuiScreen {
@@ -255,6 +376,7 @@ impl GuiElem for GuiScreen {
                 .chain(self.c_editing_songs.as_mut().map(|v| v.elem_mut()))
                 .chain(self.c_song_adder.as_mut().map(|v| v.elem_mut()).into_iter())
                 .chain([
+                    self.c_button_full.elem_mut(),
                     self.c_status_bar.elem_mut(),
                     self.c_settings.elem_mut(),
                     self.c_main_view.elem_mut(),
@@ -323,9 +445,10 @@ impl GuiElem for GuiScreen {
     }
     fn draw(&mut self, info: &mut DrawInfo, _g: &mut Graphics2D) {
         if self.config.init {
+            let hotkeys = info.gui_config.hotkeys;
             info.actions.extend([
                 GuiAction::AddKeybind(
-                    Some((KeyBinding::ctrl(VirtualKeyCode::Q), true)),
+                    hotkeys.quit.map(|b| (b, true)),
                     KeyAction {
                         category: "General".to_owned(),
                         title: "Quit".to_owned(),
@@ -336,7 +459,7 @@ impl GuiElem for GuiScreen {
                     Box::new(|_| {}),
                 ),
                 GuiAction::AddKeybind(
-                    Some((KeyBinding::ctrl(VirtualKeyCode::I), true)),
+                    hotkeys.idle.map(|b| (b, true)),
                     KeyAction {
                         category: "General".to_owned(),
                         title: "Idle".to_owned(),
@@ -347,7 +470,56 @@ impl GuiElem for GuiScreen {
                     Box::new(|_| {}),
                 ),
                 GuiAction::AddKeybind(
-                    Some((KeyBinding::ctrl(VirtualKeyCode::F), true)),
+                    hotkeys.compact.map(|b| (b, true)),
+                    KeyAction {
+                        category: "General".to_owned(),
+                        title: "Compact mode".to_owned(),
+                        description: "switches to a small window with only the current song and playback controls, or back"
+                            .to_owned(),
+                        action: Box::new(|| {
+                            vec![GuiAction::Do(Box::new(|gui| gui.gui.toggle_compact()))]
+                        }),
+                        enabled: true,
+                    },
+                    Box::new(|_| {}),
+                ),
+                GuiAction::AddKeybind(
+                    hotkeys.repeat.map(|b| (b, true)),
+                    KeyAction {
+                        category: "Queue".to_owned(),
+                        title: "Repeat".to_owned(),
+                        description: "switches between repeat off, repeat all and repeat one"
+                            .to_owned(),
+                        action: Box::new(|| {
+                            vec![GuiAction::Build(Box::new(|db| {
+                                vec![GuiAction::SendToServer(Action::QueueSetRepeat(
+                                    match db.repeat {
+                                        RepeatMode::Off => RepeatMode::All,
+                                        RepeatMode::All => RepeatMode::One,
+                                        RepeatMode::One => RepeatMode::Off,
+                                    },
+                                ))]
+                            }))]
+                        }),
+                        enabled: true,
+                    },
+                    Box::new(|_| {}),
+                ),
+                GuiAction::AddKeybind(
+                    hotkeys.undo.map(|b| (b, true)),
+                    KeyAction {
+                        category: "Queue".to_owned(),
+                        title: "Undo".to_owned(),
+                        description: "undoes the last change you made to the queue".to_owned(),
+                        action: Box::new(|| {
+                            vec![GuiAction::Do(Box::new(|gui| gui.undo_queue_action()))]
+                        }),
+                        enabled: true,
+                    },
+                    Box::new(|_| {}),
+                ),
+                GuiAction::AddKeybind(
+                    hotkeys.search_songs.map(|b| (b, true)),
                     KeyAction {
                         category: "Library".to_owned(),
                         title: "Search songs".to_owned(),
@@ -359,7 +531,115 @@ impl GuiElem for GuiScreen {
                     },
                     Box::new(|_| {}),
                 ),
+                playback_keybind(
+                    hotkeys.play_pause,
+                    "Play/Pause",
+                    "resumes or pauses playback (media key)",
+                    || {
+                        vec![GuiAction::Build(Box::new(|db| {
+                            vec![GuiAction::SendToServer(if db.playing {
+                                Action::Pause
+                            } else {
+                                Action::Resume
+                            })]
+                        }))]
+                    },
+                ),
+                playback_keybind(hotkeys.stop, "Stop", "stops playback (media key)", || {
+                    vec![GuiAction::SendToServer(Action::Stop)]
+                }),
+                playback_keybind(
+                    hotkeys.next_song,
+                    "Next song",
+                    "skips to the next song (media key)",
+                    || vec![GuiAction::SendToServer(Action::NextSong)],
+                ),
+                playback_keybind(
+                    hotkeys.next_song_alt,
+                    "Next song",
+                    "skips to the next song",
+                    || vec![GuiAction::SendToServer(Action::NextSong)],
+                ),
+                playback_keybind(
+                    hotkeys.restart_song,
+                    "Restart song",
+                    "jumps to the start of the current song (media key)",
+                    || vec![GuiAction::SendToServer(Action::SetCurrentSongPosition(0))],
+                ),
+                playback_keybind(
+                    hotkeys.restart_song_alt,
+                    "Restart song",
+                    "jumps to the start of the current song",
+                    || vec![GuiAction::SendToServer(Action::SetCurrentSongPosition(0))],
+                ),
+                playback_keybind(
+                    hotkeys.seek_forward,
+                    "Seek forward",
+                    "jumps 10 seconds ahead in the current song",
+                    || seek_relative(10_000),
+                ),
+                playback_keybind(
+                    hotkeys.seek_backward,
+                    "Seek backward",
+                    "jumps 10 seconds back in the current song",
+                    || seek_relative(-10_000),
+                ),
             ]);
+            /// no modifiers and no priority, so these don't trigger if a focused element uses the key
+            fn playback_keybind(
+                key: Option<KeyBinding>,
+                title: &str,
+                description: &str,
+                action: fn() -> Vec<GuiAction>,
+            ) -> GuiAction {
+                GuiAction::AddKeybind(
+                    key.map(|b| (b, false)),
+                    KeyAction {
+                        category: "Playback".to_owned(),
+                        title: title.to_owned(),
+                        description: description.to_owned(),
+                        action: Box::new(action),
+                        enabled: true,
+                    },
+                    Box::new(|_| {}),
+                )
+            }
+            fn seek_relative(millis: i64) -> Vec<GuiAction> {
+                vec![GuiAction::Do(Box::new(move |gui| {
+                    if let Some((position, duration)) = gui.gui.c_status_bar.playback_position() {
+                        let position = (position as i64 + millis).clamp(0, duration as i64) as u64;
+                        gui.exec_gui_action(GuiAction::SendToServer(
+                            Action::SetCurrentSongPosition(position),
+                        ));
+                    }
+                }))]
+            }
+        }
+        // compact mode
+        if self.compact_toggled {
+            self.compact_toggled = false;
+            let size = if let Some(size) = self.compact.take() {
+                size
+            } else {
+                self.compact = Some(info.pos.size());
+                self.unidle();
+                if self.settings.0 {
+                    self.settings = (false, Some(Instant::now()));
+                }
+                Vec2::new(COMPACT_SIZE.0 as f32, COMPACT_SIZE.1 as f32)
+            };
+            let compact = self.compact.is_some();
+            self.c_main_view.config_mut().enabled = !compact;
+            self.c_button_full.config_mut().enabled = compact;
+            self.c_status_bar.config_mut().pos = if compact {
+                Rectangle::from_tuples((0.0, 0.0), (0.92, 1.0))
+            } else {
+                Rectangle::from_tuples((0.0, 0.9), (1.0, 1.0))
+            };
+            if let Some(h) = &info.helper {
+                h.set_size_pixels(UVec2::new(size.x as u32, size.y as u32));
+                h.request_redraw();
+            }
         }
         // idle stuff
         if self.prev_mouse_pos != info.mouse_pos {
@@ -370,9 +650,10 @@ impl GuiElem for GuiScreen {
             self.not_idle();
         }
         if !(!info.database.playing
+            || self.compact.is_some()
             || matches!(info.database.queue.content(), QueueContent::Folder(QueueFolder { content: v, .. }) if v.is_empty()))
         {
-            // skip idle_check if paused or queue is empty
+            // skip idle_check if paused, in compact mode or if the queue is empty
             self.idle_check();
         }
         // show/hide idle_exit_hint
@@ -1,6 +1,6 @@
 use std::sync::{atomic::AtomicBool, Arc, Mutex};
 
-use musicdb_lib::server::Action;
+use musicdb_lib::{data::statistics::LibraryStatistics, server::Action};
 use speedy2d::{
     color::Color,
     dimen::Vec2,
@@ -67,8 +67,12 @@ pub struct SettingsContent {
     pub line_height: Panel<(Label, Slider)>,
     pub scroll_sensitivity: Panel<(Label, Slider)>,
     pub idle_time: Panel<(Label, Slider)>,
-    pub save_button: Button<[Label; 1]>,
+    /// save, reload dbfile
+    pub server_buttons: Panel<[Button<[Label; 1]>; 2]>,
     pub add_new_songs_button: Button<[Label; 1]>,
+    /// some information about the library, updated when the library changes
+    pub library_stats: AdvancedLabel,
+    library_stats_outdated: bool,
     pub keybinds: Vec<Panel<(AdvancedLabel, KeybindInput)>>,
     pub keybinds_should_be_updated: Arc<AtomicBool>,
     pub keybinds_updated: bool,
@@ -84,15 +88,16 @@ impl GuiElemChildren for SettingsContent {
                 self.line_height.elem_mut(),
                 self.scroll_sensitivity.elem_mut(),
                 self.idle_time.elem_mut(),
-                self.save_button.elem_mut(),
+                self.server_buttons.elem_mut(),
                 self.add_new_songs_button.elem_mut(),
+                self.library_stats.elem_mut(),
             ]
             .into_iter()
             .chain(self.keybinds.iter_mut().map(|v| v.elem_mut())),
         )
     }
     fn len(&self) -> usize {
-        8 + self.keybinds.len()
+        9 + self.keybinds.len()
     }
 }
 pub struct KeybindInput {
@@ -448,16 +453,32 @@ impl SettingsContent {
                     ),
                 ),
             ),
-            save_button: Button::new(
+            server_buttons: Panel::new(
                 GuiElemCfg::default(),
-                |_| vec![GuiAction::SendToServer(Action::Save)],
-                [Label::new(
-                    GuiElemCfg::default(),
-                    "Server: Save Changes".to_string(),
-                    Color::WHITE,
-                    None,
-                    Vec2::new(0.5, 0.5),
-                )],
+                [
+                    Button::new(
+                        GuiElemCfg::at(Rectangle::from_tuples((0.0, 0.0), (0.5, 1.0))),
+                        |_| vec![GuiAction::SendToServer(Action::Save)],
+                        [Label::new(
+                            GuiElemCfg::default(),
+                            "Server: Save Changes".to_string(),
+                            Color::WHITE,
+                            None,
+                            Vec2::new(0.5, 0.5),
+                        )],
+                    ),
+                    Button::new(
+                        GuiElemCfg::at(Rectangle::from_tuples((0.5, 0.0), (1.0, 1.0))),
+                        |_| vec![GuiAction::SendToServer(Action::ReloadDatabase)],
+                        [Label::new(
+                            GuiElemCfg::default(),
+                            "Server: Reload dbfile".to_string(),
+                            Color::WHITE,
+                            None,
+                            Vec2::new(0.5, 0.5),
+                        )],
+                    ),
+                ],
             ),
             add_new_songs_button: Button::new(
                 GuiElemCfg::default(),
@@ -470,6 +491,8 @@ impl SettingsContent {
                     Vec2::new(0.5, 0.5),
                 )],
             ),
+            library_stats: AdvancedLabel::new(GuiElemCfg::default(), Vec2::new(0.5, 0.5), vec![]),
+            library_stats_outdated: true,
             keybinds: vec![],
             keybinds_should_be_updated: Arc::new(AtomicBool::new(true)),
             keybinds_updated: false,
@@ -477,6 +500,10 @@ impl SettingsContent {
         }
     }
     pub fn draw(&mut self, info: &mut DrawInfo) -> bool {
+        if self.library_stats_outdated {
+            self.library_stats_outdated = false;
+            self.library_stats.content = library_stats_content(&info.database.statistics());
+        }
         if !self.keybinds_updated
             && self
                 .keybinds_should_be_updated
@@ -530,6 +557,9 @@ impl GuiElem for Settings {
     fn elem_mut(&mut self) -> &mut dyn GuiElem {
         self
     }
+    fn updated_library(&mut self) {
+        self.c_scroll_box.children.library_stats_outdated = true;
+    }
     fn draw(&mut self, info: &mut DrawInfo, _g: &mut Graphics2D) {
         if self.c_scroll_box.children.draw(info) {
             self.c_scroll_box.config_mut().redraw = true;
@@ -566,6 +596,42 @@ impl GuiElem for Settings {
     }
 }
 
+fn library_stats_content(stats: &LibraryStatistics) -> Vec<Vec<(AdvancedContent, f32, f32)>> {
+    let minutes = stats.duration_millis / 60_000;
+    let mut flags = stats.flags.iter().collect::<Vec<_>>();
+    flags.sort_by(|(_, a), (_, b)| b.cmp(a));
+    let mut details = format!(
+        "{} songs without album, {} without duration",
+        stats.singles, stats.songs_without_duration
+    );
+    for (flag, count) in flags.into_iter().take(5) {
+        details.push_str(&format!(" · {flag}: {count}"));
+    }
+    vec![
+        vec![(
+            AdvancedContent::Text(Content::new(
+                format!(
+                    "Your library: {} songs, {} albums, {} artists · {}h {}m · {:.1} GiB",
+                    stats.songs,
+                    stats.albums,
+                    stats.artists,
+                    minutes / 60,
+                    minutes % 60,
+                    stats.file_size as f64 / (1024.0 * 1024.0 * 1024.0)
+                ),
+                Color::WHITE,
+            )),
+            1.0,
+            1.0,
+        )],
+        vec![(
+            AdvancedContent::Text(Content::new(details, Color::LIGHT_GRAY)),
+            0.5,
+            1.0,
+        )],
+    ]
+}
+
 pub fn build_keybind_elems(
     gui: &crate::gui::Gui,
     keybinds_should_be_updated: &Arc<AtomicBool>,
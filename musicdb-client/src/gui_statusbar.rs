@@ -8,8 +8,8 @@ use speedy2d::{dimen::Vec2, shape::Rectangle};
 use crate::{
     gui::{DrawInfo, GuiElem, GuiElemCfg},
     gui_anim::AnimationController,
-    gui_playback::{image_display, CurrentInfo},
-    gui_playpause::PlayPause,
+    gui_playback::{image_display, CurrentInfo, PlaybackBar},
+    gui_playpause::{PlayPause, VolumeControl},
     gui_text::AdvancedLabel,
 };
 
@@ -19,9 +19,13 @@ pub struct StatusBar {
     current_info: CurrentInfo,
     cover_aspect_ratio: AnimationController<f32>,
     c_song_label: AdvancedLabel,
+    c_playback_bar: PlaybackBar,
     pub force_reset_texts: bool,
     c_buttons: PlayPause,
+    c_volume: VolumeControl,
     is_fav: (bool, Arc<AtomicBool>),
+    /// the last `db.current_song_format`, the text has to be regenerated if it changes
+    format: Option<(u32, u16)>,
 }
 
 impl StatusBar {
@@ -41,19 +45,38 @@ impl StatusBar {
                 Instant::now(),
             ),
             c_song_label: AdvancedLabel::new(GuiElemCfg::default(), Vec2::new(0.0, 0.5), vec![]),
+            c_playback_bar: PlaybackBar::new(GuiElemCfg::default()),
             force_reset_texts: false,
             is_fav: (false, Arc::clone(&is_fav)),
             c_buttons: PlayPause::new(GuiElemCfg::default(), is_fav),
+            c_volume: VolumeControl::new(GuiElemCfg::default()),
+            format: None,
         }
     }
+    /// The estimated playback position and the duration of the current song, in milliseconds
+    pub fn playback_position(&self) -> Option<(u64, u64)> {
+        self.c_playback_bar.playback_position()
+    }
 }
 
 impl GuiElem for StatusBar {
     fn children(&mut self) -> Box<dyn Iterator<Item = &mut dyn GuiElem> + '_> {
-        Box::new([self.c_song_label.elem_mut(), self.c_buttons.elem_mut()].into_iter())
+        Box::new(
+            [
+                self.c_song_label.elem_mut(),
+                self.c_playback_bar.elem_mut(),
+                self.c_buttons.elem_mut(),
+                self.c_volume.elem_mut(),
+            ]
+            .into_iter(),
+        )
     }
     fn draw(&mut self, info: &mut DrawInfo, g: &mut speedy2d::Graphics2D) {
         self.current_info.update(info, g);
+        if info.database.current_song_format != self.format {
+            self.format = info.database.current_song_format;
+            self.force_reset_texts = true;
+        }
         if self.current_info.new_song || self.force_reset_texts {
             self.current_info.new_song = false;
             self.force_reset_texts = false;
@@ -97,19 +120,23 @@ impl GuiElem for StatusBar {
             }
             // limit width of c_buttons
             let buttons_right_pos = 0.99;
-            let buttons_width_max = info.pos.height() * 0.7 * 4.0 / info.pos.width();
-            let buttons_width = buttons_width_max.min(0.2);
+            let buttons_width_max = info.pos.height() * 0.7 * 6.0 / info.pos.width();
+            let buttons_width = buttons_width_max.min(0.3);
             self.c_buttons.config_mut().pos = Rectangle::from_tuples(
                 (buttons_right_pos - buttons_width, 0.15),
                 (buttons_right_pos, 0.85),
             );
-            self.c_song_label.config_mut().pos = Rectangle::from_tuples(
-                (
-                    self.cover_aspect_ratio.value * info.pos.height() / info.pos.width(),
-                    0.0,
-                ),
-                (buttons_right_pos - buttons_width, 1.0),
+            let volume_right_pos = buttons_right_pos - buttons_width - 0.01;
+            let volume_width = buttons_width * 0.5;
+            self.c_volume.config_mut().pos = Rectangle::from_tuples(
+                (volume_right_pos - volume_width, 0.3),
+                (volume_right_pos, 0.7),
             );
+            let left = self.cover_aspect_ratio.value * info.pos.height() / info.pos.width();
+            let right = volume_right_pos - volume_width - 0.01;
+            self.c_song_label.config_mut().pos = Rectangle::from_tuples((left, 0.0), (right, 0.8));
+            self.c_playback_bar.config_mut().pos =
+                Rectangle::from_tuples((left + 0.01, 0.8), (right, 0.98));
         }
         // draw cover
         if let Some(Some(cover)) = self
@@ -1,4 +1,8 @@
-use std::{fmt::Display, rc::Rc, sync::Arc};
+use std::{
+    fmt::Display,
+    rc::Rc,
+    sync::{Arc, Mutex},
+};
 
 use musicdb_lib::data::CoverId;
 use speedy2d::{
@@ -151,6 +155,10 @@ pub struct TextField {
     config: GuiElemCfg,
     pub c_input: Label,
     pub c_hint: Label,
+    /// shows the first of `suggestions` which starts with the current text, Enter accepts it
+    pub c_suggestion: Label,
+    /// should be ordered by relevance
+    pub suggestions: Option<Arc<Mutex<Vec<String>>>>,
     pub on_changed: Option<Box<dyn FnMut(&str)>>,
     pub on_changed_mut: Option<Box<dyn FnMut(&mut Self, String)>>,
 }
@@ -186,10 +194,48 @@ impl TextField {
                 None,
                 Vec2::new(0.0, 0.5),
             ),
+            c_suggestion: Label::new(
+                GuiElemCfg::default().disabled(),
+                String::new(),
+                color_hint,
+                None,
+                Vec2::new(1.0, 0.5),
+            ),
+            suggestions: None,
             on_changed: None,
             on_changed_mut: None,
         }
     }
+    pub fn with_suggestions(mut self, suggestions: Arc<Mutex<Vec<String>>>) -> Self {
+        self.suggestions = Some(suggestions);
+        self
+    }
+    fn text_changed(&mut self) {
+        let content = &mut self.c_input.content;
+        if let Some(f) = &mut self.on_changed {
+            f(content.get_text());
+        }
+        if let Some(mut f) = self.on_changed_mut.take() {
+            let text = content.get_text().clone();
+            f(self, text);
+            self.on_changed_mut = Some(f);
+        }
+        self.c_hint.config_mut().enabled = self.c_input.content.get_text().is_empty();
+        let text = self.c_input.content.get_text();
+        let suggestion = if text.is_empty() {
+            None
+        } else {
+            self.suggestions.as_ref().and_then(|suggestions| {
+                suggestions
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .find(|s| s.len() > text.len() && s.starts_with(text.as_str()))
+                    .cloned()
+            })
+        };
+        *self.c_suggestion.content.text() = suggestion.unwrap_or_default();
+    }
 }
 impl GuiElem for TextField {
     fn config(&self) -> &GuiElemCfg {
@@ -199,7 +245,14 @@ impl GuiElem for TextField {
         &mut self.config
     }
     fn children(&mut self) -> Box<dyn Iterator<Item = &mut dyn GuiElem> + '_> {
-        Box::new([self.c_input.elem_mut(), self.c_hint.elem_mut()].into_iter())
+        Box::new(
+            [
+                self.c_suggestion.elem_mut(),
+                self.c_input.elem_mut(),
+                self.c_hint.elem_mut(),
+            ]
+            .into_iter(),
+        )
     }
     fn any(&self) -> &dyn std::any::Any {
         self
@@ -223,6 +276,8 @@ impl GuiElem for TextField {
         g.draw_line(info.pos.bottom_left(), info.pos.bottom_right(), t, c);
         g.draw_line(info.pos.top_left(), info.pos.bottom_left(), t, c);
         g.draw_line(info.pos.top_right(), info.pos.bottom_right(), t, c);
+        self.c_suggestion.config_mut().enabled =
+            info.has_keyboard_focus && !self.c_suggestion.content.get_text().is_empty();
     }
     fn mouse_pressed(&mut self, e: &mut EventInfo, _button: MouseButton) -> Vec<GuiAction> {
         if e.take() {
@@ -242,20 +297,8 @@ impl GuiElem for TextField {
             && !key.is_control()
             && e.take()
         {
-            let content = &mut self.c_input.content;
-            let was_empty = content.get_text().is_empty();
-            content.text().push(key);
-            if let Some(f) = &mut self.on_changed {
-                f(content.get_text());
-            }
-            if let Some(mut f) = self.on_changed_mut.take() {
-                let text = content.get_text().clone();
-                f(self, text);
-                self.on_changed_mut = Some(f);
-            }
-            if was_empty {
-                self.c_hint.config_mut().enabled = false;
-            }
+            self.c_input.content.text().push(key);
+            self.text_changed();
         }
         vec![]
     }
@@ -285,19 +328,17 @@ impl GuiElem for TextField {
                 } else {
                     content.text().pop();
                 }
-                let is_now_empty = content.get_text().is_empty();
-                if let Some(f) = &mut self.on_changed {
-                    f(content.get_text());
-                }
-                if let Some(mut f) = self.on_changed_mut.take() {
-                    let text = content.get_text().clone();
-                    f(self, text);
-                    self.on_changed_mut = Some(f);
-                }
-                if is_now_empty {
-                    self.c_hint.config_mut().enabled = true;
-                }
+                self.text_changed();
             }
+        } else if down
+            && !(modifiers.ctrl() || modifiers.alt() || modifiers.logo())
+            && key == Some(speedy2d::window::VirtualKeyCode::Return)
+            && !self.c_suggestion.content.get_text().is_empty()
+            && e.take()
+        {
+            let suggestion = std::mem::take(self.c_suggestion.content.text());
+            *self.c_input.content.text() = suggestion;
+            self.text_changed();
         }
         vec![]
     }
@@ -461,7 +502,7 @@ impl GuiElem for AdvancedLabel {
                                 if handle.is_none() {
                                     match source {
                                         ImageSource::Cover(id) => {
-                                            if let Some(img) = info.covers.get_mut(&id) {
+                                            if let Some(img) = info.thumbnails.get_mut(&id) {
                                                 if let Some(img) = img.get_init(g) {
                                                     *handle = Some(Some(img));
                                                 } else {
@@ -474,9 +515,9 @@ impl GuiElem for AdvancedLabel {
                                                     }
                                                 }
                                             } else {
-                                                info.covers.insert(
+                                                info.thumbnails.insert(
                                                     *id,
-                                                    GuiServerImage::new_cover(
+                                                    GuiServerImage::new_cover_thumbnail(
                                                         *id,
                                                         Arc::clone(&info.get_con),
                                                     ),
@@ -0,0 +1,254 @@
+use std::collections::VecDeque;
+
+use musicdb_lib::{
+    data::{
+        database::Database,
+        queue::{Queue, QueueContent},
+    },
+    server::{Action, Req},
+};
+
+/// how many queue actions can be undone
+const UNDO_LIMIT: usize = 50;
+
+/// Records the inverse of queue actions sent by the gui so they can be undone.
+/// The recorded paths become invalid if someone else changes the queue,
+/// which is detected (using `Check`s) before undoing anything.
+#[derive(Default)]
+pub struct QueueUndo {
+    stack: VecDeque<UndoEntry>,
+}
+struct UndoEntry {
+    inverse: Vec<Action>,
+    checks: Vec<Check>,
+}
+enum Check {
+    /// the element at this path should (still) be this one
+    Item(Vec<usize>, Queue),
+    /// this path should be a folder with at least this many elements
+    Folder(Vec<usize>, usize),
+}
+
+impl QueueUndo {
+    /// Call this before sending `action` to the server, while `db` still has the old queue.
+    pub fn record(&mut self, action: &Action, db: &Database) {
+        let entry = match action {
+            Action::Multiple(actions) => {
+                let mut changes = actions.iter().filter(|a| changes_queue(a));
+                match (changes.next(), changes.next()) {
+                    (None, _) => return,
+                    (Some(action), None) => inverse(action, db),
+                    // we don't know the state between the actions
+                    (Some(_), Some(_)) => None,
+                }
+            }
+            action if changes_queue(action) => inverse(action, db),
+            _ => return,
+        };
+        if let Some(entry) = entry {
+            if self.stack.len() >= UNDO_LIMIT {
+                self.stack.pop_front();
+            }
+            self.stack.push_back(entry);
+        } else {
+            // can't be undone, and the paths in older entries may no longer be correct
+            self.stack.clear();
+        }
+    }
+    pub fn clear(&mut self) {
+        self.stack.clear();
+    }
+    /// Returns the action which undoes the most recent queue action, if there is one.
+    /// If the queue was changed so that the recorded paths are wrong, everything is discarded instead.
+    pub fn undo(&mut self, db: &Database) -> Option<Action> {
+        let entry = self.stack.pop_back()?;
+        if entry.checks.iter().all(|check| check.ok(&db.queue)) {
+            Some(Action::Multiple(entry.inverse))
+        } else {
+            eprintln!("[info] can't undo, the queue was changed by someone else");
+            self.stack.clear();
+            None
+        }
+    }
+}
+
+fn changes_queue(action: &Action) -> bool {
+    matches!(
+        action,
+        Action::QueueUpdate(..)
+            | Action::QueueAdd(..)
+            | Action::QueueReplace(..)
+            | Action::QueueInsert(..)
+            | Action::QueueInsertAfterCurrent(..)
+            | Action::QueueRemove(..)
+            | Action::QueueMove(..)
+            | Action::QueueMoveInto(..)
+            | Action::QueueMoveMultiple(..)
+    )
+}
+
+fn inverse(action: &Action, db: &Database) -> Option<UndoEntry> {
+    let queue = &db.queue;
+    Some(match action {
+        Action::QueueUpdate(path, new, _) => {
+            let old = queue.get_item_at_index(path, 0)?.clone();
+            UndoEntry {
+                inverse: vec![Action::QueueUpdate(path.clone(), old, Req::none())],
+                checks: vec![Check::Item(path.clone(), new.clone())],
+            }
+        }
+        Action::QueueReplace(new, _) => UndoEntry {
+            inverse: vec![Action::QueueReplace(queue.clone(), true)],
+            checks: vec![Check::Item(vec![], new.clone())],
+        },
+        Action::QueueAdd(path, items, _) => removals(path, folder_len(queue, path)?, items),
+        Action::QueueInsert(path, pos, items, _) => {
+            removals(path, (*pos).min(folder_len(queue, path)?), items)
+        }
+        // where the server will insert the items, like in `Database::apply_action_unchecked_seq`
+        Action::QueueInsertAfterCurrent(items) => match queue.get_insert_after_current() {
+            Some((path, pos)) => removals(&path, pos.min(folder_len(queue, &path)?), items),
+            None => removals(&[], folder_len(queue, &[])?, items),
+        },
+        Action::QueueRemove(path) => {
+            let (i, parent) = path.split_last()?;
+            let removed = queue.get_item_at_index(path, 0)?.clone();
+            UndoEntry {
+                inverse: vec![Action::QueueInsert(
+                    parent.to_vec(),
+                    *i,
+                    vec![removed],
+                    Req::none(),
+                )],
+                checks: vec![Check::Folder(parent.to_vec(), *i)],
+            }
+        }
+        Action::QueueMove(from, to) => {
+            if from.is_empty() || to.is_empty() || to.starts_with(from) {
+                return None;
+            }
+            let item = queue.get_item_at_index(from, 0)?.clone();
+            moved_back(&adjust_for_removal(from, to.clone()), from, item)
+        }
+        Action::QueueMoveInto(from, parent_to) => {
+            if from.is_empty() || parent_to.starts_with(from) {
+                return None;
+            }
+            let item = queue.get_item_at_index(from, 0)?.clone();
+            let mut len = folder_len(queue, parent_to)?;
+            if from[..from.len() - 1] == parent_to[..] {
+                len -= 1;
+            }
+            let mut now_at = adjust_for_removal(from, parent_to.clone());
+            now_at.push(len);
+            moved_back(&now_at, from, item)
+        }
+        Action::QueueMoveMultiple(sources, target) => {
+            // restore the innermost folder which contains everything that was moved.
+            // unlike the other inverses, this also resets the current song in that folder.
+            let mut common = target.split_last()?.1;
+            for source in sources {
+                let parent = source.split_last()?.1;
+                let same = common
+                    .iter()
+                    .zip(parent)
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                common = &common[..same];
+            }
+            let old = queue.get_item_at_index(common, 0)?.clone();
+            UndoEntry {
+                inverse: vec![Action::QueueUpdate(common.to_vec(), old, Req::none())],
+                checks: vec![Check::Folder(common.to_vec(), 0)],
+            }
+        }
+        _ => return None,
+    })
+}
+
+/// undoes adding `items` to the folder at `path`, starting at index `start`
+fn removals(path: &[usize], start: usize, items: &[Queue]) -> UndoEntry {
+    let at = |i: usize| {
+        let mut p = path.to_vec();
+        p.push(start + i);
+        p
+    };
+    UndoEntry {
+        // remove the last one first so the other indices don't change
+        inverse: (0..items.len())
+            .rev()
+            .map(|i| Action::QueueRemove(at(i)))
+            .collect(),
+        checks: items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| Check::Item(at(i), item.clone()))
+            .collect(),
+    }
+}
+
+/// The path `to` refers to once the element at `from` has been removed,
+/// the same adjustment the database makes for `QueueMove` and `QueueMoveInto`.
+fn adjust_for_removal(from: &[usize], mut to: Vec<usize>) -> Vec<usize> {
+    let level = from.len() - 1;
+    if to.len() >= from.len() && to.starts_with(&from[..level]) && to[level] > from[level] {
+        to[level] -= 1;
+    }
+    to
+}
+
+/// moves `item`, which is now at `now_at`, back to where it was (`original`)
+fn moved_back(now_at: &[usize], original: &[usize], item: Queue) -> UndoEntry {
+    // the opposite of `adjust_for_removal`
+    let level = now_at.len() - 1;
+    let mut target = original.to_vec();
+    if target.len() >= now_at.len()
+        && target.starts_with(&now_at[..level])
+        && target[level] >= now_at[level]
+    {
+        target[level] += 1;
+    }
+    UndoEntry {
+        inverse: vec![Action::QueueMove(now_at.to_vec(), target)],
+        checks: vec![Check::Item(now_at.to_vec(), item)],
+    }
+}
+
+fn folder_len(queue: &Queue, path: &[usize]) -> Option<usize> {
+    match queue.get_item_at_index(path, 0)?.content() {
+        QueueContent::Folder(folder) => Some(folder.content.len()),
+        _ => None,
+    }
+}
+
+impl Check {
+    fn ok(&self, queue: &Queue) -> bool {
+        match self {
+            Self::Item(path, item) => queue
+                .get_item_at_index(path, 0)
+                .is_some_and(|v| same_item(v, item)),
+            Self::Folder(path, len) => folder_len(queue, path).is_some_and(|v| v >= *len),
+        }
+    }
+}
+
+/// compares the content, but not the state (like the current index), which changes during playback
+fn same_item(a: &Queue, b: &Queue) -> bool {
+    match (a.content(), b.content()) {
+        (QueueContent::Song(a), QueueContent::Song(b)) => a == b,
+        (QueueContent::Folder(a), QueueContent::Folder(b)) => {
+            a.name == b.name
+                && a.content.len() == b.content.len()
+                && a.content
+                    .iter()
+                    .zip(&b.content)
+                    .all(|(a, b)| same_item(a, b))
+        }
+        (QueueContent::Loop(a, _, x), QueueContent::Loop(b, _, y)) => a == b && same_item(x, y),
+        (QueueContent::Playlist(a), QueueContent::Playlist(b)) => {
+            a.name == b.name && a.songs == b.songs
+        }
+        (QueueContent::Conditional(a), QueueContent::Conditional(b)) => a == b,
+        _ => false,
+    }
+}
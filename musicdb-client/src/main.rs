@@ -38,10 +38,14 @@ mod gui_idle_display;
 #[cfg(feature = "speedy2d")]
 mod gui_library;
 #[cfg(feature = "speedy2d")]
+mod gui_lyrics;
+#[cfg(feature = "speedy2d")]
 mod gui_notif;
 #[cfg(feature = "speedy2d")]
 mod gui_playback;
 #[cfg(feature = "speedy2d")]
+mod gui_playlists;
+#[cfg(feature = "speedy2d")]
 mod gui_playpause;
 #[cfg(feature = "speedy2d")]
 mod gui_queue;
@@ -56,11 +60,17 @@ mod gui_statusbar;
 #[cfg(feature = "speedy2d")]
 mod gui_text;
 #[cfg(feature = "speedy2d")]
+mod gui_undo;
+#[cfg(feature = "speedy2d")]
 mod gui_wrappers;
 #[cfg(feature = "merscfg")]
 mod merscfg;
+#[cfg(feature = "mpris")]
+mod mpris;
 #[cfg(feature = "speedy2d")]
 mod textcfg;
+#[cfg(feature = "tray")]
+mod tray;
 
 #[derive(Parser, Debug)]
 struct Args {
@@ -152,10 +162,11 @@ fn main() {
                 let cm = CacheManager::new(Arc::clone(&database));
                 cm.set_memory_mib(1024, 2048);
                 cm.set_cache_songs_count(20);
+                let mut player =
+                    Player::new_client(PlayerBackendFeat::new_without_command_sending().unwrap());
+                player.set_cache_counters(cm.counters());
                 cache_manager = Some(cm);
-                Some(Player::new_client(
-                    PlayerBackendFeat::new_without_command_sending().unwrap(),
-                ))
+                Some(player)
             } else {
                 None
             };
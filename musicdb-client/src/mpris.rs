@@ -0,0 +1,375 @@
+use std::{
+    collections::HashMap,
+    net::TcpStream,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use musicdb_lib::{
+    data::{database::Database, SongId},
+    load::ToFromBytes,
+    server::Action,
+};
+use speedy2d::window::UserEventSender;
+use zbus::{
+    blocking::{connection, Connection},
+    interface,
+    names::BusName,
+    zvariant::{ObjectPath, Value},
+};
+
+use crate::gui::GuiEvent;
+
+const PATH: &str = "/org/mpris/MediaPlayer2";
+const PLAYER_INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+const NO_TRACK: &str = "/org/mpris/MediaPlayer2/TrackList/NoTrack";
+
+type Metadata = HashMap<&'static str, Value<'static>>;
+
+/// Makes the client controllable via MPRIS (D-Bus), so desktop media widgets and media keys work.
+/// Changes are sent to the bus in `update`, which the gui calls when the database changes.
+pub struct Mpris {
+    connection: Connection,
+    database: Arc<Mutex<Database>>,
+    position: Arc<Mutex<Position>>,
+    last_status: &'static str,
+    last_volume: f32,
+    last_metadata: Metadata,
+}
+
+impl Mpris {
+    /// Registers `org.mpris.MediaPlayer2.musicdb` on the session bus.
+    /// Returns `None` (and prints a warning) if there is no session bus.
+    pub fn start(
+        database: Arc<Mutex<Database>>,
+        server: &TcpStream,
+        event_sender: Arc<UserEventSender<GuiEvent>>,
+    ) -> Option<Self> {
+        let position = Arc::new(Mutex::new(Position::new()));
+        let player = Player {
+            database: Arc::clone(&database),
+            server: Mutex::new(server.try_clone().ok()?),
+            position: Arc::clone(&position),
+        };
+        let connection = match connection::Builder::session().and_then(|b| {
+            b.name("org.mpris.MediaPlayer2.musicdb")?
+                .serve_at(
+                    PATH,
+                    Root {
+                        event_sender: Mutex::new(event_sender),
+                    },
+                )?
+                .serve_at(PATH, player)?
+                .build()
+        }) {
+            Ok(v) => v,
+            Err(e) => {
+                eprintln!("[warn] couldn't start mpris: {e}");
+                return None;
+            }
+        };
+        Some(Self {
+            connection,
+            database,
+            position,
+            last_status: "Stopped",
+            last_volume: 1.0,
+            last_metadata: HashMap::new(),
+        })
+    }
+
+    /// Sends `PropertiesChanged` for everything which changed since the last call
+    /// and `Seeked` if the playback position jumped.
+    pub fn update(&mut self) {
+        let db = self.database.lock().unwrap();
+        let seeked = self.position.lock().unwrap().update(&db);
+        let mut changed: HashMap<&str, Value> = HashMap::new();
+        let status = playback_status(&db);
+        if status != self.last_status {
+            self.last_status = status;
+            changed.insert("PlaybackStatus", status.into());
+        }
+        if db.volume != self.last_volume {
+            self.last_volume = db.volume;
+            changed.insert("Volume", (db.volume as f64).into());
+        }
+        let metadata = metadata(&db);
+        drop(db);
+        if metadata != self.last_metadata {
+            changed.insert("Metadata", metadata.clone().into());
+            self.last_metadata = metadata;
+        }
+        if !changed.is_empty() {
+            if let Err(e) = self.connection.emit_signal(
+                None::<BusName>,
+                PATH,
+                "org.freedesktop.DBus.Properties",
+                "PropertiesChanged",
+                &(PLAYER_INTERFACE, changed, Vec::<&str>::new()),
+            ) {
+                eprintln!("[warn] mpris: couldn't send changes: {e}");
+            }
+        }
+        if let Some(position) = seeked {
+            _ = self.connection.emit_signal(
+                None::<BusName>,
+                PATH,
+                PLAYER_INTERFACE,
+                "Seeked",
+                &(position as i64 * 1000),
+            );
+        }
+    }
+}
+
+struct Root {
+    event_sender: Mutex<Arc<UserEventSender<GuiEvent>>>,
+}
+
+#[interface(name = "org.mpris.MediaPlayer2")]
+impl Root {
+    fn raise(&self) {}
+    fn quit(&self) {
+        _ = self.event_sender.lock().unwrap().send_event(GuiEvent::Exit);
+    }
+    #[zbus(property)]
+    fn can_quit(&self) -> bool {
+        true
+    }
+    #[zbus(property)]
+    fn can_raise(&self) -> bool {
+        false
+    }
+    #[zbus(property)]
+    fn has_track_list(&self) -> bool {
+        false
+    }
+    #[zbus(property)]
+    fn identity(&self) -> String {
+        "MusicDb".to_owned()
+    }
+    #[zbus(property)]
+    fn supported_uri_schemes(&self) -> Vec<String> {
+        vec![]
+    }
+    #[zbus(property)]
+    fn supported_mime_types(&self) -> Vec<String> {
+        vec![]
+    }
+}
+
+struct Player {
+    database: Arc<Mutex<Database>>,
+    server: Mutex<TcpStream>,
+    position: Arc<Mutex<Position>>,
+}
+
+impl Player {
+    fn send(&self, action: Action) {
+        let command = self.database.lock().unwrap().seq.pack(action);
+        if let Err(e) = command.to_bytes(&mut *self.server.lock().unwrap()) {
+            eprintln!("[warn] mpris: couldn't send command to server: {e}");
+        }
+    }
+    /// `position` is in milliseconds
+    fn set_position_ms(&self, position: i64) {
+        let db = self.database.lock().unwrap();
+        let duration = db
+            .queue
+            .get_current_song()
+            .and_then(|id| db.get_song(id))
+            .map_or(0, |song| song.duration_millis);
+        drop(db);
+        self.send(Action::SetCurrentSongPosition(
+            (position.max(0) as u64).min(duration),
+        ));
+    }
+}
+
+#[interface(name = "org.mpris.MediaPlayer2.Player")]
+impl Player {
+    fn next(&self) {
+        self.send(Action::NextSong);
+    }
+    fn previous(&self) {
+        self.send(Action::PreviousSong);
+    }
+    fn pause(&self) {
+        self.send(Action::Pause);
+    }
+    fn play_pause(&self) {
+        let playing = self.database.lock().unwrap().playing;
+        self.send(if playing {
+            Action::Pause
+        } else {
+            Action::Resume
+        });
+    }
+    fn stop(&self) {
+        self.send(Action::Stop);
+    }
+    fn play(&self) {
+        self.send(Action::Resume);
+    }
+    /// `offset` is in microseconds
+    fn seek(&self, offset: i64) {
+        let position = self.position.lock().unwrap().at(Instant::now()) as i64;
+        self.set_position_ms(position + offset / 1000);
+    }
+    fn set_position(&self, track_id: ObjectPath<'_>, position: i64) {
+        // ignore requests meant for a song which is no longer playing
+        if *track_id == *track_id_of(self.position.lock().unwrap().song) {
+            self.set_position_ms(position / 1000);
+        }
+    }
+    fn open_uri(&self, _uri: String) {}
+    #[zbus(property)]
+    fn playback_status(&self) -> String {
+        playback_status(&self.database.lock().unwrap()).to_owned()
+    }
+    #[zbus(property)]
+    fn rate(&self) -> f64 {
+        1.0
+    }
+    #[zbus(property)]
+    fn set_rate(&self, _rate: f64) {}
+    #[zbus(property)]
+    fn minimum_rate(&self) -> f64 {
+        1.0
+    }
+    #[zbus(property)]
+    fn maximum_rate(&self) -> f64 {
+        1.0
+    }
+    #[zbus(property)]
+    fn metadata(&self) -> Metadata {
+        metadata(&self.database.lock().unwrap())
+    }
+    #[zbus(property)]
+    fn volume(&self) -> f64 {
+        self.database.lock().unwrap().volume as f64
+    }
+    #[zbus(property)]
+    fn set_volume(&self, volume: f64) {
+        self.send(Action::SetVolume(volume.max(0.0) as f32));
+    }
+    /// in microseconds
+    #[zbus(property(emits_changed_signal = "false"))]
+    fn position(&self) -> i64 {
+        self.position.lock().unwrap().at(Instant::now()) as i64 * 1000
+    }
+    #[zbus(property)]
+    fn can_go_next(&self) -> bool {
+        true
+    }
+    #[zbus(property)]
+    fn can_go_previous(&self) -> bool {
+        true
+    }
+    #[zbus(property)]
+    fn can_play(&self) -> bool {
+        true
+    }
+    #[zbus(property)]
+    fn can_pause(&self) -> bool {
+        true
+    }
+    #[zbus(property)]
+    fn can_seek(&self) -> bool {
+        true
+    }
+    #[zbus(property)]
+    fn can_control(&self) -> bool {
+        true
+    }
+}
+
+/// Estimates the playback position like the playback bar does,
+/// since clients only know the position at the time of the last `SetCurrentSongPosition` or `PlaybackPosition`.
+struct Position {
+    song: Option<SongId>,
+    playing: bool,
+    /// position (ms) at the given time
+    position: (u64, Instant),
+    db_position: Option<(u64, Instant)>,
+}
+impl Position {
+    fn new() -> Self {
+        Self {
+            song: None,
+            playing: false,
+            position: (0, Instant::now()),
+            db_position: None,
+        }
+    }
+    fn at(&self, now: Instant) -> u64 {
+        if self.playing {
+            self.position.0 + now.saturating_duration_since(self.position.1).as_millis() as u64
+        } else {
+            self.position.0
+        }
+    }
+    /// returns the new position if it jumped (not including the start of a new song).
+    /// the server regularly sends the position (`PlaybackPosition`), small corrections aren't jumps.
+    fn update(&mut self, db: &Database) -> Option<u64> {
+        let now = Instant::now();
+        let mut seeked = None;
+        let song = db.queue.get_current_song().copied();
+        if song != self.song {
+            self.song = song;
+            self.position = (0, now);
+        }
+        if db.current_song_position != self.db_position {
+            self.db_position = db.current_song_position;
+            if let Some(position) = self.db_position {
+                let expected = self.at(position.1);
+                self.position = position;
+                if expected.abs_diff(position.0) > 1000 {
+                    seeked = Some(position.0);
+                }
+            }
+        }
+        if db.playing != self.playing {
+            self.position = (self.at(now), now);
+            self.playing = db.playing;
+        }
+        seeked
+    }
+}
+
+fn playback_status(db: &Database) -> &'static str {
+    if db.queue.get_current_song().is_none() {
+        "Stopped"
+    } else if db.playing {
+        "Playing"
+    } else {
+        "Paused"
+    }
+}
+
+fn track_id_of(song: Option<SongId>) -> ObjectPath<'static> {
+    match song {
+        Some(id) => ObjectPath::try_from(format!("/musicdb/song/{id}")).unwrap(),
+        None => ObjectPath::from_static_str_unchecked(NO_TRACK),
+    }
+}
+
+fn metadata(db: &Database) -> Metadata {
+    let mut map = HashMap::new();
+    let id = db.queue.get_current_song().copied();
+    map.insert("mpris:trackid", track_id_of(id).into());
+    if let Some(song) = id.and_then(|id| db.get_song(&id)) {
+        map.insert("xesam:title", song.title.clone().into());
+        map.insert("mpris:length", (song.duration_millis as i64 * 1000).into());
+        let artists: Vec<String> = std::iter::once(&song.artist)
+            .chain(&song.more_artists)
+            .filter_map(|id| db.artists().get(id))
+            .map(|artist| artist.name.clone())
+            .collect();
+        map.insert("xesam:artist", artists.into());
+        if let Some(album) = song.album.and_then(|id| db.albums().get(&id)) {
+            map.insert("xesam:album", album.name.clone().into());
+        }
+    }
+    map
+}
@@ -22,6 +22,8 @@ pub enum TextPart {
     AlbumName,
     ArtistName,
     SongDuration(bool),
+    /// The current song's sample rate and channels, if the server knows them (see `Database::current_song_format_string`)
+    SongFormat,
     /// Searches for a tag with exactly the provided value.
     /// Returns nothing or one of the following characters:
     /// `s` for Song, `a` for Album, and `A` for Artist.
@@ -150,6 +152,11 @@ impl TextBuilder {
                         });
                     }
                 }
+                TextPart::SongFormat => {
+                    if let Some(format) = current_song.and(db.current_song_format_string()) {
+                        push!(format);
+                    }
+                }
                 TextPart::TagEq(p) => {
                     for (i, gen) in all_general(db, &current_song).into_iter().enumerate() {
                         if let Some(_) = gen.and_then(|gen| gen.tags.iter().find(|t| *t == p)) {
@@ -260,6 +267,10 @@ impl TextBuilder {
                             done!();
                             vec.push(TextPart::SongDuration(true));
                         }
+                        Some('f') => {
+                            done!();
+                            vec.push(TextPart::SongFormat);
+                        }
                         Some('s') => {
                             done!();
                             vec.push(TextPart::SetScale({
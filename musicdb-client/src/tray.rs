@@ -0,0 +1,182 @@
+use std::{
+    net::TcpStream,
+    sync::{Arc, Mutex},
+};
+
+use ksni::{
+    blocking::{Handle, TrayMethods},
+    menu::StandardItem,
+    MenuItem, ToolTip,
+};
+use musicdb_lib::{data::database::Database, load::ToFromBytes, server::Action};
+use speedy2d::window::UserEventSender;
+
+use crate::gui::GuiEvent;
+
+/// A tray icon (StatusNotifierItem, via D-Bus) which shows the current song and has playback controls.
+/// Like `Mpris`, the gui calls `update` when the database changes.
+pub struct Tray {
+    handle: Handle<TrayItem>,
+    database: Arc<Mutex<Database>>,
+    last_playing: bool,
+    last_song: Option<String>,
+}
+
+impl Tray {
+    /// Returns `None` (and prints a warning) if there is no session bus or no tray.
+    pub fn start(
+        database: Arc<Mutex<Database>>,
+        server: &TcpStream,
+        event_sender: Arc<UserEventSender<GuiEvent>>,
+    ) -> Option<Self> {
+        let item = TrayItem {
+            database: Arc::clone(&database),
+            server: server.try_clone().ok()?,
+            event_sender,
+            playing: false,
+            song: None,
+        };
+        match item.spawn() {
+            Ok(handle) => Some(Self {
+                handle,
+                database,
+                last_playing: false,
+                last_song: None,
+            }),
+            Err(e) => {
+                eprintln!("[warn] couldn't show tray icon: {e}");
+                None
+            }
+        }
+    }
+
+    /// Updates the tray's menu and tooltip if the song or the playback status changed.
+    pub fn update(&mut self) {
+        let db = self.database.lock().unwrap();
+        let playing = db.playing;
+        let song = db.queue.get_current_song().and_then(|id| {
+            let song = db.get_song(id)?;
+            Some(match db.artists().get(&song.artist) {
+                Some(artist) => format!("{} - {}", song.title, artist.name),
+                None => song.title.clone(),
+            })
+        });
+        drop(db);
+        if playing != self.last_playing || song != self.last_song {
+            self.last_playing = playing;
+            self.last_song = song.clone();
+            self.handle.update(|item| {
+                item.playing = playing;
+                item.song = song;
+            });
+        }
+    }
+}
+
+struct TrayItem {
+    database: Arc<Mutex<Database>>,
+    server: TcpStream,
+    event_sender: Arc<UserEventSender<GuiEvent>>,
+    playing: bool,
+    /// the current song's title and artist
+    song: Option<String>,
+}
+
+impl TrayItem {
+    fn send(&mut self, action: Action) {
+        let command = self.database.lock().unwrap().seq.pack(action);
+        if let Err(e) = command.to_bytes(&mut self.server) {
+            eprintln!("[warn] tray: couldn't send command to server: {e}");
+        }
+    }
+    fn play_pause(&mut self) {
+        let playing = self.database.lock().unwrap().playing;
+        self.send(if playing {
+            Action::Pause
+        } else {
+            Action::Resume
+        });
+    }
+}
+
+impl ksni::Tray for TrayItem {
+    fn id(&self) -> String {
+        "musicdb".to_owned()
+    }
+    fn title(&self) -> String {
+        "MusicDb".to_owned()
+    }
+    fn icon_name(&self) -> String {
+        if self.playing {
+            "media-playback-start"
+        } else {
+            "media-playback-pause"
+        }
+        .to_owned()
+    }
+    fn tool_tip(&self) -> ToolTip {
+        ToolTip {
+            title: self.song.clone().unwrap_or_else(|| "MusicDb".to_owned()),
+            description: if self.playing { "playing" } else { "paused" }.to_owned(),
+            ..Default::default()
+        }
+    }
+    /// clicking the icon plays or pauses
+    fn activate(&mut self, _x: i32, _y: i32) {
+        self.play_pause();
+    }
+    fn menu(&self) -> Vec<MenuItem<Self>> {
+        vec![
+            StandardItem {
+                label: self.song.clone().unwrap_or_else(|| "(no song)".to_owned()),
+                enabled: false,
+                ..Default::default()
+            }
+            .into(),
+            MenuItem::Separator,
+            StandardItem {
+                label: if self.playing { "Pause" } else { "Play" }.to_owned(),
+                icon_name: if self.playing {
+                    "media-playback-pause"
+                } else {
+                    "media-playback-start"
+                }
+                .to_owned(),
+                activate: Box::new(|this: &mut Self| this.play_pause()),
+                ..Default::default()
+            }
+            .into(),
+            StandardItem {
+                label: "Previous".to_owned(),
+                icon_name: "media-skip-backward".to_owned(),
+                activate: Box::new(|this: &mut Self| this.send(Action::PreviousSong)),
+                ..Default::default()
+            }
+            .into(),
+            StandardItem {
+                label: "Next".to_owned(),
+                icon_name: "media-skip-forward".to_owned(),
+                activate: Box::new(|this: &mut Self| this.send(Action::NextSong)),
+                ..Default::default()
+            }
+            .into(),
+            StandardItem {
+                label: "Stop".to_owned(),
+                icon_name: "media-playback-stop".to_owned(),
+                activate: Box::new(|this: &mut Self| this.send(Action::Stop)),
+                ..Default::default()
+            }
+            .into(),
+            MenuItem::Separator,
+            StandardItem {
+                label: "Quit".to_owned(),
+                icon_name: "application-exit".to_owned(),
+                activate: Box::new(|this: &mut Self| {
+                    _ = this.event_sender.send_event(GuiEvent::Exit);
+                }),
+                ..Default::default()
+            }
+            .into(),
+        ]
+    }
+}
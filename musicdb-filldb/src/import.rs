@@ -0,0 +1,127 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use musicdb_lib::data::database::Database;
+use serde_json::{json, Value};
+
+/// creates the dbfile from a json export or a csv file instead of reading the song files
+pub fn import(lib_dir: &str, file: &Path) {
+    let json = if file
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("csv"))
+    {
+        match csv_to_json(file) {
+            Ok(v) => v,
+            Err(e) => {
+                eprintln!("Couldn't read {file:?}: {e}");
+                std::process::exit(1);
+            }
+        }
+    } else {
+        match fs::read_to_string(file).map(|v| serde_json::from_str::<Value>(&v)) {
+            Ok(Ok(v)) => v,
+            Ok(Err(e)) => {
+                eprintln!("{file:?} isn't valid json: {e}");
+                std::process::exit(1);
+            }
+            Err(e) => {
+                eprintln!("Couldn't read {file:?}: {e}");
+                std::process::exit(1);
+            }
+        }
+    };
+    let mut database = Database::new_empty_in_dir(PathBuf::from("."), PathBuf::from(lib_dir));
+    let problems = match database.import_json(&json) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("Can't import {file:?}: {e}");
+            std::process::exit(1);
+        }
+    };
+    for problem in &problems {
+        eprintln!("[warn] {problem}");
+    }
+    let missing = database
+        .songs()
+        .values()
+        .filter(|song| !Path::new(lib_dir).join(&song.location.rel_path).is_file())
+        .count();
+    if missing > 0 {
+        eprintln!("[warn] {missing} songs' files don't exist in {lib_dir}.");
+    }
+    eprintln!(
+        "imported {} artists, {} albums and {} songs with {} problems.",
+        database.artists().len(),
+        database.albums().len(),
+        database.songs().len(),
+        problems.len()
+    );
+    eprintln!("saving dbfile...");
+    database.save_database(None).unwrap();
+    eprintln!("done!");
+}
+
+/// Converts a csv file with one song per line to `import_json`'s format.
+/// Artists and albums are matched by name, songs without an artist get `<unknown>`, like in filldb.
+fn csv_to_json(file: &Path) -> Result<Value, csv::Error> {
+    let mut reader = csv::Reader::from_path(file)?;
+    let headers = reader.headers()?.clone();
+    let column = |name: &str| headers.iter().position(|h| h.trim() == name);
+    let (title, artist, album, path, duration, tags) = (
+        column("title"),
+        column("artist"),
+        column("album"),
+        column("path"),
+        column("duration_millis"),
+        column("tags"),
+    );
+    let mut artists = HashMap::<String, u64>::new();
+    let mut albums = HashMap::<(u64, String), u64>::new();
+    let mut songs = vec![];
+    for (line, record) in reader.records().enumerate() {
+        let record = record?;
+        let get = |col: Option<usize>| {
+            col.and_then(|i| record.get(i))
+                .map(|v| v.trim())
+                .filter(|v| !v.is_empty())
+        };
+        let (Some(song_title), Some(song_path)) = (get(title), get(path)) else {
+            // +2 because of the header and because lines start at 1
+            eprintln!("[warn] line {} has no title or path, skipping", line + 2);
+            continue;
+        };
+        let artists_len = artists.len() as u64;
+        let artist_id = *artists
+            .entry(get(artist).unwrap_or("<unknown>").to_owned())
+            .or_insert(artists_len);
+        let albums_len = albums.len() as u64;
+        let album_id = get(album).map(|name| {
+            *albums
+                .entry((artist_id, name.to_owned()))
+                .or_insert(albums_len)
+        });
+        songs.push(json!({
+            "id": songs.len(),
+            "title": song_title,
+            "path": song_path,
+            "artist": artist_id,
+            "album": album_id,
+            "duration_millis": get(duration).and_then(|v| v.parse::<u64>().ok()),
+            "tags": get(tags).map_or(vec![], |v| {
+                v.split(';').map(|t| t.trim()).filter(|t| !t.is_empty()).collect()
+            }),
+        }));
+    }
+    let artists = artists
+        .iter()
+        .map(|(name, id)| json!({ "id": id, "name": name }))
+        .collect::<Vec<_>>();
+    let albums = albums
+        .iter()
+        .map(|((artist, name), id)| json!({ "id": id, "name": name, "artist": artist }))
+        .collect::<Vec<_>>();
+    Ok(json!({ "artists": artists, "albums": albums, "songs": songs }))
+}
@@ -2,12 +2,14 @@ use std::{
     cmp::Ordering,
     collections::HashMap,
     fs,
-    io::Write,
+    io::{Read, Write},
     path::{Path, PathBuf},
     sync::{Arc, Mutex}, time::SystemTime,
 };
 
 use id3::TagLike;
+mod import;
+
 use musicdb_lib::data::{
     album::Album,
     artist::Artist,
@@ -27,21 +29,28 @@ fn main() {
     };
     let mut bad_arg = false;
     let mut skip_duration = false;
+    let mut update = false;
     let mut custom_files = None;
     let mut artist_txt = false;
     let mut artist_img = false;
+    let mut import = None;
+    let mut artist_aliases = None;
     loop {
         match args.next() {
             None => break,
             Some(arg) => match arg.as_str() {
                 "--help" => {
                     eprintln!("--skip-duration: Don't try to figure out the songs duration from file contents. This means mp3 files with the Duration field unset will have a duration of 0.");
+                    eprintln!("--update: Read the existing dbfile first. Songs whose files didn't change (same path, size and modification time) keep their duration instead of reading it again, so durations corrected by the server are kept and files don't have to be decoded again.");
                     eprintln!("--custom-files <path>: server will use <path> as its custom-files directory.");
                     eprintln!("--cf-artist-txt: For each artist, check for an <artist>.txt file. If it exists, add each line as a tag to that artist.");
                     eprintln!("--cf-artist-img: For each artist, check for an <artist>.{{jpg,png,...}} file. If it exists, add ImageExt=<extension> tag to the artist, so the image can be loaded by clients later.");
+                    eprintln!("--artist-aliases <file>: Each line of <file> is an artist name followed by its aliases, separated by ';' (for example `The Beatles;Beatles`). Songs whose artist is one of the aliases (ignoring case) are added to that artist, and the artist gets these aliases, so searches can find it by any of them.");
+                    eprintln!("--import <file>: Don't read the song files. Instead, create the dbfile from a .json file (in musicdb-server --export-json's format) or from a .csv file with the columns title,artist,album,path,duration_millis,tags (tags separated by ';', only title and path are required).");
                     return;
                 }
                 "--skip-duration" => skip_duration = true,
+                "--update" => update = true,
                 "--custom-files" => {
                     if let Some(path) = args.next() {
                         custom_files = Some(PathBuf::from(path));
@@ -52,6 +61,22 @@ fn main() {
                 }
                 "--cf-artist-txt" => artist_txt = true,
                 "--cf-artist-img" => artist_img = true,
+                "--artist-aliases" => {
+                    if let Some(path) = args.next() {
+                        artist_aliases = Some(PathBuf::from(path));
+                    } else {
+                        bad_arg = true;
+                        eprintln!("--artist-aliases <file> :: missing <file>!");
+                    }
+                }
+                "--import" => {
+                    if let Some(path) = args.next() {
+                        import = Some(PathBuf::from(path));
+                    } else {
+                        bad_arg = true;
+                        eprintln!("--import <file> :: missing <file>!");
+                    }
+                }
                 arg => {
                     bad_arg = true;
                     eprintln!("Unknown argument: {arg}");
@@ -62,6 +87,41 @@ fn main() {
     if bad_arg {
         return;
     }
+    if let Some(file) = import {
+        import::import(&lib_dir, &file);
+        return;
+    }
+    // lowercase alias -> (artist name, all aliases of that artist)
+    let artist_aliases = match artist_aliases.map(|path| fs::read_to_string(&path).map_err(|e| (path, e))) {
+        None => HashMap::new(),
+        Some(Ok(file)) => read_artist_aliases(&file),
+        Some(Err((path, e))) => {
+            eprintln!("Couldn't read {path:?}: {e}");
+            return;
+        }
+    };
+    // path -> (file size, last modified, duration) of the songs in the existing dbfile
+    let known_durations = if update {
+        match Database::load_database_from_dir(PathBuf::from("."), PathBuf::from(&lib_dir)) {
+            Ok(db) => db
+                .songs()
+                .values()
+                .filter(|song| song.duration_millis > 0)
+                .map(|song| {
+                    (
+                        song.location.rel_path.clone(),
+                        (song.file_size, song.file_last_modified_unix_timestamp, song.duration_millis),
+                    )
+                })
+                .collect(),
+            Err(e) => {
+                eprintln!("--update: Couldn't load the existing dbfile: {e}");
+                return;
+            }
+        }
+    } else {
+        HashMap::new()
+    };
     eprintln!("Library: {lib_dir}. press enter to start. result will be saved in 'dbfile'.");
     std::io::stdin().read_line(&mut String::new()).unwrap();
     // start
@@ -94,6 +154,7 @@ fn main() {
     let unknown_artist = database.add_artist_new(Artist {
         id: 0,
         name: format!("<unknown>"),
+        aliases: vec![],
         cover: None,
         albums: vec![],
         singles: vec![],
@@ -154,10 +215,14 @@ fn main() {
             .filter(|v| !v.trim().is_empty())
             .or_else(|| song_tags.artist().filter(|v| !v.trim().is_empty()))
         {
+            let (artist, aliases) = artist_aliases
+                .get(&artist.to_lowercase())
+                .map_or((artist, &[][..]), |(name, aliases)| (name.as_str(), aliases.as_slice()));
             let artist_id = if !artists.contains_key(artist) {
                 let artist_id = database.add_artist_new(Artist {
                     id: 0,
                     name: artist.to_string(),
+                    aliases: aliases.to_vec(),
                     cover: None,
                     albums: vec![],
                     singles: vec![],
@@ -220,45 +285,55 @@ fn main() {
                     .to_string_lossy()
                     .into_owned()
             });
-        database.add_song_new(Song::new(
-            DatabaseLocation {
-                rel_path: path.to_path_buf(),
-            },
-            match song_path.metadata() {
-                Ok(v) => match v.modified() {
-                    Ok(v) => if let Ok(time) = v.duration_since(SystemTime::UNIX_EPOCH) {
-                        Some(time.as_secs())
-                    } else {
-                        eprintln!(
-                            "LastModified time of song {:?} is before the UNIX-EPOCH, setting `None`.",
-                            song_path
-                        );
-                        None
-                    },
-                    Err(e) => {
-                        eprintln!(
-                            "LastModified time of song {:?} not available: {e}.",
-                            song_path
-                        );
-                        None
-                    }
-                }
+        let last_modified = match song_path.metadata() {
+            Ok(v) => match v.modified() {
+                Ok(v) => if let Ok(time) = v.duration_since(SystemTime::UNIX_EPOCH) {
+                    Some(time.as_secs())
+                } else {
+                    eprintln!(
+                        "LastModified time of song {:?} is before the UNIX-EPOCH, setting `None`.",
+                        song_path
+                    );
+                    None
+                },
                 Err(e) => {
                     eprintln!(
-                        "LastModified time of song {:?} could not be read: {e}.",
+                        "LastModified time of song {:?} not available: {e}.",
                         song_path
                     );
                     None
-                    
                 }
+            }
+            Err(e) => {
+                eprintln!(
+                    "LastModified time of song {:?} could not be read: {e}.",
+                    song_path
+                );
+                None
+                
+            }
+        };
+        // if the file didn't change, keep the old duration, it might have been corrected by the server
+        let known_duration = known_durations
+            .get(path)
+            .filter(|(size, modified, _)| {
+                *size == song_file_metadata.len() && modified.is_some() && *modified == last_modified
+            })
+            .map(|(_, _, duration)| *duration);
+        let mut song = Song::new(
+            DatabaseLocation {
+                rel_path: path.to_path_buf(),
             },
+            last_modified,
             title.clone(),
             album_id,
             artist_id,
             vec![],
             None,
             song_file_metadata.len(),
-            if let Some(dur) = song_tags.duration() {
+            if let Some(dur) = known_duration {
+                dur
+            } else if let Some(dur) = song_tags.duration() {
                 dur as u64
             } else {
                 if skip_duration {
@@ -278,7 +353,10 @@ fn main() {
                 }
             },
             general,
-        ));
+        );
+        song.bitrate = mp3_bitrate(&song_path, song.file_size, song.duration_millis);
+        song.codec = Some("MP3".to_owned());
+        database.add_song_new(song);
     }
     eprintln!("searching for covers...");
     let mut multiple_cover_options = vec![];
@@ -411,6 +489,27 @@ fn all_files_in_dir(dir: impl AsRef<Path>, vec: &mut Vec<PathBuf>) -> Result<(),
     Ok(())
 }
 
+/// average bitrate in kbit/s, from the file size (minus the id3v2 tag, which may contain large images) and duration
+fn mp3_bitrate(path: &Path, file_size: u64, duration_millis: u64) -> Option<u32> {
+    if duration_millis == 0 {
+        return None;
+    }
+    let mut header = [0u8; 10];
+    let tag_size = match fs::File::open(path).and_then(|mut f| f.read_exact(&mut header)) {
+        // the size is a 28-bit integer stored in 4 bytes, the highest bit of each byte is always 0
+        Ok(()) if header.starts_with(b"ID3") => {
+            10 + header[6..10]
+                .iter()
+                .fold(0u64, |size, byte| (size << 7) | (*byte & 0x7F) as u64)
+        }
+        _ => 0,
+    };
+    // bytes per millisecond * 8 = kbit/s
+    (file_size.saturating_sub(tag_size) * 8 / duration_millis)
+        .try_into()
+        .ok()
+}
+
 struct OnceNewline(bool);
 impl OnceNewline {
     pub fn new() -> Self {
@@ -471,3 +570,25 @@ fn get_cover(
         None
     }
 }
+
+/// Parses the `--artist-aliases` file, where each line is `name;alias;alias;...`.
+/// Returns a map from each lowercased name and alias to the name and the aliases.
+fn read_artist_aliases(file: &str) -> HashMap<String, (String, Vec<String>)> {
+    let mut out = HashMap::new();
+    for line in file.lines() {
+        let mut names = line.split(';').map(|v| v.trim()).filter(|v| !v.is_empty());
+        let Some(name) = names.next() else {
+            continue;
+        };
+        let aliases = names.map(|v| v.to_owned()).collect::<Vec<_>>();
+        for n in std::iter::once(name).chain(aliases.iter().map(|v| v.as_str())) {
+            if out
+                .insert(n.to_lowercase(), (name.to_owned(), aliases.clone()))
+                .is_some()
+            {
+                eprintln!("[warn] artist alias {n:?} is used more than once, only the last one is used.");
+            }
+        }
+    }
+    out
+}
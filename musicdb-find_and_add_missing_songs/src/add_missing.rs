@@ -0,0 +1,228 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::{BufReader, Write},
+    net::{SocketAddr, TcpStream},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use musicdb_lib::{
+    data::{
+        album::Album,
+        artist::Artist,
+        database::{ClientIo, Cover, Database},
+        metadata::{find_cover, SongMetadata},
+        song::Song,
+        DatabaseLocation, GeneralData,
+    },
+    server::{Action, Req},
+};
+
+use crate::{confirm, receive_until, send};
+
+/// Finds song files in the library which aren't in the database, reads their tags (like filldb),
+/// and adds them (and new artists, albums and album covers) to the server's database.
+/// `lib_dir` is the library on this machine, which has to be the same as the server's.
+pub fn add_missing(addr: &SocketAddr, lib_dir: &Path, yes: bool, skip_duration: bool) {
+    eprintln!("Address: {addr}, connecting...");
+    let mut db_con = TcpStream::connect(addr).unwrap();
+    writeln!(db_con, "main").unwrap();
+    let client_con: Box<dyn ClientIo> = Box::new(TcpStream::connect(addr).unwrap());
+    let mut client = musicdb_lib::server::get::Client::new(BufReader::new(client_con)).unwrap();
+    let mut db = Database::new_clientside();
+    eprint!("Loading");
+    let _ = std::io::stderr().flush();
+    receive_until(&mut db, &mut db_con, |db| db.is_client_init());
+    eprintln!(" done");
+    eprintln!("Asking server to search for unused song files in the library.");
+    eprintln!("Depending on the size of your library and your hardware, this may take a while.");
+    let unused = client
+        .find_unused_song_files(Some(&[".mp3"]))
+        .unwrap()
+        .unwrap();
+    // read tags
+    let mut songs = vec![];
+    for (path, bad_path) in unused {
+        if bad_path {
+            eprintln!(
+                "[warn] skipping {path} (original path contained newlines or wasn't unicode)"
+            );
+            continue;
+        }
+        match SongMetadata::read(lib_dir, PathBuf::from(&path), skip_duration) {
+            Ok(song) => songs.push(song),
+            Err(e) => eprintln!("[warn] error reading id3 tag of {path:?}: {e}, skipping"),
+        }
+    }
+    if songs.is_empty() {
+        eprintln!("No songs to add.");
+        return;
+    }
+    // like filldb, so albums are in the right order
+    songs.sort_by(|a, b| (a.disc, a.track, &a.rel_path).cmp(&(b.disc, b.track, &b.rel_path)));
+    // which artists and albums don't exist yet
+    let new_artists = songs
+        .iter()
+        .map(|song| song.artist.as_str())
+        .filter(|name| db.find_artist_by_name(name).is_none())
+        .collect::<BTreeSet<_>>();
+    let mut new_albums = BTreeMap::new();
+    for song in &songs {
+        if let Some(album) = &song.album {
+            let exists = db
+                .find_artist_by_name(&song.artist)
+                .is_some_and(|artist| db.find_album_by_name(artist, album).is_some());
+            if !exists {
+                // the album's directory, if all of its new songs are in the same one
+                let dir = song.rel_path.parent().map(|dir| dir.to_path_buf());
+                new_albums
+                    .entry((song.artist.as_str(), album.as_str()))
+                    .and_modify(|old: &mut Option<PathBuf>| {
+                        if *old != dir {
+                            *old = None;
+                        }
+                    })
+                    .or_insert(dir);
+            }
+        }
+    }
+    eprintln!("-------------------------");
+    for song in &songs {
+        match &song.album {
+            Some(album) => println!(
+                "+ {:?}: {} by {} on {album}",
+                song.rel_path, song.title, song.artist
+            ),
+            None => println!("+ {:?}: {} by {}", song.rel_path, song.title, song.artist),
+        }
+    }
+    for artist in &new_artists {
+        println!("new artist: {artist}");
+    }
+    for (artist, album) in new_albums.keys() {
+        println!("new album: {album} by {artist}");
+    }
+    eprintln!(
+        "Found {} songs, {} new artists and {} new albums.",
+        songs.len(),
+        new_artists.len(),
+        new_albums.len()
+    );
+    if !yes && !confirm("Add them to the database?") {
+        eprintln!("Not adding anything.");
+        return;
+    }
+    // the server assigns ids, so we add things in the order they reference each other,
+    // and wait for the server to send them back before looking up the new ids.
+    eprintln!("Adding artists...");
+    for name in &new_artists {
+        send(
+            &mut db_con,
+            Action::AddArtist(
+                Artist {
+                    id: 0,
+                    name: name.to_string(),
+                    aliases: vec![],
+                    cover: None,
+                    albums: vec![],
+                    singles: vec![],
+                    general: GeneralData::default(),
+                },
+                Req::none(),
+            ),
+        );
+    }
+    receive_until(&mut db, &mut db_con, |db| {
+        new_artists
+            .iter()
+            .all(|name| db.find_artist_by_name(name).is_some())
+    });
+    eprintln!("Adding covers...");
+    let mut covers = BTreeMap::new();
+    for dir in new_albums.values().flatten() {
+        if let Some(cover) = find_cover(lib_dir, dir) {
+            if !covers.values().any(|c| *c == cover) && db.find_cover_by_path(&cover).is_none() {
+                send(
+                    &mut db_con,
+                    Action::AddCover(
+                        Cover {
+                            location: DatabaseLocation {
+                                rel_path: cover.clone(),
+                            },
+                            data: Arc::new(Mutex::new((false, None))),
+                        },
+                        Req::none(),
+                    ),
+                );
+            }
+            covers.insert(dir.clone(), cover);
+        }
+    }
+    receive_until(&mut db, &mut db_con, |db| {
+        covers
+            .values()
+            .all(|cover| db.find_cover_by_path(cover).is_some())
+    });
+    eprintln!("Adding albums...");
+    for ((artist, name), dir) in &new_albums {
+        send(
+            &mut db_con,
+            Action::AddAlbum(
+                Album {
+                    id: 0,
+                    name: name.to_string(),
+                    artist: db.find_artist_by_name(artist).unwrap(),
+                    cover: dir
+                        .as_ref()
+                        .and_then(|dir| covers.get(dir))
+                        .and_then(|cover| db.find_cover_by_path(cover)),
+                    songs: vec![],
+                    general: GeneralData::default(),
+                },
+                Req::none(),
+            ),
+        );
+    }
+    receive_until(&mut db, &mut db_con, |db| {
+        new_albums.keys().all(|(artist, album)| {
+            db.find_artist_by_name(artist)
+                .is_some_and(|artist| db.find_album_by_name(artist, album).is_some())
+        })
+    });
+    eprintln!("Adding songs...");
+    for song in &songs {
+        let artist = db.find_artist_by_name(&song.artist).unwrap();
+        let album = song
+            .album
+            .as_ref()
+            .and_then(|album| db.find_album_by_name(artist, album));
+        send(
+            &mut db_con,
+            Action::AddSong(
+                Song::new(
+                    DatabaseLocation {
+                        rel_path: song.rel_path.clone(),
+                    },
+                    song.last_modified,
+                    song.title.clone(),
+                    album,
+                    artist,
+                    vec![],
+                    None,
+                    song.file_size,
+                    song.duration_millis,
+                    song.general.clone(),
+                ),
+                Req::none(),
+            ),
+        );
+    }
+    receive_until(&mut db, &mut db_con, |db| {
+        songs.iter().all(|song| {
+            db.songs()
+                .values()
+                .any(|s| s.location.rel_path == song.rel_path)
+        })
+    });
+    eprintln!("Done, added {} songs.", songs.len());
+}
@@ -1,31 +1,68 @@
+mod add_missing;
+
 use std::{
     io::{BufReader, Write},
     net::{SocketAddr, TcpStream},
+    path::PathBuf,
 };
 
 use clap::{Parser, Subcommand};
 use musicdb_lib::{
-    data::database::{ClientIo, Database},
+    data::{
+        database::{ClientIo, Database},
+        SongId,
+    },
     load::ToFromBytes,
-    server::Command,
+    server::{Action as DbAction, Command},
 };
 
+/// the extensions used for find-unused-song-files if `--ext` isn't given
+const DEFAULT_EXTENSIONS: &str = "mp3,flac,ogg,opus,m4a,aac,wav,wma";
+
 #[derive(Parser)]
 struct Args {
     addr: SocketAddr,
+    /// print the results of list-changed-songs and find-unused-song-files as json (to stdout)
+    #[arg(long)]
+    json: bool,
     #[clap(subcommand)]
     action: Action,
 }
 #[derive(Subcommand)]
 enum Action {
-    ListChangedSongs,
-    FindUnusedSongFiles,
+    ListChangedSongs {
+        /// remove songs whose files don't exist anymore from the database (asks first)
+        #[arg(long)]
+        remove_missing: bool,
+        /// with --remove-missing, don't ask before removing the songs
+        #[arg(long)]
+        yes: bool,
+    },
+    FindUnusedSongFiles {
+        /// which file extensions count as song files, comma-separated (like `mp3,flac,ogg`)
+        #[arg(long, value_delimiter = ',', default_value = DEFAULT_EXTENSIONS)]
+        ext: Vec<String>,
+    },
+    /// add song files which aren't in the database yet (reads id3 tags like filldb)
+    AddMissing {
+        /// the library directory on this machine (the server's lib_dir)
+        lib_dir: PathBuf,
+        /// don't ask before adding the songs
+        #[arg(long)]
+        yes: bool,
+        /// don't decode files to find their duration if it isn't in the tags
+        #[arg(long)]
+        skip_duration: bool,
+    },
 }
 
 fn main() {
     let args = Args::parse();
     match args.action {
-        Action::ListChangedSongs => {
+        Action::ListChangedSongs {
+            remove_missing,
+            yes,
+        } => {
             let addr = &args.addr;
             eprintln!("Address: {addr}, connecting...");
             let mut db_con = TcpStream::connect(addr).unwrap();
@@ -39,7 +76,8 @@ fn main() {
             loop {
                 eprint!(".");
                 let _ = std::io::stderr().flush();
-                db.apply_command(Command::from_bytes(&mut db_con).unwrap());
+                let action = db.seq.recv(Command::from_bytes(&mut db_con).unwrap());
+                db.apply_action_unchecked_seq(action, None);
                 if db.is_client_init() {
                     eprintln!(" done");
                     break;
@@ -51,68 +89,139 @@ fn main() {
             );
             let (songs_no_time, songs_new_time, songs_removed, songs_error) =
                 client.find_songs_with_changed_files().unwrap().unwrap();
-            eprintln!("-------------------------");
-            if !songs_no_time.is_empty() {
-                eprintln!(
-                    "Songs with no last-modified time ({}):",
-                    songs_no_time.len()
-                );
-                for song in songs_no_time {
-                    if let Some(song) = db.get_song(&song) {
-                        eprintln!("-{song}: {:?}", song.location.rel_path)
-                    } else {
-                        eprintln!("-{song}!")
+            if args.json {
+                let json_no_time = songs_no_time
+                    .iter()
+                    .map(|id| song_json(&db, *id))
+                    .collect::<Vec<_>>();
+                let json_new_time = songs_new_time
+                    .iter()
+                    .map(|(id, new_time)| {
+                        let mut v = song_json(&db, *id);
+                        v["old_time"] = db
+                            .get_song(id)
+                            .and_then(|song| song.file_last_modified_unix_timestamp)
+                            .into();
+                        v["new_time"] = (*new_time).into();
+                        v
+                    })
+                    .collect::<Vec<_>>();
+                let json_removed = songs_removed
+                    .iter()
+                    .map(|id| song_json(&db, *id))
+                    .collect::<Vec<_>>();
+                let json_error = songs_error
+                    .iter()
+                    .map(|(id, error)| {
+                        let mut v = song_json(&db, *id);
+                        v["error"] = error.as_str().into();
+                        v
+                    })
+                    .collect::<Vec<_>>();
+                let out = serde_json::json!({
+                    "songs_no_time": json_no_time,
+                    "songs_new_time": json_new_time,
+                    "songs_removed": json_removed,
+                    "songs_error": json_error,
+                });
+                println!("{out}");
+            } else {
+                eprintln!("-------------------------");
+                if !songs_no_time.is_empty() {
+                    eprintln!(
+                        "Songs with no last-modified time ({}):",
+                        songs_no_time.len()
+                    );
+                    for song in songs_no_time {
+                        if let Some(song) = db.get_song(&song) {
+                            eprintln!("-{song}: {:?}", song.location.rel_path)
+                        } else {
+                            eprintln!("-{song}!")
+                        }
                     }
                 }
-            }
-            if !songs_new_time.is_empty() {
-                eprintln!(
-                    "Songs with a different last-modified time ({}):",
-                    songs_new_time.len()
-                );
-                for (song, new_time) in songs_new_time {
-                    if let Some(song) = db.get_song(&song) {
-                        if let Some(old_time) = song.file_last_modified_unix_timestamp {
-                            eprintln!(
-                                "-{song}: {:?} : {old_time}->{new_time}",
-                                song.location.rel_path
-                            )
+                if !songs_new_time.is_empty() {
+                    eprintln!(
+                        "Songs with a different last-modified time ({}):",
+                        songs_new_time.len()
+                    );
+                    for (song, new_time) in songs_new_time {
+                        if let Some(song) = db.get_song(&song) {
+                            if let Some(old_time) = song.file_last_modified_unix_timestamp {
+                                eprintln!(
+                                    "-{song}: {:?} : {old_time}->{new_time}",
+                                    song.location.rel_path
+                                )
+                            } else {
+                                eprintln!("-{song}: {:?} : !->{new_time}", song.location.rel_path)
+                            }
                         } else {
-                            eprintln!("-{song}: {:?} : !->{new_time}", song.location.rel_path)
+                            eprintln!("-{song}!")
                         }
-                    } else {
-                        eprintln!("-{song}!")
                     }
                 }
-            }
-            if !songs_removed.is_empty() {
-                eprintln!(
-                    "Songs with locations that do not exist ({}):",
-                    songs_removed.len()
-                );
-                for song in songs_removed {
-                    if let Some(song) = db.get_song(&song) {
-                        eprintln!("-{song}: {:?}", song.location.rel_path)
-                    } else {
-                        eprintln!("-{song}!")
+                if !songs_removed.is_empty() {
+                    eprintln!(
+                        "Songs with locations that do not exist ({}):",
+                        songs_removed.len()
+                    );
+                    for song in &songs_removed {
+                        if let Some(song) = db.get_song(&song) {
+                            eprintln!("-{song}: {:?}", song.location.rel_path)
+                        } else {
+                            eprintln!("-{song}!")
+                        }
+                    }
+                }
+                if !songs_error.is_empty() {
+                    eprintln!(
+                        "Songs with a different last-modified time ({}):",
+                        songs_error.len()
+                    );
+                    for (song, error) in songs_error {
+                        if let Some(song) = db.get_song(&song) {
+                            eprintln!("-{song}: {:?} : {error}", song.location.rel_path)
+                        } else {
+                            eprintln!("-{song}!")
+                        }
                     }
                 }
             }
-            if !songs_error.is_empty() {
-                eprintln!(
-                    "Songs with a different last-modified time ({}):",
-                    songs_error.len()
-                );
-                for (song, error) in songs_error {
-                    if let Some(song) = db.get_song(&song) {
-                        eprintln!("-{song}: {:?} : {error}", song.location.rel_path)
-                    } else {
-                        eprintln!("-{song}!")
+            if remove_missing && !songs_removed.is_empty() {
+                eprintln!("-------------------------");
+                eprintln!("These songs will be removed from the database:");
+                for song in &songs_removed {
+                    if let Some(song) = db.get_song(song) {
+                        eprintln!("- {song}: {:?}", song.location.rel_path);
+                    }
+                }
+                if yes || confirm("Remove them?") {
+                    for song in &songs_removed {
+                        send(&mut db_con, DbAction::RemoveSong(*song));
                     }
+                    receive_until(&mut db, &mut db_con, |db| {
+                        songs_removed.iter().all(|song| db.get_song(song).is_none())
+                    });
+                    eprintln!("Removed {} songs.", songs_removed.len());
+                } else {
+                    eprintln!("Not removing anything.");
                 }
             }
         }
-        Action::FindUnusedSongFiles => {
+        Action::AddMissing {
+            lib_dir,
+            yes,
+            skip_duration,
+        } => add_missing::add_missing(&args.addr, &lib_dir, yes, skip_duration),
+        Action::FindUnusedSongFiles { ext } => {
+            // the server matches suffixes, so `mp3` would also match `test_mp3`
+            let ext = ext
+                .iter()
+                .map(|ext| ext.trim())
+                .filter(|ext| !ext.is_empty())
+                .map(|ext| format!(".{}", ext.trim_start_matches('.')))
+                .collect::<Vec<_>>();
+            let ext = ext.iter().map(|ext| ext.as_str()).collect::<Vec<_>>();
             let addr = &args.addr;
             eprintln!("Address: {addr}, connecting...");
             let client_con: Box<dyn ClientIo> = Box::new(TcpStream::connect(addr).unwrap());
@@ -123,10 +232,15 @@ fn main() {
             eprintln!(
                 "Depending on the size of your library and your hardware, this may take a while."
             );
-            let unused = client
-                .find_unused_song_files(Some(&[".mp3", ".wma"]))
-                .unwrap()
-                .unwrap();
+            let unused = client.find_unused_song_files(Some(&ext)).unwrap().unwrap();
+            if args.json {
+                let out = unused
+                    .iter()
+                    .map(|(path, bad_path)| serde_json::json!({ "path": path, "bad_path": bad_path }))
+                    .collect::<Vec<_>>();
+                println!("{}", serde_json::Value::Array(out));
+                return;
+            }
             eprintln!("-------------------------");
             for (i, (unused, bad_path)) in unused.iter().enumerate() {
                 if *bad_path {
@@ -140,3 +254,36 @@ fn main() {
         }
     }
 }
+
+/// `{"id": .., "title": .., "path": ..}`, title and path are `null` if the song doesn't exist
+fn song_json(db: &Database, id: SongId) -> serde_json::Value {
+    let song = db.get_song(&id);
+    serde_json::json!({
+        "id": id,
+        "title": song.map(|song| &song.title),
+        "path": song.map(|song| song.location.rel_path.to_string_lossy()),
+    })
+}
+
+fn send(con: &mut TcpStream, action: DbAction) {
+    action.cmd(0xFFu8).to_bytes(con).unwrap();
+}
+/// applies commands from the server until `done` returns true
+fn receive_until(db: &mut Database, con: &mut TcpStream, done: impl Fn(&Database) -> bool) {
+    while !done(db) {
+        let action = db.seq.recv(Command::from_bytes(con).unwrap());
+        db.apply_action_unchecked_seq(action, None);
+        if !db.is_client_init() {
+            eprint!(".");
+            let _ = std::io::stderr().flush();
+        }
+    }
+}
+/// asks the user, returns `true` if they answered yes
+fn confirm(question: &str) -> bool {
+    eprint!("{question} [y/N] ");
+    let _ = std::io::stderr().flush();
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer).unwrap();
+    matches!(answer.trim(), "y" | "Y" | "yes")
+}
@@ -0,0 +1,74 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use musicdb_lib::data::search_index::SearchIndex;
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use regex::RegexBuilder;
+
+const SONGS: u64 = 50_000;
+const WORDS: &[&str] = &[
+    "love", "night", "heart", "dream", "fire", "light", "rain", "summer", "blue", "dance", "road",
+    "home", "river", "star", "shadow", "gold", "wild", "city", "ocean", "ghost", "paradise",
+    "thunder", "midnight", "silver", "echo", "forever", "garden", "storm",
+];
+
+/// song titles with 2-4 random words, like "Midnight River Echo"
+fn titles() -> Vec<String> {
+    let mut rng = StdRng::seed_from_u64(0);
+    (0..SONGS)
+        .map(|i| {
+            let len = 2 + i as usize % 3;
+            WORDS
+                .choose_multiple(&mut rng, len)
+                .map(|w| {
+                    let mut w = w.to_string();
+                    w[0..1].make_ascii_uppercase();
+                    w
+                })
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect()
+}
+
+/// compares running the search regex on every title (what the client used to do)
+/// to running it only on the titles the index returns.
+fn search(c: &mut Criterion) {
+    let titles = titles();
+    let mut index = SearchIndex::new();
+    for (id, title) in titles.iter().enumerate() {
+        index.set(id as u64, title);
+    }
+    for search in ["thunder", "midnight riv", "paradise ghost"] {
+        let regex = RegexBuilder::new(search)
+            .case_insensitive(true)
+            .build()
+            .unwrap();
+        c.bench_function(&format!("regex only: {search:?}"), |b| {
+            b.iter(|| {
+                titles
+                    .iter()
+                    .filter(|title| regex.is_match(black_box(title)))
+                    .count()
+            })
+        });
+        c.bench_function(&format!("index + regex: {search:?}"), |b| {
+            b.iter(|| {
+                let candidates = index.candidates(black_box(search)).unwrap();
+                titles
+                    .iter()
+                    .enumerate()
+                    .filter(|(id, _)| candidates.contains(&(*id as u64)))
+                    .filter(|(_, title)| regex.is_match(title))
+                    .count()
+            })
+        });
+    }
+    c.bench_function("index update, 1 title changed", |b| {
+        b.iter(|| {
+            index.set(0, black_box("Changed Title"));
+            index.set(0, &titles[0]);
+        })
+    });
+}
+
+criterion_group!(benches, search);
+criterion_main!(benches);
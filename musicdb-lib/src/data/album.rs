@@ -1,8 +1,11 @@
-use std::io::{Read, Write};
+use std::{
+    collections::BTreeSet,
+    io::{Read, Write},
+};
 
-use crate::load::ToFromBytes;
+use crate::load::{ErrorContext, ToFromBytes};
 
-use super::{AlbumId, ArtistId, CoverId, GeneralData, SongId};
+use super::{database::Database, AlbumId, ArtistId, CoverId, GeneralData, SongId};
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Album {
@@ -31,13 +34,27 @@ impl ToFromBytes for Album {
     where
         T: Read,
     {
-        Ok(Self {
-            id: ToFromBytes::from_bytes(s)?,
-            name: ToFromBytes::from_bytes(s)?,
-            artist: ToFromBytes::from_bytes(s)?,
-            songs: ToFromBytes::from_bytes(s)?,
-            cover: ToFromBytes::from_bytes(s)?,
-            general: ToFromBytes::from_bytes(s)?,
-        })
+        (|| {
+            Ok(Self {
+                id: ToFromBytes::from_bytes(s).context("id")?,
+                name: ToFromBytes::from_bytes(s).context("name")?,
+                artist: ToFromBytes::from_bytes(s).context("artist")?,
+                songs: ToFromBytes::from_bytes(s).context("songs")?,
+                cover: ToFromBytes::from_bytes(s).context("cover")?,
+                general: ToFromBytes::from_bytes(s).context("general")?,
+            })
+        })()
+        .context("Album")
+    }
+}
+impl Album {
+    /// how many different discs this album's songs are on (see `Song::disc`).
+    /// `0` if none of its songs have a disc number, so albums with `disc_count() > 1` are the ones that have multiple discs.
+    pub fn disc_count(&self, db: &Database) -> usize {
+        self.songs
+            .iter()
+            .filter_map(|id| db.get_song(id)?.disc())
+            .collect::<BTreeSet<_>>()
+            .len()
     }
 }
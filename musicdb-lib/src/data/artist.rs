@@ -1,6 +1,6 @@
 use std::io::{Read, Write};
 
-use crate::load::ToFromBytes;
+use crate::load::{ErrorContext, ToFromBytes};
 
 use super::{AlbumId, ArtistId, CoverId, GeneralData, SongId};
 
@@ -8,6 +8,10 @@ use super::{AlbumId, ArtistId, CoverId, GeneralData, SongId};
 pub struct Artist {
     pub id: ArtistId,
     pub name: String,
+    /// other names of this artist, like "Beatles" for "The Beatles".
+    /// searches and tools which group songs by artist name also check these (ignoring case).
+    /// Use `Action::SetArtistAliases` to change them.
+    pub aliases: Vec<String>,
     pub cover: Option<CoverId>,
     pub albums: Vec<AlbumId>,
     pub singles: Vec<SongId>,
@@ -25,19 +29,46 @@ impl ToFromBytes for Artist {
         self.singles.to_bytes(s)?;
         self.cover.to_bytes(s)?;
         self.general.to_bytes(s)?;
+        self.aliases.to_bytes(s)?;
         Ok(())
     }
     fn from_bytes<T>(s: &mut T) -> Result<Self, std::io::Error>
     where
         T: Read,
     {
-        Ok(Self {
-            id: ToFromBytes::from_bytes(s)?,
-            name: ToFromBytes::from_bytes(s)?,
-            albums: ToFromBytes::from_bytes(s)?,
-            singles: ToFromBytes::from_bytes(s)?,
-            cover: ToFromBytes::from_bytes(s)?,
-            general: ToFromBytes::from_bytes(s)?,
-        })
+        let mut artist = Self::from_bytes_without_aliases(s)?;
+        artist.aliases = ToFromBytes::from_bytes(s)
+            .context("aliases")
+            .context("Artist")?;
+        Ok(artist)
+    }
+}
+impl Artist {
+    /// the encoding used before `aliases` were added (dbfile versions before 4)
+    pub fn from_bytes_without_aliases<T>(s: &mut T) -> Result<Self, std::io::Error>
+    where
+        T: Read,
+    {
+        (|| {
+            Ok(Self {
+                id: ToFromBytes::from_bytes(s).context("id")?,
+                name: ToFromBytes::from_bytes(s).context("name")?,
+                aliases: vec![],
+                albums: ToFromBytes::from_bytes(s).context("albums")?,
+                singles: ToFromBytes::from_bytes(s).context("singles")?,
+                cover: ToFromBytes::from_bytes(s).context("cover")?,
+                general: ToFromBytes::from_bytes(s).context("general")?,
+            })
+        })()
+        .context("Artist")
+    }
+    /// true if `name` is this artist's name, or one of its aliases (ignoring case)
+    pub fn has_name(&self, name: &str) -> bool {
+        self.name == name || {
+            let name = name.to_lowercase();
+            self.aliases
+                .iter()
+                .any(|alias| alias.to_lowercase() == name)
+        }
     }
 }
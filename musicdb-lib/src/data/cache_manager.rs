@@ -1,17 +1,22 @@
 use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
     sync::{
-        atomic::{AtomicU32, AtomicU64},
+        atomic::{AtomicBool, AtomicU32, AtomicU64},
         Arc, Mutex,
     },
     thread::{self, JoinHandle},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use colorize::AnsiColor;
 
-use crate::data::queue::{Queue, QueueContent};
+use crate::{
+    data::queue::{Queue, QueueContent},
+    server::Action,
+};
 
-use super::database::Database;
+use super::{database::Database, SongId};
 
 // CacheManage will never uncache the currently playing song or the song that will be played next.
 
@@ -21,20 +26,78 @@ pub struct CacheManager {
     /// Amount of bytes. If free system memory is greater than this number, consider caching more songs.
     pub max_avail_mem: Arc<AtomicU64>,
     pub songs_to_cache: Arc<AtomicU32>,
+    /// Also cache the covers of the songs which are being cached.
+    /// Only works if the database can read the cover files directly (not for remote clients).
+    pub cache_covers: Arc<AtomicBool>,
+    /// songs which will be cached and never uncached, see `pin`
+    pinned: Arc<Mutex<HashSet<SongId>>>,
+    counters: Arc<CacheCounters>,
+    database: Arc<Mutex<Database>>,
     #[allow(unused)]
     thread: Arc<JoinHandle<()>>,
 }
 
+/// Counts how often a song's data was already cached when it had to be played.
+/// Shared between the `CacheManager` and the `Player` (see `Player::set_cache_counters`).
+#[derive(Debug, Default)]
+pub struct CacheCounters {
+    pub hits: AtomicU64,
+    pub misses: AtomicU64,
+}
+impl CacheCounters {
+    pub fn hit(&self) {
+        self.hits.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    }
+    pub fn miss(&self) {
+        self.misses
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CacheStats {
+    pub cached_songs: usize,
+    pub cached_bytes: u64,
+    /// the song's data was cached when it started playing
+    pub hits: u64,
+    /// the song's data had to be loaded (or awaited) before it could be played
+    pub misses: u64,
+    pub available_memory: u64,
+    /// `available_memory - min_avail_mem`. If this is negative, songs are being uncached.
+    pub memory_headroom: i64,
+}
+impl Display for CacheStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} songs ({} MiB) cached, {} hits, {} misses, {} MiB available ({} MiB headroom)",
+            self.cached_songs,
+            self.cached_bytes / (1024 * 1024),
+            self.hits,
+            self.misses,
+            self.available_memory / (1024 * 1024),
+            self.memory_headroom / (1024 * 1024),
+        )
+    }
+}
+
 impl CacheManager {
     pub fn new(database: Arc<Mutex<Database>>) -> Self {
         let min_avail_mem = Arc::new(AtomicU64::new(1024 * 1024 * 1024));
         let max_avail_mem = Arc::new(AtomicU64::new(1024 * 1024 * 2048));
         // if < 2, does the same as 2.
         let songs_to_cache = Arc::new(AtomicU32::new(10));
+        let cache_covers = Arc::new(AtomicBool::new(true));
+        let pinned = Arc::new(Mutex::new(HashSet::new()));
+        let counters = Arc::new(CacheCounters::default());
         Self {
             min_avail_mem: Arc::clone(&min_avail_mem),
             max_avail_mem: Arc::clone(&max_avail_mem),
             songs_to_cache: Arc::clone(&songs_to_cache),
+            cache_covers: Arc::clone(&cache_covers),
+            pinned: Arc::clone(&pinned),
+            counters: Arc::clone(&counters),
+            database: Arc::clone(&database),
             thread: Arc::new(thread::spawn(move || {
                 let sleep_dur_long = Duration::from_secs(20);
                 let sleep_dur_short = Duration::from_secs(1);
@@ -46,6 +109,10 @@ impl CacheManager {
                 let mut sleep_short = true;
                 let cleanup_max = 100;
                 let mut cleanup_countdown = cleanup_max;
+                // when each song was last in `ids_to_cache`, used to uncache the least recently needed songs first
+                let mut last_needed = HashMap::<SongId, Instant>::new();
+                // so the warning isn't shown every time
+                let mut warned_pinned = false;
                 loop {
                     thread::sleep(if sleep_short {
                         sleep_dur_short
@@ -111,26 +178,72 @@ impl CacheManager {
                         (queue_current_song, queue_next_song, ids_to_cache)
                     };
 
+                    let covers_to_cache = if cache_covers.load(std::sync::atomic::Ordering::Relaxed)
+                        && db.remote_server_as_song_file_source.is_none()
+                    {
+                        let mut covers = vec![];
+                        for id in &ids_to_cache {
+                            if let Some(song) = db.get_song(id) {
+                                if let Some(cover) = song.cover.or_else(|| {
+                                    song.album
+                                        .and_then(|album| db.albums().get(&album))
+                                        .and_then(|album| album.cover)
+                                }) {
+                                    if !covers.contains(&cover) {
+                                        covers.push(cover);
+                                    }
+                                }
+                            }
+                        }
+                        covers
+                    } else {
+                        vec![]
+                    };
+
+                    let pinned = pinned.lock().unwrap().clone();
+
+                    let now = Instant::now();
+                    for id in &ids_to_cache {
+                        last_needed.insert(*id, now);
+                    }
+
                     if low_memory {
                         let mut found = false;
-                        for (id, song) in db.songs().iter() {
-                            if !ids_to_cache.contains(id) {
-                                if let Ok(true) = song.cached_data().uncache_data() {
-                                    eprintln!(
-                                        "[{}] CacheManager :: Uncached bytes for song '{}' (memory limit).",
-                                        "INFO".cyan(),
-                                        song.title
-                                    );
-                                    found = true;
-                                    break;
-                                }
+                        // covers are small, so uncache all of them (except the ones we still need)
+                        for (id, cover) in db.covers() {
+                            if !covers_to_cache.contains(id) && cover.uncache_data() {
+                                found = true;
+                            }
+                        }
+                        // songs which were never needed (`None`) come first
+                        let mut lru = db
+                            .songs()
+                            .iter()
+                            .filter(|(id, song)| {
+                                !ids_to_cache.contains(id)
+                                    && !pinned.contains(id)
+                                    && song.cached_data().has_cached_data().is_some()
+                            })
+                            .map(|(id, song)| (last_needed.get(id).copied(), song))
+                            .collect::<Vec<_>>();
+                        lru.sort_unstable_by_key(|(t, _)| *t);
+                        for (_, song) in lru {
+                            if let Ok(true) = song.cached_data().uncache_data() {
+                                eprintln!(
+                                    "[{}] CacheManager :: Uncached bytes for song '{}' (memory limit).",
+                                    "INFO".cyan(),
+                                    song.title
+                                );
+                                found = true;
+                                break;
                             }
                         }
                         if !found {
                             // also uncache songs that should be cached, but not current/next song
                             for id in ids_to_cache.iter().rev() {
                                 if !(queue_current_song.is_some_and(|i| i == *id)
-                                    || queue_next_song.is_some_and(|i| i == *id))
+                                    || queue_next_song.is_some_and(|i| i == *id)
+                                    || pinned.contains(id))
                                 {
                                     if let Some(song) = db.get_song(id) {
                                         if let Ok(true) = song.cached_data().uncache_data() {
@@ -149,10 +262,28 @@ impl CacheManager {
                         if found {
                             // uncache more songs
                             sleep_short = true;
+                        } else if !warned_pinned {
+                            let pinned_bytes = pinned
+                                .iter()
+                                .filter_map(|id| db.get_song(id))
+                                .filter_map(|song| song.cached_data().has_cached_data())
+                                .sum::<usize>();
+                            if pinned_bytes > 0 {
+                                warned_pinned = true;
+                                eprintln!(
+                                    "[{}] CacheManager :: Memory is low, but only pinned songs ({} MiB) and the current/next song are left in the cache.",
+                                    "WARN".yellow(),
+                                    pinned_bytes / (1024 * 1024)
+                                );
+                            }
                         }
                     } else if available_memory > max_avail_mem {
+                        warned_pinned = false;
                         // we have some memory left, maybe cache a song (or cache multiple if we know their byte-sizes)
-                        for song in &ids_to_cache {
+                        for song in ids_to_cache
+                            .iter()
+                            .chain(pinned.iter().filter(|id| !ids_to_cache.contains(id)))
+                        {
                             if let Some(song) = db.get_song(song) {
                                 match song
                                     .cached_data()
@@ -176,6 +307,14 @@ impl CacheManager {
                                 }
                             }
                         }
+                        for id in &covers_to_cache {
+                            if let Some(cover) = db.covers().get(id) {
+                                if cover.cache_data_start_thread(db.get_path(&cover.location)) {
+                                    sleep_short = true;
+                                    break;
+                                }
+                            }
+                        }
                     }
 
                     if let Some(song_id) = queue_next_song {
@@ -195,9 +334,14 @@ impl CacheManager {
                         cleanup_countdown -= 1;
                     } else {
                         cleanup_countdown = cleanup_max;
+                        eprintln!(
+                            "[{}] CacheManager :: {}",
+                            "INFO".cyan(),
+                            Self::stats_locked(&db, &counters, available_memory, min_avail_mem)
+                        );
                         for (id, song) in db.songs() {
                             if let Some(_size) = song.cached_data().has_cached_data() {
-                                if !is_in_queue(*id, &db.queue) {
+                                if !(pinned.contains(id) || is_in_queue(*id, &db.queue)) {
                                     if let Ok(true) = song.cached_data().uncache_data() {
                                         eprintln!(
                                             "[{}] CacheManager :: Uncached bytes for song '{}' (not in queue).",
@@ -213,6 +357,10 @@ impl CacheManager {
                                             folder.content.iter().any(|q| is_in_queue(id, q))
                                         }
                                         QueueContent::Loop(_, _, inner) => is_in_queue(id, inner),
+                                        QueueContent::Playlist(playlist) => {
+                                            playlist.songs.contains(&id)
+                                        }
+                                        QueueContent::Conditional(_) => false,
                                     }
                                 }
                             }
@@ -235,6 +383,87 @@ impl CacheManager {
             .store(1024 * 1024 * max, std::sync::atomic::Ordering::Relaxed);
     }
 
+    /// The song will be cached (if there is enough memory) and will never be uncached until `unpin` is used.
+    /// Pinned songs still use memory, so if too many songs are pinned, the `CacheManager` will not be able to free enough memory.
+    pub fn pin(&self, song: SongId) {
+        self.pinned.lock().unwrap().insert(song);
+    }
+    pub fn unpin(&self, song: SongId) {
+        self.pinned.lock().unwrap().remove(&song);
+    }
+    /// Applies `Action::SetCacheConfig` and `Action::SetCachePinned`, ignores all other actions
+    pub fn handle_action(&self, action: &Action) {
+        match action {
+            Action::SetCacheConfig {
+                songs,
+                min_mib,
+                max_mib,
+            } => {
+                self.set_memory_mib(*min_mib, *max_mib);
+                self.set_cache_songs_count(*songs);
+                eprintln!(
+                    "[{}] CacheManager :: Now caching up to {songs} songs, memory limits {min_mib} to {max_mib} MiB.",
+                    "INFO".cyan()
+                );
+            }
+            Action::SetCachePinned(song, true) => self.pin(*song),
+            Action::SetCachePinned(song, false) => self.unpin(*song),
+            Action::Multiple(actions) => {
+                for action in actions {
+                    self.handle_action(action);
+                }
+            }
+            _ => {}
+        }
+    }
+    /// If `true` (default), the covers of songs in the queue are cached too, see `cache_covers`.
+    pub fn set_cache_covers(&self, cache_covers: bool) {
+        self.cache_covers
+            .store(cache_covers, std::sync::atomic::Ordering::Relaxed);
+    }
+    /// Counters to be passed to `Player::set_cache_counters`
+    pub fn counters(&self) -> Arc<CacheCounters> {
+        Arc::clone(&self.counters)
+    }
+    /// Locks the database to count the cached songs and refreshes the memory info.
+    pub fn stats(&self) -> CacheStats {
+        let mut si = sysinfo::System::new_with_specifics(
+            sysinfo::RefreshKind::new().with_memory(sysinfo::MemoryRefreshKind::new().with_ram()),
+        );
+        si.refresh_memory_specifics(sysinfo::MemoryRefreshKind::new().with_ram());
+        let db = self.database.lock().unwrap();
+        Self::stats_locked(
+            &db,
+            &self.counters,
+            si.available_memory(),
+            self.min_avail_mem
+                .load(std::sync::atomic::Ordering::Relaxed),
+        )
+    }
+    fn stats_locked(
+        db: &Database,
+        counters: &CacheCounters,
+        available_memory: u64,
+        min_avail_mem: u64,
+    ) -> CacheStats {
+        let mut cached_songs = 0;
+        let mut cached_bytes = 0;
+        for song in db.songs().values() {
+            if let Some(size) = song.cached_data().has_cached_data() {
+                cached_songs += 1;
+                cached_bytes += size as u64;
+            }
+        }
+        CacheStats {
+            cached_songs,
+            cached_bytes,
+            hits: counters.hits.load(std::sync::atomic::Ordering::Relaxed),
+            misses: counters.misses.load(std::sync::atomic::Ordering::Relaxed),
+            available_memory,
+            memory_headroom: available_memory as i64 - min_avail_mem as i64,
+        }
+    }
+
     /// How many songs to load ahead of time. `< 2` behaves like `2`.
     /// Songs will be cached slowly over time.
     /// New songs will only be cached if `available_memory > max_avail_mem`.
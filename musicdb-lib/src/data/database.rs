@@ -1,29 +1,35 @@
 use rand::prelude::SliceRandom;
 use std::{
     collections::{BTreeSet, HashMap},
-    fs::{self, File},
+    fs::File,
     io::{BufReader, Read, Write},
     path::{Path, PathBuf},
     sync::{mpsc, Arc, Mutex},
-    time::{Duration, Instant},
+    time::{Duration, Instant, UNIX_EPOCH},
 };
 
 use colorize::AnsiColor;
-use rand::thread_rng;
+use rand::{rngs::StdRng, thread_rng, Rng, SeedableRng};
 
 use crate::{
-    load::ToFromBytes,
+    load::{
+        fixed_width_lengths, folders_without_weight, read_db_header, read_len, write_db_header,
+        write_file_atomically, Checksum, ErrorContext, ToFromBytes, DB_VERSION,
+    },
     server::{Action, Command, Commander, Req},
 };
 
 use super::{
     album::Album,
     artist::Artist,
-    queue::{Queue, QueueContent, QueueFolder},
-    song::Song,
+    queue::{Queue, QueueContent, QueueFolder, QueueLimitPolicy, RepeatMode, ShuffleWeight},
+    song::{Song, GAIN_TAG, MAX_GAIN_DB, RATING_TAG},
     AlbumId, ArtistId, CoverId, DatabaseLocation, SongId,
 };
 
+/// `Action::PreviousSong` restarts the current song instead if it has played for longer than this (in milliseconds)
+pub const PREVIOUS_SONG_RESTART_MILLIS: u64 = 3000;
+
 pub struct Database {
     pub seq: Commander,
     /// the directory that contains the dbfile, backups, statistics, ...
@@ -43,12 +49,28 @@ pub struct Database {
     /// Some(Some(path)) -> access to path
     pub custom_files: Option<Option<PathBuf>>,
     pub queue: Queue,
+    /// saved queues, by name. see `Action::SavePlaylist`.
+    playlists: HashMap<String, Queue>,
     /// if the database receives an update, it will inform all of its clients so they can stay in sync.
     /// this is a list containing all the clients.
     pub update_endpoints: Vec<(u64, UpdateEndpoint)>,
     pub update_endpoints_id: u64,
     /// true if a song is/should be playing
     pub playing: bool,
+    /// the playback volume, `1.0` is the default
+    pub volume: f32,
+    /// if true, nothing should be audible, but `volume` is kept so it can be restored when unmuting
+    pub muted: bool,
+    pub repeat: RepeatMode,
+    /// How long (in milliseconds) the player fades in when resuming and fades out when pausing or stopping, `0` to disable.
+    /// This isn't part of the library or sent to clients, each player uses its own database's value.
+    pub fade_millis: u64,
+    /// The current song's playback position (in milliseconds) at the given time,
+    /// set by `Action::SetCurrentSongPosition` and `Action::PlaybackPosition`. Clients can use this to estimate the playback position.
+    pub current_song_position: Option<(u64, Instant)>,
+    /// The current song's sample rate and channel count, if the server's player backend decoded it,
+    /// set by `Action::SetCurrentSongFormat`. See `current_song_format_string`.
+    pub current_song_format: Option<(u32, u16)>,
     pub command_sender: Option<mpsc::Sender<(Command, Option<u64>)>>,
     pub remote_server_as_song_file_source:
         Option<Arc<Mutex<crate::server::get::Client<Box<dyn ClientIo>>>>>,
@@ -58,6 +80,15 @@ pub struct Database {
     /// If `Some`, contains the first time and the last time data was modified.
     /// When the DB is saved, this is reset to `None` to represent that nothing was modified.
     pub times_data_modified: Option<(Instant, Instant)>,
+    /// The server saves the database once it was first modified at least this long ago (see `autosave_due`)...
+    pub autosave_max_delay: Duration,
+    /// ...and nothing was modified for at least this long, so that many changes in a row only cause one save.
+    pub autosave_quiet_period: Duration,
+    /// If `Some`, the server won't let the queue grow beyond this many songs (see `Queue::element_count`).
+    /// What happens to adds which would exceed it depends on `queue_limit_policy`.
+    /// This also applies to `QueueUpdate` and `QueueReplace`, and conditionals only resolve to as many songs as fit.
+    pub queue_limit: Option<usize>,
+    pub queue_limit_policy: QueueLimitPolicy,
 }
 pub trait ClientIo: Read + Write + Send {}
 impl<T: Read + Write + Send> ClientIo for T {}
@@ -83,6 +114,26 @@ impl Database {
     pub fn get_path_nodb(lib_directory: &impl AsRef<Path>, location: &DatabaseLocation) -> PathBuf {
         lib_directory.as_ref().join(&location.rel_path)
     }
+    /// true if data was modified and the server should save the database now,
+    /// see `autosave_max_delay` and `autosave_quiet_period`
+    pub fn autosave_due(&self, now: Instant) -> bool {
+        self.times_data_modified.is_some_and(|(first, last)| {
+            now.saturating_duration_since(first) >= self.autosave_max_delay
+                && now.saturating_duration_since(last) >= self.autosave_quiet_period
+        })
+    }
+    /// true if `Action::PreviousSong` should restart the current song:
+    /// if it has played for longer than `PREVIOUS_SONG_RESTART_MILLIS`, or if it is the first song
+    fn previous_song_restarts(&self) -> bool {
+        let position = self.current_song_position.map(|(position, time)| {
+            if self.playing {
+                position + time.elapsed().as_millis() as u64
+            } else {
+                position
+            }
+        });
+        position.is_some_and(|v| v > PREVIOUS_SONG_RESTART_MILLIS) || !self.queue.has_previous()
+    }
     fn modified_data(&mut self) {
         let now = Instant::now();
         if let Some((_first, last)) = &mut self.times_data_modified {
@@ -99,6 +150,13 @@ impl Database {
         self.modified_data();
         self.songs.get_mut(song)
     }
+    /// `current_song_format` for displaying it, like `44.1kHz · 2ch`
+    pub fn current_song_format_string(&self) -> Option<String> {
+        let (sample_rate, channels) = self.current_song_format?;
+        let khz = format!("{:.1}", sample_rate as f64 / 1000.0);
+        let khz = khz.strip_suffix(".0").unwrap_or(&khz);
+        Some(format!("{khz}kHz · {channels}ch"))
+    }
     /// adds a song to the database.
     /// ignores song.id and just assigns a new id, which it then returns.
     /// this function also adds a reference to the new song to the album (or artist.singles, if no album)
@@ -185,6 +243,69 @@ impl Database {
         }
         self.panic("database.artists all keys used - no more capacity for new artists!");
     }
+    /// removes all covers which aren't used by any song, album or artist and returns how many were removed.
+    /// because new covers get the lowest unused id, this changes the ids future covers will get,
+    /// so only do this when no clients are connected, or their databases would no longer match ours.
+    pub fn remove_orphaned_covers(&mut self) -> usize {
+        let used = self
+            .songs
+            .values()
+            .filter_map(|v| v.cover)
+            .chain(self.albums.values().filter_map(|v| v.cover))
+            .chain(self.artists.values().filter_map(|v| v.cover))
+            .collect::<BTreeSet<CoverId>>();
+        let count = self.covers.len();
+        self.covers.retain(|id, _| used.contains(id));
+        let removed = count - self.covers.len();
+        if removed > 0 {
+            self.modified_data();
+        }
+        removed
+    }
+    /// groups songs which are probably the same track, for example because it was ripped or encoded twice:
+    /// their titles and artist names are equal when ignoring case, spaces and punctuation,
+    /// and their durations differ by at most `duration_tolerance_millis` (from one song to the next, if there are more than two).
+    /// every group has at least two songs, sorted by id. deciding what to remove or merge is up to the caller.
+    pub fn find_similar_songs(&self, duration_tolerance_millis: u64) -> Vec<Vec<SongId>> {
+        fn normalize(s: &str) -> String {
+            s.chars()
+                .filter(|c| c.is_alphanumeric())
+                .flat_map(|c| c.to_lowercase())
+                .collect()
+        }
+        let mut by_name: HashMap<(String, String), Vec<(u64, SongId)>> = HashMap::new();
+        for song in self.songs.values() {
+            let artist = self
+                .artists
+                .get(&song.artist)
+                .map(|artist| normalize(&artist.name))
+                .unwrap_or_default();
+            by_name
+                .entry((normalize(&song.title), artist))
+                .or_default()
+                .push((song.duration_millis, song.id));
+        }
+        let mut groups = vec![];
+        for mut songs in by_name.into_values() {
+            songs.sort_unstable();
+            let mut group = vec![];
+            let mut prev_duration = 0;
+            for (duration, id) in songs {
+                if duration - prev_duration > duration_tolerance_millis && !group.is_empty() {
+                    groups.push(std::mem::take(&mut group));
+                }
+                group.push(id);
+                prev_duration = duration;
+            }
+            groups.push(group);
+        }
+        groups.retain(|group| group.len() > 1);
+        for group in &mut groups {
+            group.sort_unstable();
+        }
+        groups.sort_unstable();
+        groups
+    }
     /// updates an existing song in the database with the new value.
     /// uses song.id to find the correct song.
     /// if the id doesn't exist in the db, Err(()) is returned.
@@ -279,7 +400,7 @@ impl Database {
             if prev_album.artist != album.artist {
                 // remove album from previous artist
                 if let Some(prev_artist) = self.artists.get_mut(&prev_album.artist) {
-                    if let Some(i) = prev_artist.albums.iter().position(|a| *a != prev_album.id) {
+                    if let Some(i) = prev_artist.albums.iter().position(|a| *a == prev_album.id) {
                         prev_artist.albums.remove(i);
                     } else {
                         eprintln!(
@@ -476,21 +597,31 @@ impl Database {
     pub fn remove_song(&mut self, song: SongId) -> Option<Song> {
         if let Some(removed) = self.songs.remove(&song) {
             self.modified_data();
+            // so albums and artists don't reference a song which doesn't exist anymore
+            if let Some(album) = removed.album.and_then(|id| self.albums.get_mut(&id)) {
+                album.songs.retain(|id| *id != song);
+            }
+            if let Some(artist) = self.artists.get_mut(&removed.artist) {
+                artist.singles.retain(|id| *id != song);
+            }
             Some(removed)
         } else {
             None
         }
     }
-    pub fn remove_album(&mut self, song: SongId) -> Option<Song> {
-        if let Some(removed) = self.songs.remove(&song) {
+    pub fn remove_album(&mut self, album: AlbumId) -> Option<Album> {
+        if let Some(removed) = self.albums.remove(&album) {
             self.modified_data();
+            if let Some(artist) = self.artists.get_mut(&removed.artist) {
+                artist.albums.retain(|id| *id != album);
+            }
             Some(removed)
         } else {
             None
         }
     }
-    pub fn remove_artist(&mut self, song: SongId) -> Option<Song> {
-        if let Some(removed) = self.songs.remove(&song) {
+    pub fn remove_artist(&mut self, artist: ArtistId) -> Option<Artist> {
+        if let Some(removed) = self.artists.remove(&artist) {
             self.modified_data();
             Some(removed)
         } else {
@@ -510,9 +641,32 @@ impl Database {
         self.seq
             .pack(Action::QueueUpdate(vec![], self.queue.clone(), Req::none()))
             .to_bytes(con)?;
+        for (name, playlist) in &self.playlists {
+            self.seq
+                .pack(Action::SavePlaylist(name.clone(), playlist.clone()))
+                .to_bytes(con)?;
+        }
         if self.playing {
             self.seq.pack(Action::Resume).to_bytes(con)?;
         }
+        if self.volume != 1.0 {
+            self.seq
+                .pack(Action::SetVolume(self.volume))
+                .to_bytes(con)?;
+        }
+        if self.muted {
+            self.seq.pack(Action::SetMuted(true)).to_bytes(con)?;
+        }
+        if self.repeat != RepeatMode::Off {
+            self.seq
+                .pack(Action::QueueSetRepeat(self.repeat))
+                .to_bytes(con)?;
+        }
+        if self.current_song_format.is_some() {
+            self.seq
+                .pack(Action::SetCurrentSongFormat(self.current_song_format))
+                .to_bytes(con)?;
+        }
         // this allows clients to find out when init_connection is done.
         self.seq.pack(Action::InitComplete).to_bytes(con)?;
         // is initialized now - client can receive updates after this point.
@@ -524,30 +678,8 @@ impl Database {
     /// `apply_action_unchecked_seq(command.action)` if `command.seq` is correct or `0xFF`
     pub fn apply_command(&mut self, mut command: Command, client: Option<u64>) {
         if command.seq != self.seq.seq() && command.seq != 0xFF {
-            if let Some(client) = client {
-                for (udepid, udep) in &mut self.update_endpoints {
-                    if client == *udepid {
-                        let mut reqs = command.action.get_req_if_some();
-                        if reqs.is_empty() {
-                            reqs.push(Req::none());
-                        }
-                        for req in reqs {
-                            let denied = Action::Denied(req).cmd(0xFFu8);
-                            match udep {
-                                UpdateEndpoint::Bytes(w) => {
-                                    let _ = w.write(&denied.to_bytes_vec());
-                                }
-                                UpdateEndpoint::CmdChannel(w) => {
-                                    let _ = w.send(Arc::new(denied));
-                                }
-                                UpdateEndpoint::Custom(w) => w(&denied),
-                                UpdateEndpoint::CustomArc(w) => w(Arc::new(denied)),
-                                UpdateEndpoint::CustomBytes(w) => w(&denied.to_bytes_vec()),
-                            }
-                        }
-                        return;
-                    }
-                }
+            if client.is_some_and(|client| self.deny(&mut command.action, client)) {
+                return;
             }
             eprintln!(
                 "Invalid sequence number: got {} but expected {}.",
@@ -558,6 +690,161 @@ impl Database {
         }
         self.apply_action_unchecked_seq(command.action, client)
     }
+    /// sends `Action::Denied` (with the action's `Req`s) to the client. returns false if there is no such client.
+    fn deny(&mut self, action: &mut Action, client: u64) -> bool {
+        for (udepid, udep) in &mut self.update_endpoints {
+            if client == *udepid {
+                let mut reqs = action.get_req_if_some();
+                if reqs.is_empty() {
+                    reqs.push(Req::none());
+                }
+                let mut failed = false;
+                for req in reqs {
+                    let denied = Action::Denied(req).cmd(0xFFu8);
+                    match udep {
+                        UpdateEndpoint::Bytes(w) => {
+                            failed |= w.write_all(&denied.to_bytes_vec()).is_err();
+                        }
+                        UpdateEndpoint::CmdChannel(w) => {
+                            failed |= w.send(Arc::new(denied)).is_err();
+                        }
+                        UpdateEndpoint::Custom(w) => w(&denied),
+                        UpdateEndpoint::CustomArc(w) => w(Arc::new(denied)),
+                        UpdateEndpoint::CustomBytes(w) => w(&denied.to_bytes_vec()),
+                    }
+                }
+                if failed {
+                    self.remove_update_endpoints(&[client]);
+                }
+                return true;
+            }
+        }
+        false
+    }
+    /// the server replaces a conditional with its songs as soon as it becomes the current element
+    /// and broadcasts the result, so all clients end up with the same songs.
+    fn resolve_current_conditional(&mut self) {
+        let Some(QueueContent::Conditional(conditional)) =
+            self.queue.get_current().map(|v| v.content())
+        else {
+            return;
+        };
+        let mut playlist = conditional.resolve(self);
+        // the conditional itself counted as one song
+        if let Some(limit) = self.queue_limit {
+            playlist
+                .songs
+                .truncate(limit.saturating_sub(self.queue.element_count() - 1));
+        }
+        let Some(path) = self.queue.get_current_path() else {
+            return;
+        };
+        let empty = playlist.songs.is_empty();
+        self.apply_action_unchecked_seq(
+            Action::QueueUpdate(path, QueueContent::Playlist(playlist).into(), Req::none()),
+            None,
+        );
+        // nothing matched, so skip it
+        if empty {
+            self.apply_action_unchecked_seq(Action::NextSong, None);
+        }
+    }
+    /// for every folder which contains the current song and is shuffled with a weight,
+    /// picks the element which is played after the current one (see `QueueFolder::pick_next_weighted`)
+    fn pick_weighted_next(&mut self) {
+        let Some(path) = self.queue.get_current_path() else {
+            return;
+        };
+        for len in 0..path.len() {
+            let path = &path[..len];
+            let Some(QueueContent::Folder(folder)) =
+                self.queue.get_item_at_index(path, 0).map(|v| v.content())
+            else {
+                continue;
+            };
+            let mut rng = StdRng::seed_from_u64(folder.seed.unwrap_or(0) ^ folder.index as u64);
+            if let Some(order) = folder.pick_next_weighted(self, &mut rng) {
+                let (seed, weight) = (folder.seed, folder.weight.clone());
+                self.apply_action_unchecked_seq(
+                    Action::QueueSetShuffle(path.to_vec(), order, seed, weight),
+                    None,
+                );
+            }
+        }
+    }
+    /// if the action adds songs to the queue and the queue would grow beyond `queue_limit`,
+    /// either deny it (returns false) or remove elements before the current one, depending on `queue_limit_policy`.
+    /// if that doesn't make enough room, the action is denied.
+    fn enforce_queue_limit(&mut self, action: &mut Action, client: Option<u64>) -> bool {
+        let Some(limit) = self.queue_limit else {
+            return true;
+        };
+        // `replaced` songs are removed by the action
+        let (target, new_data, replaced) = match &*action {
+            Action::QueueAdd(index, new_data, _) | Action::QueueInsert(index, _, new_data, _) => {
+                (index.first().copied(), new_data.as_slice(), 0)
+            }
+            Action::QueueInsertAfterCurrent(new_data) => (None, new_data.as_slice(), 0),
+            Action::QueueUpdate(index, new_data, _) => (
+                index.first().copied(),
+                std::slice::from_ref(new_data),
+                self.queue
+                    .get_item_at_index(index, 0)
+                    .map_or(0, |v| v.element_count()),
+            ),
+            Action::QueueReplace(queue, _) => (
+                None,
+                std::slice::from_ref(queue),
+                self.queue.element_count(),
+            ),
+            _ => return true,
+        };
+        let added = new_data.iter().map(|v| v.element_count()).sum::<usize>();
+        let mut len = self.queue.element_count().saturating_sub(replaced);
+        if len + added > limit && self.queue_limit_policy == QueueLimitPolicy::DropFront {
+            // only elements which were already played, songs which were queued but haven't played yet are kept
+            let mut removed = vec![];
+            if let QueueContent::Folder(folder) = self.queue.content() {
+                for (i, elem) in folder.iter().enumerate().take(folder.index) {
+                    if len + added <= limit {
+                        break;
+                    }
+                    if Some(i) != target {
+                        len = len.saturating_sub(elem.element_count());
+                        removed.push(i);
+                    }
+                }
+            }
+            if len + added <= limit {
+                // back to front, so the indices stay valid
+                for &i in removed.iter().rev() {
+                    self.apply_action_unchecked_seq(Action::QueueRemove(vec![i]), None);
+                }
+                let shift = |i: &mut usize| *i -= removed.iter().filter(|r| **r < *i).count();
+                match action {
+                    Action::QueueAdd(index, _, _) | Action::QueueUpdate(index, _, _) => {
+                        index.first_mut().into_iter().for_each(shift)
+                    }
+                    Action::QueueInsert(index, pos, _, _) => match index.first_mut() {
+                        Some(i) => shift(i),
+                        None => shift(pos),
+                    },
+                    _ => {}
+                }
+            }
+        }
+        if len + added > limit {
+            eprintln!(
+                "[{}] Queue limit of {limit} songs reached, denying adding {added} more.",
+                "INFO".cyan()
+            );
+            if let Some(client) = client {
+                self.deny(action, client);
+            }
+            return false;
+        }
+        true
+    }
     pub fn apply_action_unchecked_seq(&mut self, mut action: Action, client: Option<u64>) {
         if !self.is_client() {
             if let Action::ErrorInfo(t, _) = &mut action {
@@ -566,11 +853,37 @@ impl Database {
                 t.clear();
             }
         }
+        // may also remove elements from the queue, or deny the action
+        if !self.is_client() && !self.enforce_queue_limit(&mut action, client) {
+            return;
+        }
         // some commands shouldn't be broadcast. these will broadcast a different command in their specific implementation.
         match &action {
             // Will broadcast `QueueSetShuffle`
-            Action::QueueShuffle(_) => (),
+            Action::QueueShuffle(..) => (),
             Action::NextSong if self.queue.is_almost_empty() => (),
+            // the server decides this, so all clients do the same thing
+            Action::PreviousSong if !self.is_client() && self.previous_song_restarts() => {
+                let restart = Action::SetCurrentSongPosition(0);
+                // through the server's loop, so the player sees it too
+                if let Some(sender) = &self.command_sender {
+                    _ = sender.send((restart.cmd(0xFFu8), None));
+                } else {
+                    self.apply_action_unchecked_seq(restart, client);
+                }
+                return;
+            }
+            // only affects the server's CacheManager
+            Action::SetCacheConfig { .. } | Action::SetCachePinned(..) => (),
+            // broadcasts the changes instead
+            Action::ReloadDatabase => {
+                if !self.is_client() {
+                    if let Err(e) = self.reload_database(client) {
+                        eprintln!("[{}] Couldn't reload the dbfile: {e}", "ERR!".red());
+                    }
+                }
+                return;
+            }
             Action::Pause if !self.playing => (),
             Action::Resume if self.playing => (),
             // since db.update_endpoints is empty for clients, this won't cause unwanted back and forth
@@ -580,24 +893,51 @@ impl Database {
             Action::Resume => self.playing = true,
             Action::Pause => self.playing = false,
             Action::Stop => self.playing = false,
+            // handled by the player
+            Action::StopAfterCurrentSong(_) => {}
+            Action::SetVolume(volume) => self.volume = volume.max(0.0),
+            Action::SetMuted(muted) => self.muted = muted,
             Action::NextSong => {
                 if !Queue::advance_index_db(self) {
                     // end of queue
-                    self.apply_action_unchecked_seq(Action::Pause, client);
+                    if self.repeat != RepeatMode::All {
+                        self.apply_action_unchecked_seq(Action::Pause, client);
+                    }
                     self.queue.init();
                 }
             }
+            Action::PreviousSong => {
+                Queue::go_back_index_db(self);
+            }
+            Action::QueueSetRepeat(mode) => self.repeat = mode,
+            Action::SetCurrentSongPosition(position) | Action::PlaybackPosition(position) => {
+                self.current_song_position = Some((position, Instant::now()))
+            }
+            Action::SetCurrentSongFormat(format) => self.current_song_format = format,
+            // handled by the CacheManager
+            Action::SetCacheConfig { .. } | Action::SetCachePinned(..) => {}
             Action::Save => {
                 if let Err(e) = self.save_database(None) {
                     eprintln!("[{}] Couldn't save: {e}", "ERR!".red());
                 }
             }
             Action::SyncDatabase(a, b, c) => self.sync(a, b, c),
+            // handled above
+            Action::ReloadDatabase => {}
             Action::QueueUpdate(index, new_data, _) => {
                 if let Some(v) = self.queue.get_item_at_index_mut(&index, 0) {
                     *v = new_data;
                 }
             }
+            Action::QueueReplace(queue, keep_current) => {
+                let current = self.queue.get_current_song().copied();
+                self.queue = queue;
+                if keep_current {
+                    if let Some(path) = current.and_then(|id| self.queue.find_song(id)) {
+                        Queue::set_index_db(self, &path);
+                    }
+                }
+            }
             Action::QueueAdd(index, new_data, _) => {
                 if let Some(v) = self.queue.get_item_at_index_mut(&index, 0) {
                     v.add_to_end(new_data, false);
@@ -608,6 +948,18 @@ impl Database {
                     v.insert(new_data, pos, false);
                 }
             }
+            Action::QueueInsertAfterCurrent(new_data) => {
+                match self.queue.get_insert_after_current() {
+                    Some((index, pos)) => {
+                        if let Some(v) = self.queue.get_item_at_index_mut(&index, 0) {
+                            v.insert(new_data, pos, false);
+                        }
+                    }
+                    None => {
+                        self.queue.add_to_end(new_data, false);
+                    }
+                }
+            }
             Action::QueueRemove(index) => {
                 self.queue.remove_by_index(&index, 0);
             }
@@ -676,19 +1028,51 @@ impl Database {
                     }
                 }
             }
+            Action::QueueMoveMultiple(sources, target) => {
+                self.queue.move_multiple(sources, &target);
+            }
             Action::QueueGoto(index) => Queue::set_index_db(self, &index),
-            Action::QueueShuffle(path) => {
-                if let Some(elem) = self.queue.get_item_at_index_mut(&path, 0) {
+            Action::PlayFrom(index) => {
+                Queue::set_index_db(self, &index);
+                self.playing = true;
+            }
+            Action::QueueShuffle(path, weight, seed) => {
+                if let Some(elem) = self.queue.get_item_at_index(&path, 0) {
                     if let QueueContent::Folder(QueueFolder {
                         index: _,
                         content,
                         name: _,
                         order: _,
-                    }) = elem.content_mut()
+                        seed: _,
+                        weight: _,
+                    }) = elem.content()
                     {
+                        // always use a seed so that every shuffle can be reproduced
+                        let seed = seed.unwrap_or_else(|| thread_rng().gen());
+                        let mut rng = StdRng::seed_from_u64(seed);
                         let mut ord: Vec<usize> = (0..content.len()).collect();
-                        ord.shuffle(&mut thread_rng());
-                        self.apply_action_unchecked_seq(Action::QueueSetShuffle(path, ord), client);
+                        if weight == ShuffleWeight::Uniform {
+                            ord.shuffle(&mut rng);
+                        } else {
+                            // weighted random sampling (Efraimidis-Spirakis): sort by u^(1/w), highest first.
+                            // this is only a preview, the next element is picked again when it is needed (see `pick_weighted_next`)
+                            let keys = content
+                                .iter()
+                                .map(|q| {
+                                    let w = weight.weight(q, self);
+                                    if w > 0.0 {
+                                        rng.gen::<f64>().powf(1.0 / w)
+                                    } else {
+                                        0.0
+                                    }
+                                })
+                                .collect::<Vec<_>>();
+                            ord.sort_by(|a, b| keys[*b].total_cmp(&keys[*a]));
+                        }
+                        self.apply_action_unchecked_seq(
+                            Action::QueueSetShuffle(path, ord, Some(seed), weight),
+                            client,
+                        );
                     } else {
                         eprintln!("(QueueShuffle) QueueElement at {path:?} not a folder!");
                     }
@@ -696,20 +1080,29 @@ impl Database {
                     eprintln!("(QueueShuffle) No QueueElement at {path:?}");
                 }
             }
-            Action::QueueSetShuffle(path, ord) => {
+            Action::QueueSetShuffle(path, ord, new_seed, new_weight) => {
                 if let Some(elem) = self.queue.get_item_at_index_mut(&path, 0) {
                     if let QueueContent::Folder(QueueFolder {
                         index,
                         content,
                         name: _,
                         order,
+                        seed,
+                        weight,
                     }) = elem.content_mut()
                     {
                         if ord.len() == content.len() {
-                            if let Some(ni) = ord.iter().position(|v| *v == *index) {
+                            // keep the current element, `index` is its position in the old `order` (if any)
+                            let current = order
+                                .as_ref()
+                                .and_then(|v| v.get(*index).copied())
+                                .unwrap_or(*index);
+                            if let Some(ni) = ord.iter().position(|v| *v == current) {
                                 *index = ni;
                             }
                             *order = Some(ord);
+                            *seed = new_seed;
+                            *weight = new_weight;
                         } else {
                             eprintln!(
                                 "[warn] can't QueueSetShuffle - length of new ord ({}) is not the same as length of content ({})!",
@@ -736,12 +1129,16 @@ impl Database {
                         content: _,
                         name: _,
                         order,
+                        seed,
+                        weight,
                     }) = elem.content_mut()
                     {
                         if let Some(ni) = order.as_ref().and_then(|v| v.get(*index).copied()) {
                             *index = ni;
                         }
                         *order = None;
+                        *seed = None;
+                        *weight = ShuffleWeight::Uniform;
                     }
                 }
             }
@@ -863,11 +1260,72 @@ impl Database {
                     v.general.tags = tags.into_iter().filter(|v| !v.starts_with(&key)).collect();
                 }
             }
+            Action::SetSongRating(id, rating) => {
+                if let Some(v) = self.get_song_mut(&id) {
+                    v.general.tags.retain(|v| !v.starts_with(RATING_TAG));
+                    if let Some(rating) = rating {
+                        v.general
+                            .tags
+                            .push(format!("{RATING_TAG}{}", rating.min(5)));
+                    }
+                }
+            }
+            Action::SetSongGain(id, gain) => {
+                if let Some(v) = self.get_song_mut(&id) {
+                    v.general.tags.retain(|v| !v.starts_with(GAIN_TAG));
+                    if gain != 0.0 && gain.is_finite() {
+                        let gain = gain.clamp(-MAX_GAIN_DB, MAX_GAIN_DB);
+                        v.general.tags.push(format!("{GAIN_TAG}{gain}"));
+                    }
+                }
+            }
+            Action::SetArtistAliases(id, aliases) => {
+                if let Some(v) = self.artists_mut().get_mut(&id) {
+                    v.aliases.clear();
+                    for alias in aliases {
+                        let alias = alias.trim();
+                        if !alias.is_empty() && !v.aliases.iter().any(|a| a == alias) {
+                            v.aliases.push(alias.to_owned());
+                        }
+                    }
+                }
+            }
+            Action::SavePlaylist(name, mut queue) => {
+                // when the playlist is added to the queue, it should start at the beginning
+                queue.init();
+                if let QueueContent::Folder(folder) = queue.content_mut() {
+                    folder.name = name.clone();
+                }
+                self.playlists.insert(name, queue);
+                self.save_playlists();
+            }
+            Action::RenamePlaylist(name, new_name) => {
+                if !self.playlists.contains_key(&new_name) {
+                    if let Some(mut queue) = self.playlists.remove(&name) {
+                        if let QueueContent::Folder(folder) = queue.content_mut() {
+                            folder.name = new_name.clone();
+                        }
+                        self.playlists.insert(new_name, queue);
+                        self.save_playlists();
+                    }
+                }
+            }
+            Action::RemovePlaylist(name) => {
+                if self.playlists.remove(&name).is_some() {
+                    self.save_playlists();
+                }
+            }
             Action::SetSongDuration(id, duration) => {
                 if let Some(song) = self.get_song_mut(&id) {
                     song.duration_millis = duration;
                 }
             }
+            Action::SongPlayed(id, time) => {
+                if let Some(song) = self.get_song_mut(&id) {
+                    song.play_count += 1;
+                    song.last_played = Some(UNIX_EPOCH + Duration::from_millis(time));
+                }
+            }
             Action::Multiple(actions) => {
                 for action in actions {
                     self.apply_action_unchecked_seq(action, client);
@@ -879,6 +1337,10 @@ impl Database {
             Action::ErrorInfo(..) => {}
             Action::Denied(..) => {}
         }
+        if !self.is_client() {
+            self.resolve_current_conditional();
+            self.pick_weighted_next();
+        }
     }
 }
 
@@ -906,13 +1368,24 @@ impl Database {
             covers: HashMap::new(),
             custom_files: None,
             queue: QueueContent::Folder(QueueFolder::default()).into(),
+            playlists: HashMap::new(),
             update_endpoints: vec![],
             update_endpoints_id: 0,
             playing: false,
+            volume: 1.0,
+            muted: false,
+            repeat: RepeatMode::Off,
+            fade_millis: 150,
+            current_song_position: None,
+            current_song_format: None,
             command_sender: None,
             remote_server_as_song_file_source: None,
             client_is_init: false,
             times_data_modified: None,
+            autosave_max_delay: Duration::from_secs(60),
+            autosave_quiet_period: Duration::from_secs(5),
+            queue_limit: None,
+            queue_limit_policy: QueueLimitPolicy::Reject,
         }
     }
     pub fn new_empty_in_dir(dir: PathBuf, lib_dir: PathBuf) -> Self {
@@ -928,13 +1401,24 @@ impl Database {
             covers: HashMap::new(),
             custom_files: None,
             queue: QueueContent::Folder(QueueFolder::default()).into(),
+            playlists: HashMap::new(),
             update_endpoints: vec![],
             update_endpoints_id: 0,
             playing: false,
+            volume: 1.0,
+            muted: false,
+            repeat: RepeatMode::Off,
+            fade_millis: 150,
+            current_song_position: None,
+            current_song_format: None,
             command_sender: None,
             remote_server_as_song_file_source: None,
             client_is_init: false,
             times_data_modified: None,
+            autosave_max_delay: Duration::from_secs(60),
+            autosave_quiet_period: Duration::from_secs(5),
+            queue_limit: None,
+            queue_limit_policy: QueueLimitPolicy::Reject,
         }
     }
     pub fn load_database_from_dir(
@@ -942,30 +1426,228 @@ impl Database {
         lib_directory: PathBuf,
     ) -> Result<Self, std::io::Error> {
         let path = dir.join("dbfile");
-        let mut file = BufReader::new(File::open(&path)?);
+        let file = BufReader::new(File::open(&path)?);
         eprintln!("[{}] loading library from {file:?}", "INFO".cyan());
-        let s = Self {
+        let (version, file) = read_db_header(file)?;
+        match version {
+            // version 0 only lacks the header, version 1 only lacks the checksum,
+            // version 2 only lacks the songs' play count and last played time,
+            // version 3 only lacks the artists' aliases,
+            // version 4 only uses `u64`s instead of varints for lengths,
+            // version 5 only lacks the songs' bitrate and codec,
+            // version 6 only differs in the encoding of queues, which aren't in the dbfile
+            0..=7 => {}
+            v => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!(
+                        "dbfile has format version {v}, but this version of musicdb only supports versions up to {DB_VERSION}. Please update musicdb."
+                    ),
+                ))
+            }
+        }
+        if version < DB_VERSION {
+            eprintln!(
+                "[{}] dbfile has format version {version}, it will be saved as version {DB_VERSION}",
+                "INFO".cyan()
+            );
+        }
+        let mut file = Checksum::new(file);
+        // which part of the dbfile is being read, for the error message
+        let mut reading = "artists";
+        let data = (|| {
+            let _fixed = (version < 5).then(fixed_width_lengths);
+            let artists = if version >= 4 {
+                ToFromBytes::from_bytes(&mut file)?
+            } else {
+                let len = read_len(&mut file)?;
+                let mut artists = HashMap::with_capacity(len);
+                for _ in 0..len {
+                    artists.insert(
+                        ArtistId::from_bytes(&mut file)?,
+                        Artist::from_bytes_without_aliases(&mut file)?,
+                    );
+                }
+                artists
+            };
+            reading = "albums";
+            let albums = ToFromBytes::from_bytes(&mut file)?;
+            reading = "songs";
+            let songs = if version >= 6 {
+                ToFromBytes::from_bytes(&mut file)?
+            } else {
+                let len = read_len(&mut file)?;
+                let mut songs = HashMap::with_capacity(len);
+                for _ in 0..len {
+                    let id = SongId::from_bytes(&mut file)?;
+                    let song = if version >= 3 {
+                        Song::from_bytes_without_quality(&mut file)?
+                    } else {
+                        Song::from_bytes_without_play_stats(&mut file)?
+                    };
+                    songs.insert(id, song);
+                }
+                songs
+            };
+            reading = "covers";
+            let covers = ToFromBytes::from_bytes(&mut file)?;
+            reading = "the checksum";
+            if version >= 2 {
+                file.verify()?;
+            }
+            Ok((artists, albums, songs, covers))
+        })();
+        let (artists, albums, songs, covers) = data.map_err(|e: std::io::Error| {
+            std::io::Error::new(
+                e.kind(),
+                format!(
+                    "dbfile {path:?} is corrupted or incomplete, error at byte {} (after the header) while reading {reading}: {e}",
+                    file.position()
+                ),
+            )
+        })?;
+        let mut s = Self {
             seq: Commander::new(false),
             db_dir: dir,
             db_file: path,
             lib_directory,
-            artists: ToFromBytes::from_bytes(&mut file)?,
-            albums: ToFromBytes::from_bytes(&mut file)?,
-            songs: ToFromBytes::from_bytes(&mut file)?,
-            covers: ToFromBytes::from_bytes(&mut file)?,
+            artists,
+            albums,
+            songs,
+            covers,
             custom_files: None,
             queue: QueueContent::Folder(QueueFolder::default()).into(),
+            playlists: HashMap::new(),
             update_endpoints: vec![],
             update_endpoints_id: 0,
             playing: false,
+            volume: 1.0,
+            muted: false,
+            repeat: RepeatMode::Off,
+            fade_millis: 150,
+            current_song_position: None,
+            current_song_format: None,
             command_sender: None,
             remote_server_as_song_file_source: None,
             client_is_init: false,
             times_data_modified: None,
+            autosave_max_delay: Duration::from_secs(60),
+            autosave_quiet_period: Duration::from_secs(5),
+            queue_limit: None,
+            queue_limit_policy: QueueLimitPolicy::Reject,
         };
+        s.load_playlists();
         eprintln!("[{}] loaded library", "INFO".green());
         Ok(s)
     }
+    /// Reads the dbfile again and applies the differences using `Remove*`, `Add*` and `Modify*` actions,
+    /// so connected clients get the same changes. If that doesn't reproduce the dbfile's library
+    /// (for example because `Add*` wouldn't assign the new ids), the whole library is sent using `SyncDatabase` instead.
+    /// Does nothing if there is no dbfile or if there are unsaved changes, because those would be lost.
+    fn reload_database(&mut self, client: Option<u64>) -> Result<(), std::io::Error> {
+        if !self.db_file.is_file() {
+            eprintln!(
+                "[{}] not reloading the dbfile: {:?} doesn't exist (yet)",
+                "WARN".yellow(),
+                self.db_file
+            );
+            return Ok(());
+        }
+        if self.times_data_modified.is_some() {
+            eprintln!(
+                "[{}] not reloading the dbfile: there are unsaved changes, which would be lost. Save first.",
+                "WARN".yellow()
+            );
+            return Ok(());
+        }
+        let new = Self::load_database_from_dir(self.db_dir.clone(), self.lib_directory.clone())?;
+        let (artists, albums, songs) = (new.artists, new.albums, new.songs);
+        let mut changes = 0;
+        let mut apply = |db: &mut Self, action: Action| {
+            changes += 1;
+            db.apply_action_unchecked_seq(action, client);
+        };
+        for id in removed_ids(&self.songs, &songs) {
+            apply(self, Action::RemoveSong(id));
+        }
+        for id in removed_ids(&self.albums, &albums) {
+            apply(self, Action::RemoveAlbum(id));
+        }
+        for id in removed_ids(&self.artists, &artists) {
+            apply(self, Action::RemoveArtist(id));
+        }
+        let added = (
+            added_ids(&self.artists, &artists),
+            added_ids(&self.albums, &albums),
+            added_ids(&self.songs, &songs),
+            added_ids(&self.covers, &new.covers),
+        );
+        let matches =
+            if let (Some(new_artists), Some(new_albums), Some(new_songs), Some(new_covers)) = added
+            {
+                // without albums and songs, `AddAlbum` and `AddSong` add them (and `Modify*` fixes the order later)
+                for id in new_artists {
+                    let artist = Artist {
+                        albums: vec![],
+                        singles: vec![],
+                        ..artists[&id].clone()
+                    };
+                    apply(self, Action::AddArtist(artist, Req::none()));
+                }
+                for id in new_albums {
+                    let album = Album {
+                        songs: vec![],
+                        ..albums[&id].clone()
+                    };
+                    apply(self, Action::AddAlbum(album, Req::none()));
+                }
+                for id in new_songs {
+                    apply(self, Action::AddSong(songs[&id].clone(), Req::none()));
+                }
+                for id in new_covers {
+                    apply(self, Action::AddCover(new.covers[&id].clone(), Req::none()));
+                }
+                for song in songs.values() {
+                    if self.songs.get(&song.id) != Some(song) {
+                        apply(self, Action::ModifySong(song.clone(), Req::none()));
+                    }
+                }
+                for album in albums.values() {
+                    if self.albums.get(&album.id) != Some(album) {
+                        apply(self, Action::ModifyAlbum(album.clone(), Req::none()));
+                    }
+                }
+                for artist in artists.values() {
+                    if self.artists.get(&artist.id) != Some(artist) {
+                        apply(self, Action::ModifyArtist(artist.clone(), Req::none()));
+                    }
+                }
+                self.songs == songs && self.albums == albums && self.artists == artists
+            } else {
+                false
+            };
+        if matches {
+            eprintln!("[{}] reloaded the dbfile, {changes} changes", "INFO".cyan());
+        } else {
+            eprintln!(
+                "[{}] reloaded the dbfile, sending the whole library to clients",
+                "INFO".cyan()
+            );
+            apply(
+                self,
+                Action::SyncDatabase(
+                    artists.into_values().collect(),
+                    albums.into_values().collect(),
+                    songs.into_values().collect(),
+                ),
+            );
+        }
+        // changed or removed covers, clients load them from the server anyway
+        self.covers = new.covers;
+        // the library now matches the dbfile, so there is nothing to save
+        self.times_data_modified = None;
+        Ok(())
+    }
     /// saves the database's contents. save path can be overridden
     pub fn save_database(&mut self, path: Option<PathBuf>) -> Result<PathBuf, std::io::Error> {
         let path = if let Some(p) = path {
@@ -978,42 +1660,140 @@ impl Database {
             return Ok(path);
         }
         eprintln!("[{}] saving db to {path:?}", "INFO".cyan());
-        if path.try_exists()? {
-            let backup_name = format!(
-                "dbfile-{}",
-                std::time::SystemTime::now()
-                    .duration_since(std::time::SystemTime::UNIX_EPOCH)
-                    .map(|d| d.as_secs())
-                    .unwrap_or(0),
+        // the previous dbfile is kept as dbfile.bak
+        let saved = write_file_atomically(&path, true, |file| {
+            write_db_header(file)?;
+            let mut file = Checksum::new(file);
+            self.artists.to_bytes(&mut file)?;
+            self.albums.to_bytes(&mut file)?;
+            self.songs.to_bytes(&mut file)?;
+            self.covers.to_bytes(&mut file)?;
+            file.finish()?;
+            Ok(())
+        });
+        if let Err(e) = saved {
+            eprintln!(
+                "[{}] Couldn't save db, the previous dbfile was kept: {e}",
+                "ERR!".red()
             );
-            if let Err(e) = fs::rename(&path, self.db_dir.join(&backup_name)) {
-                eprintln!(
-                    "[{}] Couldn't move previous dbfile to {backup_name}!",
-                    "ERR!".red()
-                );
-                return Err(e);
-            }
-        }
-        let mut file = fs::OpenOptions::new()
-            .write(true)
-            .truncate(true)
-            .create(true)
-            .open(&path)?;
-        self.artists.to_bytes(&mut file)?;
-        self.albums.to_bytes(&mut file)?;
-        self.songs.to_bytes(&mut file)?;
-        self.covers.to_bytes(&mut file)?;
+            return Err(e);
+        }
         eprintln!("[{}] saved db", "INFO".green());
         // all changes saved, data no longer modified
         self.times_data_modified = None;
         Ok(path)
     }
+    /// saves the queue and the current song's playback position (in milliseconds)
+    /// to the `playbackstate` file in `db_dir`, so they can be restored after a restart.
+    pub fn save_playback_state(&self, position: Option<u64>) -> Result<(), std::io::Error> {
+        // client mode, do nothing
+        if self.db_file.as_os_str().is_empty() {
+            return Ok(());
+        }
+        write_file_atomically(&self.db_dir.join("playbackstate"), false, |file| {
+            write_db_header(file)?;
+            self.queue.to_bytes(file)?;
+            position.to_bytes(file)
+        })
+    }
+    /// restores the queue from the `playbackstate` file, if it exists,
+    /// and returns the playback position (in milliseconds) that was saved with it.
+    /// The state is discarded if it was saved by a version of musicdb with a different `DB_VERSION`.
+    pub fn load_playback_state(&mut self) -> Option<u64> {
+        if self.db_file.as_os_str().is_empty() {
+            return None;
+        }
+        let path = self.db_dir.join("playbackstate");
+        let file = BufReader::new(File::open(&path).ok()?);
+        match (|| -> Result<_, std::io::Error> {
+            let (version, mut file) = read_db_header(file)?;
+            if version != DB_VERSION {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("saved with format version {version}, not {DB_VERSION}, discarding it"),
+                ));
+            }
+            Ok((
+                Queue::from_bytes(&mut file)?,
+                Option::<u64>::from_bytes(&mut file)?,
+            ))
+        })() {
+            Ok((queue, position)) => {
+                eprintln!("[{}] restored queue from {path:?}", "INFO".cyan());
+                self.queue = queue;
+                position
+            }
+            Err(e) => {
+                eprintln!(
+                    "[{}] Couldn't load playback state from {path:?}: {e}",
+                    "WARN".yellow()
+                );
+                None
+            }
+        }
+    }
+    /// saves all playlists to the `playlists` file in `db_dir`
+    fn save_playlists(&self) {
+        // client mode, do nothing
+        if self.db_file.as_os_str().is_empty() {
+            return;
+        }
+        let path = self.db_dir.join("playlists");
+        let saved = write_file_atomically(&path, false, |file| {
+            write_db_header(file)?;
+            self.playlists.to_bytes(file)
+        });
+        if let Err(e) = saved {
+            eprintln!(
+                "[{}] Couldn't save playlists to {path:?}: {e}",
+                "ERR!".red()
+            );
+        }
+    }
+    /// loads the playlists from the `playlists` file in `db_dir`, if it exists
+    fn load_playlists(&mut self) {
+        let path = self.db_dir.join("playlists");
+        let Ok(bytes) = std::fs::read(&path) else {
+            return;
+        };
+        let decode = |mut rest: &[u8], version: u32| {
+            let _fixed = (version < 5).then(fixed_width_lengths);
+            let _without_weight = (version < 7).then(folders_without_weight);
+            let playlists = HashMap::from_bytes(&mut rest)?;
+            if rest.is_empty() {
+                Ok(playlists)
+            } else {
+                Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "unexpected data at the end of the file",
+                ))
+            }
+        };
+        let playlists = match read_db_header(bytes.as_slice()) {
+            Ok((version, _)) if version > DB_VERSION => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("file has format version {version}, but this version of musicdb only supports versions up to {DB_VERSION}"),
+            )),
+            Ok((version, rest)) if version > 0 => decode(rest.into_inner().1, version),
+            // no header: saved before the header was added, with or without varint lengths
+            _ => decode(&bytes, 5).or_else(|e| decode(&bytes, 4).map_err(|_| e)),
+        };
+        match playlists {
+            Ok(playlists) => self.playlists = playlists,
+            Err(e) => eprintln!(
+                "[{}] Couldn't load playlists from {path:?}: {e}",
+                "WARN".yellow()
+            ),
+        }
+    }
     pub fn broadcast_update(&mut self, update: Action, client: Option<u64>) -> Action {
         match update {
             Action::InitComplete => return update,
             _ => {}
         }
-        if !self.is_client() {
+        // sent twice a second, so it doesn't change the seq.
+        // otherwise, clients' commands would often be denied because their seq is outdated.
+        if !self.is_client() && !matches!(update, Action::PlaybackPosition(_)) {
             self.seq.inc();
         }
         let mut update = self.seq.pack(update);
@@ -1021,18 +1801,18 @@ impl Database {
         let mut remove = vec![];
         let mut bytes = None;
         let mut arc = None;
-        for (i, (udepid, udep)) in self.update_endpoints.iter_mut().enumerate() {
+        for (udepid, udep) in self.update_endpoints.iter_mut() {
             if reqs.iter().any(|r| r.is_some()) && client.is_some_and(|v| *udepid == v) {
                 update.action.put_req_all(reqs.clone());
                 match udep {
                     UpdateEndpoint::Bytes(writer) => {
                         if writer.write_all(&update.to_bytes_vec()).is_err() {
-                            remove.push(i);
+                            remove.push(*udepid);
                         }
                     }
                     UpdateEndpoint::CmdChannel(sender) => {
                         if sender.send(Arc::new(update.clone())).is_err() {
-                            remove.push(i);
+                            remove.push(*udepid);
                         }
                     }
                     UpdateEndpoint::Custom(func) => func(&update),
@@ -1052,7 +1832,7 @@ impl Database {
                         bytes = Some(update.to_bytes_vec());
                     }
                     if writer.write_all(bytes.as_ref().unwrap()).is_err() {
-                        remove.push(i);
+                        remove.push(*udepid);
                     }
                 }
                 UpdateEndpoint::CmdChannel(sender) => {
@@ -1060,7 +1840,7 @@ impl Database {
                         arc = Some(Arc::new(update.clone()));
                     }
                     if sender.send(arc.clone().unwrap()).is_err() {
-                        remove.push(i);
+                        remove.push(*udepid);
                     }
                 }
                 UpdateEndpoint::Custom(func) => func(&update),
@@ -1078,19 +1858,25 @@ impl Database {
                 }
             }
         }
-        if !remove.is_empty() {
-            eprintln!(
-                "[info] closing {} connections, {} are still active",
-                remove.len(),
-                self.update_endpoints.len() - remove.len()
-            );
-            for i in remove.into_iter().rev() {
-                self.update_endpoints.remove(i);
-            }
-        }
+        self.remove_update_endpoints(&remove);
         update.action.put_req_all(reqs);
         update.action
     }
+    /// removes the update endpoints with these ids, for example because writing to them failed (the client disconnected).
+    /// ids can be repeated or no longer exist.
+    pub fn remove_update_endpoints(&mut self, ids: &[u64]) {
+        let len = self.update_endpoints.len();
+        self.update_endpoints.retain(|(id, _)| !ids.contains(id));
+        if self.update_endpoints.len() == len {
+            return;
+        }
+        eprintln!(
+            "[{}] closing {} connections, {} are still active",
+            "INFO".cyan(),
+            len - self.update_endpoints.len(),
+            self.update_endpoints.len()
+        );
+    }
     pub fn sync(&mut self, artists: Vec<Artist>, albums: Vec<Album>, songs: Vec<Song>) {
         self.modified_data();
         self.artists = artists.iter().map(|v| (v.id, v.clone())).collect();
@@ -1099,6 +1885,29 @@ impl Database {
     }
 }
 
+/// ids which are in `old` but not in `new`
+fn removed_ids<T>(old: &HashMap<u64, T>, new: &HashMap<u64, T>) -> Vec<u64> {
+    old.keys()
+        .filter(|id| !new.contains_key(id))
+        .copied()
+        .collect()
+}
+/// ids which are in `new` but not in `old`, ordered, or `None` if adding them one by one
+/// wouldn't result in these ids, because the `add_*_new` functions always use the lowest unused id.
+fn added_ids<T>(old: &HashMap<u64, T>, new: &HashMap<u64, T>) -> Option<Vec<u64>> {
+    let mut added = new
+        .keys()
+        .filter(|id| !old.contains_key(id))
+        .copied()
+        .collect::<Vec<_>>();
+    added.sort_unstable();
+    let mut free = (0..).filter(|id| !old.contains_key(id));
+    added
+        .iter()
+        .all(|id| free.next() == Some(*id))
+        .then_some(added)
+}
+
 impl Database {
     pub fn songs(&self) -> &HashMap<SongId, Song> {
         &self.songs
@@ -1112,6 +1921,10 @@ impl Database {
     pub fn covers(&self) -> &HashMap<CoverId, Cover> {
         &self.covers
     }
+    /// saved queues, by name
+    pub fn playlists(&self) -> &HashMap<String, Queue> {
+        &self.playlists
+    }
     /// you should probably use a Command to do this...
     pub fn songs_mut(&mut self) -> &mut HashMap<SongId, Song> {
         self.modified_data();
@@ -1172,6 +1985,31 @@ impl Cover {
             }
         }
     }
+    /// If the cover's data is cached, returns the number of bytes.
+    pub fn has_cached_data(&self) -> Option<usize> {
+        self.data.lock().unwrap().1.as_ref().map(|(_, v)| v.len())
+    }
+    /// If no data is cached and it isn't already being loaded, starts a thread to load it from `path`.
+    /// Returns `true` if a thread was started.
+    pub fn cache_data_start_thread(&self, path: PathBuf) -> bool {
+        let mut data = self.data.lock().unwrap();
+        if data.0 || data.1.is_some() {
+            return false;
+        }
+        data.0 = true;
+        let data = Arc::clone(&self.data);
+        std::thread::spawn(move || {
+            let bytes = std::fs::read(&path).ok();
+            *data.lock().unwrap() = (false, bytes.map(|bytes| (Instant::now(), bytes)));
+        });
+        true
+    }
+    /// Drops the cached data, unless it is still being loaded.
+    /// Returns `true` if there was data to drop.
+    pub fn uncache_data(&self) -> bool {
+        let mut data = self.data.lock().unwrap();
+        !data.0 && data.1.take().is_some()
+    }
 }
 impl ToFromBytes for Cover {
     fn to_bytes<T>(&self, s: &mut T) -> Result<(), std::io::Error>
@@ -1185,8 +2023,623 @@ impl ToFromBytes for Cover {
         T: std::io::Read,
     {
         Ok(Self {
-            location: ToFromBytes::from_bytes(s)?,
+            location: ToFromBytes::from_bytes(s)
+                .context("location")
+                .context("Cover")?,
             data: Arc::new(Mutex::new((false, None))),
         })
     }
 }
+
+#[cfg(test)]
+use crate::test::test_db;
+
+#[test]
+fn test_remove_broken_update_endpoints() {
+    struct BrokenPipe;
+    impl Write for BrokenPipe {
+        fn write(&mut self, _buf: &[u8]) -> std::io::Result<usize> {
+            Err(std::io::ErrorKind::BrokenPipe.into())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+    let (mut db, _dir) = test_db("endpoints");
+    let working = Shared::default();
+    db.update_endpoints
+        .push((0, UpdateEndpoint::Bytes(Box::new(BrokenPipe))));
+    db.update_endpoints
+        .push((1, UpdateEndpoint::Bytes(Box::new(working.clone()))));
+    db.update_endpoints
+        .push((2, UpdateEndpoint::Bytes(Box::new(BrokenPipe))));
+    // the client which sent the command gets the update (with its req) twice, and both writes fail
+    db.apply_action_unchecked_seq(
+        Action::QueueAdd(vec![], vec![], crate::server::Requester::new().inc()),
+        Some(2),
+    );
+    assert_eq!(
+        db.update_endpoints.iter().map(|v| v.0).collect::<Vec<_>>(),
+        vec![1]
+    );
+    assert!(!working.0.lock().unwrap().is_empty());
+    db.apply_action_unchecked_seq(Action::SetVolume(0.5), None);
+    assert_eq!(db.update_endpoints.len(), 1);
+}
+
+#[test]
+fn test_queue_replace() {
+    let folder = |ids: &[SongId]| -> Queue {
+        QueueContent::Folder(QueueFolder {
+            content: ids
+                .iter()
+                .map(|id| QueueContent::Song(*id).into())
+                .collect(),
+            ..Default::default()
+        })
+        .into()
+    };
+    let (mut db, _dir) = test_db("replace");
+    db.apply_action_unchecked_seq(Action::QueueReplace(folder(&[1, 2, 3]), true), None);
+    db.apply_action_unchecked_seq(Action::QueueGoto(vec![1]), None);
+    assert_eq!(db.queue.get_current_song(), Some(&2));
+    // the current song is in the new queue, so it stays the current song
+    db.apply_action_unchecked_seq(Action::QueueReplace(folder(&[4, 3, 2]), true), None);
+    assert_eq!(db.queue.get_current_song(), Some(&2));
+    assert_eq!(db.queue.get_next_song(), None);
+    // it isn't, or it shouldn't be kept, so the new queue starts at its own index
+    db.apply_action_unchecked_seq(Action::QueueReplace(folder(&[5, 6]), true), None);
+    assert_eq!(db.queue.get_current_song(), Some(&5));
+    db.apply_action_unchecked_seq(Action::QueueReplace(folder(&[6, 5]), false), None);
+    assert_eq!(db.queue.get_current_song(), Some(&6));
+}
+
+#[test]
+fn test_queue_insert_after_current() {
+    let song = |id: SongId| -> Queue { QueueContent::Song(id).into() };
+    let (mut db, _dir) = test_db("play-next");
+    // nothing is playing, so the song is added to the (empty) queue
+    db.apply_action_unchecked_seq(Action::QueueInsertAfterCurrent(vec![song(1)]), None);
+    assert_eq!(db.queue.get_current_song(), Some(&1));
+    db.apply_action_unchecked_seq(
+        Action::QueueAdd(
+            vec![],
+            vec![QueueContent::Loop(
+                2,
+                0,
+                Box::new(
+                    QueueContent::Folder(QueueFolder {
+                        content: vec![song(2), song(3)],
+                        ..Default::default()
+                    })
+                    .into(),
+                ),
+            )
+            .into()],
+            Req::none(),
+        ),
+        None,
+    );
+    db.apply_action_unchecked_seq(Action::QueueInsertAfterCurrent(vec![song(4)]), None);
+    assert_eq!(db.queue.get_next_song(), Some(&4));
+    // inside the loop's folder
+    db.apply_action_unchecked_seq(Action::QueueGoto(vec![2, 0, 0]), None);
+    assert_eq!(db.queue.get_current_song(), Some(&2));
+    db.apply_action_unchecked_seq(Action::QueueInsertAfterCurrent(vec![song(5)]), None);
+    assert_eq!(db.queue.get_next_song(), Some(&5));
+    assert!(db
+        .queue
+        .get_item_at_index(&[2, 0, 1], 0)
+        .is_some_and(|v| matches!(v.content(), QueueContent::Song(5))));
+}
+
+#[test]
+fn test_play_from() {
+    let (mut db, _dir) = test_db("play-from");
+    db.apply_action_unchecked_seq(
+        Action::QueueAdd(
+            vec![],
+            vec![QueueContent::Song(1).into(), QueueContent::Song(2).into()],
+            Req::none(),
+        ),
+        None,
+    );
+    assert!(!db.playing);
+    db.apply_action_unchecked_seq(Action::PlayFrom(vec![1]), None);
+    assert!(db.playing);
+    assert_eq!(db.queue.get_current_song(), Some(&2));
+}
+
+#[test]
+fn test_previous_song() {
+    let (mut db, _dir) = test_db("previous");
+    db.apply_action_unchecked_seq(
+        Action::QueueAdd(
+            vec![],
+            vec![QueueContent::Song(1).into(), QueueContent::Song(2).into()],
+            Req::none(),
+        ),
+        None,
+    );
+    db.apply_action_unchecked_seq(Action::QueueGoto(vec![1]), None);
+    db.apply_action_unchecked_seq(Action::PreviousSong, None);
+    assert_eq!(db.queue.get_current_song(), Some(&1));
+    // the first song restarts
+    db.apply_action_unchecked_seq(Action::SetCurrentSongPosition(1000), None);
+    db.apply_action_unchecked_seq(Action::PreviousSong, None);
+    assert_eq!(db.queue.get_current_song(), Some(&1));
+    assert_eq!(db.current_song_position.map(|v| v.0), Some(0));
+    // after a few seconds, the song restarts
+    db.apply_action_unchecked_seq(Action::QueueGoto(vec![1]), None);
+    db.apply_action_unchecked_seq(Action::SetCurrentSongPosition(5000), None);
+    db.apply_action_unchecked_seq(Action::PreviousSong, None);
+    assert_eq!(db.queue.get_current_song(), Some(&2));
+    assert_eq!(db.current_song_position.map(|v| v.0), Some(0));
+}
+
+#[test]
+fn test_reload_database() {
+    use super::GeneralData;
+    let (mut db, dir) = test_db("reload");
+    let artist = |name: &str| Artist {
+        id: 0,
+        name: name.to_owned(),
+        aliases: vec![],
+        cover: None,
+        albums: vec![],
+        singles: vec![],
+        general: GeneralData::default(),
+    };
+    let song = |title: &str, album, artist| {
+        Song::new(
+            DatabaseLocation::from(format!("{title}.mp3")),
+            None,
+            title.to_owned(),
+            album,
+            artist,
+            vec![],
+            None,
+            0,
+            0,
+            GeneralData::default(),
+        )
+    };
+    let artist_id = db.add_artist_new(artist("artist"));
+    let album_id = db.add_album_new(Album {
+        id: 0,
+        name: "album".to_owned(),
+        artist: artist_id,
+        cover: None,
+        songs: vec![],
+        general: GeneralData::default(),
+    });
+    db.add_song_new(song("a", Some(album_id), artist_id));
+    db.add_song_new(song("b", Some(album_id), artist_id));
+    db.add_song_new(song("c", None, artist_id));
+    db.save_database(None).unwrap();
+    // another program changes the dbfile
+    let mut other = Database::load_database_from_dir(dir.to_path_buf(), dir.to_path_buf()).unwrap();
+    other.songs_mut().get_mut(&0).unwrap().title = "a (remastered)".to_owned();
+    other.remove_song(2);
+    let artist2 = other.add_artist_new(artist("artist 2"));
+    other.add_song_new(song("d", None, artist2));
+    other.add_song_new(song("e", Some(album_id), artist_id));
+    other.save_database(None).unwrap();
+    db.apply_action_unchecked_seq(Action::ReloadDatabase, None);
+    assert_eq!(db.songs, other.songs);
+    assert_eq!(db.albums, other.albums);
+    assert_eq!(db.artists, other.artists);
+    // ids which `AddSong` wouldn't assign
+    let mut f = song("f", None, artist2);
+    f.id = 10;
+    other.songs_mut().insert(10, f);
+    other
+        .artists_mut()
+        .get_mut(&artist2)
+        .unwrap()
+        .singles
+        .push(10);
+    other.save_database(None).unwrap();
+    db.apply_action_unchecked_seq(Action::ReloadDatabase, None);
+    assert_eq!(db.songs, other.songs);
+    assert_eq!(db.artists, other.artists);
+    // unsaved changes aren't overwritten
+    db.apply_action_unchecked_seq(Action::SetSongRating(10, Some(5)), None);
+    other.remove_song(10);
+    other.save_database(None).unwrap();
+    db.apply_action_unchecked_seq(Action::ReloadDatabase, None);
+    assert!(db.songs.contains_key(&10));
+    assert!(db.times_data_modified.is_some());
+    // changing an artist's aliases has to be saved, too
+    db.save_database(None).unwrap();
+    db.apply_action_unchecked_seq(
+        Action::SetArtistAliases(artist2, vec!["b".to_owned()]),
+        None,
+    );
+    assert!(db.times_data_modified.is_some());
+}
+
+#[test]
+fn test_load_old_dbfile() {
+    use super::GeneralData;
+    use crate::load::{write_len, DB_MAGIC};
+    let (mut db, dir) = test_db("old-dbfile");
+    let artist = db.add_artist_new(Artist {
+        id: 0,
+        name: "artist".to_owned(),
+        aliases: vec![],
+        cover: None,
+        albums: vec![],
+        singles: vec![],
+        general: GeneralData::default(),
+    });
+    for title in ["a", "b"] {
+        let mut song = Song::new(
+            DatabaseLocation::from(format!("{title}.mp3")),
+            None,
+            title.to_owned(),
+            None,
+            artist,
+            vec![],
+            None,
+            1000,
+            60000,
+            GeneralData::default(),
+        );
+        song.general.tags.push("Genre=Rock".to_owned());
+        song.play_count = 3;
+        db.add_song_new(song);
+    }
+    // write the library like older versions of musicdb did
+    for version in [3u32, 4, 5] {
+        let write = |file: &mut std::io::BufWriter<File>| {
+            file.write_all(&DB_MAGIC)?;
+            version.to_bytes(file)?;
+            let _fixed = (version < 5).then(fixed_width_lengths);
+            let mut file = Checksum::new(file);
+            write_len(db.artists.len(), &mut file)?;
+            for (id, artist) in &db.artists {
+                id.to_bytes(&mut file)?;
+                let mut bytes = artist.to_bytes_vec();
+                if version < 4 {
+                    bytes.truncate(bytes.len() - artist.aliases.to_bytes_vec().len());
+                }
+                file.write_all(&bytes)?;
+            }
+            db.albums.to_bytes(&mut file)?;
+            write_len(db.songs.len(), &mut file)?;
+            for (id, song) in &db.songs {
+                id.to_bytes(&mut file)?;
+                let bytes = song.to_bytes_vec();
+                let quality = song.bitrate.to_bytes_vec().len() + song.codec.to_bytes_vec().len();
+                file.write_all(&bytes[..bytes.len() - quality])?;
+            }
+            db.covers.to_bytes(&mut file)?;
+            file.finish()?;
+            Ok(())
+        };
+        write_file_atomically(&dir.join("dbfile"), false, write).unwrap();
+        let loaded = Database::load_database_from_dir(dir.to_path_buf(), dir.to_path_buf())
+            .unwrap_or_else(|e| panic!("version {version}: {e}"));
+        assert_eq!(loaded.artists, db.artists, "version {version}");
+        assert_eq!(loaded.albums, db.albums, "version {version}");
+        assert_eq!(loaded.songs, db.songs, "version {version}");
+    }
+}
+
+#[test]
+fn test_playlists_file() {
+    use crate::load::DB_MAGIC;
+    let (mut db, dir) = test_db("playlists");
+    let playlist: Queue = QueueContent::Folder(QueueFolder::default()).into();
+    db.apply_action_unchecked_seq(Action::SavePlaylist("p".to_owned(), playlist), None);
+    let path = dir.join("playlists");
+    assert!(std::fs::read(&path).unwrap().starts_with(&DB_MAGIC));
+    let load = || {
+        let mut db = Database::new_empty_in_dir(dir.to_path_buf(), dir.to_path_buf());
+        db.load_playlists();
+        db.playlists
+    };
+    assert_eq!(load(), db.playlists);
+    // saved before the header (and the folders' `weight`) was added
+    let mut bytes = db.playlists.to_bytes_vec();
+    bytes.truncate(bytes.len() - ShuffleWeight::Uniform.to_bytes_vec().len());
+    std::fs::write(&path, bytes).unwrap();
+    assert_eq!(load(), db.playlists);
+    // saved by a newer version of musicdb
+    let mut bytes = DB_MAGIC.to_vec();
+    (DB_VERSION + 1).to_bytes(&mut bytes).unwrap();
+    db.playlists.to_bytes(&mut bytes).unwrap();
+    std::fs::write(&path, bytes).unwrap();
+    assert!(load().is_empty());
+}
+
+#[test]
+fn test_playback_state_file() {
+    let (mut db, dir) = test_db("playbackstate");
+    let song = |id: SongId| -> Queue { QueueContent::Song(id).into() };
+    db.apply_action_unchecked_seq(
+        Action::QueueAdd(vec![], vec![song(1), song(2)], Req::none()),
+        None,
+    );
+    db.save_playback_state(Some(1234)).unwrap();
+    let mut restored = Database::new_empty_in_dir(dir.to_path_buf(), dir.to_path_buf());
+    assert_eq!(restored.load_playback_state(), Some(1234));
+    assert_eq!(restored.queue, db.queue);
+    // saved before the header was added, or by another version
+    let path = dir.join("playbackstate");
+    let mut bytes = db.queue.to_bytes_vec();
+    Some(1234u64).to_bytes(&mut bytes).unwrap();
+    std::fs::write(&path, bytes).unwrap();
+    let mut restored = Database::new_empty_in_dir(dir.to_path_buf(), dir.to_path_buf());
+    assert_eq!(restored.load_playback_state(), None);
+    assert_eq!(restored.queue, Database::new_clientside().queue);
+}
+
+#[test]
+fn test_queue_limit() {
+    let song = |id: SongId| -> Queue { QueueContent::Song(id).into() };
+    let songs = |db: &Database| -> Vec<SongId> {
+        (0..)
+            .map_while(|i| db.queue.get_item_at_index(&[i], 0))
+            .filter_map(|v| match v.content() {
+                QueueContent::Song(id) => Some(*id),
+                _ => None,
+            })
+            .collect()
+    };
+    let (mut db, _dir) = test_db("queue-limit");
+    db.queue_limit = Some(3);
+    let denied = Arc::new(Mutex::new(vec![]));
+    let d = Arc::clone(&denied);
+    db.update_endpoints.push((
+        0,
+        UpdateEndpoint::Custom(Box::new(move |cmd| {
+            if let Action::Denied(req) = &cmd.action {
+                d.lock().unwrap().push(*req);
+            }
+        })),
+    ));
+    db.apply_action_unchecked_seq(
+        Action::QueueAdd(vec![], vec![song(1), song(2), song(3)], Req::none()),
+        None,
+    );
+    let req = crate::server::Requester::new().inc();
+    db.apply_action_unchecked_seq(Action::QueueAdd(vec![], vec![song(4)], req), Some(0));
+    assert_eq!(songs(&db), vec![1, 2, 3]);
+    assert_eq!(*denied.lock().unwrap(), vec![req]);
+    // only songs which were already played are removed
+    db.queue_limit_policy = QueueLimitPolicy::DropFront;
+    db.apply_action_unchecked_seq(Action::QueueGoto(vec![1]), None);
+    db.apply_action_unchecked_seq(Action::QueueAdd(vec![], vec![song(4)], req), Some(0));
+    assert_eq!(songs(&db), vec![2, 3, 4]);
+    assert_eq!(db.queue.get_current_song(), Some(&2));
+    db.apply_action_unchecked_seq(Action::QueueInsert(vec![], 2, vec![song(5)], req), Some(0));
+    assert_eq!(songs(&db), vec![2, 3, 4]);
+    assert_eq!(denied.lock().unwrap().len(), 2);
+    // the insert position is adjusted
+    db.apply_action_unchecked_seq(Action::QueueGoto(vec![1]), None);
+    db.apply_action_unchecked_seq(
+        Action::QueueInsert(vec![], 2, vec![song(5)], Req::none()),
+        None,
+    );
+    assert_eq!(songs(&db), vec![3, 5, 4]);
+    assert_eq!(db.queue.get_current_song(), Some(&3));
+    // can't make enough room
+    db.apply_action_unchecked_seq(
+        Action::QueueAdd(vec![], vec![song(6), song(7), song(8)], req),
+        Some(0),
+    );
+    assert_eq!(songs(&db), vec![3, 5, 4]);
+    assert_eq!(denied.lock().unwrap().len(), 3);
+    // replacing elements counts too
+    let two_songs: Queue = QueueContent::Folder(QueueFolder {
+        content: vec![song(6), song(7)],
+        ..Default::default()
+    })
+    .into();
+    db.apply_action_unchecked_seq(Action::QueueUpdate(vec![2], two_songs, req), Some(0));
+    db.apply_action_unchecked_seq(
+        Action::QueueReplace(
+            QueueContent::Folder(QueueFolder {
+                content: vec![song(6), song(7), song(8), song(9)],
+                ..Default::default()
+            })
+            .into(),
+            false,
+        ),
+        Some(0),
+    );
+    assert_eq!(songs(&db), vec![3, 5, 4]);
+    assert_eq!(denied.lock().unwrap().len(), 5);
+}
+
+#[test]
+fn test_queue_conditional() {
+    use super::{
+        filter::{Filter, FilterType},
+        queue::QueueConditional,
+        GeneralData,
+    };
+    let (mut db, _dir) = test_db("conditional");
+    for (title, tags) in [
+        ("a", vec![]),
+        ("b", vec!["Fav"]),
+        ("c", vec![]),
+        ("d", vec!["Fav"]),
+    ] {
+        db.add_song_new(Song::new(
+            DatabaseLocation::from(format!("{title}.mp3")),
+            None,
+            title.to_owned(),
+            None,
+            0,
+            vec![],
+            None,
+            0,
+            0,
+            GeneralData {
+                tags: tags.into_iter().map(|t| t.to_owned()).collect(),
+            },
+        ));
+    }
+    let conditional = |tag: &str| -> Queue {
+        QueueContent::Conditional(QueueConditional {
+            name: tag.to_owned(),
+            filter: Filter {
+                and: true,
+                filters: vec![FilterType::TagEq(tag.to_owned())],
+            },
+            count: 0,
+            random: false,
+        })
+        .into()
+    };
+    let queue: Queue = QueueContent::Folder(QueueFolder {
+        content: vec![
+            QueueContent::Song(0).into(),
+            conditional("Fav"),
+            conditional("nothing has this tag"),
+            QueueContent::Song(2).into(),
+        ],
+        ..Default::default()
+    })
+    .into();
+    let queue = Queue::from_bytes(&mut std::io::Cursor::new(queue.to_bytes_vec())).unwrap();
+    db.apply_action_unchecked_seq(Action::QueueReplace(queue, false), None);
+    assert_eq!(db.queue.get_current_song(), Some(&0));
+    // resolved when it becomes the current element
+    db.apply_action_unchecked_seq(Action::NextSong, None);
+    assert!(matches!(
+        db.queue.get_item_at_index(&[1], 0).map(|v| v.content()),
+        Some(QueueContent::Playlist(p)) if p.songs == vec![1, 3]
+    ));
+    assert_eq!(db.queue.get_current_song(), Some(&1));
+    db.apply_action_unchecked_seq(Action::NextSong, None);
+    assert_eq!(db.queue.get_current_song(), Some(&3));
+    // empty conditionals are skipped
+    db.apply_action_unchecked_seq(Action::NextSong, None);
+    assert_eq!(db.queue.get_current_song(), Some(&2));
+}
+
+#[test]
+fn test_weighted_shuffle() {
+    use super::GeneralData;
+    let (mut db, _dir) = test_db("weighted-shuffle");
+    for title in ["a", "b", "c", "d"] {
+        db.add_song_new(Song::new(
+            DatabaseLocation::from(format!("{title}.mp3")),
+            None,
+            title.to_owned(),
+            None,
+            0,
+            vec![],
+            None,
+            0,
+            0,
+            GeneralData {
+                tags: vec!["Rating=1".to_owned()],
+            },
+        ));
+    }
+    let song = |id: SongId| -> Queue { QueueContent::Song(id).into() };
+    let folder = |content: Vec<Queue>| -> Queue {
+        QueueContent::Folder(QueueFolder {
+            content,
+            ..Default::default()
+        })
+        .into()
+    };
+    let rating = ShuffleWeight::Tag("Rating=".to_owned());
+    // a folder's weight is the average of its songs
+    db.apply_action_unchecked_seq(Action::SetSongRating(3, Some(3)), None);
+    assert_eq!(rating.weight(&folder(vec![song(2), song(3)]), &db), 2.0);
+    assert_eq!(
+        ShuffleWeight::LeastRecentlyPlayed.weight(&song(0), &db),
+        1.0 + ShuffleWeight::NEVER_PLAYED_DAYS
+    );
+    db.apply_action_unchecked_seq(
+        Action::QueueAdd(
+            vec![],
+            vec![song(0), song(1), song(2), folder(vec![song(3)])],
+            Req::none(),
+        ),
+        None,
+    );
+    db.apply_action_unchecked_seq(Action::QueueShuffle(vec![], rating, Some(1)), None);
+    assert_eq!(db.queue.get_current_song(), Some(&0));
+    // the next element is picked with the weights at that time, not at the time of the shuffle
+    db.apply_action_unchecked_seq(Action::SetSongRating(1, Some(0)), None);
+    db.apply_action_unchecked_seq(Action::SetSongRating(2, Some(0)), None);
+    assert_eq!(db.queue.get_next_song(), Some(&3));
+    db.apply_action_unchecked_seq(Action::SetSongRating(3, Some(0)), None);
+    db.apply_action_unchecked_seq(Action::SetSongRating(1, Some(5)), None);
+    assert_eq!(db.queue.get_next_song(), Some(&1));
+    db.apply_action_unchecked_seq(Action::NextSong, None);
+    assert_eq!(db.queue.get_current_song(), Some(&1));
+    db.apply_action_unchecked_seq(Action::SetSongRating(2, Some(1)), None);
+    assert_eq!(db.queue.get_next_song(), Some(&2));
+}
+
+#[test]
+fn test_find_similar_songs() {
+    use super::GeneralData;
+    let (mut db, _dir) = test_db("similar");
+    for name in ["Artist", "Other Artist"] {
+        db.add_artist_new(Artist {
+            id: 0,
+            name: name.to_owned(),
+            aliases: vec![],
+            cover: None,
+            albums: vec![],
+            singles: vec![],
+            general: GeneralData::default(),
+        });
+    }
+    for (title, artist, duration_millis) in [
+        ("Song", 0, 200_000),
+        ("song!", 0, 201_000),
+        ("Song", 1, 200_000),
+        ("Song", 0, 260_000),
+        ("Another Song", 0, 200_000),
+        ("  S O N G", 0, 202_500),
+    ] {
+        db.add_song_new(Song::new(
+            DatabaseLocation::from(format!("{title}.mp3")),
+            None,
+            title.to_owned(),
+            None,
+            artist,
+            vec![],
+            None,
+            0,
+            duration_millis,
+            GeneralData::default(),
+        ));
+    }
+    assert_eq!(db.find_similar_songs(2000), vec![vec![0, 1, 5]]);
+    assert_eq!(db.find_similar_songs(1000), vec![vec![0, 1]]);
+    assert!(db.find_similar_songs(0).is_empty());
+}
+
+#[test]
+fn test_playback_position_keeps_seq() {
+    let (mut db, _dir) = test_db("position");
+    db.apply_action_unchecked_seq(Action::SetVolume(0.5), None);
+    let seq = db.seq.seq();
+    db.apply_action_unchecked_seq(Action::PlaybackPosition(1000), None);
+    assert_eq!(db.seq.seq(), seq);
+    // a command sent before the position update is still accepted
+    db.apply_command(Action::SetVolume(0.25).cmd(seq), None);
+    assert_eq!(db.volume, 0.25);
+}
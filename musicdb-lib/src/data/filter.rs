@@ -0,0 +1,160 @@
+use crate::load::ToFromBytes;
+
+use super::GeneralData;
+
+/// Filters artists, albums or songs by their tags.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Filter {
+    /// if `true`, all filters have to pass, otherwise just one
+    pub and: bool,
+    pub filters: Vec<FilterType>,
+}
+#[derive(Clone, Debug, PartialEq)]
+pub enum FilterType {
+    Nested(Filter),
+    Not(Filter),
+    TagEq(String),
+    TagStartsWith(String),
+    /// true if the tag is '<String><Integer>' and Integer is between min and max (both inclusive)
+    /// note: <String> usually ends with '='.
+    TagWithValueInt(String, i32, i32),
+}
+impl Filter {
+    pub fn passes(&self, gd: &GeneralData) -> bool {
+        if self.filters.is_empty() {
+            return true;
+        }
+        let mut iter = self.filters.iter().map(|v| v.passes(gd));
+        if self.and {
+            iter.all(|v| v)
+        } else {
+            iter.any(|v| v)
+        }
+    }
+    pub fn get_mut(&mut self, path: &[usize]) -> Option<Result<&mut FilterType, &mut Self>> {
+        if let Some(i) = path.first() {
+            let p = &path[1..];
+            if let Some(f) = self.filters.get_mut(*i) {
+                f.get_mut(p)
+            } else {
+                None
+            }
+        } else {
+            Some(Err(self))
+        }
+    }
+}
+impl FilterType {
+    pub fn passes(&self, gd: &GeneralData) -> bool {
+        match self {
+            Self::Nested(f) => f.passes(gd),
+            Self::Not(f) => !f.passes(gd),
+            Self::TagEq(v) => gd.tags.iter().any(|t| t == v),
+            Self::TagStartsWith(v) => gd.tags.iter().any(|t| t.starts_with(v)),
+            Self::TagWithValueInt(v, min, max) => gd.tags.iter().any(|t| {
+                if t.starts_with(v) {
+                    if let Ok(val) = t[v.len()..].parse() {
+                        *min <= val && val <= *max
+                    } else {
+                        false
+                    }
+                } else {
+                    false
+                }
+            }),
+        }
+    }
+    pub fn get_mut(&mut self, path: &[usize]) -> Option<Result<&mut Self, &mut Filter>> {
+        if path.is_empty() {
+            Some(Ok(self))
+        } else {
+            if let Some(f) = self.inner_filter() {
+                f.get_mut(path)
+            } else {
+                None
+            }
+        }
+    }
+    pub fn inner_filter(&mut self) -> Option<&mut Filter> {
+        match self {
+            Self::Nested(f) | Self::Not(f) => Some(f),
+            Self::TagEq(_) | Self::TagStartsWith(_) | Self::TagWithValueInt(..) => None,
+        }
+    }
+}
+
+impl ToFromBytes for Filter {
+    fn to_bytes<T>(&self, s: &mut T) -> Result<(), std::io::Error>
+    where
+        T: std::io::Write,
+    {
+        self.and.to_bytes(s)?;
+        self.filters.to_bytes(s)?;
+        Ok(())
+    }
+    fn from_bytes<T>(s: &mut T) -> Result<Self, std::io::Error>
+    where
+        T: std::io::Read,
+    {
+        Ok(Self {
+            and: ToFromBytes::from_bytes(s)?,
+            filters: ToFromBytes::from_bytes(s)?,
+        })
+    }
+}
+impl ToFromBytes for FilterType {
+    fn to_bytes<T>(&self, s: &mut T) -> Result<(), std::io::Error>
+    where
+        T: std::io::Write,
+    {
+        match self {
+            Self::Nested(f) => {
+                s.write_all(&[0])?;
+                f.to_bytes(s)?;
+            }
+            Self::Not(f) => {
+                s.write_all(&[1])?;
+                f.to_bytes(s)?;
+            }
+            Self::TagEq(v) => {
+                s.write_all(&[2])?;
+                v.to_bytes(s)?;
+            }
+            Self::TagStartsWith(v) => {
+                s.write_all(&[3])?;
+                v.to_bytes(s)?;
+            }
+            Self::TagWithValueInt(v, min, max) => {
+                s.write_all(&[4])?;
+                v.to_bytes(s)?;
+                min.to_bytes(s)?;
+                max.to_bytes(s)?;
+            }
+        }
+        Ok(())
+    }
+    fn from_bytes<T>(s: &mut T) -> Result<Self, std::io::Error>
+    where
+        T: std::io::Read,
+    {
+        let mut byte = [0];
+        s.read_exact(&mut byte)?;
+        Ok(match byte[0] {
+            0 => Self::Nested(ToFromBytes::from_bytes(s)?),
+            1 => Self::Not(ToFromBytes::from_bytes(s)?),
+            2 => Self::TagEq(ToFromBytes::from_bytes(s)?),
+            3 => Self::TagStartsWith(ToFromBytes::from_bytes(s)?),
+            4 => Self::TagWithValueInt(
+                ToFromBytes::from_bytes(s)?,
+                ToFromBytes::from_bytes(s)?,
+                ToFromBytes::from_bytes(s)?,
+            ),
+            b => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("invalid filter type {b}"),
+                ))
+            }
+        })
+    }
+}
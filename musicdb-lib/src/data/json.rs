@@ -0,0 +1,278 @@
+use std::{
+    collections::HashMap,
+    io::Write,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use serde_json::{json, Map, Value};
+
+use super::{
+    album::Album,
+    artist::Artist,
+    database::{Cover, Database},
+    song::Song,
+    DatabaseLocation, GeneralData,
+};
+
+impl Database {
+    /// The library (artists, albums, songs and covers, but not the queue) as json,
+    /// for backups which humans can read, or for other tools.
+    /// Everything is sorted by id, and ids are the same as in the database,
+    /// so the relationships between artists, albums, songs and covers are kept.
+    /// This is only for exporting, there is no way to load it again.
+    pub fn export_json(&self) -> Value {
+        json!({
+            "artists": sorted_by_id(self.artists()).map(|(_, artist)| json!({
+                "id": artist.id,
+                "name": artist.name,
+                "aliases": artist.aliases,
+                "cover": artist.cover,
+                "albums": artist.albums,
+                "singles": artist.singles,
+                "tags": artist.general.tags,
+            })).collect::<Vec<_>>(),
+            "albums": sorted_by_id(self.albums()).map(|(_, album)| json!({
+                "id": album.id,
+                "name": album.name,
+                "artist": album.artist,
+                "cover": album.cover,
+                "songs": album.songs,
+                "tags": album.general.tags,
+            })).collect::<Vec<_>>(),
+            "songs": sorted_by_id(self.songs()).map(|(_, song)| json!({
+                "id": song.id,
+                "title": song.title,
+                "path": song.location.rel_path.to_string_lossy(),
+                "album": song.album,
+                "artist": song.artist,
+                "more_artists": song.more_artists,
+                "cover": song.cover,
+                "duration_millis": song.duration_millis,
+                "file_size": song.file_size,
+                "file_last_modified": song.file_last_modified_unix_timestamp,
+                "bitrate": song.bitrate,
+                "codec": song.codec,
+                "play_count": song.play_count,
+                "last_played": song.last_played
+                    .and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok())
+                    .map(|t| t.as_secs()),
+                "tags": song.general.tags,
+            })).collect::<Vec<_>>(),
+            "covers": sorted_by_id(self.covers()).map(|(id, cover)| json!({
+                "id": id,
+                "path": cover.location.rel_path.to_string_lossy(),
+            })).collect::<Vec<_>>(),
+        })
+    }
+    /// writes `export_json` to `w`, with indentation
+    pub fn export_json_to(&self, mut w: impl Write) -> serde_json::Result<()> {
+        serde_json::to_writer_pretty(&mut w, &self.export_json())?;
+        writeln!(w).map_err(serde_json::Error::io)
+    }
+    /// Adds everything from json in `export_json`'s format to the database.
+    /// The ids in the json are only used to find out what references what,
+    /// everything gets a new id, so this can also be used to merge two libraries.
+    /// Entries which are missing required fields, or which reference an artist that doesn't exist, are skipped,
+    /// references to albums or covers which don't exist are removed.
+    /// Returns a list of these problems, or an error if the json doesn't look like an export at all.
+    pub fn import_json(&mut self, json: &Value) -> Result<Vec<String>, String> {
+        let mut problems = vec![];
+        let list = |key: &str| match json.get(key) {
+            None => Ok(vec![]),
+            Some(Value::Array(list)) => Ok(list
+                .iter()
+                .filter_map(|v| v.as_object())
+                .collect::<Vec<_>>()),
+            Some(_) => Err(format!("{key} is not a list")),
+        };
+        let (covers, mut artists, mut albums, mut songs) = (
+            list("covers")?,
+            list("artists")?,
+            list("albums")?,
+            list("songs")?,
+        );
+        // keep the order of albums in artists and songs in albums/artists
+        let position = |entries: &[&Map<String, Value>], key: &str| {
+            entries
+                .iter()
+                .flat_map(|e| ids(e, key).into_iter().enumerate())
+                .map(|(i, id)| (id, i))
+                .collect::<HashMap<_, _>>()
+        };
+        let album_pos = position(&artists, "albums");
+        let mut song_pos = position(&albums, "songs");
+        song_pos.extend(position(&artists, "singles"));
+        let sort_key = |pos: &HashMap<u64, usize>, e: &Map<String, Value>| {
+            let id = e.get("id").and_then(|v| v.as_u64());
+            (id.and_then(|id| pos.get(&id).copied()), id)
+        };
+        artists.sort_by_key(|e| e.get("id").and_then(|v| v.as_u64()));
+        albums.sort_by_key(|e| sort_key(&album_pos, e));
+        songs.sort_by_key(|e| sort_key(&song_pos, e));
+
+        let mut new_covers = HashMap::new();
+        for e in covers {
+            match (id(e), string(e, "path")) {
+                (Some(id), Some(path)) => {
+                    let new_id = self.add_cover_new(Cover {
+                        location: DatabaseLocation {
+                            rel_path: PathBuf::from(path),
+                        },
+                        data: Arc::new(Mutex::new((false, None))),
+                    });
+                    new_covers.insert(id, new_id);
+                }
+                _ => problems.push(format!("cover {e:?} has no id or path, skipping")),
+            }
+        }
+        let cover = |e: &Map<String, Value>, what: &str, problems: &mut Vec<String>| {
+            let id = e.get("cover")?.as_u64()?;
+            let new_id = new_covers.get(&id).copied();
+            if new_id.is_none() {
+                problems.push(format!("{what} has cover {id}, which doesn't exist"));
+            }
+            new_id
+        };
+        let mut new_artists = HashMap::new();
+        for e in artists {
+            let (Some(id), Some(name)) = (id(e), string(e, "name")) else {
+                problems.push(format!("artist {e:?} has no id or name, skipping"));
+                continue;
+            };
+            let what = format!("artist {id} ({name})");
+            let new_id = self.add_artist_new(Artist {
+                id: 0,
+                name,
+                aliases: strings(e, "aliases"),
+                cover: cover(e, &what, &mut problems),
+                albums: vec![],
+                singles: vec![],
+                general: general(e),
+            });
+            new_artists.insert(id, new_id);
+        }
+        let mut new_albums = HashMap::new();
+        for e in albums {
+            let (Some(id), Some(name)) = (id(e), string(e, "name")) else {
+                problems.push(format!("album {e:?} has no id or name, skipping"));
+                continue;
+            };
+            let what = format!("album {id} ({name})");
+            let Some(&artist) = e
+                .get("artist")
+                .and_then(|v| v.as_u64())
+                .and_then(|a| new_artists.get(&a))
+            else {
+                problems.push(format!("{what} has no valid artist, skipping"));
+                continue;
+            };
+            let new_id = self.add_album_new(Album {
+                id: 0,
+                name,
+                artist,
+                cover: cover(e, &what, &mut problems),
+                songs: vec![],
+                general: general(e),
+            });
+            new_albums.insert(id, new_id);
+        }
+        for e in songs {
+            let (Some(id), Some(title), Some(path)) =
+                (id(e), string(e, "title"), string(e, "path"))
+            else {
+                problems.push(format!("song {e:?} has no id, title or path, skipping"));
+                continue;
+            };
+            let what = format!("song {id} ({title})");
+            let Some(&artist) = e
+                .get("artist")
+                .and_then(|v| v.as_u64())
+                .and_then(|a| new_artists.get(&a))
+            else {
+                problems.push(format!("{what} has no valid artist, skipping"));
+                continue;
+            };
+            let album = e.get("album").and_then(|v| v.as_u64()).and_then(|a| {
+                let new_id = new_albums.get(&a).copied();
+                if new_id.is_none() {
+                    problems.push(format!("{what} is on album {a}, which doesn't exist"));
+                }
+                new_id
+            });
+            let more_artists = ids(e, "more_artists")
+                .into_iter()
+                .filter_map(|a| {
+                    let new_id = new_artists.get(&a).copied();
+                    if new_id.is_none() {
+                        problems.push(format!("{what} has artist {a}, which doesn't exist"));
+                    }
+                    new_id
+                })
+                .collect();
+            let mut song = Song::new(
+                DatabaseLocation {
+                    rel_path: PathBuf::from(path),
+                },
+                e.get("file_last_modified").and_then(|v| v.as_u64()),
+                title,
+                album,
+                artist,
+                more_artists,
+                cover(e, &what, &mut problems),
+                e.get("file_size").and_then(|v| v.as_u64()).unwrap_or(0),
+                e.get("duration_millis")
+                    .and_then(|v| v.as_u64())
+                    .unwrap_or(0),
+                general(e),
+            );
+            song.play_count = e.get("play_count").and_then(|v| v.as_u64()).unwrap_or(0);
+            song.last_played = e
+                .get("last_played")
+                .and_then(|v| v.as_u64())
+                .map(|secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs));
+            song.bitrate = e
+                .get("bitrate")
+                .and_then(|v| v.as_u64())
+                .and_then(|v| v.try_into().ok());
+            song.codec = string(e, "codec");
+            self.add_song_new(song);
+        }
+        Ok(problems)
+    }
+}
+
+fn id(e: &Map<String, Value>) -> Option<u64> {
+    e.get("id")?.as_u64()
+}
+fn ids(e: &Map<String, Value>, key: &str) -> Vec<u64> {
+    e.get(key)
+        .and_then(|v| v.as_array())
+        .map(|v| v.iter().filter_map(|v| v.as_u64()).collect())
+        .unwrap_or_default()
+}
+fn string(e: &Map<String, Value>, key: &str) -> Option<String> {
+    Some(e.get(key)?.as_str()?.to_owned())
+}
+fn strings(e: &Map<String, Value>, key: &str) -> Vec<String> {
+    e.get(key)
+        .and_then(|v| v.as_array())
+        .map(|v| {
+            v.iter()
+                .filter_map(|v| Some(v.as_str()?.to_owned()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+fn general(e: &Map<String, Value>) -> GeneralData {
+    GeneralData {
+        tags: strings(e, "tags"),
+    }
+}
+
+fn sorted_by_id<T>(map: &HashMap<u64, T>) -> impl Iterator<Item = (&u64, &T)> {
+    let mut v = map.iter().collect::<Vec<_>>();
+    v.sort_unstable_by_key(|(id, _)| **id);
+    v.into_iter()
+}
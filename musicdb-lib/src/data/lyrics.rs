@@ -0,0 +1,121 @@
+use std::path::PathBuf;
+
+use super::song::{Song, LYRICS_TAG};
+
+/// A song's lyrics, either plain text (no timestamps) or parsed from the LRC format,
+/// where lines start with one or more `[mm:ss.xx]` timestamps.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Lyrics {
+    /// the lines and, for timed lyrics, the time (in milliseconds) at which they start.
+    /// timed lines are sorted by their time.
+    pub lines: Vec<(Option<u64>, String)>,
+}
+
+impl Song {
+    /// the lyrics from the song's `Lyrics=` tag, if it has one
+    pub fn lyrics_tag(&self) -> Option<&str> {
+        self.general
+            .tags
+            .iter()
+            .find_map(|tag| tag.strip_prefix(LYRICS_TAG))
+    }
+    /// where a `.lrc` file for this song would be, relative to the library directory.
+    /// clients can load it using `get::Client::song_file_by_path`.
+    pub fn lyrics_sidecar_path(&self) -> PathBuf {
+        self.location.rel_path.with_extension("lrc")
+    }
+}
+
+impl Lyrics {
+    /// Parses LRC or plain text lyrics. If no line has a timestamp, all lines are kept as they are.
+    /// Metadata tags like `[ar:Artist]` are ignored, except for `[offset:+/-ms]`.
+    pub fn parse(text: &str) -> Self {
+        let text = text.trim_start_matches('\u{feff}');
+        let mut offset = 0i64;
+        let mut timed = vec![];
+        for line in text.lines() {
+            let mut rest = line.trim();
+            let mut times = vec![];
+            while let Some((tag, after)) = rest.strip_prefix('[').and_then(|v| v.split_once(']')) {
+                if let Some(time) = parse_timestamp(tag) {
+                    times.push(time);
+                } else if let Some(v) = tag.strip_prefix("offset:") {
+                    offset = v.trim().parse().unwrap_or(0);
+                }
+                rest = after;
+            }
+            for time in times {
+                timed.push((time, rest.trim().to_owned()));
+            }
+        }
+        if timed.is_empty() {
+            return Self {
+                lines: text
+                    .lines()
+                    .map(|v| (None, v.trim_end().to_owned()))
+                    .collect(),
+            };
+        }
+        // a positive offset means the lyrics should appear earlier
+        timed.sort_by_key(|(time, _)| *time);
+        Self {
+            lines: timed
+                .into_iter()
+                .map(|(time, line)| (Some((time as i64 - offset).max(0) as u64), line))
+                .collect(),
+        }
+    }
+    /// true if the lines have timestamps
+    pub fn is_timed(&self) -> bool {
+        self.lines.iter().any(|(time, _)| time.is_some())
+    }
+    /// the index of the line which is being sung at `position` (in milliseconds),
+    /// or `None` if the lyrics aren't timed or the first line hasn't started yet.
+    pub fn current_line(&self, position: u64) -> Option<usize> {
+        self.lines
+            .iter()
+            .rposition(|(time, _)| time.is_some_and(|time| time <= position))
+    }
+}
+
+/// `mm:ss`, `mm:ss.xx` or `mm:ss.xxx` to milliseconds
+fn parse_timestamp(tag: &str) -> Option<u64> {
+    let (min, sec) = tag.split_once(':')?;
+    let min = min.trim().parse::<u64>().ok()?;
+    let (sec, frac) = sec.split_once(['.', ':']).unwrap_or((sec, ""));
+    let sec = sec.trim().parse::<u64>().ok()?;
+    let millis = if frac.is_empty() {
+        0
+    } else {
+        if !frac.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        // `.5` is 500ms, `.05` is 50ms, `.005` is 5ms
+        let frac = &frac[..frac.len().min(3)];
+        frac.parse::<u64>().ok()? * 10u64.pow(3 - frac.len() as u32)
+    };
+    Some(min * 60_000 + sec * 1000 + millis)
+}
+
+#[test]
+fn test_parse_lyrics() {
+    let lyrics = Lyrics::parse(
+        "[ar:Someone]\n[offset:+100]\n[00:01.50]first\n[00:10.00][01:02.3]chorus\n\n[00:05]second",
+    );
+    assert_eq!(
+        lyrics.lines,
+        vec![
+            (Some(1400), "first".to_owned()),
+            (Some(4900), "second".to_owned()),
+            (Some(9900), "chorus".to_owned()),
+            (Some(62200), "chorus".to_owned()),
+        ]
+    );
+    assert_eq!(lyrics.current_line(0), None);
+    assert_eq!(lyrics.current_line(5000), Some(1));
+    assert_eq!(lyrics.current_line(100_000), Some(3));
+    let lyrics = Lyrics::parse("no\ntimestamps [here]\n");
+    assert!(!lyrics.is_timed());
+    assert_eq!(lyrics.lines.len(), 2);
+    assert_eq!(lyrics.current_line(1000), None);
+}
@@ -0,0 +1,154 @@
+use std::{
+    collections::HashMap,
+    path::{Component, Path, PathBuf},
+};
+
+use super::{
+    database::Database,
+    queue::{Queue, QueueContent},
+    SongId,
+};
+
+/// Parses an M3U or M3U8 playlist and finds the songs in the database.
+/// Entries can be relative to `playlist_dir` (the directory containing the playlist file),
+/// relative to the library directory, or absolute paths inside the library directory.
+/// Returns the songs (to be added using `Action::QueueAdd`) and all entries which couldn't be found.
+pub fn import_m3u(
+    content: &str,
+    playlist_dir: Option<&Path>,
+    db: &Database,
+) -> (Vec<Queue>, Vec<String>) {
+    let songs_by_path = db
+        .songs()
+        .values()
+        .map(|song| (normalize(&song.location.rel_path), song.id))
+        .collect::<HashMap<PathBuf, SongId>>();
+    let mut queue = vec![];
+    let mut unresolved = vec![];
+    for line in content.lines() {
+        // strip a BOM, which some programs write at the start of m3u8 files
+        let entry = line.trim_start_matches('\u{feff}').trim();
+        // `#EXTM3U`, `#EXTINF:...` and other comments
+        if entry.is_empty() || entry.starts_with('#') {
+            continue;
+        }
+        if let Some(id) = resolve(entry, playlist_dir, db, &songs_by_path) {
+            queue.push(QueueContent::Song(id).into());
+        } else {
+            unresolved.push(entry.to_owned());
+        }
+    }
+    (queue, unresolved)
+}
+
+/// Writes the songs in the queues to an extended M3U playlist, in the order in which they would be played
+/// (loops are only included once, disabled elements and unresolved conditionals are skipped).
+/// Paths are absolute if `db.lib_directory` is known, otherwise relative to the library directory.
+/// Returns the playlist and the songs which aren't in the database (and aren't in the playlist).
+pub fn to_m3u(queues: &[Queue], db: &Database) -> (String, Vec<SongId>) {
+    fn songs(queue: &Queue, out: &mut Vec<SongId>) {
+        if !queue.enabled() {
+            return;
+        }
+        match queue.content() {
+            QueueContent::Song(id) => out.push(*id),
+            QueueContent::Folder(folder) => folder.iter().for_each(|q| songs(q, out)),
+            QueueContent::Loop(_, _, inner) => songs(inner, out),
+            QueueContent::Playlist(playlist) => out.extend(&playlist.songs),
+            QueueContent::Conditional(_) => {}
+        }
+    }
+    let mut ids = vec![];
+    for queue in queues {
+        songs(queue, &mut ids);
+    }
+    let mut out = String::from("#EXTM3U\n");
+    let mut unresolved = vec![];
+    for id in ids {
+        let Some(song) = db.get_song(&id) else {
+            unresolved.push(id);
+            continue;
+        };
+        let title = match db.artists().get(&song.artist) {
+            Some(artist) => format!("{} - {}", artist.name, song.title),
+            None => song.title.clone(),
+        };
+        out.push_str(&format!(
+            "#EXTINF:{},{}\n{}\n",
+            song.duration_millis / 1000,
+            title.replace('\n', " "),
+            db.get_path(&song.location).to_string_lossy()
+        ));
+    }
+    (out, unresolved)
+}
+
+fn resolve(
+    entry: &str,
+    playlist_dir: Option<&Path>,
+    db: &Database,
+    songs_by_path: &HashMap<PathBuf, SongId>,
+) -> Option<SongId> {
+    let entry = if let Some(url) = entry.strip_prefix("file://") {
+        percent_decode(url)
+    } else {
+        entry.to_owned()
+    };
+    // playlists created on windows
+    let path = PathBuf::from(entry.replace('\\', "/"));
+    let mut candidates = vec![];
+    if path.is_absolute() {
+        candidates.push(path);
+    } else {
+        if let Some(dir) = playlist_dir {
+            candidates.push(dir.join(&path));
+        }
+        candidates.push(path);
+    }
+    candidates.into_iter().find_map(|path| {
+        let path = normalize(&path);
+        let lib_dir = normalize(&db.lib_directory);
+        let rel_path = if !lib_dir.as_os_str().is_empty() {
+            path.strip_prefix(&lib_dir).unwrap_or(&path)
+        } else {
+            &path
+        };
+        songs_by_path.get(rel_path).copied()
+    })
+}
+
+/// removes `.` and resolves `..` without accessing the filesystem
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for c in path.components() {
+        match c {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            if let Some(v) = s
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                out.push(v);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
@@ -0,0 +1,160 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use colorize::AnsiColor;
+use id3::TagLike;
+
+use super::{database::Database, AlbumId, ArtistId, CoverId, GeneralData};
+
+/// the artist used for songs without an artist tag, like in filldb
+pub const UNKNOWN_ARTIST: &str = "<unknown>";
+/// the tags `SongMetadata` creates from the file's tags.
+/// when a file is read again, these should be replaced, all other tags were added by users.
+pub const FILE_TAGS: [&str; 4] = ["TrackNr=", "DiscNr=", "Year=", "Genre="];
+
+/// the information about a song file which filldb would use
+pub struct SongMetadata {
+    pub rel_path: PathBuf,
+    pub disc: Option<u32>,
+    pub track: Option<u32>,
+    pub title: String,
+    pub artist: String,
+    pub album: Option<String>,
+    pub last_modified: Option<u64>,
+    pub file_size: u64,
+    pub duration_millis: u64,
+    pub general: GeneralData,
+}
+impl SongMetadata {
+    /// reads the id3 tag of the file `lib_dir/rel_path`
+    pub fn read(
+        lib_dir: &Path,
+        rel_path: PathBuf,
+        skip_duration: bool,
+    ) -> Result<Self, id3::Error> {
+        let abs_path = lib_dir.join(&rel_path);
+        let tag = id3::Tag::read_from_path(&abs_path)?;
+        Ok(Self::from_tag(rel_path, &abs_path, &tag, skip_duration))
+    }
+    pub fn from_tag(
+        rel_path: PathBuf,
+        abs_path: &Path,
+        tag: &id3::Tag,
+        skip_duration: bool,
+    ) -> Self {
+        let mut general = GeneralData::default();
+        match (tag.track(), tag.total_tracks()) {
+            (None, None) => {}
+            (Some(n), Some(t)) => general.tags.push(format!("TrackNr={n}/{t}")),
+            (Some(n), None) => general.tags.push(format!("TrackNr={n}")),
+            (None, Some(t)) => general.tags.push(format!("TrackNr=?/{t}")),
+        }
+        match (tag.disc(), tag.total_discs()) {
+            (None, None) => {}
+            (Some(n), Some(t)) => general.tags.push(format!("DiscNr={n}/{t}")),
+            (Some(n), None) => general.tags.push(format!("DiscNr={n}")),
+            (None, Some(t)) => general.tags.push(format!("DiscNr=?/{t}")),
+        }
+        if let Some(year) = tag.year() {
+            general.tags.push(format!("Year={year}"));
+        }
+        if let Some(genre) = tag.genre_parsed() {
+            general.tags.push(format!("Genre={genre}"));
+        }
+        let non_empty = |v: &&str| !v.trim().is_empty();
+        let artist = tag
+            .album_artist()
+            .filter(non_empty)
+            .or_else(|| tag.artist().filter(non_empty));
+        let metadata = abs_path.metadata().ok();
+        Self {
+            disc: tag.disc(),
+            track: tag.track(),
+            title: tag.title().filter(non_empty).map_or_else(
+                || {
+                    abs_path
+                        .file_stem()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                        .into_owned()
+                },
+                |title| title.to_owned(),
+            ),
+            // like filldb, songs without an artist don't get an album
+            album: tag
+                .album()
+                .filter(non_empty)
+                .filter(|_| artist.is_some())
+                .map(|album| album.to_owned()),
+            artist: artist.unwrap_or(UNKNOWN_ARTIST).to_owned(),
+            last_modified: metadata
+                .as_ref()
+                .and_then(|v| v.modified().ok())
+                .and_then(|v| v.duration_since(SystemTime::UNIX_EPOCH).ok())
+                .map(|v| v.as_secs()),
+            file_size: metadata.map_or(0, |v| v.len()),
+            duration_millis: if let Some(dur) = tag.duration() {
+                dur as u64
+            } else if skip_duration {
+                eprintln!(
+                    "[{}] Duration of song {abs_path:?} not found in tags, using 0 instead!",
+                    "WARN".yellow()
+                );
+                0
+            } else {
+                match mp3_duration::from_path(abs_path) {
+                    Ok(dur) => dur.as_millis().min(u64::MAX as _) as u64,
+                    Err(e) => {
+                        eprintln!("[{}] Duration of song {abs_path:?} not found in tags and can't be determined from the file contents either ({e}). Using duration 0 instead.", "WARN".yellow());
+                        0
+                    }
+                }
+            },
+            rel_path,
+            general,
+        }
+    }
+}
+
+impl Database {
+    /// the artist with this name, or, if there is none, the artist with this alias (see `Artist::has_name`)
+    pub fn find_artist_by_name(&self, name: &str) -> Option<ArtistId> {
+        self.artists()
+            .values()
+            .find(|artist| artist.name == name)
+            .or_else(|| self.artists().values().find(|artist| artist.has_name(name)))
+            .map(|artist| artist.id)
+    }
+    pub fn find_album_by_name(&self, artist: ArtistId, name: &str) -> Option<AlbumId> {
+        self.albums()
+            .values()
+            .find(|album| album.artist == artist && album.name == name)
+            .map(|album| album.id)
+    }
+    pub fn find_cover_by_path(&self, rel_path: &Path) -> Option<CoverId> {
+        self.covers()
+            .iter()
+            .find(|(_, cover)| cover.location.rel_path == rel_path)
+            .map(|(id, _)| *id)
+    }
+}
+
+/// the largest image in the directory, like filldb's `get_cover`
+pub fn find_cover(lib_dir: &Path, rel_dir: &Path) -> Option<PathBuf> {
+    fs::read_dir(lib_dir.join(rel_dir))
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            entry
+                .path()
+                .extension()
+                .and_then(|v| v.to_str())
+                .is_some_and(|v| matches!(v.to_lowercase().as_str(), "png" | "jpg" | "jpeg"))
+        })
+        .filter_map(|entry| Some((entry.metadata().ok()?.len(), entry.file_name())))
+        .max()
+        .map(|(_, name)| rel_dir.join(name))
+}
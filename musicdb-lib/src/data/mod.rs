@@ -3,14 +3,25 @@ use std::{
     path::PathBuf,
 };
 
-use crate::load::ToFromBytes;
+use crate::load::{ErrorContext, ToFromBytes};
 
 pub mod album;
 pub mod artist;
 pub mod cache_manager;
 pub mod database;
+pub mod filter;
+#[cfg(feature = "json")]
+pub mod json;
+pub mod lyrics;
+pub mod m3u;
+#[cfg(feature = "metadata")]
+pub mod metadata;
 pub mod queue;
+pub mod search_index;
 pub mod song;
+pub mod statistics;
+#[cfg(feature = "thumbnails")]
+pub mod thumbnail;
 
 pub type SongId = u64;
 pub type AlbumId = u64;
@@ -41,7 +52,7 @@ impl ToFromBytes for DatabaseLocation {
         T: Read,
     {
         Ok(Self {
-            rel_path: ToFromBytes::from_bytes(s)?,
+            rel_path: ToFromBytes::from_bytes(s).context("rel_path")?,
         })
     }
 }
@@ -70,7 +81,7 @@ impl ToFromBytes for GeneralData {
         T: Read,
     {
         Ok(Self {
-            tags: ToFromBytes::from_bytes(s)?,
+            tags: ToFromBytes::from_bytes(s).context("tags")?,
         })
     }
 }
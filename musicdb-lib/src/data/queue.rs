@@ -1,8 +1,11 @@
-use std::ops::AddAssign;
+use std::{collections::HashMap, ops::AddAssign};
 
-use crate::load::ToFromBytes;
+use colorize::AnsiColor;
+use rand::{distributions::Distribution, seq::SliceRandom, thread_rng, Rng};
 
-use super::{database::Database, SongId};
+use crate::load::{reading_folders_without_weight, ToFromBytes};
+
+use super::{database::Database, filter::Filter, SongId};
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Queue {
@@ -14,6 +17,8 @@ pub enum QueueContent {
     Song(SongId),
     Folder(QueueFolder),
     Loop(usize, usize, Box<Queue>),
+    Playlist(QueuePlaylist),
+    Conditional(QueueConditional),
 }
 #[derive(Clone, Debug, Default, PartialEq)]
 pub struct QueueFolder {
@@ -21,6 +26,31 @@ pub struct QueueFolder {
     pub content: Vec<Queue>,
     pub name: String,
     pub order: Option<Vec<usize>>,
+    /// the seed that was used to generate `order`, if it was shuffled
+    pub seed: Option<u64>,
+    /// while shuffled, the element which is played after the current one is picked using this weight
+    /// (see `pick_next_weighted`), so the weights are the ones at the time it is picked.
+    pub weight: ShuffleWeight,
+}
+/// like a folder which can only contain songs, but much smaller (in memory and when sent to clients).
+/// `index` is the index of the current song in `songs`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct QueuePlaylist {
+    pub index: usize,
+    pub name: String,
+    pub songs: Vec<SongId>,
+}
+/// a placeholder which the server replaces with a playlist of matching songs once it becomes the current element (see `resolve`).
+/// until then, it behaves like a song which can't be played.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct QueueConditional {
+    pub name: String,
+    /// songs (not albums or artists) whose tags pass this filter can be chosen
+    pub filter: Filter,
+    /// at most this many songs are chosen, `0` means all matching songs
+    pub count: usize,
+    /// if true, the songs are chosen and ordered randomly, otherwise the first songs (by id) are used
+    pub random: bool,
 }
 
 impl Queue {
@@ -36,9 +66,17 @@ impl Queue {
 
     pub fn add_to_end(&mut self, v: Vec<Self>, skip_init: bool) -> Option<usize> {
         match &mut self.content {
-            QueueContent::Song(_) => None,
+            QueueContent::Song(_) | QueueContent::Conditional(_) => None,
             QueueContent::Folder(folder) => folder.add_to_end(v, skip_init),
             QueueContent::Loop(..) => None,
+            QueueContent::Playlist(playlist) => {
+                let len = playlist.songs.len();
+                if playlist.insert(v, len) {
+                    Some(len)
+                } else {
+                    None
+                }
+            }
         }
     }
     pub fn is_current(&self, index: &[usize]) -> bool {
@@ -46,7 +84,7 @@ impl Queue {
             return true;
         }
         match self.content() {
-            QueueContent::Song(_) => true,
+            QueueContent::Song(_) | QueueContent::Conditional(_) => true,
             QueueContent::Folder(folder) => {
                 folder.index == index[0]
                     && folder
@@ -54,13 +92,15 @@ impl Queue {
                         .is_some_and(|c| c.is_current(&index[1..]))
             }
             QueueContent::Loop(_, _, inner) => index[0] == 0 && inner.is_current(&index[1..]),
+            QueueContent::Playlist(playlist) => playlist.index == index[0] && index.len() == 1,
         }
     }
     pub fn insert(&mut self, v: Vec<Self>, index: usize, skip_init: bool) -> bool {
         match &mut self.content {
-            QueueContent::Song(_) => false,
+            QueueContent::Song(_) | QueueContent::Conditional(_) => false,
             QueueContent::Folder(folder) => folder.insert(v, index, skip_init),
             QueueContent::Loop(..) => false,
+            QueueContent::Playlist(playlist) => playlist.insert(v, index),
         }
     }
 
@@ -69,9 +109,10 @@ impl Queue {
             return true;
         }
         match &self.content {
-            QueueContent::Song(_) => false,
+            QueueContent::Song(_) | QueueContent::Conditional(_) => false,
             QueueContent::Folder(folder) => folder.content.iter().all(|v| v.is_empty()),
             QueueContent::Loop(_total, _done, inner) => inner.is_empty(),
+            QueueContent::Playlist(playlist) => playlist.songs.is_empty(),
         }
     }
     /// returns true if there is at most one song in the queue
@@ -83,7 +124,7 @@ impl Queue {
             return 0;
         }
         match &self.content {
-            QueueContent::Song(_) => 1,
+            QueueContent::Song(_) | QueueContent::Conditional(_) => 1,
             QueueContent::Folder(folder) => {
                 let mut o = 0;
                 for v in folder.content.iter() {
@@ -95,6 +136,7 @@ impl Queue {
                 o
             }
             QueueContent::Loop(_total, _done, inner) => inner.is_almost_empty_int(),
+            QueueContent::Playlist(playlist) => playlist.songs.len().min(2) as u8,
         }
     }
     pub fn len(&self) -> usize {
@@ -102,7 +144,7 @@ impl Queue {
             return 0;
         }
         match &self.content {
-            QueueContent::Song(_) => 1,
+            QueueContent::Song(_) | QueueContent::Conditional(_) => 1,
             QueueContent::Folder(folder) => folder.len(),
             QueueContent::Loop(total, _done, inner) => {
                 if *total == 0 {
@@ -111,6 +153,30 @@ impl Queue {
                     total.saturating_mul(inner.len())
                 }
             }
+            QueueContent::Playlist(playlist) => playlist.songs.len(),
+        }
+    }
+    /// true if this contains an (enabled) `Loop` which repeats forever, so the queue never ends
+    pub fn has_infinite_loop(&self) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        match &self.content {
+            QueueContent::Song(_) | QueueContent::Playlist(_) | QueueContent::Conditional(_) => {
+                false
+            }
+            QueueContent::Folder(folder) => folder.content.iter().any(|v| v.has_infinite_loop()),
+            QueueContent::Loop(total, _done, inner) => *total == 0 || inner.has_infinite_loop(),
+        }
+    }
+    /// the number of songs stored in this element, including disabled ones and counting `Loop`s only once.
+    /// unlike `len`, this reflects how much space the queue takes up (see `Database::queue_limit`).
+    pub fn element_count(&self) -> usize {
+        match &self.content {
+            QueueContent::Song(_) | QueueContent::Conditional(_) => 1,
+            QueueContent::Folder(folder) => folder.content.iter().map(|v| v.element_count()).sum(),
+            QueueContent::Loop(_total, _done, inner) => inner.element_count(),
+            QueueContent::Playlist(playlist) => playlist.songs.len(),
         }
     }
     pub fn duration_total(&self, db: &Database) -> QueueDuration {
@@ -124,27 +190,43 @@ impl Queue {
         self.add_duration(&mut dur, db);
         dur
     }
+    /// sum of all song durations in milliseconds. infinite loops are only counted once.
+    pub fn total_duration(&self, db: &Database) -> u64 {
+        self.duration_total(db).millis
+    }
+    /// like `total_duration`, but only the current and following songs
+    pub fn remaining_duration(&self, db: &Database) -> u64 {
+        self.duration_remaining(db).millis
+    }
+    /// like `remaining_duration`, but the part of the current song that has already been played is subtracted.
+    /// `position` is the current playback position in milliseconds.
+    pub fn remaining_duration_from_current(&self, db: &Database, position: u64) -> u64 {
+        self.remaining_duration(db).saturating_sub(position)
+    }
     pub fn add_duration(&self, dur: &mut QueueDuration, db: &Database) {
         if self.enabled {
             match &self.content {
                 QueueContent::Song(v) => {
-                    dur.millis += db.get_song(v).map(|s| s.duration_millis).unwrap_or(0)
+                    dur.millis += db.get_song(v).map(|s| s.duration_millis).unwrap_or(0);
+                    dur.songs += 1;
                 }
-                QueueContent::Folder(QueueFolder {
-                    index,
-                    content,
-                    name: _,
-                    order: _,
-                }) => {
-                    for (i, inner) in content.iter().enumerate() {
-                        if dur.include_past || i >= *index {
+                QueueContent::Folder(folder) => {
+                    // iter() respects the shuffled order, which `index` refers to
+                    for (i, inner) in folder.iter().enumerate() {
+                        if dur.include_past || i >= folder.index {
                             inner.add_duration(dur, db);
                         }
                     }
                 }
                 QueueContent::Loop(total, done, inner) => {
                     if *total == 0 {
+                        // count one iteration, otherwise the duration would be infinite
                         dur.infinite = true;
+                        if dur.include_past {
+                            *dur += inner.duration_total(db);
+                        } else {
+                            inner.add_duration(dur, db);
+                        }
                     } else if dur.include_past {
                         // <total duration> * <total iterations>
                         let dt = inner.duration_total(db);
@@ -160,35 +242,133 @@ impl Queue {
                         }
                     }
                 }
+                QueueContent::Playlist(QueuePlaylist {
+                    index,
+                    name: _,
+                    songs,
+                }) => {
+                    for (i, id) in songs.iter().enumerate() {
+                        if dur.include_past || i >= *index {
+                            dur.millis += db.get_song(id).map(|s| s.duration_millis).unwrap_or(0);
+                            dur.songs += 1;
+                        }
+                    }
+                }
+                // the songs aren't known until it is resolved
+                QueueContent::Conditional(_) => {}
             }
         }
     }
 
     /// recursively descends the queue until the current active element is found, then returns it.
+    /// this is either a song or a playlist.
     pub fn get_current(&self) -> Option<&Self> {
         match &self.content {
-            QueueContent::Song(_) => Some(self),
+            QueueContent::Song(_) | QueueContent::Playlist(_) | QueueContent::Conditional(_) => {
+                Some(self)
+            }
             QueueContent::Folder(folder) => folder.get_current_immut()?.get_current(),
             QueueContent::Loop(_, _, inner) => inner.get_current(),
         }
     }
+    /// the path of the element returned by `get_current`, as used by `get_item_at_index`
+    pub fn get_current_path(&self) -> Option<Vec<usize>> {
+        let mut path = vec![];
+        let mut queue = self;
+        loop {
+            match &queue.content {
+                QueueContent::Song(_)
+                | QueueContent::Playlist(_)
+                | QueueContent::Conditional(_) => return Some(path),
+                QueueContent::Folder(folder) => {
+                    queue = folder.get_current_immut()?;
+                    path.push(folder.index);
+                }
+                QueueContent::Loop(_, _, inner) => {
+                    queue = inner;
+                    path.push(0);
+                }
+            }
+        }
+    }
     pub fn get_current_song(&self) -> Option<&SongId> {
-        if let QueueContent::Song(id) = self.get_current()?.content() {
-            Some(id)
-        } else {
-            None
+        match self.get_current()?.content() {
+            QueueContent::Song(id) => Some(id),
+            QueueContent::Playlist(playlist) => playlist.songs.get(playlist.index),
+            _ => None,
+        }
+    }
+    /// where elements have to be inserted (using `Action::QueueInsert`) to be played right after the current element:
+    /// the path of the innermost folder containing the current element, and the index after the current element.
+    /// returns `None` if there is no current element.
+    pub fn get_insert_after_current(&self) -> Option<(Vec<usize>, usize)> {
+        let mut path = vec![];
+        let mut out = None;
+        let mut queue = self;
+        loop {
+            match &queue.content {
+                QueueContent::Song(_)
+                | QueueContent::Playlist(_)
+                | QueueContent::Conditional(_) => return out,
+                QueueContent::Folder(folder) => {
+                    queue = folder.get_current_immut()?;
+                    out = Some((path.clone(), folder.index + 1));
+                    path.push(folder.index);
+                }
+                QueueContent::Loop(_, _, inner) => {
+                    queue = inner;
+                    path.push(0);
+                }
+            }
         }
     }
     pub fn get_next_song(&self) -> Option<&SongId> {
-        if let QueueContent::Song(id) = self.get_next()?.content() {
-            Some(id)
-        } else {
-            None
+        match &self.content {
+            QueueContent::Song(_) | QueueContent::Conditional(_) => None,
+            QueueContent::Folder(folder) => {
+                let current = folder.get_current_immut()?;
+                if let Some(v) = current.get_next_song() {
+                    Some(v)
+                } else {
+                    folder
+                        .get_at(folder.first_non_empty(folder.index + 1)?)?
+                        .get_first_song()
+                }
+            }
+            QueueContent::Loop(total, current, inner) => {
+                if let Some(v) = inner.get_next_song() {
+                    Some(v)
+                } else if *total == 0 || current < total {
+                    inner.get_first_song()
+                } else {
+                    None
+                }
+            }
+            QueueContent::Playlist(playlist) => playlist.songs.get(playlist.index + 1),
         }
     }
+    /// the song this element would start with after `init()`
+    fn get_first_song(&self) -> Option<&SongId> {
+        if !self.enabled {
+            return None;
+        }
+        match &self.content {
+            QueueContent::Song(id) => Some(id),
+            QueueContent::Folder(folder) => {
+                folder.get_at(folder.first_non_empty(0)?)?.get_first_song()
+            }
+            QueueContent::Loop(_, _, inner) => inner.get_first_song(),
+            QueueContent::Playlist(playlist) => playlist.songs.first(),
+            QueueContent::Conditional(_) => None,
+        }
+    }
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+    /// for playlists, this returns the playlist itself if the next song is a part of it.
     pub fn get_next(&self) -> Option<&Self> {
         match &self.content {
-            QueueContent::Song(_) => None,
+            QueueContent::Song(_) | QueueContent::Conditional(_) => None,
             QueueContent::Folder(folder) => folder.get_next(),
             QueueContent::Loop(total, current, inner) => {
                 if let Some(v) = inner.get_next() {
@@ -199,11 +379,20 @@ impl Queue {
                     None
                 }
             }
+            QueueContent::Playlist(playlist) => {
+                if playlist.index + 1 < playlist.songs.len() {
+                    Some(self)
+                } else {
+                    None
+                }
+            }
         }
     }
     pub fn get_first(&self) -> Option<&Self> {
         match &self.content {
-            QueueContent::Song(..) => Some(self),
+            QueueContent::Song(..) | QueueContent::Playlist(..) | QueueContent::Conditional(..) => {
+                Some(self)
+            }
             QueueContent::Folder(folder) => folder.get_first(),
             QueueContent::Loop(_, _, q) => q.get_first(),
         }
@@ -215,19 +404,21 @@ impl Queue {
     }
     pub fn init(&mut self) {
         match &mut self.content {
-            QueueContent::Song(..) => {}
+            QueueContent::Song(..) | QueueContent::Conditional(..) => {}
             QueueContent::Folder(folder) => {
-                folder.index = 0;
                 for v in &mut folder.content {
                     v.init();
                 }
+                // skip disabled elements at the start
+                folder.index = folder.first_non_empty(0).unwrap_or(0);
             }
             QueueContent::Loop(_, _, inner) => inner.init(),
+            QueueContent::Playlist(playlist) => playlist.index = 0,
         }
     }
     pub fn advance_index_inner(&mut self) -> bool {
         match &mut self.content {
-            QueueContent::Song(_) => false,
+            QueueContent::Song(_) | QueueContent::Conditional(_) => false,
             QueueContent::Folder(folder) => folder.advance_index_inner(),
             QueueContent::Loop(total, current, inner) => {
                 if inner.advance_index_inner() {
@@ -243,6 +434,84 @@ impl Queue {
                     }
                 }
             }
+            QueueContent::Playlist(playlist) => {
+                if playlist.index + 1 < playlist.songs.len() {
+                    playlist.index += 1;
+                    true
+                } else {
+                    playlist.index = 0;
+                    false
+                }
+            }
+        }
+    }
+
+    /// the opposite of `advance_index_db`, used by `Action::PreviousSong`.
+    /// returns `false` (and doesn't change anything) if the current song is the first one.
+    pub fn go_back_index_db(db: &mut Database) -> bool {
+        db.queue.go_back_index_inner()
+    }
+    pub fn go_back_index_inner(&mut self) -> bool {
+        match &mut self.content {
+            QueueContent::Song(_) | QueueContent::Conditional(_) => false,
+            QueueContent::Folder(folder) => folder.go_back_index_inner(),
+            QueueContent::Loop(_, current, inner) => {
+                if inner.go_back_index_inner() {
+                    true
+                } else if *current > 0 {
+                    // back to the end of the previous repetition
+                    *current -= 1;
+                    inner.init_last();
+                    true
+                } else {
+                    false
+                }
+            }
+            QueueContent::Playlist(playlist) => {
+                if playlist.index > 0 {
+                    playlist.index -= 1;
+                    true
+                } else {
+                    false
+                }
+            }
+        }
+    }
+    /// true if `go_back_index_inner` would succeed
+    pub fn has_previous(&self) -> bool {
+        match &self.content {
+            QueueContent::Song(_) | QueueContent::Conditional(_) => false,
+            QueueContent::Folder(folder) => {
+                folder
+                    .get_current_immut()
+                    .is_some_and(|current| current.has_previous())
+                    || folder.last_non_empty(folder.index).is_some()
+            }
+            QueueContent::Loop(_, current, inner) => *current > 0 || inner.has_previous(),
+            QueueContent::Playlist(playlist) => playlist.index > 0,
+        }
+    }
+    /// like `init`, but the last song will be the current one
+    pub fn init_last(&mut self) {
+        match &mut self.content {
+            QueueContent::Song(..) | QueueContent::Conditional(..) => {}
+            QueueContent::Folder(folder) => {
+                for v in &mut folder.content {
+                    v.init();
+                }
+                if let Some(i) = folder.last_non_empty(folder.content.len()) {
+                    folder.index = i;
+                    if let Some(last) = folder.get_current_mut() {
+                        last.init_last();
+                    }
+                } else {
+                    folder.index = 0;
+                }
+            }
+            QueueContent::Loop(_, _, inner) => inner.init_last(),
+            QueueContent::Playlist(playlist) => {
+                playlist.index = playlist.songs.len().saturating_sub(1)
+            }
         }
     }
 
@@ -264,7 +533,7 @@ impl Queue {
         };
         build_index.push(i);
         match &mut self.content {
-            QueueContent::Song(_) => {}
+            QueueContent::Song(_) | QueueContent::Conditional(_) => {}
             QueueContent::Folder(folder) => {
                 folder.index = i;
                 if let Some(c) = folder.get_current_mut() {
@@ -280,11 +549,12 @@ impl Queue {
                 }
                 inner.set_index_inner(index, depth + 1, build_index, keep_child_indices)
             }
+            QueueContent::Playlist(playlist) => playlist.index = i,
         }
     }
     pub fn reset_index(&mut self) {
         match self.content_mut() {
-            QueueContent::Song(_) => {}
+            QueueContent::Song(_) | QueueContent::Conditional(_) => {}
             QueueContent::Folder(folder) => {
                 folder.index = 0;
                 for v in &mut folder.content {
@@ -295,13 +565,14 @@ impl Queue {
                 *done = 0;
                 i.reset_index();
             }
+            QueueContent::Playlist(playlist) => playlist.index = 0,
         }
     }
 
     pub fn get_item_at_index(&self, index: &[usize], depth: usize) -> Option<&Self> {
         if let Some(i) = index.get(depth) {
             match &self.content {
-                QueueContent::Song(_) => None,
+                QueueContent::Song(_) | QueueContent::Conditional(_) => None,
                 QueueContent::Folder(folder) => {
                     if let Some(v) = folder.get_at(*i) {
                         v.get_item_at_index(index, depth + 1)
@@ -310,6 +581,8 @@ impl Queue {
                     }
                 }
                 QueueContent::Loop(_, _, inner) => inner.get_item_at_index(index, depth + 1),
+                // the songs in a playlist aren't `Queue`s
+                QueueContent::Playlist(_) => None,
             }
         } else {
             Some(self)
@@ -318,7 +591,7 @@ impl Queue {
     pub fn get_item_at_index_mut(&mut self, index: &[usize], depth: usize) -> Option<&mut Self> {
         if let Some(i) = index.get(depth) {
             match &mut self.content {
-                QueueContent::Song(_) => None,
+                QueueContent::Song(_) | QueueContent::Conditional(_) => None,
                 QueueContent::Folder(folder) => {
                     if let Some(v) = folder.get_mut_at(*i) {
                         v.get_item_at_index_mut(index, depth + 1)
@@ -327,6 +600,7 @@ impl Queue {
                     }
                 }
                 QueueContent::Loop(_, _, inner) => inner.get_item_at_index_mut(index, depth + 1),
+                QueueContent::Playlist(_) => None,
             }
         } else {
             Some(self)
@@ -336,7 +610,7 @@ impl Queue {
     pub fn remove_by_index(&mut self, index: &[usize], depth: usize) -> Option<Self> {
         if let Some(i) = index.get(depth) {
             match &mut self.content {
-                QueueContent::Song(_) => None,
+                QueueContent::Song(_) | QueueContent::Conditional(_) => None,
                 QueueContent::Folder(folder) => {
                     if depth + 1 < index.len() {
                         if let Some(v) = folder.get_mut_at(*i) {
@@ -376,11 +650,199 @@ impl Queue {
                         None
                     }
                 }
+                QueueContent::Playlist(playlist) => {
+                    if depth + 1 == index.len() && *i < playlist.songs.len() {
+                        if playlist.index > *i {
+                            playlist.index -= 1;
+                        }
+                        Some(QueueContent::Song(playlist.songs.remove(*i)).into())
+                    } else {
+                        None
+                    }
+                }
             }
         } else {
             None
         }
     }
+    /// the path of the first occurrence of this song in the queue, which can be used in `set_index_db`
+    pub fn find_song(&self, id: SongId) -> Option<Vec<usize>> {
+        match self.content() {
+            QueueContent::Song(song) => (*song == id).then(Vec::new),
+            QueueContent::Folder(folder) => folder.iter().enumerate().find_map(|(i, queue)| {
+                let mut path = queue.find_song(id)?;
+                path.insert(0, i);
+                Some(path)
+            }),
+            QueueContent::Loop(_, _, inner) => {
+                let mut path = inner.find_song(id)?;
+                path.insert(0, 0);
+                Some(path)
+            }
+            QueueContent::Playlist(playlist) => playlist
+                .songs
+                .iter()
+                .position(|song| *song == id)
+                .map(|i| vec![i]),
+            QueueContent::Conditional(_) => None,
+        }
+    }
+    /// finds songs which are in the queue more than once. only the first (or, if `keep_last`, the last) one is kept.
+    /// the paths are in reverse order, so they can be removed one after the other without affecting each other.
+    pub fn find_duplicate_song_paths(&self, keep_last: bool) -> Vec<Vec<usize>> {
+        fn find(queue: &Queue, path: &mut Vec<usize>, out: &mut HashMap<SongId, Vec<Vec<usize>>>) {
+            match queue.content() {
+                QueueContent::Song(id) => out.entry(*id).or_default().push(path.clone()),
+                QueueContent::Folder(folder) => {
+                    for (i, queue) in folder.iter().enumerate() {
+                        path.push(i);
+                        find(queue, path, out);
+                        path.pop();
+                    }
+                }
+                QueueContent::Loop(_, _, inner) => {
+                    path.push(0);
+                    find(inner, path, out);
+                    path.pop();
+                }
+                QueueContent::Playlist(playlist) => {
+                    for (i, id) in playlist.songs.iter().enumerate() {
+                        path.push(i);
+                        out.entry(*id).or_default().push(path.clone());
+                        path.pop();
+                    }
+                }
+                QueueContent::Conditional(_) => {}
+            }
+        }
+        let mut occurrences = HashMap::new();
+        find(self, &mut vec![], &mut occurrences);
+        let mut paths = occurrences
+            .into_values()
+            .flat_map(|mut paths| {
+                if keep_last {
+                    paths.pop();
+                } else if !paths.is_empty() {
+                    paths.remove(0);
+                }
+                paths
+            })
+            .collect::<Vec<_>>();
+        paths.sort_unstable_by(|a, b| b.cmp(a));
+        paths
+    }
+    /// moves the elements at `sources` to `target` (like `QueueMove`, so `target` is the path of the new position),
+    /// keeping their relative order. sources which are inside another source are moved along with it,
+    /// sources which contain `target` are ignored. returns false if nothing was moved.
+    pub fn move_multiple(&mut self, mut sources: Vec<Vec<usize>>, target: &[usize]) -> bool {
+        if target.is_empty() {
+            return false;
+        }
+        let target_parent = &target[..target.len() - 1];
+        // sorted paths are in queue order, parents before their children
+        sources.sort();
+        sources.dedup();
+        sources.retain(|s| !s.is_empty() && !target_parent.starts_with(s));
+        let mut kept: Vec<Vec<usize>> = Vec::with_capacity(sources.len());
+        for s in sources {
+            if !kept.iter().any(|k| s.starts_with(k)) {
+                kept.push(s);
+            }
+        }
+        let sources = kept;
+        if sources.is_empty() {
+            return false;
+        }
+        // every removed element which comes before the target in the same folder shifts it by one.
+        // this has to use the original paths, so it is done before removing anything.
+        let mut new_target = target.to_vec();
+        for s in &sources {
+            let d = s.len() - 1;
+            if d < target.len() && target[..d] == s[..d] && s[d] < target[d] {
+                new_target[d] -= 1;
+            }
+        }
+        // make sure the target accepts the elements before removing them, otherwise they would be lost
+        let is_song = |s: &Vec<usize>| {
+            matches!(
+                self.get_item_at_index(s, 0).map(|v| v.content()),
+                Some(QueueContent::Song(_))
+            ) || matches!(
+                self.get_item_at_index(&s[..s.len() - 1], 0)
+                    .map(|v| v.content()),
+                Some(QueueContent::Playlist(_))
+            )
+        };
+        let parent_len = match self
+            .get_item_at_index(target_parent, 0)
+            .map(|v| v.content())
+        {
+            Some(QueueContent::Folder(folder)) => folder.content.len(),
+            Some(QueueContent::Playlist(playlist)) if sources.iter().all(is_song) => {
+                playlist.songs.len()
+            }
+            _ => return false,
+        };
+        let removed_from_parent = sources
+            .iter()
+            .filter(|s| s.len() == target.len() && s[..s.len() - 1] == *target_parent)
+            .count();
+        if *new_target.last().unwrap() > parent_len.saturating_sub(removed_from_parent) {
+            return false;
+        }
+        let current = sources.iter().position(|s| self.is_current(s));
+        // remove back to front, so the paths of elements which weren't removed yet stay valid
+        let mut elems = vec![];
+        for (i, s) in sources.iter().enumerate().rev() {
+            if let Some(elem) = self.remove_by_index(s, 0) {
+                elems.push((i, elem));
+            }
+        }
+        elems.reverse();
+        let current = current.and_then(|c| elems.iter().position(|(i, _)| *i == c));
+        let elems = elems.into_iter().map(|(_, elem)| elem).collect();
+        let (pos, parent) = new_target.split_last().unwrap();
+        if let Some(parent) = self.get_item_at_index_mut(parent, 0) {
+            if !parent.insert(elems, *pos, true) {
+                eprintln!(
+                    "[{}] move_multiple: couldn't insert elements at {new_target:?} (was {target:?})",
+                    "WARN".yellow()
+                );
+                return false;
+            }
+        }
+        if let Some(c) = current {
+            *new_target.last_mut().unwrap() += c;
+            self.set_index_inner(&new_target, 0, vec![], true);
+        }
+        true
+    }
+}
+
+impl QueueConditional {
+    /// chooses the songs for this conditional. the result is not deterministic:
+    /// it depends on the library at the time this is called and, if `random`, on chance.
+    pub fn resolve(&self, db: &Database) -> QueuePlaylist {
+        let mut songs = db
+            .songs()
+            .values()
+            .filter(|song| self.filter.passes(&song.general))
+            .map(|song| song.id)
+            .collect::<Vec<_>>();
+        if self.random {
+            songs.shuffle(&mut thread_rng());
+        } else {
+            songs.sort_unstable();
+        }
+        if self.count > 0 {
+            songs.truncate(self.count);
+        }
+        QueuePlaylist {
+            index: 0,
+            name: self.name.clone(),
+            songs,
+        }
+    }
 }
 
 impl QueueFolder {
@@ -464,8 +926,8 @@ impl QueueFolder {
             if let Some(v) = v.get_next() {
                 Some(v)
             } else {
-                if let Some(v) = self.get_at(self.index + 1) {
-                    v.get_current()
+                if let Some(v) = self.get_at(self.first_non_empty(self.index + 1)?) {
+                    v.get_first()
                 } else {
                     None
                 }
@@ -475,12 +937,67 @@ impl QueueFolder {
         }
     }
     pub fn get_first(&self) -> Option<&Queue> {
-        if let Some(order) = &self.order {
-            self.content.get(*order.first()?)
+        self.get_at(self.first_non_empty(0)?)?.get_first()
+    }
+    /// the index of the first element at or after `from` which isn't disabled or empty
+    pub fn first_non_empty(&self, from: usize) -> Option<usize> {
+        (from..self.content.len()).find(|i| self.get_at(*i).is_some_and(|v| !v.is_empty()))
+    }
+    /// the index of the last element before `before` which isn't disabled or empty
+    pub fn last_non_empty(&self, before: usize) -> Option<usize> {
+        (0..before.min(self.content.len()))
+            .rev()
+            .find(|i| self.get_at(*i).is_some_and(|v| !v.is_empty()))
+    }
+    pub fn go_back_index_inner(&mut self) -> bool {
+        if let Some(c) = self.get_current_mut() {
+            if c.go_back_index_inner() {
+                true
+            } else if let Some(i) = self.last_non_empty(self.index) {
+                self.index = i;
+                if let Some(prev) = self.get_current_mut() {
+                    prev.init_last();
+                }
+                true
+            } else {
+                false
+            }
         } else {
-            self.content.first()
+            false
         }
     }
+    /// if this folder is shuffled with a `weight`, picks the element which is played after the current one
+    /// from those which come after it in `order`, and returns the new `order`, or `None` if it stays the same.
+    /// `rng` should be seeded the same way for the same folder and `index`, so that calling this again
+    /// keeps the element that was already picked, unless the weights changed.
+    pub fn pick_next_weighted(&self, db: &Database, rng: &mut impl Rng) -> Option<Vec<usize>> {
+        if self.weight == ShuffleWeight::Uniform {
+            return None;
+        }
+        let order = self.order.as_ref()?;
+        let next = self.index + 1;
+        // sorted, so the position of the candidates in `order` doesn't affect which one is picked
+        let mut candidates = order
+            .get(next..)?
+            .iter()
+            .copied()
+            .filter(|i| self.content.get(*i).is_some_and(|v| !v.is_empty()))
+            .collect::<Vec<_>>();
+        candidates.sort_unstable();
+        let weights = candidates
+            .iter()
+            .map(|i| self.weight.weight(&self.content[*i], db));
+        let picked = candidates[rand::distributions::WeightedIndex::new(weights)
+            .ok()?
+            .sample(rng)];
+        let pos = order.iter().position(|i| *i == picked)?;
+        if pos == next {
+            return None;
+        }
+        let mut order = order.clone();
+        order.swap(next, pos);
+        Some(order)
+    }
     pub fn advance_index_inner(&mut self) -> bool {
         if let Some(c) = self.get_current_mut() {
             if c.advance_index_inner() {
@@ -491,9 +1008,12 @@ impl QueueFolder {
                     if self.index + 1 < self.content.len() {
                         // can advance
                         self.index += 1;
-                        if self.content[self.index].enabled {
-                            self.content[self.index].init();
-                            break true;
+                        // skip disabled (and empty) elements
+                        if let Some(next) = self.get_mut_at(self.index) {
+                            if !next.is_empty() {
+                                next.init();
+                                break true;
+                            }
                         }
                     } else {
                         // can't advance: index would be out of bounds
@@ -535,6 +1055,30 @@ impl QueueFolder {
         }
     }
 }
+impl QueuePlaylist {
+    /// inserts the songs, but only if all elements in `v` are songs
+    pub fn insert(&mut self, v: Vec<Queue>, index: usize) -> bool {
+        if index > self.songs.len() {
+            return false;
+        }
+        let songs = v
+            .iter()
+            .map(|v| match v.content() {
+                QueueContent::Song(id) => Some(*id),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>();
+        if let Some(songs) = songs {
+            if self.index >= index && !self.songs.is_empty() {
+                self.index += songs.len();
+            }
+            self.songs.splice(index..index, songs);
+            true
+        } else {
+            false
+        }
+    }
+}
 pub struct QueueFolderIter<'a> {
     folder: &'a QueueFolder,
     index: usize,
@@ -602,6 +1146,14 @@ impl ToFromBytes for QueueContent {
                 current.to_bytes(s)?;
                 inner.to_bytes(s)?;
             }
+            Self::Playlist(playlist) => {
+                s.write_all(&[0b00111100])?;
+                playlist.to_bytes(s)?;
+            }
+            Self::Conditional(conditional) => {
+                s.write_all(&[0b00110011])?;
+                conditional.to_bytes(s)?;
+            }
         }
         Ok(())
     }
@@ -619,11 +1171,16 @@ impl ToFromBytes for QueueContent {
                 ToFromBytes::from_bytes(s)?,
                 Box::new(ToFromBytes::from_bytes(s)?),
             ),
+            // new in this version - older queues just don't contain this byte, so they still load fine.
+            0b00111100 => Self::Playlist(ToFromBytes::from_bytes(s)?),
+            0b00110011 => Self::Conditional(ToFromBytes::from_bytes(s)?),
             _ => Self::Folder(QueueFolder {
                 index: 0,
                 content: vec![],
                 name: "<invalid byte received>".to_string(),
                 order: None,
+                seed: None,
+                weight: ShuffleWeight::Uniform,
             }),
         })
     }
@@ -637,6 +1194,8 @@ impl ToFromBytes for QueueFolder {
         ToFromBytes::to_bytes(&self.content, s)?;
         ToFromBytes::to_bytes(&self.name, s)?;
         ToFromBytes::to_bytes(&self.order, s)?;
+        ToFromBytes::to_bytes(&self.seed, s)?;
+        ToFromBytes::to_bytes(&self.weight, s)?;
         Ok(())
     }
     fn from_bytes<T>(s: &mut T) -> Result<Self, std::io::Error>
@@ -648,17 +1207,219 @@ impl ToFromBytes for QueueFolder {
             content: ToFromBytes::from_bytes(s)?,
             name: ToFromBytes::from_bytes(s)?,
             order: ToFromBytes::from_bytes(s)?,
+            seed: ToFromBytes::from_bytes(s)?,
+            weight: if reading_folders_without_weight() {
+                ShuffleWeight::Uniform
+            } else {
+                ToFromBytes::from_bytes(s)?
+            },
         };
         Ok(v)
     }
 }
+impl ToFromBytes for QueuePlaylist {
+    fn to_bytes<T>(&self, s: &mut T) -> Result<(), std::io::Error>
+    where
+        T: std::io::prelude::Write,
+    {
+        ToFromBytes::to_bytes(&self.index, s)?;
+        ToFromBytes::to_bytes(&self.name, s)?;
+        ToFromBytes::to_bytes(&self.songs, s)?;
+        Ok(())
+    }
+    fn from_bytes<T>(s: &mut T) -> Result<Self, std::io::Error>
+    where
+        T: std::io::prelude::Read,
+    {
+        Ok(Self {
+            index: ToFromBytes::from_bytes(s)?,
+            name: ToFromBytes::from_bytes(s)?,
+            songs: ToFromBytes::from_bytes(s)?,
+        })
+    }
+}
+
+/// what happens when the end of the queue (or of the current song) is reached
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RepeatMode {
+    /// stop at the end of the queue
+    #[default]
+    Off,
+    /// restart the queue once it ends
+    All,
+    /// play the current song again when it ends. skipping still goes to the next song.
+    /// the queue doesn't advance while the song repeats, so `QueueContent::Loop`s around it don't count these repetitions
+    /// and continue normally once the mode is changed. `Action::StopAfterCurrentSong` still lets the song finish once.
+    /// if the backend supports it (see `PlayerBackend::set_repeat_current`), the song repeats without a gap.
+    One,
+}
+impl ToFromBytes for RepeatMode {
+    fn to_bytes<T>(&self, s: &mut T) -> Result<(), std::io::Error>
+    where
+        T: std::io::prelude::Write,
+    {
+        s.write_all(&[match self {
+            Self::Off => 0,
+            Self::All => 1,
+            Self::One => 2,
+        }])
+    }
+    fn from_bytes<T>(s: &mut T) -> Result<Self, std::io::Error>
+    where
+        T: std::io::prelude::Read,
+    {
+        let mut byte = [0];
+        s.read_exact(&mut byte)?;
+        Ok(match byte[0] {
+            1 => Self::All,
+            2 => Self::One,
+            _ => Self::Off,
+        })
+    }
+}
+impl ToFromBytes for QueueConditional {
+    fn to_bytes<T>(&self, s: &mut T) -> Result<(), std::io::Error>
+    where
+        T: std::io::prelude::Write,
+    {
+        ToFromBytes::to_bytes(&self.name, s)?;
+        ToFromBytes::to_bytes(&self.filter, s)?;
+        ToFromBytes::to_bytes(&self.count, s)?;
+        ToFromBytes::to_bytes(&self.random, s)?;
+        Ok(())
+    }
+    fn from_bytes<T>(s: &mut T) -> Result<Self, std::io::Error>
+    where
+        T: std::io::prelude::Read,
+    {
+        Ok(Self {
+            name: ToFromBytes::from_bytes(s)?,
+            filter: ToFromBytes::from_bytes(s)?,
+            count: ToFromBytes::from_bytes(s)?,
+            random: ToFromBytes::from_bytes(s)?,
+        })
+    }
+}
+
+/// what the server does when adding songs would make the queue longer than `Database::queue_limit`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum QueueLimitPolicy {
+    /// deny the `QueueAdd`/`QueueInsert`/..., the queue stays as it is
+    #[default]
+    Reject,
+    /// remove elements from the start of the queue which were already played until the new songs fit.
+    /// if that isn't enough, the action is denied like with `Reject`.
+    DropFront,
+}
+impl std::str::FromStr for QueueLimitPolicy {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "reject" => Ok(Self::Reject),
+            "drop-front" => Ok(Self::DropFront),
+            _ => Err(format!("expected `reject` or `drop-front`, not `{s}`")),
+        }
+    }
+}
+
+/// how the elements of a shuffled folder are weighted when the next one is picked (see `QueueFolder::weight`)
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum ShuffleWeight {
+    /// every element is equally likely to be picked next
+    #[default]
+    Uniform,
+    /// songs with a higher value for this tag (e.g. `Rating=`) are more likely to be picked next.
+    /// songs without the tag (or with a non-numeric value) have a weight of 1.
+    Tag(String),
+    /// songs which weren't played for a long time are more likely to be picked next.
+    /// the weight is `1` plus the number of days since the song was last played,
+    /// songs which were never played count as played `NEVER_PLAYED_DAYS` days ago.
+    LeastRecentlyPlayed,
+}
+impl ShuffleWeight {
+    /// see `LeastRecentlyPlayed`
+    pub const NEVER_PLAYED_DAYS: f64 = 365.0;
+    /// the weight of a folder, loop or playlist is the average weight of the songs in it, `1` if it has none.
+    pub fn weight(&self, queue: &Queue, db: &Database) -> f64 {
+        fn songs<'a>(queue: &'a Queue, out: &mut Vec<&'a SongId>) {
+            match queue.content() {
+                QueueContent::Song(id) => out.push(id),
+                QueueContent::Folder(folder) => folder.content.iter().for_each(|v| songs(v, out)),
+                QueueContent::Loop(_, _, inner) => songs(inner, out),
+                QueueContent::Playlist(playlist) => out.extend(&playlist.songs),
+                QueueContent::Conditional(_) => {}
+            }
+        }
+        if *self == Self::Uniform {
+            return 1.0;
+        }
+        let mut ids = vec![];
+        songs(queue, &mut ids);
+        if ids.is_empty() {
+            return 1.0;
+        }
+        ids.iter().map(|id| self.song_weight(id, db)).sum::<f64>() / ids.len() as f64
+    }
+    fn song_weight(&self, id: &SongId, db: &Database) -> f64 {
+        let Some(song) = db.get_song(id) else {
+            return 1.0;
+        };
+        let weight = match self {
+            Self::Uniform => None,
+            Self::Tag(tag) => song
+                .general
+                .tags
+                .iter()
+                .find_map(|t| t.strip_prefix(tag.as_str())?.trim().parse::<f64>().ok()),
+            Self::LeastRecentlyPlayed => Some(
+                1.0 + song.last_played.map_or(Self::NEVER_PLAYED_DAYS, |time| {
+                    time.elapsed().map_or(0.0, |v| v.as_secs_f64() / 86400.0)
+                }),
+            ),
+        };
+        weight
+            .filter(|w| w.is_finite())
+            .map(|w| w.max(0.0))
+            .unwrap_or(1.0)
+    }
+}
+impl ToFromBytes for ShuffleWeight {
+    fn to_bytes<T>(&self, s: &mut T) -> Result<(), std::io::Error>
+    where
+        T: std::io::prelude::Write,
+    {
+        match self {
+            Self::Uniform => s.write_all(&[0])?,
+            Self::Tag(tag) => {
+                s.write_all(&[1])?;
+                tag.to_bytes(s)?;
+            }
+            Self::LeastRecentlyPlayed => s.write_all(&[2])?,
+        }
+        Ok(())
+    }
+    fn from_bytes<T>(s: &mut T) -> Result<Self, std::io::Error>
+    where
+        T: std::io::prelude::Read,
+    {
+        let mut byte = [0];
+        s.read_exact(&mut byte)?;
+        Ok(match byte[0] {
+            1 => Self::Tag(ToFromBytes::from_bytes(s)?),
+            2 => Self::LeastRecentlyPlayed,
+            _ => Self::Uniform,
+        })
+    }
+}
 
 #[derive(Clone, Copy)]
 pub struct QueueDuration {
     pub include_past: bool,
     pub infinite: bool,
-    /// number of milliseconds (that we know of)
+    /// number of milliseconds (that we know of). if `infinite`, loops are only counted once.
     pub millis: u64,
+    /// number of songs, counted like `millis`
+    pub songs: u64,
     /// number of milliseconds from the <random> element - only accurate the first time it is reached in queue.
     pub random_known_millis: u64,
     /// number of <random> elements, which could have pretty much any duration.
@@ -676,6 +1437,7 @@ impl QueueDuration {
             include_past,
             infinite: false,
             millis: 0,
+            songs: 0,
             random_known_millis: 0,
             random_counter: 0,
         }
@@ -687,7 +1449,224 @@ impl AddAssign<QueueDuration> for QueueDuration {
             self.infinite = true;
         }
         self.millis += rhs.millis;
+        self.songs += rhs.songs;
         self.random_known_millis += rhs.random_known_millis;
         self.random_counter += rhs.random_counter;
     }
 }
+
+#[test]
+fn test_move_multiple() {
+    fn songs(ids: &[SongId]) -> Vec<Queue> {
+        ids.iter()
+            .map(|id| QueueContent::Song(*id).into())
+            .collect()
+    }
+    fn folder(content: Vec<Queue>) -> Queue {
+        QueueContent::Folder(QueueFolder {
+            content,
+            ..Default::default()
+        })
+        .into()
+    }
+    fn flat(q: &Queue, out: &mut Vec<SongId>) {
+        match q.content() {
+            QueueContent::Song(id) => out.push(*id),
+            QueueContent::Folder(f) => f.iter().for_each(|q| flat(q, out)),
+            QueueContent::Loop(_, _, q) => flat(q, out),
+            QueueContent::Playlist(p) => out.extend(&p.songs),
+            QueueContent::Conditional(_) => {}
+        }
+    }
+    fn ids(q: &Queue) -> Vec<SongId> {
+        let mut out = vec![];
+        flat(q, &mut out);
+        out
+    }
+    // multiple elements from the same folder, target after some of them
+    let mut q = folder(songs(&[1, 2, 3, 4, 5]));
+    assert!(q.move_multiple(vec![vec![4], vec![0], vec![2]], &[3]));
+    assert_eq!(ids(&q), [2, 1, 3, 5, 4]);
+    // to the end
+    let mut q = folder(songs(&[1, 2, 3, 4, 5]));
+    assert!(q.move_multiple(vec![vec![0], vec![1]], &[5]));
+    assert_eq!(ids(&q), [3, 4, 5, 1, 2]);
+    // nested selection: [1, 0] is moved with its parent [1], duplicates are ignored
+    let mut q = folder(vec![
+        QueueContent::Song(1).into(),
+        folder(songs(&[2, 3])),
+        QueueContent::Song(4).into(),
+    ]);
+    assert!(q.move_multiple(vec![vec![1], vec![1, 0], vec![2], vec![2]], &[0]));
+    assert_eq!(ids(&q), [2, 3, 4, 1]);
+    // from different folders into another folder, the target's path shifts
+    let mut q = folder(vec![
+        QueueContent::Song(1).into(),
+        folder(songs(&[2, 3])),
+        folder(songs(&[4, 5])),
+    ]);
+    assert!(q.move_multiple(vec![vec![0], vec![1, 1]], &[2, 1]));
+    assert_eq!(ids(&q), [2, 4, 1, 3, 5]);
+    // a folder can't be moved into itself
+    let mut q = folder(vec![folder(songs(&[1, 2])), QueueContent::Song(3).into()]);
+    assert!(!q.move_multiple(vec![vec![0]], &[0, 1]));
+    assert_eq!(ids(&q), [1, 2, 3]);
+    // the current song stays the current song
+    let mut q = folder(songs(&[1, 2, 3, 4]));
+    q.set_index_inner(&[1], 0, vec![], false);
+    assert!(q.move_multiple(vec![vec![1], vec![2]], &[4]));
+    assert_eq!(ids(&q), [1, 4, 2, 3]);
+    assert_eq!(q.get_current_song(), Some(&2));
+    // a folder can't be moved into a playlist, and nothing is removed
+    let mut q = folder(vec![
+        QueueContent::Playlist(QueuePlaylist {
+            songs: vec![1, 2],
+            ..Default::default()
+        })
+        .into(),
+        QueueContent::Song(3).into(),
+        folder(songs(&[4])),
+    ]);
+    assert!(!q.move_multiple(vec![vec![1], vec![2]], &[0, 1]));
+    assert_eq!(ids(&q), [1, 2, 3, 4]);
+    assert!(q.move_multiple(vec![vec![1]], &[0, 1]));
+    assert_eq!(ids(&q), [1, 3, 2, 4]);
+    // past the end of the target folder
+    let mut q = folder(vec![folder(songs(&[1, 2])), QueueContent::Song(3).into()]);
+    assert!(!q.move_multiple(vec![vec![1]], &[0, 3]));
+    assert_eq!(ids(&q), [1, 2, 3]);
+}
+
+#[test]
+fn test_skip_disabled() {
+    let mut disabled: Queue = QueueContent::Song(2).into();
+    disabled.set_enabled(false);
+    let mut q: Queue = QueueContent::Folder(QueueFolder {
+        content: vec![
+            QueueContent::Song(1).into(),
+            disabled.clone(),
+            QueueContent::Song(3).into(),
+        ],
+        ..Default::default()
+    })
+    .into();
+    q.init();
+    assert_eq!(q.get_current_song(), Some(&1));
+    assert_eq!(q.get_next_song(), Some(&3));
+    assert!(q.advance_index_inner());
+    assert_eq!(q.get_current_song(), Some(&3));
+    assert_eq!(q.get_next_song(), None);
+    // disabled elements at the start are skipped too
+    let mut q: Queue = QueueContent::Folder(QueueFolder {
+        content: vec![disabled, QueueContent::Song(3).into()],
+        ..Default::default()
+    })
+    .into();
+    q.init();
+    assert_eq!(q.get_current_song(), Some(&3));
+}
+
+#[test]
+fn test_insert_after_current() {
+    let mut q: Queue = QueueContent::Folder(QueueFolder {
+        content: vec![
+            QueueContent::Song(1).into(),
+            QueueContent::Loop(
+                2,
+                0,
+                Box::new(
+                    QueueContent::Folder(QueueFolder {
+                        content: vec![QueueContent::Song(2).into(), QueueContent::Song(3).into()],
+                        ..Default::default()
+                    })
+                    .into(),
+                ),
+            )
+            .into(),
+        ],
+        ..Default::default()
+    })
+    .into();
+    q.init();
+    assert_eq!(q.get_insert_after_current(), Some((vec![], 1)));
+    q.set_index_inner(&[1, 0, 1], 0, vec![], false);
+    assert_eq!(q.get_current_song(), Some(&3));
+    let (path, index) = q.get_insert_after_current().unwrap();
+    assert_eq!((path.as_slice(), index), (&[1, 0][..], 2));
+    assert!(q.get_item_at_index_mut(&path, 0).unwrap().insert(
+        vec![QueueContent::Song(4).into()],
+        index,
+        false
+    ));
+    assert_eq!(q.get_next_song(), Some(&4));
+    // nothing to play
+    let q: Queue = QueueContent::Folder(QueueFolder::default()).into();
+    assert_eq!(q.get_insert_after_current(), None);
+}
+
+#[test]
+fn test_go_back() {
+    let mut disabled: Queue = QueueContent::Song(2).into();
+    disabled.set_enabled(false);
+    let folder = |content| -> Queue {
+        QueueContent::Folder(QueueFolder {
+            content,
+            ..Default::default()
+        })
+        .into()
+    };
+    let mut q = folder(vec![
+        QueueContent::Song(1).into(),
+        disabled,
+        folder(vec![
+            QueueContent::Song(3).into(),
+            QueueContent::Song(4).into(),
+        ]),
+        QueueContent::Loop(
+            2,
+            0,
+            Box::new(folder(vec![
+                QueueContent::Song(5).into(),
+                QueueContent::Song(6).into(),
+            ])),
+        )
+        .into(),
+    ]);
+    q.init();
+    assert!(!q.has_previous());
+    let mut forward = vec![*q.get_current_song().unwrap()];
+    while q.advance_index_inner() {
+        forward.push(*q.get_current_song().unwrap());
+        if forward.len() == 7 {
+            break;
+        }
+    }
+    assert_eq!(forward, [1, 3, 4, 5, 6, 5, 6]);
+    // going back goes through the loop's repetitions and skips the disabled song
+    let mut back = vec![];
+    while q.has_previous() {
+        assert!(q.go_back_index_inner());
+        back.push(*q.get_current_song().unwrap());
+    }
+    assert_eq!(back, [5, 6, 5, 4, 3, 1]);
+    assert!(!q.go_back_index_inner());
+    assert_eq!(q.get_current_song(), Some(&1));
+}
+
+#[test]
+fn test_has_infinite_loop() {
+    let song: Queue = QueueContent::Song(1).into();
+    let finite: Queue = QueueContent::Loop(2, 0, Box::new(song.clone())).into();
+    let mut infinite: Queue = QueueContent::Loop(0, 0, Box::new(song.clone())).into();
+    let folder = |content| -> Queue {
+        QueueContent::Folder(QueueFolder {
+            content,
+            ..Default::default()
+        })
+        .into()
+    };
+    assert!(!folder(vec![song.clone(), finite.clone()]).has_infinite_loop());
+    assert!(folder(vec![song.clone(), folder(vec![infinite.clone()])]).has_infinite_loop());
+    infinite.set_enabled(false);
+    assert!(!folder(vec![song, infinite]).has_infinite_loop());
+}
@@ -0,0 +1,120 @@
+use std::collections::{HashMap, HashSet};
+
+/// Maps the lowercased trigrams (3 chars) of names (artist names, album names, song titles) to their ids,
+/// so a search only has to look at names which contain all trigrams of the search text
+/// instead of running a regex over every name in the library.
+///
+/// Use `set` and `remove`/`retain` when the names change, unchanged names are skipped,
+/// so updating the index after a small change to the library is cheap.
+#[derive(Debug, Default)]
+pub struct SearchIndex {
+    trigrams: HashMap<[char; 3], HashSet<u64>>,
+    /// the lowercased names which are in the index
+    names: HashMap<u64, String>,
+}
+
+impl SearchIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+    /// adds the name with this id, or updates it if it changed
+    pub fn set(&mut self, id: u64, name: &str) {
+        let name = name.to_lowercase();
+        if self.names.get(&id) == Some(&name) {
+            return;
+        }
+        self.remove(id);
+        for trigram in trigrams(&name) {
+            self.trigrams.entry(trigram).or_default().insert(id);
+        }
+        self.names.insert(id, name);
+    }
+    pub fn remove(&mut self, id: u64) {
+        if let Some(name) = self.names.remove(&id) {
+            for trigram in trigrams(&name) {
+                if let Some(ids) = self.trigrams.get_mut(&trigram) {
+                    ids.remove(&id);
+                    if ids.is_empty() {
+                        self.trigrams.remove(&trigram);
+                    }
+                }
+            }
+        }
+    }
+    /// removes all ids for which `keep` returns false
+    pub fn retain(&mut self, mut keep: impl FnMut(u64) -> bool) {
+        let removed = self
+            .names
+            .keys()
+            .copied()
+            .filter(|id| !keep(*id))
+            .collect::<Vec<_>>();
+        for id in removed {
+            self.remove(id);
+        }
+    }
+    /// The ids of all names which may contain `search` (ignoring case).
+    /// Returns `None` if the index can't help, because `search` is shorter than 3 chars
+    /// or isn't plain text (contains regex syntax), in which case every name is a candidate.
+    pub fn candidates(&self, search: &str) -> Option<HashSet<u64>> {
+        if search.contains(|ch| "\\.+*?()|[]{}^$".contains(ch)) {
+            return None;
+        }
+        let mut lists = trigrams(&search.to_lowercase())
+            .map(|trigram| self.trigrams.get(&trigram))
+            .collect::<Option<Vec<_>>>()
+            .unwrap_or_default();
+        if lists.is_empty() {
+            // search is too short, or one of the trigrams isn't in any name
+            return if search.chars().count() < 3 {
+                None
+            } else {
+                Some(HashSet::new())
+            };
+        }
+        // intersect, starting with the smallest set
+        lists.sort_unstable_by_key(|ids| ids.len());
+        let (first, rest) = lists.split_first().unwrap();
+        Some(
+            first
+                .iter()
+                .filter(|id| rest.iter().all(|ids| ids.contains(id)))
+                .copied()
+                .collect(),
+        )
+    }
+}
+
+fn trigrams(s: &str) -> impl Iterator<Item = [char; 3]> + '_ {
+    let mut chars = s.chars();
+    let mut window = [chars.next(), chars.next()];
+    chars.filter_map(move |ch| {
+        let trigram = [window[0]?, window[1]?, ch];
+        window = [window[1], Some(ch)];
+        Some(trigram)
+    })
+}
+
+#[test]
+fn test_search_index() {
+    let mut index = SearchIndex::new();
+    index.set(1, "The Beatles");
+    index.set(2, "Beat It");
+    index.set(3, "Pet Sounds");
+    assert_eq!(index.candidates("beat"), Some(HashSet::from([1, 2])));
+    assert_eq!(index.candidates("BEATL"), Some(HashSet::from([1])));
+    assert_eq!(index.candidates("xyz"), Some(HashSet::new()));
+    assert_eq!(index.candidates("be"), None);
+    assert_eq!(index.candidates("be.t"), None);
+    index.set(2, "Thriller");
+    assert_eq!(index.candidates("beat"), Some(HashSet::from([1])));
+    index.retain(|id| id != 1);
+    assert_eq!(index.candidates("beat"), Some(HashSet::new()));
+    assert_eq!(index.len(), 2);
+}
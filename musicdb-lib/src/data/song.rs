@@ -3,20 +3,39 @@ use std::{
     io::{Read, Write},
     mem::replace,
     path::PathBuf,
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
     thread::JoinHandle,
-    time::Instant,
+    time::{Instant, SystemTime},
 };
 
 use colorize::AnsiColor;
 
-use crate::load::ToFromBytes;
+use crate::{
+    load::{ErrorContext, ToFromBytes},
+    server::get::read_with_progress,
+};
 
 use super::{
     database::{ClientIo, Database},
     AlbumId, ArtistId, CoverId, DatabaseLocation, GeneralData, SongId,
 };
 
+/// A song's rating (`0` to `5`) is stored as a tag like `Rating=4`, so filters and `ShuffleWeight::Tag` can use it.
+/// Use `Action::SetSongRating` to change it.
+pub const RATING_TAG: &str = "Rating=";
+/// The disc a song is on is stored as a tag like `DiscNr=2` or `DiscNr=2/3` (total number of discs), which filldb adds.
+pub const DISC_TAG: &str = "DiscNr=";
+/// Embedded lyrics are stored as a tag like `Lyrics=<the lyrics>`, as plain text or in the LRC format (see `lyrics::Lyrics`).
+pub const LYRICS_TAG: &str = "Lyrics=";
+/// A manual volume adjustment for songs which are much louder or quieter than the rest of the library,
+/// stored as a tag like `Gain=-3.5` (in dB). Use `Action::SetSongGain` to change it.
+pub const GAIN_TAG: &str = "Gain=";
+/// `Gain=` values are clamped to `-MAX_GAIN_DB..=MAX_GAIN_DB`, so a typo can't make a song deafening
+pub const MAX_GAIN_DB: f32 = 24.0;
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct Song {
     pub id: SongId,
@@ -31,6 +50,14 @@ pub struct Song {
     /// song duration in milliseconds
     pub duration_millis: u64,
     pub general: GeneralData,
+    /// how often the song started playing on the server
+    pub play_count: u64,
+    /// when the song last started playing on the server
+    pub last_played: Option<SystemTime>,
+    /// average bitrate of the audio data in kbit/s, `None` if unknown (filldb sets this, older dbs don't have it until a rescan)
+    pub bitrate: Option<u32>,
+    /// the audio format, like `MP3`, `None` if unknown
+    pub codec: Option<String>,
     /// None => No cached data
     /// Some(Err) => No cached data yet, but a thread is working on loading it.
     /// Some(Ok(data)) => Cached data is available.
@@ -61,14 +88,99 @@ impl Song {
             file_size,
             duration_millis,
             general,
-            cached_data: CachedData(Arc::new(Mutex::new((Err(None), None)))),
+            play_count: 0,
+            last_played: None,
+            bitrate: None,
+            codec: None,
+            cached_data: CachedData(Arc::new(Mutex::new((Err(None), None))), Default::default()),
         }
     }
 
     pub fn cached_data(&self) -> &CachedData {
         &self.cached_data
     }
+
+    /// the rating from the song's `Rating=` tag, if it has a valid one
+    pub fn rating(&self) -> Option<u8> {
+        self.general.tags.iter().find_map(|tag| {
+            tag.strip_prefix(RATING_TAG)?
+                .parse::<u8>()
+                .ok()
+                .filter(|rating| *rating <= 5)
+        })
+    }
+    /// the manual volume adjustment (in dB) from the song's `Gain=` tag, if it has a valid one, clamped to `MAX_GAIN_DB`
+    pub fn gain_db(&self) -> Option<f32> {
+        self.general.tags.iter().find_map(|tag| {
+            tag.strip_prefix(GAIN_TAG)?
+                .trim()
+                .parse::<f32>()
+                .ok()
+                .filter(|v| v.is_finite())
+                .map(|v| v.clamp(-MAX_GAIN_DB, MAX_GAIN_DB))
+        })
+    }
+    /// `gain_db` as a linear volume multiplier, `1.0` if the song has no `Gain=` tag
+    pub fn gain(&self) -> f32 {
+        self.gain_db().map_or(1.0, |db| 10f32.powf(db / 20.0))
+    }
+    /// the number of the disc this song is on, from its `DiscNr=` tag
+    pub fn disc(&self) -> Option<u32> {
+        self.general.tags.iter().find_map(|tag| {
+            tag.strip_prefix(DISC_TAG)?
+                .split('/')
+                .next()?
+                .trim()
+                .parse()
+                .ok()
+        })
+    }
+}
+/// which of the song's `ReplayGain...Gain=` tags should be used to adjust its volume
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ReplayGainMode {
+    #[default]
+    Off,
+    /// use `ReplayGainTrackGain=`
+    Track,
+    /// use `ReplayGainAlbumGain=`, or `ReplayGainTrackGain=` if the song has no album gain
+    Album,
+}
+impl ReplayGainMode {
+    /// the linear volume multiplier for this song, `1.0` if the mode is off or the song has no (valid) tag
+    pub fn gain(&self, song: &Song) -> f32 {
+        let tag_db = |key: &str| {
+            song.general.tags.iter().find_map(|tag| {
+                let val = tag.strip_prefix(key)?.strip_prefix('=')?.trim();
+                let val = val
+                    .strip_suffix("dB")
+                    .or_else(|| val.strip_suffix("db"))
+                    .or_else(|| val.strip_suffix("DB"))
+                    .unwrap_or(val)
+                    .trim();
+                val.parse::<f32>().ok().filter(|v| v.is_finite())
+            })
+        };
+        let db = match self {
+            Self::Off => None,
+            Self::Track => tag_db("ReplayGainTrackGain"),
+            Self::Album => tag_db("ReplayGainAlbumGain").or_else(|| tag_db("ReplayGainTrackGain")),
+        };
+        db.map(|db| 10f32.powf(db / 20.0)).unwrap_or(1.0)
+    }
+}
+impl std::str::FromStr for ReplayGainMode {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Self::Off),
+            "track" => Ok(Self::Track),
+            "album" => Ok(Self::Album),
+            _ => Err(format!("expected `off`, `track` or `album`, not `{s}`")),
+        }
+    }
 }
+
 impl CachedData {
     pub fn uncache_data(&self) -> Result<bool, ()> {
         let mut cached = self.0.lock().unwrap();
@@ -160,9 +272,16 @@ impl CachedData {
         } else {
             Ok(db.get_path(&song.location))
         };
+        // until the real size is known, assume the file still has the size it had when it was added
+        let progress = Arc::clone(&self.1);
+        progress.loaded.store(0, Ordering::Relaxed);
+        progress
+            .total
+            .store((song.file_size as usize).max(1), Ordering::Relaxed);
         cd.0 = Ok(Err(std::thread::spawn(move || {
-            let data = Self::load_data(src)?;
-            Some(Arc::new(data))
+            let data = Self::load_data(src, &progress);
+            progress.total.store(0, Ordering::Relaxed);
+            Some(Arc::new(data?))
         })));
         Ok(started())
     }
@@ -184,6 +303,18 @@ impl CachedData {
             None
         }
     }
+    /// While a thread is loading the song's data, how much of it was loaded so far, from `0.0` to `1.0`.
+    /// `None` if the data is cached or isn't being loaded.
+    /// This doesn't lock the mutex, so it can be used while another thread is in `cached_data_await`.
+    pub fn loading_progress(&self) -> Option<f32> {
+        let total = self.1.total.load(Ordering::Relaxed);
+        if total == 0 {
+            None
+        } else {
+            let loaded = self.1.loaded.load(Ordering::Relaxed);
+            Some((loaded as f32 / total as f32).min(1.0))
+        }
+    }
     /// Gets the cached data, if available.
     /// If a thread is running to load the data, it is awaited.
     /// This function doesn't block.
@@ -211,11 +342,19 @@ impl CachedData {
                 Arc<Mutex<crate::server::get::Client<Box<dyn ClientIo>>>>,
             ),
         >,
+        progress: &LoadingProgress,
     ) -> Option<Vec<u8>> {
+        let progress = |loaded, total: usize| {
+            progress.total.store(total.max(1), Ordering::Relaxed);
+            progress.loaded.store(loaded, Ordering::Relaxed);
+        };
         match src {
             Ok(path) => {
                 eprintln!("[{}] loading song from {:?}", "INFO".cyan(), path);
-                match std::fs::read(&path) {
+                match std::fs::File::open(&path).and_then(|mut file| {
+                    let len = file.metadata()?.len() as usize;
+                    read_with_progress(&mut file, len, progress)
+                }) {
                     Ok(v) => {
                         eprintln!("[{}] loaded song from {:?}", "INFO".green(), path);
                         Some(v)
@@ -231,7 +370,7 @@ impl CachedData {
                 match dlcon
                     .lock()
                     .unwrap()
-                    .song_file(id)
+                    .song_file_with_progress(id, progress)
                     .expect("problem with downloader connection...")
                 {
                     Ok(data) => Some(data),
@@ -271,26 +410,71 @@ impl ToFromBytes for Song {
         self.file_size.to_bytes(s)?;
         self.duration_millis.to_bytes(s)?;
         self.general.to_bytes(s)?;
+        self.play_count.to_bytes(s)?;
+        self.last_played.to_bytes(s)?;
+        self.bitrate.to_bytes(s)?;
+        self.codec.to_bytes(s)?;
         Ok(())
     }
     fn from_bytes<T>(s: &mut T) -> Result<Self, std::io::Error>
     where
         T: Read,
     {
-        Ok(Self {
-            id: ToFromBytes::from_bytes(s)?,
-            location: ToFromBytes::from_bytes(s)?,
-            file_last_modified_unix_timestamp: ToFromBytes::from_bytes(s)?,
-            title: ToFromBytes::from_bytes(s)?,
-            album: ToFromBytes::from_bytes(s)?,
-            artist: ToFromBytes::from_bytes(s)?,
-            more_artists: ToFromBytes::from_bytes(s)?,
-            cover: ToFromBytes::from_bytes(s)?,
-            file_size: ToFromBytes::from_bytes(s)?,
-            duration_millis: ToFromBytes::from_bytes(s)?,
-            general: ToFromBytes::from_bytes(s)?,
-            cached_data: CachedData(Arc::new(Mutex::new((Err(None), None)))),
-        })
+        let mut song = Self::from_bytes_without_quality(s)?;
+        song.bitrate = ToFromBytes::from_bytes(s)
+            .context("bitrate")
+            .context("Song")?;
+        song.codec = ToFromBytes::from_bytes(s)
+            .context("codec")
+            .context("Song")?;
+        Ok(song)
+    }
+}
+impl Song {
+    /// the encoding used before `bitrate` and `codec` were added (dbfile versions before 6)
+    pub fn from_bytes_without_quality<T>(s: &mut T) -> Result<Self, std::io::Error>
+    where
+        T: Read,
+    {
+        let mut song = Self::from_bytes_without_play_stats(s)?;
+        song.play_count = ToFromBytes::from_bytes(s)
+            .context("play_count")
+            .context("Song")?;
+        song.last_played = ToFromBytes::from_bytes(s)
+            .context("last_played")
+            .context("Song")?;
+        Ok(song)
+    }
+    /// the encoding used before `play_count` and `last_played` were added (dbfile versions before 3)
+    pub fn from_bytes_without_play_stats<T>(s: &mut T) -> Result<Self, std::io::Error>
+    where
+        T: Read,
+    {
+        (|| {
+            Ok(Self {
+                id: ToFromBytes::from_bytes(s).context("id")?,
+                location: ToFromBytes::from_bytes(s).context("location")?,
+                file_last_modified_unix_timestamp: ToFromBytes::from_bytes(s)
+                    .context("file_last_modified_unix_timestamp")?,
+                title: ToFromBytes::from_bytes(s).context("title")?,
+                album: ToFromBytes::from_bytes(s).context("album")?,
+                artist: ToFromBytes::from_bytes(s).context("artist")?,
+                more_artists: ToFromBytes::from_bytes(s).context("more_artists")?,
+                cover: ToFromBytes::from_bytes(s).context("cover")?,
+                file_size: ToFromBytes::from_bytes(s).context("file_size")?,
+                duration_millis: ToFromBytes::from_bytes(s).context("duration_millis")?,
+                general: ToFromBytes::from_bytes(s).context("general")?,
+                play_count: 0,
+                last_played: None,
+                bitrate: None,
+                codec: None,
+                cached_data: CachedData(
+                    Arc::new(Mutex::new((Err(None), None))),
+                    Default::default(),
+                ),
+            })
+        })()
+        .context("Song")
     }
 }
 
@@ -302,7 +486,15 @@ pub struct CachedData(
             Option<usize>,
         )>,
     >,
+    pub Arc<LoadingProgress>,
 );
+/// How many of a song's bytes the thread started by `CachedData` has loaded, see `CachedData::loading_progress`.
+#[derive(Debug, Default)]
+pub struct LoadingProgress {
+    pub loaded: AtomicUsize,
+    /// `0` if no thread is loading the song
+    pub total: AtomicUsize,
+}
 impl PartialEq for CachedData {
     fn eq(&self, _other: &Self) -> bool {
         // for testing
@@ -311,6 +503,6 @@ impl PartialEq for CachedData {
 }
 impl Clone for CachedData {
     fn clone(&self) -> Self {
-        Self(Arc::clone(&self.0))
+        Self(Arc::clone(&self.0), Arc::clone(&self.1))
     }
 }
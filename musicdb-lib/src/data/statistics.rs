@@ -0,0 +1,63 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::database::Database;
+
+/// Totals for the whole library, see `Database::statistics`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LibraryStatistics {
+    pub songs: usize,
+    pub albums: usize,
+    pub artists: usize,
+    pub covers: usize,
+    /// sum of all songs' durations
+    pub duration_millis: u64,
+    /// sum of all songs' file sizes, in bytes
+    pub file_size: u64,
+    /// songs which aren't on an album
+    pub singles: usize,
+    /// songs with a duration of 0 (unknown)
+    pub songs_without_duration: usize,
+    /// for each flag (a tag without `=`), how many songs have it
+    pub flags: BTreeMap<String, usize>,
+    /// for each property (`Key=` of `Key=Value` tags), how many songs have it
+    pub properties: BTreeMap<String, usize>,
+}
+
+impl Database {
+    pub fn statistics(&self) -> LibraryStatistics {
+        let mut stats = LibraryStatistics {
+            songs: self.songs().len(),
+            albums: self.albums().len(),
+            artists: self.artists().len(),
+            covers: self.covers().len(),
+            ..Default::default()
+        };
+        for song in self.songs().values() {
+            stats.duration_millis = stats.duration_millis.saturating_add(song.duration_millis);
+            stats.file_size = stats.file_size.saturating_add(song.file_size);
+            if song.album.is_none() {
+                stats.singles += 1;
+            }
+            if song.duration_millis == 0 {
+                stats.songs_without_duration += 1;
+            }
+            // songs with a tag twice (or two `Genre=` tags) are only counted once
+            let mut flags = BTreeSet::new();
+            let mut properties = BTreeSet::new();
+            for tag in &song.general.tags {
+                if let Some(i) = tag.find('=') {
+                    properties.insert(&tag[..=i]);
+                } else {
+                    flags.insert(tag.as_str());
+                }
+            }
+            for flag in flags {
+                *stats.flags.entry(flag.to_owned()).or_default() += 1;
+            }
+            for property in properties {
+                *stats.properties.entry(property.to_owned()).or_default() += 1;
+            }
+        }
+        stats
+    }
+}
@@ -0,0 +1,91 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    fs,
+    hash::{Hash, Hasher},
+    io::Cursor,
+    path::{Path, PathBuf},
+};
+
+use colorize::AnsiColor;
+use image::{DynamicImage, ImageOutputFormat};
+
+use super::{database::Database, CoverId};
+
+/// thumbnails fit into a square of this size (in pixels)
+pub const THUMBNAIL_SIZE: u32 = 128;
+
+impl Database {
+    /// The path of the cover's file and the path its thumbnail is cached at (in `<db_dir>/thumbnails/`).
+    /// Use `load_or_create_thumbnail` with these after unlocking the database, because creating a thumbnail can be slow.
+    pub fn cover_thumbnail_paths(&self, id: CoverId) -> Option<(PathBuf, PathBuf)> {
+        let cover = self.covers().get(&id)?;
+        // named after the cover's path, not its id, because ids of removed covers are reused
+        let mut hasher = DefaultHasher::new();
+        cover.location.rel_path.hash(&mut hasher);
+        Some((
+            self.get_path(&cover.location),
+            self.db_dir
+                .join("thumbnails")
+                .join(format!("{:016x}.jpg", hasher.finish())),
+        ))
+    }
+}
+
+/// Returns the thumbnail at `thumbnail_path`, or, if it doesn't exist or is older than the cover,
+/// creates it from the cover at `cover_path` and saves it there.
+pub fn load_or_create_thumbnail(
+    cover_path: &Path,
+    thumbnail_path: &Path,
+) -> Result<Vec<u8>, String> {
+    let modified = |path: &Path| path.metadata().and_then(|m| m.modified()).ok();
+    if let (Some(thumbnail), Some(cover)) = (modified(thumbnail_path), modified(cover_path)) {
+        if thumbnail >= cover {
+            if let Ok(bytes) = fs::read(thumbnail_path) {
+                return Ok(bytes);
+            }
+        }
+    }
+    let cover = fs::read(cover_path).map_err(|e| format!("can't read {cover_path:?}: {e}"))?;
+    let thumbnail = create_thumbnail(&cover, THUMBNAIL_SIZE)
+        .map_err(|e| format!("can't create thumbnail of {cover_path:?}: {e}"))?;
+    if let Err(e) = thumbnail_path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|()| fs::write(thumbnail_path, &thumbnail))
+    {
+        eprintln!(
+            "[{}] Couldn't save thumbnail {thumbnail_path:?}: {e}",
+            "WARN".yellow()
+        );
+    }
+    Ok(thumbnail)
+}
+
+/// Scales the image down to fit into a `size`x`size` square (keeping its aspect ratio) and encodes it as jpeg.
+/// Smaller images aren't scaled up.
+pub fn create_thumbnail(image: &[u8], size: u32) -> image::ImageResult<Vec<u8>> {
+    let image = image::load_from_memory(image)?;
+    let image = if image.width() > size || image.height() > size {
+        image.thumbnail(size, size)
+    } else {
+        image
+    };
+    let mut out = Cursor::new(vec![]);
+    DynamicImage::ImageRgb8(image.to_rgb8()).write_to(&mut out, ImageOutputFormat::Jpeg(85))?;
+    Ok(out.into_inner())
+}
+
+#[test]
+fn test_create_thumbnail() {
+    let mut png = Cursor::new(vec![]);
+    DynamicImage::new_rgba8(300, 150)
+        .write_to(&mut png, ImageOutputFormat::Png)
+        .unwrap();
+    let thumbnail = create_thumbnail(png.get_ref(), THUMBNAIL_SIZE).unwrap();
+    let thumbnail = image::load_from_memory(&thumbnail).unwrap();
+    assert_eq!((thumbnail.width(), thumbnail.height()), (128, 64));
+    // small images stay the same size
+    let thumbnail = create_thumbnail(&create_thumbnail(png.get_ref(), 16).unwrap(), 64).unwrap();
+    let thumbnail = image::load_from_memory(&thumbnail).unwrap();
+    assert_eq!((thumbnail.width(), thumbnail.height()), (16, 8));
+}
@@ -1,5 +1,6 @@
 pub mod data;
 pub mod load;
-#[cfg(feature = "playback")]
+#[cfg(any(feature = "playback", feature = "render"))]
 pub mod player;
 pub mod server;
+mod test;
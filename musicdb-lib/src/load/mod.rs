@@ -1,7 +1,11 @@
 use std::{
-    collections::{HashMap, VecDeque},
-    io::{Read, Write},
-    path::PathBuf,
+    cell::Cell,
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    ffi::OsString,
+    fs::{self, File},
+    io::{BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 pub trait ToFromBytes: Sized {
@@ -18,6 +22,280 @@ pub trait ToFromBytes: Sized {
     }
 }
 
+/// The dbfile format:
+///
+/// - `DB_MAGIC` (8 bytes)
+/// - the format version (`u32`, big endian)
+/// - artists, albums, songs and covers (each a `HashMap<id, value>` encoded with `ToFromBytes`)
+/// - the CRC-32 of the artists, albums, songs and covers (`u32`, big endian)
+///
+/// Increase `DB_VERSION` whenever the `ToFromBytes` encoding of any of these changes,
+/// and add a migration for the previous version to `Database::load_database_from_dir`.
+/// The `playlists` and `playbackstate` files (see `Database::load_playlists` and `Database::load_playback_state`)
+/// start with the same header, so `DB_VERSION` also has to be increased when the encoding of `Queue` changes.
+///
+/// Version history:
+/// - 0: no header, files start with the artists. Written by all versions before the header was added.
+/// - 1: added the header, no other changes
+/// - 2: added the checksum at the end
+/// - 3: added `play_count` and `last_played` to songs
+/// - 4: added `aliases` to artists
+/// - 5: lengths of strings and collections are varints (see `write_len`) instead of `u64`s
+/// - 6: added `bitrate` and `codec` to songs
+/// - 7: added `weight` to queue folders. This only affects the `playlists` and `playbackstate` files, not the dbfile.
+pub const DB_VERSION: u32 = 7;
+/// Since the artists map used to be the first thing in the file, and its length is encoded as a big-endian `u64`,
+/// a file without header would have to contain about 7.9 quintillion artists to start with these bytes.
+pub const DB_MAGIC: [u8; 8] = *b"musicdb\0";
+
+pub fn write_db_header<T: Write>(s: &mut T) -> Result<(), std::io::Error> {
+    s.write_all(&DB_MAGIC)?;
+    DB_VERSION.to_bytes(s)
+}
+/// The rest of the file after `read_db_header`, with the bytes which weren't part of a header put back in front.
+pub type DbReader<T> = std::io::Chain<std::io::Cursor<Vec<u8>>, T>;
+/// Reads the header and returns the format version and a reader for the rest of the file.
+/// If there is no header (version 0), the bytes which were read are put back.
+pub fn read_db_header<T: Read>(mut s: T) -> Result<(u32, DbReader<T>), std::io::Error> {
+    let mut magic = [0u8; 8];
+    s.read_exact(&mut magic)?;
+    if magic == DB_MAGIC {
+        let version = u32::from_bytes(&mut s)?;
+        Ok((version, std::io::Cursor::new(vec![]).chain(s)))
+    } else {
+        Ok((0, std::io::Cursor::new(magic.to_vec()).chain(s)))
+    }
+}
+
+thread_local! {
+    static FIXED_WIDTH_LENGTHS: Cell<bool> = const { Cell::new(false) };
+    static FOLDERS_WITHOUT_WEIGHT: Cell<bool> = const { Cell::new(false) };
+}
+/// Writes the length of a string or collection as a LEB128 varint:
+/// 7 bits per byte, lowest bits first, the highest bit is set if more bytes follow.
+/// Lengths below 128 only need one byte instead of eight.
+/// While a `fixed_width_lengths` guard exists, writes a `u64` instead, like dbfiles before version 5.
+pub fn write_len<T: Write>(len: usize, s: &mut T) -> Result<(), std::io::Error> {
+    if FIXED_WIDTH_LENGTHS.get() {
+        return len.to_bytes(s);
+    }
+    let mut len = len as u64;
+    loop {
+        let byte = (len & 0x7F) as u8;
+        len >>= 7;
+        if len == 0 {
+            return s.write_all(&[byte]);
+        }
+        s.write_all(&[byte | 0x80])?;
+    }
+}
+/// Reads a length written by `write_len`, or a `u64` while a `fixed_width_lengths` guard exists.
+pub fn read_len<T: Read>(s: &mut T) -> Result<usize, std::io::Error> {
+    if FIXED_WIDTH_LENGTHS.get() {
+        return usize::from_bytes(s);
+    }
+    let mut len = 0u64;
+    let mut byte = [0u8];
+    for shift in (0..64).step_by(7) {
+        s.read_exact(&mut byte)?;
+        let bits = (byte[0] & 0x7F) as u64;
+        if shift == 63 && bits > 1 {
+            break;
+        }
+        len |= bits << shift;
+        if byte[0] & 0x80 == 0 {
+            return usize::try_from(len).map_err(|_| {
+                std::io::Error::new(std::io::ErrorKind::InvalidData, "length too large")
+            });
+        }
+    }
+    Err(std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        "invalid varint length",
+    ))
+}
+/// While the returned guard exists, `read_len` and `write_len` (on this thread) use lengths encoded as `u64`s,
+/// which is how they were encoded before dbfile version 5.
+pub fn fixed_width_lengths() -> FixedWidthLengths {
+    FixedWidthLengths(FIXED_WIDTH_LENGTHS.replace(true))
+}
+/// see `fixed_width_lengths`
+pub struct FixedWidthLengths(bool);
+impl Drop for FixedWidthLengths {
+    fn drop(&mut self) {
+        FIXED_WIDTH_LENGTHS.set(self.0);
+    }
+}
+/// While the returned guard exists, `QueueFolder::from_bytes` (on this thread) doesn't read the folder's `weight`,
+/// which is how folders were encoded before version 7.
+pub fn folders_without_weight() -> FoldersWithoutWeight {
+    FoldersWithoutWeight(FOLDERS_WITHOUT_WEIGHT.replace(true))
+}
+/// see `folders_without_weight`
+pub struct FoldersWithoutWeight(bool);
+impl Drop for FoldersWithoutWeight {
+    fn drop(&mut self) {
+        FOLDERS_WITHOUT_WEIGHT.set(self.0);
+    }
+}
+pub(crate) fn reading_folders_without_weight() -> bool {
+    FOLDERS_WITHOUT_WEIGHT.get()
+}
+
+/// Adds the name of a type or field to an error from `from_bytes`,
+/// so that it says where decoding failed, like `Song.location.rel_path: failed to fill whole buffer`.
+pub trait ErrorContext {
+    fn context(self, name: &'static str) -> Self;
+}
+impl<V> ErrorContext for Result<V, std::io::Error> {
+    fn context(self, name: &'static str) -> Self {
+        self.map_err(|e| {
+            let kind = e.kind();
+            let mut error = if e.get_ref().is_some_and(|e| e.is::<ContextError>()) {
+                *e.into_inner().unwrap().downcast::<ContextError>().unwrap()
+            } else {
+                ContextError {
+                    path: vec![],
+                    error: e,
+                }
+            };
+            error.path.insert(0, name);
+            std::io::Error::new(kind, error)
+        })
+    }
+}
+/// The error wrapped by `ErrorContext::context`. The `std::io::Error` keeps the original error's kind.
+#[derive(Debug)]
+pub struct ContextError {
+    /// the outermost type or field comes first
+    pub path: Vec<&'static str>,
+    pub error: std::io::Error,
+}
+impl std::fmt::Display for ContextError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.path.join("."), self.error)
+    }
+}
+impl std::error::Error for ContextError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// Writes a file so that a crash (or an error in `write`) can never leave a partially written file at `path`:
+/// everything is written to `<path>.tmp`, which is then renamed to `path`.
+/// If `backup` is true, the previous version of the file (if there is one) is kept as `<path>.bak`.
+/// If this returns an error, `path` still contains the old data.
+pub fn write_file_atomically(
+    path: &Path,
+    backup: bool,
+    write: impl FnOnce(&mut BufWriter<File>) -> Result<(), std::io::Error>,
+) -> Result<(), std::io::Error> {
+    let tmp = path_with_suffix(path, ".tmp");
+    let written = (|| {
+        let mut file = BufWriter::new(File::create(&tmp)?);
+        write(&mut file)?;
+        // make sure the data is on disk before the rename, otherwise a crash could still leave an empty file
+        file.into_inner().map_err(|e| e.into_error())?.sync_all()
+    })();
+    if let Err(e) = written {
+        _ = fs::remove_file(&tmp);
+        return Err(e);
+    }
+    if backup && path.try_exists()? {
+        let bak = path_with_suffix(path, ".bak");
+        _ = fs::remove_file(&bak);
+        // a hard link means we don't have to copy the file, but not every filesystem supports them
+        if fs::hard_link(path, &bak).is_err() {
+            fs::copy(path, &bak)?;
+        }
+    }
+    fs::rename(&tmp, path)
+}
+fn path_with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Wraps a reader or writer and calculates the CRC-32 (the one used by zip, png, ...)
+/// of all bytes that pass through it. Also counts the bytes, so errors can include the position.
+pub struct Checksum<T> {
+    inner: T,
+    crc: u32,
+    bytes: u64,
+}
+impl<T> Checksum<T> {
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            crc: 0xFFFFFFFF,
+            bytes: 0,
+        }
+    }
+    pub fn checksum(&self) -> u32 {
+        !self.crc
+    }
+    /// number of bytes read or written so far
+    pub fn position(&self) -> u64 {
+        self.bytes
+    }
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+    fn update(&mut self, buf: &[u8]) {
+        for byte in buf {
+            self.crc ^= *byte as u32;
+            for _ in 0..8 {
+                self.crc = (self.crc >> 1) ^ (0xEDB88320 & (self.crc & 1).wrapping_neg());
+            }
+        }
+        self.bytes += buf.len() as u64;
+    }
+}
+impl<T: Read> Checksum<T> {
+    /// reads the checksum (which isn't part of the checksummed data) and compares it
+    pub fn verify(&mut self) -> Result<(), std::io::Error> {
+        let expected = u32::from_bytes(&mut self.inner)?;
+        if expected == self.checksum() {
+            Ok(())
+        } else {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "checksum mismatch: expected {expected:08x}, but data has {:08x}",
+                    self.checksum()
+                ),
+            ))
+        }
+    }
+}
+impl<T: Write> Checksum<T> {
+    /// writes the checksum (it isn't included in the checksum itself)
+    pub fn finish(mut self) -> Result<T, std::io::Error> {
+        let checksum = self.checksum();
+        checksum.to_bytes(&mut self.inner)?;
+        Ok(self.inner)
+    }
+}
+impl<T: Read> Read for Checksum<T> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.update(&buf[..len]);
+        Ok(len)
+    }
+}
+impl<T: Write> Write for Checksum<T> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let len = self.inner.write(buf)?;
+        self.update(&buf[..len]);
+        Ok(len)
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
 // impl ToFromBytes
 
 // common types (String, Vec, ...)
@@ -27,14 +305,14 @@ impl ToFromBytes for String {
     where
         T: Write,
     {
-        self.len().to_bytes(s)?;
+        write_len(self.len(), s)?;
         s.write_all(self.as_bytes())
     }
     fn from_bytes<T>(s: &mut T) -> Result<Self, std::io::Error>
     where
         T: Read,
     {
-        let len = ToFromBytes::from_bytes(s)?;
+        let len = read_len(s)?;
         let mut buf = vec![0; len];
         s.read_exact(&mut buf)?;
         Ok(String::from_utf8_lossy(&buf).into_owned())
@@ -63,7 +341,7 @@ where
     where
         T: Write,
     {
-        self.len().to_bytes(s)?;
+        write_len(self.len(), s)?;
         for elem in self {
             elem.to_bytes(s)?;
         }
@@ -73,7 +351,7 @@ where
     where
         T: Read,
     {
-        let len = ToFromBytes::from_bytes(s)?;
+        let len = read_len(s)?;
         let mut buf = Vec::with_capacity(len);
         for _ in 0..len {
             buf.push(ToFromBytes::from_bytes(s)?);
@@ -89,7 +367,7 @@ where
     where
         T: Write,
     {
-        self.len().to_bytes(s)?;
+        write_len(self.len(), s)?;
         for elem in self {
             elem.to_bytes(s)?;
         }
@@ -99,7 +377,7 @@ where
     where
         T: Read,
     {
-        let len = ToFromBytes::from_bytes(s)?;
+        let len = read_len(s)?;
         let mut buf = VecDeque::with_capacity(len);
         for _ in 0..len {
             buf.push_back(ToFromBytes::from_bytes(s)?);
@@ -145,7 +423,7 @@ where
     where
         T: Write,
     {
-        self.len().to_bytes(s)?;
+        write_len(self.len(), s)?;
         for (key, val) in self.iter() {
             key.to_bytes(s)?;
             val.to_bytes(s)?;
@@ -156,7 +434,7 @@ where
     where
         T: Read,
     {
-        let len = ToFromBytes::from_bytes(s)?;
+        let len = read_len(s)?;
         let mut o = Self::with_capacity(len);
         for _ in 0..len {
             o.insert(ToFromBytes::from_bytes(s)?, ToFromBytes::from_bytes(s)?);
@@ -164,6 +442,60 @@ where
         Ok(o)
     }
 }
+impl<C> ToFromBytes for HashSet<C>
+where
+    C: ToFromBytes + std::cmp::Eq + std::hash::Hash,
+{
+    fn to_bytes<T>(&self, s: &mut T) -> Result<(), std::io::Error>
+    where
+        T: Write,
+    {
+        write_len(self.len(), s)?;
+        for elem in self.iter() {
+            elem.to_bytes(s)?;
+        }
+        Ok(())
+    }
+    fn from_bytes<T>(s: &mut T) -> Result<Self, std::io::Error>
+    where
+        T: Read,
+    {
+        let len = read_len(s)?;
+        let mut o = Self::with_capacity(len);
+        for _ in 0..len {
+            o.insert(ToFromBytes::from_bytes(s)?);
+        }
+        Ok(o)
+    }
+}
+impl<K, V> ToFromBytes for BTreeMap<K, V>
+where
+    K: ToFromBytes + std::cmp::Ord,
+    V: ToFromBytes,
+{
+    fn to_bytes<T>(&self, s: &mut T) -> Result<(), std::io::Error>
+    where
+        T: Write,
+    {
+        write_len(self.len(), s)?;
+        for (key, val) in self.iter() {
+            key.to_bytes(s)?;
+            val.to_bytes(s)?;
+        }
+        Ok(())
+    }
+    fn from_bytes<T>(s: &mut T) -> Result<Self, std::io::Error>
+    where
+        T: Read,
+    {
+        let len = read_len(s)?;
+        let mut o = Self::new();
+        for _ in 0..len {
+            o.insert(ToFromBytes::from_bytes(s)?, ToFromBytes::from_bytes(s)?);
+        }
+        Ok(o)
+    }
+}
 
 // - for (i/u)(size/8/16/32/64/128)
 
@@ -355,3 +687,385 @@ impl ToFromBytes for i128 {
         Ok(Self::from_be_bytes(b))
     }
 }
+
+// - bool, floats
+
+impl ToFromBytes for bool {
+    fn to_bytes<T>(&self, s: &mut T) -> Result<(), std::io::Error>
+    where
+        T: Write,
+    {
+        s.write_all(&[*self as u8])
+    }
+    fn from_bytes<T>(s: &mut T) -> Result<Self, std::io::Error>
+    where
+        T: Read,
+    {
+        Ok(u8::from_bytes(s)? != 0)
+    }
+}
+// floats are encoded as their bits (IEEE 754, big endian), so NaN payloads and -0.0 are preserved
+impl ToFromBytes for f32 {
+    fn to_bytes<T>(&self, s: &mut T) -> Result<(), std::io::Error>
+    where
+        T: Write,
+    {
+        self.to_bits().to_bytes(s)
+    }
+    fn from_bytes<T>(s: &mut T) -> Result<Self, std::io::Error>
+    where
+        T: Read,
+    {
+        Ok(Self::from_bits(u32::from_bytes(s)?))
+    }
+}
+impl ToFromBytes for f64 {
+    fn to_bytes<T>(&self, s: &mut T) -> Result<(), std::io::Error>
+    where
+        T: Write,
+    {
+        self.to_bits().to_bytes(s)
+    }
+    fn from_bytes<T>(s: &mut T) -> Result<Self, std::io::Error>
+    where
+        T: Read,
+    {
+        Ok(Self::from_bits(u64::from_bytes(s)?))
+    }
+}
+
+// - time
+
+/// encoded as milliseconds (`u64`), so anything smaller than a millisecond is lost
+impl ToFromBytes for Duration {
+    fn to_bytes<T>(&self, s: &mut T) -> Result<(), std::io::Error>
+    where
+        T: Write,
+    {
+        (self.as_millis().min(u64::MAX as u128) as u64).to_bytes(s)
+    }
+    fn from_bytes<T>(s: &mut T) -> Result<Self, std::io::Error>
+    where
+        T: Read,
+    {
+        Ok(Duration::from_millis(u64::from_bytes(s)?))
+    }
+}
+/// encoded as seconds since the unix epoch (`i64`, negative for times before 1970)
+/// plus nanoseconds (`u32`, always positive, so -0.5s is -1s + 500_000_000ns)
+impl ToFromBytes for SystemTime {
+    fn to_bytes<T>(&self, s: &mut T) -> Result<(), std::io::Error>
+    where
+        T: Write,
+    {
+        let (secs, nanos) = match self.duration_since(UNIX_EPOCH) {
+            Ok(d) => (d.as_secs() as i64, d.subsec_nanos()),
+            Err(e) => {
+                let d = e.duration();
+                if d.subsec_nanos() == 0 {
+                    (-(d.as_secs() as i64), 0)
+                } else {
+                    (-(d.as_secs() as i64) - 1, 1_000_000_000 - d.subsec_nanos())
+                }
+            }
+        };
+        secs.to_bytes(s)?;
+        nanos.to_bytes(s)
+    }
+    fn from_bytes<T>(s: &mut T) -> Result<Self, std::io::Error>
+    where
+        T: Read,
+    {
+        let secs = i64::from_bytes(s)?;
+        let nanos = u32::from_bytes(s)?;
+        let time = if secs >= 0 {
+            UNIX_EPOCH.checked_add(Duration::from_secs(secs as u64))
+        } else {
+            UNIX_EPOCH.checked_sub(Duration::from_secs(secs.unsigned_abs()))
+        };
+        time.and_then(|t| t.checked_add(Duration::from_nanos(nanos as u64)))
+            .ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("SystemTime out of range: {secs}s + {nanos}ns"),
+                )
+            })
+    }
+}
+
+// - tuples (elements are encoded one after the other)
+
+macro_rules! impl_to_from_bytes_tuple {
+    ($($t:ident $i:tt),*) => {
+        impl<$($t),*> ToFromBytes for ($($t,)*)
+        where
+            $($t: ToFromBytes,)*
+        {
+            fn to_bytes<T>(&self, s: &mut T) -> Result<(), std::io::Error>
+            where
+                T: Write,
+            {
+                $(self.$i.to_bytes(s)?;)*
+                Ok(())
+            }
+            fn from_bytes<T>(s: &mut T) -> Result<Self, std::io::Error>
+            where
+                T: Read,
+            {
+                Ok(($($t::from_bytes(s)?,)*))
+            }
+        }
+    };
+}
+impl_to_from_bytes_tuple!(A 0);
+impl_to_from_bytes_tuple!(A 0, B 1);
+impl_to_from_bytes_tuple!(A 0, B 1, C 2);
+impl_to_from_bytes_tuple!(A 0, B 1, C 2, D 3);
+
+#[test]
+fn test_to_from_bytes_bool_float_tuple() {
+    for v in [true, false] {
+        assert_eq!(
+            bool::from_bytes(&mut v.to_bytes_vec().as_slice()).unwrap(),
+            v
+        );
+    }
+    for v in [
+        0.0f32,
+        -0.0,
+        1.5,
+        f32::MIN_POSITIVE,
+        f32::MAX,
+        f32::INFINITY,
+        f32::NEG_INFINITY,
+        f32::NAN,
+        -f32::NAN,
+    ] {
+        let r = f32::from_bytes(&mut v.to_bytes_vec().as_slice()).unwrap();
+        assert_eq!(r.to_bits(), v.to_bits());
+    }
+    for v in [
+        0.0f64,
+        -0.0,
+        1.5,
+        f64::MIN_POSITIVE,
+        f64::MAX,
+        f64::INFINITY,
+        f64::NEG_INFINITY,
+        f64::NAN,
+        -f64::NAN,
+    ] {
+        let r = f64::from_bytes(&mut v.to_bytes_vec().as_slice()).unwrap();
+        assert_eq!(r.to_bits(), v.to_bits());
+    }
+    let v = (1u8, "two".to_owned(), (true, -3i64), Some(4.5f32));
+    assert_eq!(
+        <(u8, String, (bool, i64), Option<f32>)>::from_bytes(&mut v.to_bytes_vec().as_slice())
+            .unwrap(),
+        v
+    );
+}
+
+#[test]
+fn test_to_from_bytes_sets_maps() {
+    let set = HashSet::from([1u64, 5, 42, u64::MAX]);
+    assert_eq!(
+        HashSet::<u64>::from_bytes(&mut set.to_bytes_vec().as_slice()).unwrap(),
+        set
+    );
+    let map = BTreeMap::from([
+        ("b".to_owned(), vec![2u8]),
+        ("a".to_owned(), vec![]),
+        ("c".to_owned(), vec![3, 3]),
+    ]);
+    let bytes = map.to_bytes_vec();
+    assert_eq!(
+        BTreeMap::<String, Vec<u8>>::from_bytes(&mut bytes.as_slice()).unwrap(),
+        map
+    );
+    // same encoding as a `HashMap`
+    assert_eq!(
+        HashMap::<String, Vec<u8>>::from_bytes(&mut bytes.as_slice()).unwrap(),
+        map.into_iter().collect()
+    );
+    assert_eq!(
+        HashSet::<u64>::new().to_bytes_vec(),
+        Vec::<u64>::new().to_bytes_vec()
+    );
+}
+
+#[test]
+fn test_varint_len() {
+    for (len, encoded) in [
+        (0usize, vec![0u8]),
+        (1, vec![1]),
+        (127, vec![0x7F]),
+        (128, vec![0x80, 0x01]),
+        (300, vec![0xAC, 0x02]),
+        (16383, vec![0xFF, 0x7F]),
+        (16384, vec![0x80, 0x80, 0x01]),
+        (u32::MAX as usize, vec![0xFF, 0xFF, 0xFF, 0xFF, 0x0F]),
+    ] {
+        let mut b = vec![];
+        write_len(len, &mut b).unwrap();
+        assert_eq!(b, encoded, "{len}");
+        assert_eq!(read_len(&mut b.as_slice()).unwrap(), len);
+    }
+    let mut b = vec![];
+    write_len(usize::MAX, &mut b).unwrap();
+    assert_eq!(b.len(), 10);
+    assert_eq!(read_len(&mut b.as_slice()).unwrap(), usize::MAX);
+    // too long, more than 64 bits, or missing the last byte
+    assert!(read_len(&mut [0x80; 11].as_slice()).is_err());
+    assert!(
+        read_len(&mut [0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x02].as_slice())
+            .is_err()
+    );
+    assert!(read_len(&mut [0x80].as_slice()).is_err());
+    // old encoding
+    let b = ("abc".to_owned(), vec![1u8, 2]).to_bytes_vec();
+    assert_eq!(b.len(), 1 + 3 + 1 + 2);
+    let mut old = 3u64.to_be_bytes().to_vec();
+    old.extend(b"abc");
+    old.extend(2u64.to_be_bytes());
+    old.extend([1, 2]);
+    {
+        let _fixed = fixed_width_lengths();
+        assert_eq!(
+            <(String, Vec<u8>)>::from_bytes(&mut old.as_slice()).unwrap(),
+            ("abc".to_owned(), vec![1, 2])
+        );
+    }
+    assert_eq!(
+        <(String, Vec<u8>)>::from_bytes(&mut b.as_slice()).unwrap(),
+        ("abc".to_owned(), vec![1, 2])
+    );
+}
+
+#[test]
+fn test_to_from_bytes_time() {
+    let now = SystemTime::now();
+    assert_eq!(
+        SystemTime::from_bytes(&mut now.to_bytes_vec().as_slice()).unwrap(),
+        now
+    );
+    for t in [
+        UNIX_EPOCH,
+        UNIX_EPOCH - Duration::from_millis(1500),
+        UNIX_EPOCH - Duration::from_secs(86400 * 365 * 100),
+        UNIX_EPOCH + Duration::new(1, 999_999_999),
+    ] {
+        assert_eq!(
+            SystemTime::from_bytes(&mut t.to_bytes_vec().as_slice()).unwrap(),
+            t
+        );
+    }
+    let d = Duration::from_millis(123_456);
+    assert_eq!(
+        Duration::from_bytes(&mut d.to_bytes_vec().as_slice()).unwrap(),
+        d
+    );
+}
+
+#[test]
+fn test_checksum() {
+    let mut w = Checksum::new(vec![]);
+    w.write_all(b"123456789").unwrap();
+    // the standard CRC-32 check value
+    assert_eq!(w.checksum(), 0xCBF43926);
+    let b = w.finish().unwrap();
+    let mut r = Checksum::new(b.as_slice());
+    r.read_exact(&mut [0; 9]).unwrap();
+    r.verify().unwrap();
+    let mut b = b;
+    b[3] = b'x';
+    let mut r = Checksum::new(b.as_slice());
+    r.read_exact(&mut [0; 9]).unwrap();
+    assert!(r.verify().is_err());
+}
+
+#[test]
+fn test_write_file_atomically() {
+    let dir = crate::test::TestDir::new("atomic");
+    let path = dir.join("dbfile");
+    let write = |data: &'static [u8]| move |f: &mut BufWriter<File>| f.write_all(data);
+    write_file_atomically(&path, true, write(b"old")).unwrap();
+    assert!(!path_with_suffix(&path, ".bak").exists());
+    // a save which fails halfway through
+    let err = write_file_atomically(&path, true, |f| {
+        f.write_all(b"partial")?;
+        Err(std::io::Error::other("simulated crash"))
+    });
+    assert!(err.is_err());
+    assert_eq!(fs::read(&path).unwrap(), b"old");
+    assert!(!path_with_suffix(&path, ".tmp").exists());
+    write_file_atomically(&path, true, write(b"new")).unwrap();
+    assert_eq!(fs::read(&path).unwrap(), b"new");
+    assert_eq!(fs::read(path_with_suffix(&path, ".bak")).unwrap(), b"old");
+}
+
+#[test]
+fn test_db_header() {
+    // with header
+    let mut b = vec![];
+    write_db_header(&mut b).unwrap();
+    5u64.to_bytes(&mut b).unwrap();
+    let (version, mut rest) = read_db_header(b.as_slice()).unwrap();
+    assert_eq!(version, DB_VERSION);
+    assert_eq!(u64::from_bytes(&mut rest).unwrap(), 5);
+    // without header (version 0)
+    let b = 5u64.to_bytes_vec();
+    let (version, mut rest) = read_db_header(b.as_slice()).unwrap();
+    assert_eq!(version, 0);
+    assert_eq!(u64::from_bytes(&mut rest).unwrap(), 5);
+}
+
+#[test]
+fn test_error_context() {
+    use crate::data::{song::Song, DatabaseLocation, GeneralData};
+    let song = Song::new(
+        DatabaseLocation::from("some/song.mp3"),
+        None,
+        "title".to_owned(),
+        None,
+        0,
+        vec![],
+        None,
+        0,
+        0,
+        GeneralData::default(),
+    );
+    let bytes = song.to_bytes_vec();
+    // cut off in the middle of the path
+    let e = Song::from_bytes(&mut &bytes[..12]).unwrap_err();
+    assert_eq!(e.kind(), std::io::ErrorKind::UnexpectedEof);
+    assert!(e.to_string().starts_with("Song.location.rel_path: "), "{e}");
+}
+
+#[test]
+fn test_song_quality_migration() {
+    use crate::data::{song::Song, DatabaseLocation, GeneralData};
+    let mut song = Song::new(
+        DatabaseLocation::from("some/song.mp3"),
+        None,
+        "title".to_owned(),
+        None,
+        0,
+        vec![],
+        None,
+        0,
+        0,
+        GeneralData::default(),
+    );
+    // unknown bitrate and codec are the old encoding plus two `None`s
+    let bytes = song.to_bytes_vec();
+    let old_len = bytes.len() - 2 * None::<u32>.to_bytes_vec().len();
+    let old = Song::from_bytes_without_quality(&mut &bytes[..old_len]).unwrap();
+    assert_eq!(old, song);
+    song.bitrate = Some(320);
+    song.codec = Some("MP3".to_owned());
+    assert_eq!(
+        Song::from_bytes(&mut song.to_bytes_vec().as_slice()).unwrap(),
+        song
+    );
+}
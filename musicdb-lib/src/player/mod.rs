@@ -1,16 +1,32 @@
 #[cfg(feature = "playback-via-playback-rs")]
 pub mod playback_rs;
+#[cfg(feature = "render")]
+pub mod render;
 #[cfg(feature = "playback-via-rodio")]
 pub mod rodio;
+#[cfg(feature = "playback")]
+pub mod sleep;
 #[cfg(feature = "playback-via-playback-rs")]
 pub type PlayerBackendFeat<T> = playback_rs::PlayerBackendPlaybackRs<T>;
 #[cfg(feature = "playback-via-rodio")]
 pub type PlayerBackendFeat<T> = rodio::PlayerBackendRodio<T>;
+#[cfg(feature = "playback-via-sleep")]
+pub type PlayerBackendFeat<T> = sleep::PlayerBackendSleep<T>;
 
-use std::{collections::HashMap, ffi::OsStr, sync::Arc};
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+pub use crate::data::song::ReplayGainMode;
 
 use crate::{
-    data::{database::Database, song::CachedData, SongId},
+    data::{
+        cache_manager::CacheCounters, database::Database, queue::RepeatMode, song::CachedData,
+        SongId,
+    },
     server::Action,
 };
 
@@ -18,19 +34,48 @@ pub struct Player<T: PlayerBackend<SongCustomData>> {
     cached: HashMap<SongId, CachedData>,
     pub backend: T,
     allow_sending_commands: bool,
+    pub replaygain_mode: ReplayGainMode,
+    /// pause instead of going to the next song once the current song finishes, then reset to `false`.
+    /// set by `Action::StopAfterCurrentSong`.
+    pub stop_after_current: bool,
+    cache_counters: Arc<CacheCounters>,
+    /// if the current song's `play_count` was already increased
+    play_counted: bool,
+    /// the backend's `current_song_repetitions` when it was last checked
+    repetitions: u64,
+    /// if a song's file can't be loaded, go to the next song, even if playback is paused.
+    /// after `MAX_SKIPPED_UNPLAYABLE` songs in a row were skipped, playback is paused instead.
+    /// if false (the default), songs are only skipped while playing, and every song causes an error.
+    pub skip_unplayable: bool,
+    /// songs which were skipped since the last song which could be loaded, reported in a single `ErrorInfo`
+    skipped_unplayable: Vec<String>,
+    /// the song at which skipping stopped because of `MAX_SKIPPED_UNPLAYABLE`.
+    /// it isn't skipped again until playback is resumed.
+    skipping_stopped_at: Option<SongId>,
+    /// what was last passed to `PlayerBackend::set_repeat_current`
+    repeat_current: bool,
+    /// what was last passed to `PlayerBackend::set_fade`
+    fade_millis: u64,
+    /// the current song and the gain which was last passed to `PlayerBackend::set_gain` for it
+    current_gain: Option<(SongId, f32)>,
 }
+/// how many songs can be skipped in a row by `Player::skip_unplayable` before playback is paused,
+/// so that a queue (or loop) of only broken songs doesn't keep the player busy forever.
+pub const MAX_SKIPPED_UNPLAYABLE: usize = 5;
 
 pub struct SongCustomData {
     load_duration: bool,
 }
 pub trait PlayerBackend<T> {
-    /// load the next song from its bytes
+    /// load the next song from its bytes.
+    /// `duration_millis` is the song's duration from the database, `0` if it isn't known (see `current_song_duration`).
     fn load_next_song(
         &mut self,
         id: SongId,
         filename: &OsStr,
         bytes: Arc<Vec<u8>>,
-        load_duration: bool,
+        duration_millis: u64,
+        gain: f32,
         custom_data: T,
     );
 
@@ -49,6 +94,15 @@ pub trait PlayerBackend<T> {
     /// calling `resume` after this was called but before a new song was loaded does nothing.
     fn clear(&mut self);
 
+    /// set the master volume (`1.0` is the default). the song's `gain` is applied on top of this.
+    fn set_volume(&mut self, volume: f32);
+    fn volume(&self) -> f32;
+    /// replace the current song's `gain` (from `load_next_song`), for example because the song's `Gain=` tag changed.
+    /// Backends which can't do this can ignore it, then the new gain is used the next time the song is loaded.
+    fn set_gain(&mut self, gain: f32) {
+        _ = gain;
+    }
+
     /// Should be `true` after calling `resume()` or `next(true)` if `current_song().is_some()`
     fn playing(&self) -> bool;
 
@@ -74,13 +128,46 @@ pub trait PlayerBackend<T> {
 
     /// If possible, return the current song's duration in milliseconds.
     /// It could also just return `None`.
-    /// If `load_duration` was `false` in either `load_next_song` or `next`, for performance reasons,
+    /// If `duration_millis` wasn't `0` in `load_next_song` or `load_duration` was `false` in `next`, for performance reasons,
     /// this should probably return `None` (unless getting the duration is virtually free).
     /// `load_duration` can be ignored if you don't want to load the duration anyway, it's just there to prevent you from loading the duration if it won't be used anyway
     fn current_song_duration(&self) -> Option<u64>;
 
     /// If known, get the current playback position in the song, in milliseconds.
     fn current_song_playback_position(&self) -> Option<u64>;
+    /// Jump to a position in the current song, in milliseconds. Does nothing if this isn't possible.
+    fn seek(&mut self, position: u64);
+
+    /// If `true`, the current song (and songs which become the current song later) should start again
+    /// without a gap when they end, and `song_finished` should stay `false`. Used for `RepeatMode::One`.
+    /// Backends which can't do this can ignore it, then the `Player` loads the song again once it finishes.
+    fn set_repeat_current(&mut self, repeat: bool) {
+        _ = repeat;
+    }
+    /// How often the current song started again because of `set_repeat_current` since it was loaded (or restarted by `stop` or `seek`),
+    /// so that the `Player` can count these as plays too. Backends which ignore `set_repeat_current` can ignore this.
+    fn current_song_repetitions(&self) -> u64 {
+        0
+    }
+
+    /// How long (in milliseconds) `resume` should fade in and `pause` and `stop` should fade out, `0` for no fading.
+    /// Backends which fade may keep playing for this long after `pause` or `stop`, but `playing` should return `false` immediately.
+    /// Backends which can't do this can ignore it.
+    fn set_fade(&mut self, millis: u64) {
+        _ = millis;
+    }
+
+    /// The current song's sample rate and channel count, as decoded (before any resampling for the output device).
+    /// `None` if there is no current song, it couldn't be decoded, or the backend doesn't decode songs.
+    fn current_song_format(&self) -> Option<(u32, u16)> {
+        None
+    }
+
+    /// If the backend still has to decode the current song before it can play it, how much was decoded, from `0.0` to `1.0`.
+    /// `None` once the song is ready (or if there is no current song).
+    /// Loading the song's bytes happens before the backend gets them, see `Player::loading_progress`.
+    /// performance notes: must be fast, as it is called repeatedly
+    fn loading_progress(&self) -> Option<f32>;
 }
 
 impl<T: PlayerBackend<SongCustomData>> Player<T> {
@@ -89,6 +176,17 @@ impl<T: PlayerBackend<SongCustomData>> Player<T> {
             cached: HashMap::new(),
             backend,
             allow_sending_commands: true,
+            replaygain_mode: ReplayGainMode::Off,
+            stop_after_current: false,
+            cache_counters: Arc::new(CacheCounters::default()),
+            play_counted: false,
+            repetitions: 0,
+            skip_unplayable: false,
+            skipped_unplayable: vec![],
+            skipping_stopped_at: None,
+            repeat_current: false,
+            fade_millis: 0,
+            current_gain: None,
         }
     }
     pub fn new_client(backend: T) -> Self {
@@ -96,13 +194,46 @@ impl<T: PlayerBackend<SongCustomData>> Player<T> {
             cached: HashMap::new(),
             backend,
             allow_sending_commands: false,
+            replaygain_mode: ReplayGainMode::Off,
+            stop_after_current: false,
+            cache_counters: Arc::new(CacheCounters::default()),
+            play_counted: false,
+            repetitions: 0,
+            skip_unplayable: false,
+            skipped_unplayable: vec![],
+            skipping_stopped_at: None,
+            repeat_current: false,
+            fade_millis: 0,
+            current_gain: None,
+        }
+    }
+    /// Use the `CacheManager`'s counters (`CacheManager::counters`) to count cache hits and misses
+    /// How much of the queue's current song was loaded, from `0.0` to `1.0`, while its bytes are being fetched
+    /// (from disk or, for clients, from the server) or decoded by the backend.
+    /// `None` once the song is ready to be played, or if there is no current song.
+    pub fn loading_progress(&self, db: &Database) -> Option<f32> {
+        let id = db.queue.get_current_song().copied()?;
+        if self.backend.current_song().is_some_and(|(v, _, _)| v == id) {
+            self.backend.loading_progress()
+        } else {
+            db.get_song(&id)?.cached_data().loading_progress()
         }
     }
+    pub fn set_cache_counters(&mut self, counters: Arc<CacheCounters>) {
+        self.cache_counters = counters;
+    }
     pub fn handle_action(&mut self, action: &Action) {
         match action {
             Action::Resume => self.resume(),
             Action::Pause => self.pause(),
             Action::Stop => self.stop(),
+            Action::StopAfterCurrentSong(stop) => self.stop_after_current = *stop,
+            Action::SetCurrentSongPosition(position) => self.backend.seek(*position),
+            Action::Multiple(actions) => {
+                for action in actions {
+                    self.handle_action(action);
+                }
+            }
             _ => {}
         }
     }
@@ -124,11 +255,41 @@ impl<T: PlayerBackend<SongCustomData>> Player<T> {
         self.update_uncache_opt(db, false)
     }
     pub fn update_uncache_opt(&mut self, db: &mut Database, allow_uncaching: bool) {
+        // `StopAfterCurrentSong` has to see the song finish
+        let repeat_current = db.repeat == RepeatMode::One && !self.stop_after_current;
+        if repeat_current != self.repeat_current {
+            self.repeat_current = repeat_current;
+            self.backend.set_repeat_current(repeat_current);
+        }
+        let finished_song = if self.backend.song_finished() {
+            self.backend.current_song().map(|v| v.0)
+        } else {
+            None
+        };
         if self.allow_sending_commands {
             if self.allow_sending_commands && self.backend.song_finished() {
-                db.apply_action_unchecked_seq(Action::NextSong, None);
+                if self.stop_after_current {
+                    // pause first so the next song is loaded, but doesn't start playing.
+                    // this also works with loops, since `NextSong` still advances the queue normally.
+                    self.stop_after_current = false;
+                    db.apply_action_unchecked_seq(
+                        Action::Multiple(vec![
+                            Action::StopAfterCurrentSong(false),
+                            Action::Pause,
+                            Action::NextSong,
+                        ]),
+                        None,
+                    );
+                } else if db.repeat != RepeatMode::One {
+                    db.apply_action_unchecked_seq(Action::NextSong, None);
+                }
             }
         }
+        // if the current song is still the one that just finished (repeat one, repeat all or a loop with only one song),
+        // the backend has to load it again, otherwise it would stay finished.
+        if finished_song.is_some() && finished_song == db.queue.get_current_song().copied() {
+            self.backend.clear();
+        }
 
         let queue_current_song = db.queue.get_current_song().copied();
         let queue_next_song = db.queue.get_next_song().copied();
@@ -137,11 +298,13 @@ impl<T: PlayerBackend<SongCustomData>> Player<T> {
             (None, None) => (),
             (Some(a), Some(b)) if a == b => (),
             (_, Some(id)) => {
+                self.play_counted = false;
                 if self.backend.next_song().map(|v| v.0) == queue_current_song {
                     let load_duration = self
                         .backend
                         .next_song()
                         .is_some_and(|(_, _, t)| t.load_duration);
+                    self.cache_counters.hit();
                     self.backend.next(db.playing, load_duration);
                     if self.allow_sending_commands && load_duration {
                         if let Some(dur) = self.backend.current_song_duration() {
@@ -150,11 +313,13 @@ impl<T: PlayerBackend<SongCustomData>> Player<T> {
                     }
                 } else if let Some(song) = db.get_song(&id) {
                     self.cached.insert(id, song.cached_data().clone());
-                    if let Some(bytes) = song
-                        .cached_data()
-                        .get_data_or_maybe_start_thread(db, song)
-                        .or_else(|| song.cached_data().cached_data_await())
-                    {
+                    let bytes = song.cached_data().get_data_or_maybe_start_thread(db, song);
+                    if bytes.is_some() {
+                        self.cache_counters.hit();
+                    } else {
+                        self.cache_counters.miss();
+                    }
+                    if let Some(bytes) = bytes.or_else(|| song.cached_data().cached_data_await()) {
                         let load_duration = song.duration_millis == 0;
                         self.backend.load_next_song(
                             id,
@@ -163,7 +328,8 @@ impl<T: PlayerBackend<SongCustomData>> Player<T> {
                                 .file_name()
                                 .unwrap_or_else(|| OsStr::new("")),
                             bytes,
-                            load_duration,
+                            song.duration_millis,
+                            self.replaygain_mode.gain(song) * song.gain(),
                             SongCustomData { load_duration },
                         );
                         self.backend.next(db.playing, load_duration);
@@ -176,20 +342,9 @@ impl<T: PlayerBackend<SongCustomData>> Player<T> {
                             }
                         }
                     } else {
-                        // only show an error if the user tries to play the song.
-                        // otherwise, the error might be spammed.
-                        if self.allow_sending_commands && db.playing {
-                            db.apply_action_unchecked_seq(
-                                Action::ErrorInfo(
-                                    format!("Couldn't load bytes for song {id}"),
-                                    format!(
-                                        "Song: {}\nby {:?} on {:?}",
-                                        song.title, song.artist, song.album
-                                    ),
-                                ),
-                                None,
-                            );
-                            db.apply_action_unchecked_seq(Action::NextSong, None);
+                        if self.allow_sending_commands {
+                            let song = format!("{} (#{id})", song.title);
+                            self.song_unplayable(db, id, song);
                         }
                         self.backend.clear();
                     }
@@ -199,6 +354,25 @@ impl<T: PlayerBackend<SongCustomData>> Player<T> {
             }
             (Some(_), None) => self.backend.clear(),
         }
+        // a song could be loaded (or the queue ended), so report the songs which were skipped to get here
+        if !self.skipped_unplayable.is_empty()
+            && self.backend.current_song().map(|v| v.0) == queue_current_song
+        {
+            let skipped = std::mem::take(&mut self.skipped_unplayable);
+            db.apply_action_unchecked_seq(
+                Action::ErrorInfo(
+                    format!("Skipped {} songs which couldn't be loaded", skipped.len()),
+                    skipped.join("\n"),
+                ),
+                None,
+            );
+        }
+        if self.allow_sending_commands {
+            let format = self.backend.current_song_format();
+            if format != db.current_song_format {
+                db.apply_action_unchecked_seq(Action::SetCurrentSongFormat(format), None);
+            }
+        }
         match (self.backend.next_song().map(|v| v.0), queue_next_song) {
             (None, None) => (),
             (Some(a), Some(b)) if a == b => (),
@@ -216,7 +390,8 @@ impl<T: PlayerBackend<SongCustomData>> Player<T> {
                                 .file_name()
                                 .unwrap_or_else(|| OsStr::new("")),
                             bytes,
-                            load_duration,
+                            song.duration_millis,
+                            self.replaygain_mode.gain(song) * song.gain(),
                             SongCustomData { load_duration },
                         );
                     }
@@ -224,6 +399,25 @@ impl<T: PlayerBackend<SongCustomData>> Player<T> {
             }
             (Some(_), None) => (),
         }
+        // muting is a volume override, so `db.volume` can be restored when unmuting
+        let volume = if db.muted { 0.0 } else { db.volume };
+        if self.backend.volume() != volume {
+            self.backend.set_volume(volume);
+        }
+        // the gain was passed to `load_next_song`, but the song's `Gain=` tag (or the replaygain mode) could change while it is playing
+        if let Some(id) = self.backend.current_song().map(|v| v.0) {
+            if let Some(song) = db.get_song(&id) {
+                let gain = self.replaygain_mode.gain(song) * song.gain();
+                if self.current_gain != Some((id, gain)) {
+                    self.current_gain = Some((id, gain));
+                    self.backend.set_gain(gain);
+                }
+            }
+        }
+        if db.fade_millis != self.fade_millis {
+            self.fade_millis = db.fade_millis;
+            self.backend.set_fade(db.fade_millis);
+        }
         if db.playing != self.backend.playing() {
             if db.playing {
                 self.backend.resume();
@@ -232,6 +426,27 @@ impl<T: PlayerBackend<SongCustomData>> Player<T> {
             }
         }
 
+        // a song which the backend repeated without a gap was played again,
+        // just like a song which finished and was loaded again.
+        let repetitions = self.backend.current_song_repetitions();
+        if repetitions > self.repetitions {
+            self.play_counted = false;
+        }
+        self.repetitions = repetitions;
+        // count a play once the song actually starts playing.
+        // only the server does this, so syncplayers don't count songs again.
+        if self.allow_sending_commands && !self.play_counted && db.playing {
+            if let Some(id) = self.backend.current_song().map(|v| v.0) {
+                self.play_counted = true;
+                if db.get_song(&id).is_some() {
+                    let now = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .map_or(0, |v| v.as_millis() as u64);
+                    db.apply_action_unchecked_seq(Action::SongPlayed(id, now), None);
+                }
+            }
+        }
+
         if allow_uncaching {
             for (&id, cd) in &self.cached {
                 if Some(id) != queue_current_song && Some(id) != queue_next_song {
@@ -243,4 +458,41 @@ impl<T: PlayerBackend<SongCustomData>> Player<T> {
             }
         }
     }
+    /// the current song's bytes couldn't be loaded
+    fn song_unplayable(&mut self, db: &mut Database, id: SongId, song: String) {
+        if !self.skip_unplayable {
+            // only show an error if the user tries to play the song.
+            // otherwise, the error might be spammed.
+            if db.playing {
+                db.apply_action_unchecked_seq(
+                    Action::ErrorInfo(format!("Couldn't load bytes for song {id}"), song),
+                    None,
+                );
+                db.apply_action_unchecked_seq(Action::NextSong, None);
+            }
+            return;
+        }
+        if !db.playing && self.skipping_stopped_at == Some(id) {
+            return;
+        }
+        self.skipping_stopped_at = None;
+        self.skipped_unplayable.push(song);
+        if self.skipped_unplayable.len() < MAX_SKIPPED_UNPLAYABLE {
+            db.apply_action_unchecked_seq(Action::NextSong, None);
+        } else {
+            let skipped = std::mem::take(&mut self.skipped_unplayable);
+            self.skipping_stopped_at = Some(id);
+            db.apply_action_unchecked_seq(Action::Pause, None);
+            db.apply_action_unchecked_seq(
+                Action::ErrorInfo(
+                    format!(
+                        "Paused after {} songs in a row couldn't be loaded",
+                        skipped.len()
+                    ),
+                    skipped.join("\n"),
+                ),
+                None,
+            );
+        }
+    }
 }
@@ -11,6 +11,9 @@ use super::PlayerBackend;
 
 pub struct PlayerBackendPlaybackRs<T> {
     player: playback_rs::Player,
+    volume: f32,
+    current_gain: f32,
+    next_gain: f32,
     current: Option<(SongId, Option<playback_rs::Song>, T)>,
     next: Option<(SongId, Option<playback_rs::Song>, T)>,
     command_sender: Option<std::sync::mpsc::Sender<(Command, Option<u64>)>>,
@@ -30,6 +33,9 @@ impl<T> PlayerBackendPlaybackRs<T> {
     ) -> Result<Self, Box<dyn std::error::Error>> {
         Ok(Self {
             player: playback_rs::Player::new(None)?,
+            volume: 1.0,
+            current_gain: 1.0,
+            next_gain: 1.0,
             current: None,
             next: None,
             command_sender,
@@ -43,7 +49,8 @@ impl<T> PlayerBackend<T> for PlayerBackendPlaybackRs<T> {
         id: SongId,
         filename: &OsStr,
         bytes: Arc<Vec<u8>>,
-        _load_duration: bool,
+        _duration_millis: u64,
+        gain: f32,
         custom_data: T,
     ) {
         let mut hint = Hint::new();
@@ -51,7 +58,8 @@ impl<T> PlayerBackend<T> for PlayerBackendPlaybackRs<T> {
             hint.with_extension(ext);
         }
         let reader = Box::new(Cursor::new(ArcVec(bytes)));
-        let loaded_song = match playback_rs::Song::new(reader, &hint, None) {
+        self.next_gain = gain;
+        let loaded_song = match playback_rs::Song::new(reader, &hint, Some(self.volume * gain)) {
             Ok(v) => Some(v),
             Err(e) => {
                 if let Some(s) = &self.command_sender {
@@ -98,6 +106,7 @@ impl<T> PlayerBackend<T> for PlayerBackendPlaybackRs<T> {
         self.player.stop();
         self.player.skip();
         self.current = self.next.take();
+        self.current_gain = self.next_gain;
         if let Some((id, song, _)) = &self.current {
             if let Some(song) = song {
                 if let Err(e) = self.player.play_song_now(song, None) {
@@ -129,6 +138,32 @@ impl<T> PlayerBackend<T> for PlayerBackendPlaybackRs<T> {
         self.current = None;
         self.next = None;
     }
+    /// playback_rs applies the volume when a song is loaded,
+    /// so the current song is restarted (at the same position) with the new volume.
+    fn set_volume(&mut self, volume: f32) {
+        self.volume = volume;
+        if let Some((_, Some(song), _)) = &mut self.current {
+            *song = song.with_volume_adjustment(volume * self.current_gain);
+            if let Some((pos, _)) = self.player.get_playback_position() {
+                let playing = self.player.is_playing();
+                if self.player.play_song_now(song, Some(pos)).is_ok() {
+                    self.player.set_playing(playing);
+                }
+            }
+        }
+        if let Some((_, Some(song), _)) = &mut self.next {
+            *song = song.with_volume_adjustment(volume * self.next_gain);
+        }
+    }
+    fn volume(&self) -> f32 {
+        self.volume
+    }
+    fn set_gain(&mut self, gain: f32) {
+        if self.current_gain != gain {
+            self.current_gain = gain;
+            self.set_volume(self.volume);
+        }
+    }
     fn playing(&self) -> bool {
         self.player.is_playing()
     }
@@ -160,6 +195,13 @@ impl<T> PlayerBackend<T> for PlayerBackendPlaybackRs<T> {
             .get_playback_position()
             .map(|v| v.0.as_millis() as _)
     }
+    fn seek(&mut self, position: u64) {
+        self.player.seek(Duration::from_millis(position));
+    }
+    fn loading_progress(&self) -> Option<f32> {
+        // songs are fully decoded in `load_next_song`, there is no partial progress
+        None
+    }
 }
 
 pub struct ArcVec(pub Arc<Vec<u8>>);
@@ -0,0 +1,254 @@
+use std::{
+    ffi::OsStr,
+    fs::File,
+    io::{BufWriter, Seek, SeekFrom, Write},
+    path::Path,
+    sync::Arc,
+};
+
+use colorize::AnsiColor;
+use rc_u8_reader::ArcU8Reader;
+use rodio::{source::UniformSourceIterator, Decoder, Source};
+
+use crate::data::SongId;
+
+use super::PlayerBackend;
+
+const CHANNELS: u16 = 2;
+const SAMPLE_RATE: u32 = 44100;
+
+/// A backend which doesn't play anything, but decodes every song as fast as possible
+/// and appends it to a wav file (16 bit, 44.1kHz, stereo).
+/// Call `finish` once the queue is done to write the wav header.
+pub struct PlayerBackendRender<T> {
+    file: BufWriter<File>,
+    /// number of bytes of sample data written to `file`
+    data_len: u64,
+    volume: f32,
+    playing: bool,
+    current: Option<(SongId, Arc<Vec<u8>>, f32, Option<u64>, bool, T)>,
+    next: Option<(SongId, Arc<Vec<u8>>, f32, T)>,
+    /// the current song's format before it was converted to the output format
+    current_format: Option<(u32, u16)>,
+}
+
+impl<T> PlayerBackendRender<T> {
+    pub fn new(path: impl AsRef<Path>) -> Result<Self, std::io::Error> {
+        let mut file = BufWriter::new(File::create(path)?);
+        // placeholder, overwritten in `finish`
+        write_wav_header(&mut file, 0)?;
+        Ok(Self {
+            file,
+            data_len: 0,
+            volume: 1.0,
+            playing: false,
+            current: None,
+            next: None,
+            current_format: None,
+        })
+    }
+    /// writes the wav header. the file is incomplete until this is called.
+    pub fn finish(mut self) -> Result<(), std::io::Error> {
+        self.file.seek(SeekFrom::Start(0))?;
+        write_wav_header(&mut self.file, self.data_len)?;
+        self.file.flush()
+    }
+    /// decodes the current song and writes it to the file (unless it was already written)
+    fn render_current(&mut self) {
+        if let Some((id, bytes, gain, duration, done, _)) = &mut self.current {
+            if *done {
+                return;
+            }
+            *done = true;
+            match Decoder::new(ArcU8Reader::new(Arc::clone(bytes))) {
+                Ok(decoder) => {
+                    self.current_format = Some((decoder.sample_rate(), decoder.channels()));
+                    let volume = self.volume * *gain;
+                    let samples: UniformSourceIterator<_, i16> =
+                        UniformSourceIterator::new(decoder, CHANNELS, SAMPLE_RATE);
+                    let mut count = 0u64;
+                    for sample in samples {
+                        let sample =
+                            (sample as f32 * volume).clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+                        if let Err(e) = self.file.write_all(&sample.to_le_bytes()) {
+                            eprintln!(
+                                "[{}] Couldn't write song #{id} to output file: {e}",
+                                "ERR!".red()
+                            );
+                            break;
+                        }
+                        count += 1;
+                    }
+                    self.data_len += count * 2;
+                    *duration = Some(count * 1000 / (SAMPLE_RATE as u64 * CHANNELS as u64));
+                    eprintln!("[{}] rendered song #{id}", "INFO".cyan());
+                }
+                Err(e) => {
+                    eprintln!(
+                        "[{}] Couldn't decode song #{id}, skipping it: {e}",
+                        "WARN".yellow()
+                    );
+                }
+            }
+        }
+    }
+}
+
+impl<T> PlayerBackend<T> for PlayerBackendRender<T> {
+    fn load_next_song(
+        &mut self,
+        id: SongId,
+        _filename: &OsStr,
+        bytes: Arc<Vec<u8>>,
+        _duration_millis: u64,
+        gain: f32,
+        custom_data: T,
+    ) {
+        self.next = Some((id, bytes, gain, custom_data));
+    }
+    fn pause(&mut self) {
+        self.playing = false;
+    }
+    fn stop(&mut self) {
+        self.playing = false;
+    }
+    fn resume(&mut self) {
+        self.playing = true;
+        self.render_current();
+    }
+    fn next(&mut self, play: bool, _load_duration: bool) {
+        self.current_format = None;
+        self.current = self
+            .next
+            .take()
+            .map(|(id, bytes, gain, t)| (id, bytes, gain, None, false, t));
+        self.playing = play;
+        if play {
+            self.render_current();
+        }
+    }
+    fn clear(&mut self) {
+        self.current = None;
+        self.next = None;
+        self.current_format = None;
+    }
+    fn set_volume(&mut self, volume: f32) {
+        self.volume = volume;
+    }
+    fn volume(&self) -> f32 {
+        self.volume
+    }
+    fn playing(&self) -> bool {
+        self.playing && self.current.is_some()
+    }
+    fn current_song(&self) -> Option<(SongId, bool, &T)> {
+        self.current.as_ref().map(|v| (v.0, true, &v.5))
+    }
+    fn next_song(&self) -> Option<(SongId, bool, &T)> {
+        self.next.as_ref().map(|v| (v.0, true, &v.3))
+    }
+    fn gen_data_mut(&mut self) -> (Option<&mut T>, Option<&mut T>) {
+        (
+            self.current.as_mut().map(|v| &mut v.5),
+            self.next.as_mut().map(|v| &mut v.3),
+        )
+    }
+    fn song_finished_polling(&self) -> bool {
+        true
+    }
+    fn song_finished(&self) -> bool {
+        self.current.as_ref().is_some_and(|v| v.4)
+    }
+    fn current_song_duration(&self) -> Option<u64> {
+        self.current.as_ref().and_then(|v| v.3)
+    }
+    fn current_song_playback_position(&self) -> Option<u64> {
+        None
+    }
+    fn seek(&mut self, _position: u64) {}
+    fn current_song_format(&self) -> Option<(u32, u16)> {
+        self.current_format
+    }
+    fn loading_progress(&self) -> Option<f32> {
+        None
+    }
+}
+
+fn write_wav_header(w: &mut impl Write, data_len: u64) -> Result<(), std::io::Error> {
+    // wav can't be larger than 4GiB, the header will be wrong in that case, but most programs can still play the file
+    let data_len = data_len.min(u32::MAX as u64 - 36) as u32;
+    let block_align = CHANNELS * 2;
+    w.write_all(b"RIFF")?;
+    w.write_all(&(36 + data_len).to_le_bytes())?;
+    w.write_all(b"WAVE")?;
+    w.write_all(b"fmt ")?;
+    w.write_all(&16u32.to_le_bytes())?;
+    // PCM
+    w.write_all(&1u16.to_le_bytes())?;
+    w.write_all(&CHANNELS.to_le_bytes())?;
+    w.write_all(&SAMPLE_RATE.to_le_bytes())?;
+    w.write_all(&(SAMPLE_RATE * block_align as u32).to_le_bytes())?;
+    w.write_all(&block_align.to_le_bytes())?;
+    w.write_all(&16u16.to_le_bytes())?;
+    w.write_all(b"data")?;
+    w.write_all(&data_len.to_le_bytes())?;
+    Ok(())
+}
+
+#[test]
+fn test_render_queue() {
+    use crate::{
+        data::{artist::Artist, queue::QueueContent, song::Song, GeneralData},
+        player::Player,
+        server::{Action, Req},
+    };
+    let (mut db, dir) = crate::test::test_db("render");
+    let artist = db.add_artist_new(Artist {
+        id: 0,
+        name: "Artist".to_owned(),
+        aliases: vec![],
+        cover: None,
+        albums: vec![],
+        singles: vec![],
+        general: GeneralData::default(),
+    });
+    // short wav files in the output format, so nothing is resampled
+    let mut queue = vec![];
+    for (i, samples) in [4410u64, 8820].into_iter().enumerate() {
+        let mut wav = vec![];
+        write_wav_header(&mut wav, samples * 2).unwrap();
+        wav.extend((0..samples).flat_map(|s| (s as i16).to_le_bytes()));
+        let file = format!("{i}.wav");
+        std::fs::write(dir.join(&file), wav).unwrap();
+        let song = Song::new(
+            file.into(),
+            None,
+            format!("Song {i}"),
+            None,
+            artist,
+            vec![],
+            None,
+            0,
+            0,
+            GeneralData::default(),
+        );
+        queue.push(QueueContent::Song(db.add_song_new(song)).into());
+    }
+    db.apply_action_unchecked_seq(Action::QueueAdd(vec![], queue, Req::none()), None);
+    db.apply_action_unchecked_seq(Action::Resume, None);
+    let output = dir.join("output.wav");
+    let mut player = Player::new(PlayerBackendRender::new(&output).unwrap());
+    for _ in 0..1000 {
+        if !db.playing {
+            break;
+        }
+        player.update(&mut db);
+    }
+    assert!(!db.playing);
+    player.backend.finish().unwrap();
+    let rendered = std::fs::read(&output).unwrap();
+    let data_len = (4410 + 8820) * 2;
+    assert_eq!(rendered.len(), 44 + data_len);
+    assert_eq!(&rendered[40..44], &(data_len as u32).to_le_bytes());
+    assert_eq!(&rendered[44..48], &[0, 0, 1, 0]);
+}
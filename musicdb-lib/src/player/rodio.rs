@@ -1,7 +1,19 @@
-use std::{ffi::OsStr, sync::Arc};
+use std::{
+    ffi::OsStr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
 
+use colorize::AnsiColor;
 use rc_u8_reader::ArcU8Reader;
-use rodio::{decoder::DecoderError, Decoder, OutputStream, OutputStreamHandle, Sink, Source};
+use rodio::{
+    cpal::traits::HostTrait, decoder::DecoderError, source::SeekError, Decoder, DeviceTrait,
+    OutputStream, OutputStreamHandle, Sink, Source,
+};
 
 use crate::{
     data::SongId,
@@ -15,11 +27,32 @@ pub struct PlayerBackendRodio<T> {
     output_stream: OutputStream,
     #[allow(unused)]
     output_stream_handle: OutputStreamHandle,
-    sink: Sink,
+    sink: Arc<Sink>,
     stopped: bool,
-    current: Option<(SongId, Arc<Vec<u8>>, Option<u128>, T)>,
-    next: Option<(SongId, Arc<Vec<u8>>, Option<MyDecoder>, T)>,
+    volume: f32,
+    current: Option<(SongId, Arc<Vec<u8>>, Option<u128>, f32, T)>,
+    next: Option<(SongId, Arc<Vec<u8>>, Option<MyDecoder>, f32, T)>,
     command_sender: Option<std::sync::mpsc::Sender<(Command, Option<u64>)>>,
+    /// see `set_repeat_current`, shared with the current song's `RepeatSource`
+    repeat: Arc<AtomicBool>,
+    /// the sink's position (in milliseconds) when the current song last started again because of `repeat`
+    repeat_start: Arc<AtomicU64>,
+    /// how often the current song started again because of `repeat`, see `current_song_repetitions`
+    repetitions: Arc<AtomicU64>,
+    /// the current song's sample rate and channels, see `current_song_format`
+    current_format: Option<(u32, u16)>,
+    /// see `set_fade`, shared with the current song's `RepeatSource`
+    fade: Arc<Fade>,
+}
+
+/// The `RepeatSource` moves its volume towards `audible` over `millis` milliseconds,
+/// and `pause` and `stop` wait for it (see `after_fade`) before pausing the sink.
+#[derive(Default)]
+struct Fade {
+    millis: AtomicU64,
+    audible: AtomicBool,
+    /// changed by `pause`, `stop`, `next` and `clear`, so that a pause which is waiting for the fade knows it was replaced
+    generation: AtomicU64,
 }
 
 impl<T> PlayerBackendRodio<T> {
@@ -34,27 +67,133 @@ impl<T> PlayerBackendRodio<T> {
     pub fn new_with_optional_command_sending(
         command_sender: Option<std::sync::mpsc::Sender<(Command, Option<u64>)>>,
     ) -> Result<Self, Box<dyn std::error::Error>> {
-        let (output_stream, output_stream_handle) = rodio::OutputStream::try_default()?;
+        Self::new_with_device(command_sender, None)
+    }
+    /// like `new_with_optional_command_sending`, but uses the output device with the given name.
+    /// if `device` is `None` or no device with that name exists, the default device is used.
+    pub fn new_with_device(
+        command_sender: Option<std::sync::mpsc::Sender<(Command, Option<u64>)>>,
+        device: Option<&str>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let (output_stream, output_stream_handle) = output_stream(device)?;
         let sink = Sink::try_new(&output_stream_handle)?;
         Ok(Self {
             output_stream,
             output_stream_handle,
-            sink,
+            sink: Arc::new(sink),
             stopped: true,
+            volume: 1.0,
             current: None,
             next: None,
             command_sender,
+            repeat: Arc::new(AtomicBool::new(false)),
+            repeat_start: Arc::new(AtomicU64::new(0)),
+            repetitions: Arc::new(AtomicU64::new(0)),
+            current_format: None,
+            fade: Arc::new(Fade::default()),
         })
     }
 }
 
+impl<T> PlayerBackendRodio<T> {
+    fn repeat_source(&self, decoder: MyDecoder, bytes: &Arc<Vec<u8>>) -> RepeatSource {
+        self.repeat_start.store(0, Ordering::Relaxed);
+        self.repetitions.store(0, Ordering::Relaxed);
+        RepeatSource {
+            decoder,
+            bytes: Arc::clone(bytes),
+            repeat: Arc::clone(&self.repeat),
+            repeat_start: Arc::clone(&self.repeat_start),
+            repetitions: Arc::clone(&self.repetitions),
+            samples: 0,
+            fade: Arc::clone(&self.fade),
+            level: if self.fade.audible.load(Ordering::Relaxed) {
+                1.0
+            } else {
+                0.0
+            },
+        }
+    }
+    /// Runs `f` once the fade started by `pause` or `stop` is done, on another thread so the player isn't blocked.
+    /// Does nothing if `pause`, `stop`, `next` or `clear` are called in the meantime.
+    fn after_fade(&self, f: impl FnOnce(&Sink, &Fade) + Send + 'static) {
+        let generation = self.fade.generation.fetch_add(1, Ordering::Relaxed) + 1;
+        let millis = self.fade.millis.load(Ordering::Relaxed);
+        let (sink, fade) = (Arc::clone(&self.sink), Arc::clone(&self.fade));
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(millis));
+            if fade.generation.load(Ordering::Relaxed) == generation {
+                f(&sink, &fade);
+            }
+        });
+    }
+    fn playing_or_fading(&self) -> bool {
+        !(self.sink.is_paused() || self.sink.empty())
+    }
+    /// names of all available output devices, which can be used in `new_with_device` or `set_device`
+    pub fn list_devices() -> Vec<String> {
+        match rodio::cpal::default_host().output_devices() {
+            Ok(devices) => devices.filter_map(|d| d.name().ok()).collect(),
+            Err(e) => {
+                eprintln!("[{}] Couldn't list output devices: {e}", "WARN".yellow());
+                vec![]
+            }
+        }
+    }
+    /// switch to another output device (or the default one if `None` or not found).
+    /// the current song will restart from the beginning on the new device.
+    pub fn set_device(&mut self, device: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+        let (output_stream, output_stream_handle) = output_stream(device)?;
+        let sink = Sink::try_new(&output_stream_handle)?;
+        let paused = !self.playing();
+        self.fade.generation.fetch_add(1, Ordering::Relaxed);
+        self.sink.clear();
+        self.output_stream = output_stream;
+        self.output_stream_handle = output_stream_handle;
+        self.sink = Arc::new(sink);
+        if let Some((_, bytes, _, gain, _)) = &self.current {
+            self.sink.set_volume(self.volume * gain);
+            if !self.stopped {
+                if let Ok(decoder) = decoder_from_bytes(Arc::clone(bytes)) {
+                    self.sink.append(self.repeat_source(decoder, bytes));
+                }
+            }
+        }
+        if paused {
+            self.sink.pause();
+        }
+        Ok(())
+    }
+}
+
+fn output_stream(
+    device: Option<&str>,
+) -> Result<(OutputStream, OutputStreamHandle), rodio::StreamError> {
+    if let Some(name) = device {
+        let device = rodio::cpal::default_host()
+            .output_devices()
+            .ok()
+            .and_then(|mut devices| devices.find(|d| d.name().is_ok_and(|n| n == name)));
+        if let Some(device) = device {
+            return OutputStream::try_from_device(&device);
+        } else {
+            eprintln!(
+                "[{}] Output device {name:?} not found, using default device",
+                "WARN".yellow()
+            );
+        }
+    }
+    OutputStream::try_default()
+}
+
 impl<T> PlayerBackend<T> for PlayerBackendRodio<T> {
     fn load_next_song(
         &mut self,
         id: SongId,
         _filename: &OsStr,
         bytes: Arc<Vec<u8>>,
-        _load_duration: bool,
+        _duration_millis: u64,
+        gain: f32,
         custom_data: T,
     ) {
         let decoder = decoder_from_bytes(Arc::clone(&bytes));
@@ -71,39 +210,73 @@ impl<T> PlayerBackend<T> for PlayerBackendRodio<T> {
                 .unwrap();
             }
         }
-        self.next = Some((id, bytes, decoder.ok(), custom_data));
+        self.next = Some((id, bytes, decoder.ok(), gain, custom_data));
     }
     fn pause(&mut self) {
-        self.sink.pause();
+        self.fade.audible.store(false, Ordering::Relaxed);
+        if self.fade.millis.load(Ordering::Relaxed) == 0 || !self.playing_or_fading() {
+            self.fade.generation.fetch_add(1, Ordering::Relaxed);
+            self.sink.pause();
+        } else {
+            self.after_fade(|sink, fade| {
+                // `resume` was called while fading out
+                if !fade.audible.load(Ordering::Relaxed) {
+                    sink.pause();
+                }
+            });
+        }
     }
     fn stop(&mut self) {
         if !self.stopped {
-            self.sink.clear();
-            if let Some((_, bytes, _, _)) = &self.current {
-                if let Ok(decoder) = decoder_from_bytes(Arc::clone(bytes)) {
-                    self.sink.append(decoder);
+            let fading = self.fade.millis.load(Ordering::Relaxed) > 0 && self.playing_or_fading();
+            self.fade.audible.store(false, Ordering::Relaxed);
+            let source = self.current.as_ref().and_then(|(_, bytes, _, _, _)| {
+                let decoder = decoder_from_bytes(Arc::clone(bytes)).ok()?;
+                Some(self.repeat_source(decoder, bytes))
+            });
+            let restart = move |sink: &Sink, fade: &Fade| {
+                sink.clear();
+                if let Some(source) = source {
+                    sink.append(source);
+                }
+                // `resume` was called while fading out, the song should still restart
+                if fade.audible.load(Ordering::Relaxed) {
+                    sink.play();
                 }
+            };
+            if fading {
+                self.after_fade(restart);
+            } else {
+                self.fade.generation.fetch_add(1, Ordering::Relaxed);
+                restart(&self.sink, &self.fade);
             }
         }
     }
     fn resume(&mut self) {
         self.stopped = false;
+        // if `pause` is still fading out, this cancels it
+        self.fade.audible.store(true, Ordering::Relaxed);
         self.sink.play();
     }
     fn next(&mut self, play: bool, load_duration: bool) {
         self.stopped = false;
+        self.fade.generation.fetch_add(1, Ordering::Relaxed);
+        self.fade.audible.store(play, Ordering::Relaxed);
         self.sink.clear();
+        self.current_format = None;
         self.current = self
             .next
             .take()
-            .map(|(id, bytes, mut decoder, custom_data)| {
+            .map(|(id, bytes, mut decoder, gain, custom_data)| {
+                self.sink.set_volume(self.volume * gain);
                 let duration = if let Some(decoder) = decoder.take() {
+                    self.current_format = Some((decoder.sample_rate(), decoder.channels()));
                     let duration = if load_duration {
                         dbg!(decoder.total_duration().map(|v| v.as_millis()))
                     } else {
                         None
                     };
-                    self.sink.append(decoder);
+                    self.sink.append(self.repeat_source(decoder, &bytes));
                     if play {
                         self.sink.play();
                     }
@@ -111,25 +284,44 @@ impl<T> PlayerBackend<T> for PlayerBackendRodio<T> {
                 } else {
                     None
                 };
-                (id, bytes, duration, custom_data)
+                (id, bytes, duration, gain, custom_data)
             });
     }
     fn clear(&mut self) {
+        self.fade.generation.fetch_add(1, Ordering::Relaxed);
         self.sink.clear();
     }
+    fn set_volume(&mut self, volume: f32) {
+        self.volume = volume;
+        let gain = self
+            .current
+            .as_ref()
+            .map_or(1.0, |(_, _, _, gain, _)| *gain);
+        self.sink.set_volume(self.volume * gain);
+    }
+    fn volume(&self) -> f32 {
+        self.volume
+    }
+    fn set_gain(&mut self, gain: f32) {
+        if let Some((_, _, _, current_gain, _)) = &mut self.current {
+            *current_gain = gain;
+            self.sink.set_volume(self.volume * gain);
+        }
+    }
+    /// `false` while fading out after `pause` or `stop`
     fn playing(&self) -> bool {
-        !(self.sink.is_paused() || self.sink.empty())
+        self.playing_or_fading() && self.fade.audible.load(Ordering::Relaxed)
     }
     fn current_song(&self) -> Option<(SongId, bool, &T)> {
-        self.current.as_ref().map(|(id, _, _, t)| (*id, true, t))
+        self.current.as_ref().map(|(id, _, _, _, t)| (*id, true, t))
     }
     fn next_song(&self) -> Option<(SongId, bool, &T)> {
-        self.next.as_ref().map(|(id, _, _, t)| (*id, true, t))
+        self.next.as_ref().map(|(id, _, _, _, t)| (*id, true, t))
     }
     fn gen_data_mut(&mut self) -> (Option<&mut T>, Option<&mut T>) {
         (
-            self.current.as_mut().map(|(_, _, _, t)| t),
-            self.next.as_mut().map(|(_, _, _, t)| t),
+            self.current.as_mut().map(|(_, _, _, _, t)| t),
+            self.next.as_mut().map(|(_, _, _, _, t)| t),
         )
     }
     fn song_finished_polling(&self) -> bool {
@@ -141,15 +333,126 @@ impl<T> PlayerBackend<T> for PlayerBackendRodio<T> {
     fn current_song_duration(&self) -> Option<u64> {
         self.current
             .as_ref()
-            .and_then(|(_, _, dur, _)| dur.map(|v| v as _))
+            .and_then(|(_, _, dur, _, _)| dur.map(|v| v as _))
     }
     fn current_song_playback_position(&self) -> Option<u64> {
+        if self.current.is_some() {
+            let pos = self.sink.get_pos().as_millis() as u64;
+            Some(pos.saturating_sub(self.repeat_start.load(Ordering::Relaxed)))
+        } else {
+            None
+        }
+    }
+    fn set_repeat_current(&mut self, repeat: bool) {
+        self.repeat.store(repeat, Ordering::Relaxed);
+    }
+    fn current_song_repetitions(&self) -> u64 {
+        self.repetitions.load(Ordering::Relaxed)
+    }
+    fn seek(&mut self, position: u64) {
+        if let Err(e) = self.sink.try_seek(Duration::from_millis(position)) {
+            eprintln!("[{}] Couldn't seek: {e}", "WARN".yellow());
+        }
+    }
+    fn set_fade(&mut self, millis: u64) {
+        self.fade.millis.store(millis, Ordering::Relaxed);
+    }
+    fn current_song_format(&self) -> Option<(u32, u16)> {
+        self.current_format
+    }
+    fn loading_progress(&self) -> Option<f32> {
+        // rodio decodes while playing, so a song is ready as soon as its bytes were loaded
         None
     }
 }
 
 type MyDecoder = Decoder<ArcU8Reader<Vec<u8>>>;
 
+/// Plays the song, and if it ends while `repeat` is set, starts it again without a gap.
+/// The song's bytes are already in memory, so this only needs a new decoder, not a new song in the sink
+/// (which would have a gap and make `song_finished` true).
+struct RepeatSource {
+    decoder: MyDecoder,
+    bytes: Arc<Vec<u8>>,
+    repeat: Arc<AtomicBool>,
+    repeat_start: Arc<AtomicU64>,
+    repetitions: Arc<AtomicU64>,
+    /// samples since the source was added to the sink, which is what the sink's position is based on
+    samples: u64,
+    fade: Arc<Fade>,
+    /// the current volume of the fade, from `0.0` to `1.0`
+    level: f32,
+}
+impl RepeatSource {
+    fn samples_to_millis(&self, samples: u64) -> u64 {
+        let per_second = self.decoder.sample_rate() as u64 * self.decoder.channels() as u64;
+        (samples * 1000).checked_div(per_second).unwrap_or(0)
+    }
+}
+impl Iterator for RepeatSource {
+    type Item = i16;
+    fn next(&mut self) -> Option<i16> {
+        let sample = match self.decoder.next() {
+            Some(sample) => sample,
+            None if self.repeat.load(Ordering::Relaxed) => {
+                self.decoder = decoder_from_bytes(Arc::clone(&self.bytes)).ok()?;
+                self.repeat_start
+                    .store(self.samples_to_millis(self.samples), Ordering::Relaxed);
+                self.repetitions.fetch_add(1, Ordering::Relaxed);
+                self.decoder.next()?
+            }
+            None => return None,
+        };
+        self.samples += 1;
+        let target = if self.fade.audible.load(Ordering::Relaxed) {
+            1.0
+        } else {
+            0.0
+        };
+        if self.level != target {
+            let millis = self.fade.millis.load(Ordering::Relaxed);
+            let per_second = self.decoder.sample_rate() as f32 * self.decoder.channels() as f32;
+            let step = if millis == 0 {
+                1.0
+            } else {
+                1000.0 / (millis as f32 * per_second)
+            };
+            self.level = if target > self.level {
+                (self.level + step).min(target)
+            } else {
+                (self.level - step).max(target)
+            };
+        }
+        if self.level == 1.0 {
+            Some(sample)
+        } else {
+            Some((sample as f32 * self.level) as i16)
+        }
+    }
+}
+impl Source for RepeatSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.decoder.current_frame_len()
+    }
+    fn channels(&self) -> u16 {
+        self.decoder.channels()
+    }
+    fn sample_rate(&self) -> u32 {
+        self.decoder.sample_rate()
+    }
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+    /// the sink sets its position to `pos`, so the position is no longer relative to `repeat_start`
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.decoder.try_seek(pos)?;
+        self.repeat_start.store(0, Ordering::Relaxed);
+        let per_second = self.decoder.sample_rate() as u64 * self.decoder.channels() as u64;
+        self.samples = pos.as_millis() as u64 * per_second / 1000;
+        Ok(())
+    }
+}
+
 fn decoder_from_bytes(bytes: Arc<Vec<u8>>) -> Result<MyDecoder, DecoderError> {
-    Decoder::new(ArcU8Reader::new(Arc::clone(&bytes))).map(|decoder| decoder)
+    Decoder::new(ArcU8Reader::new(bytes))
 }
@@ -0,0 +1,289 @@
+use std::{
+    ffi::OsStr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crate::data::SongId;
+
+use super::PlayerBackend;
+
+/// A backend which doesn't decode or play anything. A song "plays" for its `duration_millis` (wall-clock time),
+/// then `song_finished` becomes `true`, so the queue advances like it would with a real backend.
+/// Useful for testing, or running a server with `--play-audio` on a machine without audio output.
+pub struct PlayerBackendSleep<T> {
+    volume: f32,
+    repeat: bool,
+    current: Option<SleepSong<T>>,
+    next: Option<(SongId, u64, T)>,
+}
+struct SleepSong<T> {
+    id: SongId,
+    duration: Duration,
+    /// how long the song played before it was last paused
+    played: Duration,
+    /// `Some` while playing
+    resumed_at: Option<Instant>,
+    custom_data: T,
+}
+impl<T> SleepSong<T> {
+    fn position(&self) -> Duration {
+        self.played + self.resumed_at.map_or(Duration::ZERO, |v| v.elapsed())
+    }
+}
+
+impl<T> PlayerBackendSleep<T> {
+    pub fn new() -> Self {
+        Self {
+            volume: 1.0,
+            repeat: false,
+            current: None,
+            next: None,
+        }
+    }
+    /// like `new`, for compatibility with the other backends (see `PlayerBackendFeat`)
+    pub fn new_without_command_sending() -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self::new())
+    }
+}
+impl<T> Default for PlayerBackendSleep<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> PlayerBackend<T> for PlayerBackendSleep<T> {
+    fn load_next_song(
+        &mut self,
+        id: SongId,
+        _filename: &OsStr,
+        _bytes: Arc<Vec<u8>>,
+        duration_millis: u64,
+        _gain: f32,
+        custom_data: T,
+    ) {
+        self.next = Some((id, duration_millis, custom_data));
+    }
+    fn pause(&mut self) {
+        if let Some(song) = &mut self.current {
+            song.played = song.position();
+            song.resumed_at = None;
+        }
+    }
+    fn stop(&mut self) {
+        if let Some(song) = &mut self.current {
+            song.played = Duration::ZERO;
+            song.resumed_at = None;
+        }
+    }
+    fn resume(&mut self) {
+        if let Some(song) = &mut self.current {
+            if song.resumed_at.is_none() {
+                song.resumed_at = Some(Instant::now());
+            }
+        }
+    }
+    fn next(&mut self, play: bool, _load_duration: bool) {
+        self.current = self
+            .next
+            .take()
+            .map(|(id, duration, custom_data)| SleepSong {
+                id,
+                duration: Duration::from_millis(duration),
+                played: Duration::ZERO,
+                resumed_at: play.then(Instant::now),
+                custom_data,
+            });
+    }
+    fn clear(&mut self) {
+        self.current = None;
+        self.next = None;
+    }
+    fn set_volume(&mut self, volume: f32) {
+        self.volume = volume;
+    }
+    fn volume(&self) -> f32 {
+        self.volume
+    }
+    fn playing(&self) -> bool {
+        self.current
+            .as_ref()
+            .is_some_and(|song| song.resumed_at.is_some())
+    }
+    fn current_song(&self) -> Option<(SongId, bool, &T)> {
+        self.current
+            .as_ref()
+            .map(|song| (song.id, true, &song.custom_data))
+    }
+    fn next_song(&self) -> Option<(SongId, bool, &T)> {
+        self.next.as_ref().map(|(id, _, t)| (*id, true, t))
+    }
+    fn gen_data_mut(&mut self) -> (Option<&mut T>, Option<&mut T>) {
+        (
+            self.current.as_mut().map(|song| &mut song.custom_data),
+            self.next.as_mut().map(|(_, _, t)| t),
+        )
+    }
+    fn song_finished_polling(&self) -> bool {
+        true
+    }
+    fn song_finished(&self) -> bool {
+        !self.repeat
+            && self
+                .current
+                .as_ref()
+                .is_some_and(|song| song.position() >= song.duration)
+    }
+    /// the duration from the database, there is no way to find it out if it's `0`
+    fn current_song_duration(&self) -> Option<u64> {
+        self.current
+            .as_ref()
+            .map(|song| song.duration.as_millis() as u64)
+            .filter(|v| *v > 0)
+    }
+    fn current_song_playback_position(&self) -> Option<u64> {
+        self.current.as_ref().map(|song| {
+            let pos = song.position().as_millis() as u64;
+            let duration = song.duration.as_millis() as u64;
+            if self.repeat && duration > 0 {
+                pos % duration
+            } else {
+                pos.min(duration)
+            }
+        })
+    }
+    fn current_song_repetitions(&self) -> u64 {
+        match &self.current {
+            Some(song) if self.repeat && song.duration > Duration::ZERO => {
+                (song.position().as_nanos() / song.duration.as_nanos()) as u64
+            }
+            _ => 0,
+        }
+    }
+    fn set_repeat_current(&mut self, repeat: bool) {
+        if let (false, Some(song)) = (repeat, &mut self.current) {
+            // the song should finish at the end of the current repetition, not immediately
+            if song.duration > Duration::ZERO {
+                let pos = song.position();
+                let repetitions = pos.as_nanos() / song.duration.as_nanos();
+                song.played = pos - song.duration * repetitions as u32;
+                song.resumed_at = song.resumed_at.map(|_| Instant::now());
+            }
+        }
+        self.repeat = repeat;
+    }
+    fn seek(&mut self, position: u64) {
+        if let Some(song) = &mut self.current {
+            song.played = Duration::from_millis(position);
+            if song.resumed_at.is_some() {
+                song.resumed_at = Some(Instant::now());
+            }
+        }
+    }
+    fn loading_progress(&self) -> Option<f32> {
+        None
+    }
+}
+
+/// a database in a temporary directory, with a song (and an empty file) for each duration in the queue
+#[cfg(test)]
+fn test_db(
+    name: &str,
+    durations: &[u64],
+) -> (crate::data::database::Database, crate::test::TestDir) {
+    use crate::{
+        data::{artist::Artist, queue::QueueContent, song::Song, GeneralData},
+        server::{Action, Req},
+    };
+    let (mut db, dir) = crate::test::test_db(name);
+    let artist = db.add_artist_new(Artist {
+        id: 0,
+        name: "Artist".to_owned(),
+        aliases: vec![],
+        cover: None,
+        albums: vec![],
+        singles: vec![],
+        general: GeneralData::default(),
+    });
+    let mut queue = vec![];
+    for (i, duration) in durations.iter().enumerate() {
+        let file = format!("{i}.mp3");
+        std::fs::write(dir.join(&file), "not actually audio").unwrap();
+        let song = Song::new(
+            file.into(),
+            None,
+            format!("Song {i}"),
+            None,
+            artist,
+            vec![],
+            None,
+            0,
+            *duration,
+            GeneralData::default(),
+        );
+        queue.push(QueueContent::Song(db.add_song_new(song)).into());
+    }
+    db.apply_action_unchecked_seq(Action::QueueAdd(vec![], queue, Req::none()), None);
+    (db, dir)
+}
+
+#[test]
+fn test_sleep_backend_plays_queue() {
+    use crate::{player::Player, server::Action};
+    let (mut db, _dir) = test_db("sleep", &[60, 100, 40]);
+    db.apply_action_unchecked_seq(Action::Resume, None);
+    let mut player = Player::new(PlayerBackendSleep::new());
+    let start = Instant::now();
+    // (song, when it started playing)
+    let mut played: Vec<(SongId, Duration)> = vec![];
+    while db.playing && start.elapsed() < Duration::from_secs(5) {
+        player.update(&mut db);
+        if let Some((id, _, _)) = player.backend.current_song() {
+            // at the end of the queue, the first song is loaded again, but paused
+            if player.backend.playing() && played.last().map(|v| v.0) != Some(id) {
+                played.push((id, start.elapsed()));
+            }
+        }
+        std::thread::sleep(Duration::from_millis(2));
+    }
+    let elapsed = start.elapsed();
+    assert_eq!(
+        played.iter().map(|v| v.0).collect::<Vec<_>>(),
+        vec![0, 1, 2],
+        "{played:?}"
+    );
+    // songs play for (at least) their duration, but not much longer
+    assert!(played[1].1 >= Duration::from_millis(60), "{played:?}");
+    assert!(played[2].1 >= Duration::from_millis(160), "{played:?}");
+    assert!(elapsed >= Duration::from_millis(200), "{elapsed:?}");
+    assert!(elapsed < Duration::from_secs(2), "{elapsed:?}");
+    assert!(db.songs().values().all(|song| song.play_count == 1));
+}
+
+#[test]
+fn test_sleep_backend_repeat_one() {
+    use crate::{data::queue::RepeatMode, player::Player, server::Action};
+    let (mut db, _dir) = test_db("sleep-repeat", &[30, 1000]);
+    db.apply_action_unchecked_seq(Action::QueueSetRepeat(RepeatMode::One), None);
+    db.apply_action_unchecked_seq(Action::Resume, None);
+    let mut player = Player::new(PlayerBackendSleep::new());
+    let update_for = |player: &mut Player<PlayerBackendSleep<_>>, db: &mut _, ms| {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_millis(ms) {
+            player.update(db);
+            std::thread::sleep(Duration::from_millis(2));
+        }
+    };
+    // the first song repeats in the backend, so it never finishes
+    update_for(&mut player, &mut db, 100);
+    assert_eq!(player.backend.current_song().map(|v| v.0), Some(0));
+    assert!(player.backend.current_song_playback_position().unwrap() < 30);
+    // but every repetition is counted as a play
+    let plays = db.songs()[&0].play_count;
+    assert!((3..=5).contains(&plays), "{plays}");
+    assert!(db.songs()[&0].last_played.is_some());
+    // after the current repetition, the queue continues
+    db.apply_action_unchecked_seq(Action::QueueSetRepeat(RepeatMode::Off), None);
+    update_for(&mut player, &mut db, 100);
+    assert_eq!(player.backend.current_song().map(|v| v.0), Some(1));
+}
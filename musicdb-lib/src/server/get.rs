@@ -1,12 +1,12 @@
 use std::{
     fs,
     io::{BufRead, BufReader, Read, Write},
-    path::{Path, PathBuf},
+    path::{Component, Path, PathBuf},
     sync::{Arc, Mutex},
     time::{Instant, SystemTime},
 };
 
-use crate::data::{database::Database, CoverId, SongId};
+use crate::data::{database::Database, filter::FilterType, CoverId, SongId};
 
 pub struct Client<T: Write + Read>(BufReader<T>);
 impl<T: Write + Read> Client<T> {
@@ -36,11 +36,16 @@ impl<T: Write + Read> Client<T> {
             Ok(Err(response))
         }
     }
-    pub fn song_file(&mut self, id: SongId) -> Result<Result<Vec<u8>, String>, std::io::Error> {
+    /// A small version of the cover (see `data::thumbnail`), for lists and other places where the full image isn't needed.
+    /// Returns an error if the server was built without the `thumbnails` feature, use `cover_bytes` then.
+    pub fn cover_thumbnail(
+        &mut self,
+        id: CoverId,
+    ) -> Result<Result<Vec<u8>, String>, std::io::Error> {
         writeln!(
             self.0.get_mut(),
             "{}",
-            con_get_encode_string(&format!("song-file\n{id}",))
+            con_get_encode_string(&format!("cover-thumbnail\n{id}"))
         )?;
         self.0.get_mut().flush()?;
         let mut response = String::new();
@@ -57,6 +62,33 @@ impl<T: Write + Read> Client<T> {
             Ok(Err(response))
         }
     }
+    pub fn song_file(&mut self, id: SongId) -> Result<Result<Vec<u8>, String>, std::io::Error> {
+        self.song_file_with_progress(id, |_, _| ())
+    }
+    /// like `song_file`, but calls `progress(received, total)` while the song's bytes are being received
+    pub fn song_file_with_progress(
+        &mut self,
+        id: SongId,
+        progress: impl FnMut(usize, usize),
+    ) -> Result<Result<Vec<u8>, String>, std::io::Error> {
+        writeln!(
+            self.0.get_mut(),
+            "{}",
+            con_get_encode_string(&format!("song-file\n{id}",))
+        )?;
+        self.0.get_mut().flush()?;
+        let mut response = String::new();
+        self.0.read_line(&mut response)?;
+        if response.starts_with("len: ") {
+            if let Ok(len) = response[4..].trim().parse() {
+                Ok(Ok(read_with_progress(&mut self.0, len, progress)?))
+            } else {
+                Ok(Err(response))
+            }
+        } else {
+            Ok(Err(response))
+        }
+    }
     pub fn custom_file(&mut self, path: &str) -> Result<Result<Vec<u8>, String>, std::io::Error> {
         writeln!(
             self.0.get_mut(),
@@ -239,6 +271,46 @@ impl<T: Write + Read> Client<T> {
             };
         }
     }
+    /// The ids of all songs with a tag `<tag><integer>` where the integer is between `min` and `max` (both inclusive),
+    /// like `FilterType::TagWithValueInt`. For example, `("Year=", 1990, 1999)` finds all songs from the 90s.
+    /// The server does the filtering, so the client doesn't need the whole database for this.
+    pub fn songs_with_tag_value_int(
+        &mut self,
+        tag: &str,
+        min: i32,
+        max: i32,
+    ) -> Result<Result<Vec<SongId>, String>, std::io::Error> {
+        if tag.contains(['\n', '\r']) {
+            return Ok(Err("tag can't contain newlines".to_owned()));
+        }
+        writeln!(
+            self.0.get_mut(),
+            "{}",
+            con_get_encode_string(&format!("songs-with-tag-value-int\n{tag}\n{min}\n{max}"))
+        )?;
+        self.0.get_mut().flush()?;
+        let mut response = String::new();
+        self.0.read_line(&mut response)?;
+        let len_line = response.trim();
+        if len_line.starts_with("len: ") {
+            if let Ok(len) = len_line[4..].trim().parse() {
+                let mut out = Vec::with_capacity(len);
+                for _ in 0..len {
+                    let mut line = String::new();
+                    self.0.read_line(&mut line)?;
+                    match line.trim().parse() {
+                        Ok(id) => out.push(id),
+                        Err(e) => return Ok(Err(format!("error parsing songid '{line}': {e}"))),
+                    }
+                }
+                Ok(Ok(out))
+            } else {
+                Ok(Err(format!("bad len in len-line: {len_line}")))
+            }
+        } else {
+            Ok(Err(response))
+        }
+    }
     /// tell the server to search for files that are not in its song database.
     ///
     /// ## `extensions`:
@@ -352,6 +424,44 @@ pub fn handle_one_connection_as_get(
                             writeln!(connection.get_mut(), "bad id")?;
                         }
                     }
+                    "cover-thumbnail" => {
+                        if let Some(cover_id) = request.next().and_then(|id| id.parse().ok()) {
+                            let dbl = db.lock().unwrap();
+                            let thumbnail =
+                                if let Some(get_con) = &dbl.remote_server_as_song_file_source {
+                                    match get_con.lock().unwrap().cover_thumbnail(cover_id) {
+                                        Ok(v) => v,
+                                        Err(e) => Err(e.to_string()),
+                                    }
+                                } else {
+                                    #[cfg(feature = "thumbnails")]
+                                    match dbl.cover_thumbnail_paths(cover_id) {
+                                        Some((cover, thumbnail)) => {
+                                            drop(dbl);
+                                            crate::data::thumbnail::load_or_create_thumbnail(
+                                                &cover, &thumbnail,
+                                            )
+                                        }
+                                        None => Err("no cover".to_owned()),
+                                    }
+                                    #[cfg(not(feature = "thumbnails"))]
+                                    Err("no thumbnails".to_owned())
+                                };
+                            match thumbnail {
+                                Ok(bytes) => {
+                                    writeln!(connection.get_mut(), "len: {}", bytes.len())?;
+                                    connection.get_mut().write_all(&bytes)?;
+                                }
+                                Err(e) => writeln!(
+                                    connection.get_mut(),
+                                    "{}",
+                                    e.trim().replace('\n', " ")
+                                )?,
+                            }
+                        } else {
+                            writeln!(connection.get_mut(), "bad id")?;
+                        }
+                    }
                     "song-file" => {
                         if let Some(bytes) =
                             request
@@ -385,7 +495,7 @@ pub fn handle_one_connection_as_get(
                         if let Some(bytes) =
                             request.next().and_then(|path| 'load_custom_file_data: {
                                 let db = db.lock().unwrap();
-                                let mut parent = match &db.custom_files {
+                                let parent = match &db.custom_files {
                                     None => {
                                         if let Some(con) = &db.remote_server_as_song_file_source {
                                             if let Ok(Ok(data)) =
@@ -409,20 +519,8 @@ pub fn handle_one_connection_as_get(
                                     }
                                     Some(Some(p)) => Some(p.clone()),
                                 };
-                                // check for malicious paths [TODO: Improve]
-                                if Path::new(path).is_absolute() {
-                                    parent = None;
-                                }
-                                if let Some(parent) = parent {
-                                    let path = parent.join(path);
-                                    if path.starts_with(parent) {
-                                        fs::read(path).ok()
-                                    } else {
-                                        None
-                                    }
-                                } else {
-                                    None
-                                }
+                                drop(db);
+                                fs::read(path_in_dir(&parent?, Path::new(path))?).ok()
                             })
                         {
                             writeln!(connection.get_mut(), "len: {}", bytes.len())?;
@@ -433,22 +531,8 @@ pub fn handle_one_connection_as_get(
                     }
                     "song-file-by-path" => {
                         if let Some(bytes) = request.next().and_then(|path| {
-                            let db = db.lock().unwrap();
-                            let mut parent = Some(db.lib_directory.clone());
-                            // check for malicious paths [TODO: Improve]
-                            if Path::new(path).is_absolute() {
-                                parent = None;
-                            }
-                            if let Some(parent) = parent {
-                                let path = parent.join(path);
-                                if path.starts_with(parent) {
-                                    fs::read(path).ok()
-                                } else {
-                                    None
-                                }
-                            } else {
-                                None
-                            }
+                            let lib_dir = db.lock().unwrap().lib_directory.clone();
+                            fs::read(path_in_dir(&lib_dir, Path::new(path))?).ok()
                         }) {
                             writeln!(connection.get_mut(), "len: {}", bytes.len())?;
                             connection.get_mut().write_all(&bytes)?;
@@ -456,6 +540,31 @@ pub fn handle_one_connection_as_get(
                             writeln!(connection.get_mut(), "no data")?;
                         }
                     }
+                    "songs-with-tag-value-int" => {
+                        let query = (|| {
+                            let tag = request.next()?.to_owned();
+                            let min = request.next()?.trim().parse().ok()?;
+                            let max = request.next()?.trim().parse().ok()?;
+                            Some(FilterType::TagWithValueInt(tag, min, max))
+                        })();
+                        if let Some(filter) = query {
+                            let mut songs = db
+                                .lock()
+                                .unwrap()
+                                .songs()
+                                .values()
+                                .filter(|song| filter.passes(&song.general))
+                                .map(|song| song.id)
+                                .collect::<Vec<_>>();
+                            songs.sort_unstable();
+                            writeln!(connection.get_mut(), "len: {}", songs.len())?;
+                            for id in songs {
+                                writeln!(connection.get_mut(), "{id}")?;
+                            }
+                        } else {
+                            writeln!(connection.get_mut(), "bad request")?;
+                        }
+                    }
                     "find-songs-with-changed-files" => {
                         let db_lock = db.lock().unwrap();
                         let lib_directory = db_lock.lib_directory.clone();
@@ -601,6 +710,44 @@ pub fn handle_one_connection_as_get(
     }
 }
 
+/// `dir.join(rel_path)`, but only if that is inside of `dir`, otherwise `None`.
+/// Used for paths sent by clients, so they can't read arbitrary files on the server:
+/// `rel_path` may not be absolute or contain `..`, and, if the file exists,
+/// it may not be a symlink (or in a symlinked directory) which leads outside of `dir`.
+/// reads exactly `len` bytes in chunks, calling `progress(read, len)` after each one
+pub fn read_with_progress(
+    r: &mut impl Read,
+    len: usize,
+    mut progress: impl FnMut(usize, usize),
+) -> std::io::Result<Vec<u8>> {
+    let mut bytes = vec![0; len];
+    let mut read = 0;
+    progress(read, len);
+    while read < len {
+        let end = (read + 64 * 1024).min(len);
+        r.read_exact(&mut bytes[read..end])?;
+        read = end;
+        progress(read, len);
+    }
+    Ok(bytes)
+}
+
+pub fn path_in_dir(dir: &Path, rel_path: &Path) -> Option<PathBuf> {
+    if !rel_path
+        .components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        return None;
+    }
+    let path = dir.join(rel_path);
+    match (path.canonicalize(), dir.canonicalize()) {
+        (Ok(canonical), Ok(dir)) if canonical.starts_with(&dir) => Some(path),
+        (Ok(_), _) => None,
+        // doesn't exist, so it can't be read anyway
+        (Err(_), _) => Some(path),
+    }
+}
+
 pub fn con_get_decode_line(line: &str) -> String {
     let mut o = String::new();
     let mut chars = line.chars();
@@ -712,3 +859,115 @@ fn find_unused_song_files_internal(
         }
     }
 }
+
+#[test]
+fn test_path_in_dir() {
+    let dir = crate::test::TestDir::new("get");
+    fs::create_dir_all(dir.join("lib/a")).unwrap();
+    fs::write(dir.join("lib/a/file.txt"), "ok").unwrap();
+    fs::write(dir.join("secret.txt"), "secret").unwrap();
+    let lib = dir.join("lib");
+    for ok in ["a/file.txt", "./a/file.txt", "a/missing.txt"] {
+        assert_eq!(path_in_dir(&lib, Path::new(ok)), Some(lib.join(ok)), "{ok}");
+    }
+    for bad in [
+        "../secret.txt",
+        "a/../../secret.txt",
+        "../../../../../../etc/passwd",
+        "a/../a/file.txt",
+        "/etc/passwd",
+    ] {
+        assert_eq!(path_in_dir(&lib, Path::new(bad)), None, "{bad}");
+    }
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(dir.join("secret.txt"), lib.join("link.txt")).unwrap();
+        assert_eq!(path_in_dir(&lib, Path::new("link.txt")), None);
+    }
+}
+
+#[test]
+fn test_songs_with_tag_value_int() {
+    use crate::data::{artist::Artist, song::Song, GeneralData};
+    let mut db = Database::new_empty_in_dir(PathBuf::new(), PathBuf::new());
+    let artist = db.add_artist_new(Artist {
+        id: 0,
+        name: "Artist".to_owned(),
+        aliases: vec![],
+        cover: None,
+        albums: vec![],
+        singles: vec![],
+        general: GeneralData::default(),
+    });
+    let mut ids = vec![];
+    for tags in [
+        vec!["Year=1989"],
+        vec!["Year=1990", "Genre=Rock"],
+        vec!["Year=1999"],
+        vec!["Year=2000"],
+        vec!["Year=199x"],
+        vec![],
+    ] {
+        let general = GeneralData {
+            tags: tags.into_iter().map(|t| t.to_owned()).collect(),
+        };
+        let song = Song::new(
+            format!("{}.mp3", ids.len()).into(),
+            None,
+            format!("Song {}", ids.len()),
+            None,
+            artist,
+            vec![],
+            None,
+            0,
+            0,
+            general,
+        );
+        ids.push(db.add_song_new(song));
+    }
+    let db = Arc::new(Mutex::new(db));
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = std::thread::spawn(move || {
+        let mut con = BufReader::new(listener.accept().unwrap().0);
+        let mut line = String::new();
+        con.read_line(&mut line).unwrap();
+        assert_eq!(line.trim(), "get");
+        handle_one_connection_as_get(db, &mut con).unwrap();
+    });
+    let mut client =
+        Client::new(BufReader::new(std::net::TcpStream::connect(addr).unwrap())).unwrap();
+    assert_eq!(
+        client
+            .songs_with_tag_value_int("Year=", 1990, 1999)
+            .unwrap(),
+        Ok(vec![ids[1], ids[2]])
+    );
+    assert_eq!(
+        client
+            .songs_with_tag_value_int("Year=", 3000, 4000)
+            .unwrap(),
+        Ok(vec![])
+    );
+    assert!(client
+        .songs_with_tag_value_int("Year=\n", 0, 1)
+        .unwrap()
+        .is_err());
+    drop(client);
+    server.join().unwrap();
+}
+
+#[test]
+fn test_read_with_progress() {
+    let data = (0..200_000).map(|i| i as u8).collect::<Vec<_>>();
+    let mut reports = vec![];
+    let read = read_with_progress(&mut data.as_slice(), data.len(), |read, len| {
+        reports.push((read, len))
+    })
+    .unwrap();
+    assert_eq!(read, data);
+    assert_eq!(reports.first(), Some(&(0, data.len())));
+    assert_eq!(reports.last(), Some(&(data.len(), data.len())));
+    assert!(reports.len() > 2 && reports.windows(2).all(|w| w[0].0 < w[1].0));
+    assert!(read_with_progress(&mut &data[..10], 20, |_, _| ()).is_err());
+}
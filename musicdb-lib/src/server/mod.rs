@@ -18,8 +18,8 @@ use crate::{
         album::Album,
         artist::Artist,
         database::{Cover, Database, UpdateEndpoint},
-        queue::Queue,
-        song::Song,
+        queue::{Queue, RepeatMode, ShuffleWeight},
+        song::{ReplayGainMode, Song},
         AlbumId, ArtistId, SongId,
     },
     load::ToFromBytes,
@@ -81,18 +81,33 @@ impl Action {
             | Self::Pause
             | Self::Stop
             | Self::NextSong
+            | Self::PreviousSong
+            | Self::StopAfterCurrentSong(_)
+            | Self::SetVolume(_)
+            | Self::SetMuted(_)
+            | Self::SetCurrentSongPosition(_)
+            | Self::PlaybackPosition(_)
+            | Self::SetCurrentSongFormat(_)
+            | Self::SetCacheConfig { .. }
+            | Self::SetCachePinned(_, _)
             | Self::SyncDatabase(_, _, _)
+            | Self::QueueSetRepeat(_)
+            | Self::QueueReplace(_, _)
+            | Self::QueueInsertAfterCurrent(_)
             | Self::QueueRemove(_)
             | Self::QueueMove(_, _)
             | Self::QueueMoveInto(_, _)
+            | Self::QueueMoveMultiple(_, _)
             | Self::QueueGoto(_)
-            | Self::QueueShuffle(_)
-            | Self::QueueSetShuffle(_, _)
+            | Self::PlayFrom(_)
+            | Self::QueueShuffle(_, _, _)
+            | Self::QueueSetShuffle(_, _, _, _)
             | Self::QueueUnshuffle(_)
             | Self::RemoveSong(_)
             | Self::RemoveAlbum(_)
             | Self::RemoveArtist(_)
             | Self::SetSongDuration(_, _)
+            | Self::SongPlayed(_, _)
             | Self::TagSongFlagSet(_, _)
             | Self::TagSongFlagUnset(_, _)
             | Self::TagAlbumFlagSet(_, _)
@@ -105,8 +120,15 @@ impl Action {
             | Self::TagAlbumPropertyUnset(_, _)
             | Self::TagArtistPropertySet(_, _, _)
             | Self::TagArtistPropertyUnset(_, _)
+            | Self::SetSongRating(_, _)
+            | Self::SetSongGain(_, _)
+            | Self::SetArtistAliases(_, _)
+            | Self::SavePlaylist(_, _)
+            | Self::RenamePlaylist(_, _)
+            | Self::RemovePlaylist(_)
             | Self::InitComplete
             | Self::Save
+            | Self::ReloadDatabase
             | Self::ErrorInfo(_, _) => vec![],
             Self::Multiple(actions) => actions.iter_mut().flat_map(|v| v.req_mut()).collect(),
         }
@@ -176,21 +198,64 @@ pub enum Action {
     Pause,
     Stop,
     NextSong,
+    /// Go back to the previous song, or restart the current song if it has been playing for a few seconds
+    /// (see `database::PREVIOUS_SONG_RESTART_MILLIS`) or if there is no previous song.
+    PreviousSong,
+    /// If `true`, pause once the current song finishes playing (instead of continuing with the next song).
+    /// Resets to `false` when that happens.
+    StopAfterCurrentSong(bool),
+    /// `1.0` is the default volume. This is stored even while muted.
+    SetVolume(f32),
+    /// Mute or unmute without changing the volume
+    SetMuted(bool),
+    /// Jump to a position (in milliseconds) in the current song
+    SetCurrentSongPosition(u64),
+    /// The current song's playback position (in milliseconds), sent by the server about twice a second while playing
+    /// so clients can show the correct position. Unlike `SetCurrentSongPosition`, this doesn't seek.
+    PlaybackPosition(u64),
+    /// The sample rate and channel count of the current song, as decoded by the server's player backend.
+    /// Sent by the server, `None` if no song is loaded or the backend doesn't decode songs.
+    SetCurrentSongFormat(Option<(u32, u16)>),
+    /// Reconfigures the server's `CacheManager` (only if it was started with `--advanced-cache`),
+    /// see `CacheManager::set_cache_songs_count` and `CacheManager::set_memory_mib`.
+    /// Only accepted from `control` connections, `main` connections (clients) can't change this.
+    SetCacheConfig {
+        songs: u32,
+        min_mib: u64,
+        max_mib: u64,
+    },
+    /// Pin (`true`) or unpin (`false`) a song in the server's `CacheManager`, see `CacheManager::pin`.
+    /// Like `SetCacheConfig`, only accepted from `control` connections.
+    SetCachePinned(SongId, bool),
     SyncDatabase(Vec<Artist>, Vec<Album>, Vec<Song>),
     QueueUpdate(Vec<usize>, Queue, Req),
     QueueAdd(Vec<usize>, Vec<Queue>, Req),
+    /// Replace the entire queue in one step, for example to play a playlist.
+    /// If `true` and the current song is somewhere in the new queue, that song stays the current song
+    /// (so it keeps playing), otherwise the new queue's current song is used.
+    QueueReplace(Queue, bool),
     QueueInsert(Vec<usize>, usize, Vec<Queue>, Req),
+    /// Insert the elements right after the current one ("play next"), see `Queue::get_insert_after_current`,
+    /// or add them to the end of the queue if nothing is playing.
+    /// If the current song is in a folder inside a loop, they become part of that loop.
+    QueueInsertAfterCurrent(Vec<Queue>),
     QueueRemove(Vec<usize>),
     /// Move an element from A to B
     QueueMove(Vec<usize>, Vec<usize>),
     /// Take an element from A and add it to the end of the folder B
     QueueMoveInto(Vec<usize>, Vec<usize>),
+    /// Move all elements from A to B, keeping their order (see `Queue::move_multiple`)
+    QueueMoveMultiple(Vec<Vec<usize>>, Vec<usize>),
     QueueGoto(Vec<usize>),
-    // sent by clients when they want to shuffle a folder
-    QueueShuffle(Vec<usize>),
-    // sent by the server when the folder was shuffled
-    QueueSetShuffle(Vec<usize>, Vec<usize>),
+    /// `QueueGoto` and `Resume` in one step
+    PlayFrom(Vec<usize>),
+    // sent by clients when they want to shuffle a folder.
+    // if no seed is given, the server picks a random one.
+    QueueShuffle(Vec<usize>, ShuffleWeight, Option<u64>),
+    // sent by the server when the folder was shuffled or its next element was picked (path, order, seed, weight)
+    QueueSetShuffle(Vec<usize>, Vec<usize>, Option<u64>, ShuffleWeight),
     QueueUnshuffle(Vec<usize>),
+    QueueSetRepeat(RepeatMode),
 
     /// .id field is ignored!
     AddSong(Song, Req),
@@ -206,6 +271,9 @@ pub enum Action {
     RemoveAlbum(AlbumId),
     RemoveArtist(ArtistId),
     SetSongDuration(SongId, u64),
+    /// The song started playing (again) at the given time, in milliseconds since the unix epoch.
+    /// Increases its `play_count` and sets its `last_played`. Only the server's player sends this.
+    SongPlayed(SongId, u64),
     /// Add the given Tag to the song's tags, if it isn't set already.
     TagSongFlagSet(SongId, String),
     /// Remove the given Tag fron the song's tags, if it exists.
@@ -223,11 +291,30 @@ pub enum Action {
     TagAlbumPropertyUnset(AlbumId, String),
     TagArtistPropertySet(ArtistId, String, String),
     TagArtistPropertyUnset(ArtistId, String),
+    /// Sets the song's rating (`0` to `5`, higher values are treated as `5`), or removes it if `None`.
+    /// Ratings are stored as a `Rating=` tag (see `song::RATING_TAG`), not as a separate field.
+    SetSongRating(SongId, Option<u8>),
+    /// Sets a manual volume adjustment (in dB) for the song, which the player applies on top of the volume and ReplayGain.
+    /// `0.0` (or a value which isn't finite) removes it, other values are clamped to `song::MAX_GAIN_DB`.
+    /// Stored as a `Gain=` tag (see `song::GAIN_TAG`).
+    SetSongGain(SongId, f32),
+    /// Replaces the artist's aliases (see `Artist::aliases`). Empty and duplicate aliases are removed.
+    SetArtistAliases(ArtistId, Vec<String>),
+
+    /// Saves the queue as a playlist with the given name (see `Database::playlists`), replacing any playlist with the same name.
+    /// The server saves playlists to the `playlists` file in its `db_dir`, so they are available to all clients.
+    SavePlaylist(String, Queue),
+    /// Renames a playlist (old name, new name). Does nothing if the new name is already in use.
+    RenamePlaylist(String, String),
+    RemovePlaylist(String),
 
     Multiple(Vec<Self>),
 
     InitComplete,
     Save,
+    /// Read the server's dbfile again, for example after `musicdb-filldb --update` rewrote it,
+    /// and apply (and broadcast) the differences. Does nothing if the server doesn't use a local dbfile.
+    ReloadDatabase,
     ErrorInfo(String, String),
 
     /// The server denied a request or an action.
@@ -252,6 +339,34 @@ impl Command {
     }
 }
 
+/// settings for the server's `Player`, only used if `play_audio` is `true`.
+#[derive(Clone, Debug)]
+pub struct PlayerConfig {
+    pub replaygain_mode: ReplayGainMode,
+    /// see `Player::skip_unplayable`
+    pub skip_unplayable: bool,
+    /// the name of the output device (see `list_audio_devices`), or `None` for the default device.
+    /// only the rodio backend supports this.
+    pub audio_device: Option<String>,
+}
+impl Default for PlayerConfig {
+    fn default() -> Self {
+        Self {
+            replaygain_mode: ReplayGainMode::Off,
+            skip_unplayable: false,
+            audio_device: None,
+        }
+    }
+}
+/// the names of the output devices which can be used as `PlayerConfig::audio_device`,
+/// or `None` if the player backend this was compiled with always uses the default device.
+pub fn list_audio_devices() -> Option<Vec<String>> {
+    #[cfg(feature = "playback-via-rodio")]
+    return Some(crate::player::rodio::PlayerBackendRodio::<()>::list_devices());
+    #[cfg(not(feature = "playback-via-rodio"))]
+    None
+}
+
 /// starts handling database.command_sender events and optionally spawns a tcp server.
 /// this function creates a new command_sender.
 /// if you wish to implement your own server, set db.command_sender to None,
@@ -271,7 +386,14 @@ pub fn run_server(
     sender_sender: Option<Box<dyn FnOnce(mpsc::Sender<(Command, Option<u64>)>)>>,
     play_audio: bool,
 ) {
-    run_server_caching_thread_opt(database, addr_tcp, sender_sender, None, play_audio)
+    run_server_caching_thread_opt(
+        database,
+        addr_tcp,
+        sender_sender,
+        None,
+        play_audio,
+        PlayerConfig::default(),
+    )
 }
 pub fn run_server_caching_thread_opt(
     database: Arc<Mutex<Database>>,
@@ -279,11 +401,14 @@ pub fn run_server_caching_thread_opt(
     sender_sender: Option<Box<dyn FnOnce(mpsc::Sender<(Command, Option<u64>)>)>>,
     caching_thread: Option<Box<dyn FnOnce(&mut crate::data::cache_manager::CacheManager)>>,
     play_audio: bool,
+    player_config: PlayerConfig,
 ) {
     #[cfg(not(feature = "playback"))]
     if play_audio {
         panic!("Can't run the server: cannot play audio because the `playback` feature was disabled when compiling, but `play_audio` was set to `true`!");
     }
+    #[cfg(not(feature = "playback"))]
+    let _ = player_config;
 
     use std::time::Instant;
 
@@ -292,6 +417,8 @@ pub fn run_server_caching_thread_opt(
     use crate::player::playback_rs::PlayerBackendPlaybackRs;
     #[cfg(feature = "playback-via-rodio")]
     use crate::player::rodio::PlayerBackendRodio;
+    #[cfg(feature = "playback-via-sleep")]
+    use crate::player::sleep::PlayerBackendSleep;
     #[cfg(any(
         feature = "playback",
         feature = "playback-via-playback-rs",
@@ -303,20 +430,44 @@ pub fn run_server_caching_thread_opt(
     // these commands are sent to the database asap.
     let (command_sender, command_receiver) = mpsc::channel();
 
+    // restore the queue (and playback position) from before the last restart
+    #[cfg(feature = "playback")]
+    let mut restore_position = database.lock().unwrap().load_playback_state();
+    #[cfg(not(feature = "playback"))]
+    database.lock().unwrap().load_playback_state();
     #[cfg(feature = "playback")]
     let mut player = if play_audio {
         #[cfg(feature = "playback-via-playback-rs")]
         let backend = PlayerBackendPlaybackRs::new(command_sender.clone()).unwrap();
         #[cfg(feature = "playback-via-rodio")]
-        let backend = PlayerBackendRodio::new(command_sender.clone()).unwrap();
-        Some(Player::new(backend))
+        let backend = PlayerBackendRodio::new_with_device(
+            Some(command_sender.clone()),
+            player_config.audio_device.as_deref(),
+        )
+        .unwrap();
+        #[cfg(not(feature = "playback-via-rodio"))]
+        if let Some(device) = &player_config.audio_device {
+            eprintln!(
+                "[{}] Output device {device:?} can't be chosen with this player backend (only rodio supports it), using default device",
+                "WARN".yellow()
+            );
+        }
+        #[cfg(feature = "playback-via-sleep")]
+        let backend = PlayerBackendSleep::new();
+        let mut player = Player::new(backend);
+        player.replaygain_mode = player_config.replaygain_mode;
+        player.skip_unplayable = player_config.skip_unplayable;
+        Some(player)
     } else {
         None
     };
-    #[allow(unused)]
     let cache_manager = if let Some(func) = caching_thread {
         let mut cm = CacheManager::new(Arc::clone(&database));
         func(&mut cm);
+        #[cfg(feature = "playback")]
+        if let Some(player) = &mut player {
+            player.set_cache_counters(cm.counters());
+        }
         Some(cm)
     } else {
         None
@@ -387,6 +538,11 @@ pub fn run_server_caching_thread_opt(
     };
     let mut check = 0;
     let mut checkf = true;
+    let mut last_playback_state_save = Instant::now();
+    // the queue (as bytes) and position which were saved last, to avoid rewriting an unchanged file
+    let mut last_playback_state = None;
+    #[cfg(feature = "playback")]
+    let mut last_position_broadcast = Instant::now();
     loop {
         check += 1;
         #[cfg(feature = "playback")]
@@ -406,19 +562,71 @@ pub fn run_server_caching_thread_opt(
                 } else {
                     player.update(&mut db);
                 }
+                // seek to the restored position once the song is loaded
+                if restore_position.is_some()
+                    && player
+                        .backend
+                        .current_song()
+                        .is_some_and(|(_, loaded, _)| loaded)
+                {
+                    player.backend.seek(restore_position.take().unwrap());
+                }
             }
+            #[cfg(feature = "playback")]
+            let position = player
+                .as_ref()
+                .and_then(|p| p.backend.current_song_playback_position());
+            #[cfg(not(feature = "playback"))]
+            let position = None;
             // autosave if necessary
-            if let Some((first, last)) = db.times_data_modified {
-                let now = Instant::now();
-                if (now - first).as_secs_f32() > 60.0 && (now - last).as_secs_f32() > 5.0 {
-                    if let Err(e) = db.save_database(None) {
-                        eprintln!("[{}] Autosave failed: {e}", "ERR!".red());
+            let now = Instant::now();
+            // the playback position changes without modifying data, so save this more often
+            let mut save_playback_state = (now - last_playback_state_save).as_secs_f32() > 10.0;
+            if db.autosave_due(now) {
+                if let Err(e) = db.save_database(None) {
+                    eprintln!("[{}] Autosave failed: {e}", "ERR!".red());
+                }
+                save_playback_state = true;
+            }
+            if save_playback_state {
+                last_playback_state_save = now;
+                // only if the queue changed or the position moved (which it doesn't while paused)
+                let mut queue = vec![];
+                if db.queue.to_bytes(&mut queue).is_ok() {
+                    let state = Some((queue, position));
+                    if state != last_playback_state {
+                        match db.save_playback_state(position) {
+                            Ok(()) => last_playback_state = state,
+                            Err(e) => {
+                                eprintln!("[{}] Couldn't save playback state: {e}", "ERR!".red())
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        // clients only estimate the position, so send them the real one regularly
+        #[cfg(feature = "playback")]
+        if let Some(player) = &player {
+            if last_position_broadcast.elapsed() >= Duration::from_millis(500) {
+                last_position_broadcast = Instant::now();
+                let mut db = database.lock().unwrap();
+                if let Some(position) = player.backend.current_song_playback_position() {
+                    // while paused, only if it changed (i.e. a different song was loaded)
+                    if db.playing || db.current_song_position.map(|v| v.0) != Some(position) {
+                        db.apply_action_unchecked_seq(Action::PlaybackPosition(position), None);
                     }
                 }
             }
         }
         if let Ok((command, client)) = command_receiver.recv_timeout(dur) {
             checkf = true;
+            // clients can't reconfigure the cache, only control connections can
+            if client.is_none() {
+                if let Some(cm) = &cache_manager {
+                    cm.handle_action(&command.action);
+                }
+            }
             #[cfg(feature = "playback")]
             if let Some(player) = &mut player {
                 player.handle_action(&command.action);
@@ -434,6 +642,7 @@ pub fn handle_one_connection_as_main(
     mut send_to: (impl Write + Sync + Send + 'static),
     command_sender: &mpsc::Sender<(Command, Option<u64>)>,
 ) -> Result<(), std::io::Error> {
+    let database = Arc::clone(&db);
     // sync database
     let mut db = db.lock().unwrap();
     db.init_connection(&mut send_to)?;
@@ -451,6 +660,8 @@ pub fn handle_one_connection_as_main(
     // drop the mutex lock
     drop(db);
     handle_one_connection_as_control(connection, command_sender, Some(udepid));
+    // the client disconnected, so don't keep sending updates to it
+    database.lock().unwrap().remove_update_endpoints(&[udepid]);
     Ok(())
 }
 pub fn handle_one_connection_as_control(
@@ -481,27 +692,47 @@ const BYTE_RESUME: u8 = 0b01_000_000;
 const BYTE_PAUSE: u8 = 0b01_000_001;
 const BYTE_STOP: u8 = 0b01_000_010;
 const BYTE_NEXT_SONG: u8 = 0b01_000_100;
+const BYTE_PREVIOUS_SONG: u8 = 0b01_000_011;
+const BYTE_STOP_AFTER_CURRENT_SONG: u8 = 0b01_001_000;
+const BYTE_SET_VOLUME: u8 = 0b01_001_001;
+const BYTE_SET_MUTED: u8 = 0b01_001_010;
+const BYTE_SET_CURRENT_SONG_POSITION: u8 = 0b01_001_011;
+const BYTE_SET_CURRENT_SONG_FORMAT: u8 = 0b01_001_100;
+const BYTE_PLAYBACK_POSITION: u8 = 0b01_001_101;
 
 const BYTE_MULTIPLE: u8 = 0b01_010_100;
 const BYTE_INIT_COMPLETE: u8 = 0b01_010_000;
 const BYTE_SET_SONG_DURATION: u8 = 0b01_010_001;
+const BYTE_SONG_PLAYED: u8 = 0b01_010_101;
 const BYTE_SAVE: u8 = 0b01_010_010;
+const BYTE_RELOAD_DATABASE: u8 = 0b01_010_011;
 const BYTE_ERRORINFO: u8 = 0b01_100_010;
 const BYTE_DENIED: u8 = 0b01_100_011;
 
 const BYTE_QUEUE_UPDATE: u8 = 0b10_000_000;
 const BYTE_QUEUE_ADD: u8 = 0b10_000_001;
+const BYTE_QUEUE_REPLACE: u8 = 0b10_000_101;
+const BYTE_QUEUE_INSERT_AFTER_CURRENT: u8 = 0b10_000_110;
+const BYTE_PLAY_FROM: u8 = 0b10_000_111;
 const BYTE_QUEUE_INSERT: u8 = 0b10_000_010;
 const BYTE_QUEUE_REMOVE: u8 = 0b10_000_100;
 const BYTE_QUEUE_MOVE: u8 = 0b10_001_000;
 const BYTE_QUEUE_MOVE_INTO: u8 = 0b10_001_001;
 const BYTE_QUEUE_GOTO: u8 = 0b10_001_010;
+const BYTE_QUEUE_SET_REPEAT: u8 = 0b10_000_011;
+const BYTE_QUEUE_MOVE_MULTIPLE: u8 = 0b10_001_011;
 const BYTE_QUEUE_ACTION: u8 = 0b10_001_100;
 const SUBBYTE_ACTION_SHUFFLE: u8 = 0b01_000_001;
 const SUBBYTE_ACTION_SET_SHUFFLE: u8 = 0b01_000_010;
 const SUBBYTE_ACTION_UNSHUFFLE: u8 = 0b01_000_100;
 
 const BYTE_SYNC_DATABASE: u8 = 0b10_010_100;
+const BYTE_SET_CACHE_CONFIG: u8 = 0b10_010_000;
+const BYTE_SET_CACHE_PINNED: u8 = 0b10_010_001;
+const BYTE_PLAYLIST: u8 = 0b10_010_010;
+const SUBBYTE_PLAYLIST_SAVE: u8 = 0b01_000_001;
+const SUBBYTE_PLAYLIST_RENAME: u8 = 0b01_000_010;
+const SUBBYTE_PLAYLIST_REMOVE: u8 = 0b01_000_100;
 
 const BYTE_LIB_ADD: u8 = 0b10_100_000;
 const BYTE_LIB_MODIFY: u8 = 0b10_100_001;
@@ -523,6 +754,9 @@ const SUBBYTE_TAG_ALBUM_PROPERTY_SET: u8 = 0b10_100_000;
 const SUBBYTE_TAG_ALBUM_PROPERTY_UNSET: u8 = 0b10_100_001;
 const SUBBYTE_TAG_ARTIST_PROPERTY_SET: u8 = 0b10_100_010;
 const SUBBYTE_TAG_ARTIST_PROPERTY_UNSET: u8 = 0b10_100_100;
+const SUBBYTE_TAG_SONG_RATING: u8 = 0b10_011_000;
+const SUBBYTE_TAG_ARTIST_ALIASES: u8 = 0b10_011_001;
+const SUBBYTE_TAG_SONG_GAIN: u8 = 0b10_011_010;
 
 impl ToFromBytes for Command {
     fn to_bytes<T>(&self, s: &mut T) -> Result<(), std::io::Error>
@@ -568,6 +802,31 @@ impl ToFromBytes for Action {
             Self::Pause => s.write_all(&[BYTE_PAUSE])?,
             Self::Stop => s.write_all(&[BYTE_STOP])?,
             Self::NextSong => s.write_all(&[BYTE_NEXT_SONG])?,
+            Self::PreviousSong => s.write_all(&[BYTE_PREVIOUS_SONG])?,
+            Self::StopAfterCurrentSong(stop) => {
+                s.write_all(&[BYTE_STOP_AFTER_CURRENT_SONG])?;
+                stop.to_bytes(s)?;
+            }
+            Self::SetVolume(volume) => {
+                s.write_all(&[BYTE_SET_VOLUME])?;
+                volume.to_bytes(s)?;
+            }
+            Self::SetMuted(muted) => {
+                s.write_all(&[BYTE_SET_MUTED])?;
+                muted.to_bytes(s)?;
+            }
+            Self::SetCurrentSongPosition(position) => {
+                s.write_all(&[BYTE_SET_CURRENT_SONG_POSITION])?;
+                position.to_bytes(s)?;
+            }
+            Self::PlaybackPosition(position) => {
+                s.write_all(&[BYTE_PLAYBACK_POSITION])?;
+                position.to_bytes(s)?;
+            }
+            Self::SetCurrentSongFormat(format) => {
+                s.write_all(&[BYTE_SET_CURRENT_SONG_FORMAT])?;
+                format.to_bytes(s)?;
+            }
             Self::SyncDatabase(a, b, c) => {
                 s.write_all(&[BYTE_SYNC_DATABASE])?;
                 a.to_bytes(s)?;
@@ -586,6 +845,11 @@ impl ToFromBytes for Action {
                 new_data.to_bytes(s)?;
                 req.to_bytes(s)?;
             }
+            Self::QueueReplace(queue, keep_current) => {
+                s.write_all(&[BYTE_QUEUE_REPLACE])?;
+                queue.to_bytes(s)?;
+                keep_current.to_bytes(s)?;
+            }
             Self::QueueInsert(index, pos, new_data, req) => {
                 s.write_all(&[BYTE_QUEUE_INSERT])?;
                 index.to_bytes(s)?;
@@ -593,6 +857,10 @@ impl ToFromBytes for Action {
                 new_data.to_bytes(s)?;
                 req.to_bytes(s)?;
             }
+            Self::QueueInsertAfterCurrent(new_data) => {
+                s.write_all(&[BYTE_QUEUE_INSERT_AFTER_CURRENT])?;
+                new_data.to_bytes(s)?;
+            }
             Self::QueueRemove(index) => {
                 s.write_all(&[BYTE_QUEUE_REMOVE])?;
                 index.to_bytes(s)?;
@@ -611,22 +879,54 @@ impl ToFromBytes for Action {
                 s.write_all(&[BYTE_QUEUE_GOTO])?;
                 index.to_bytes(s)?;
             }
-            Self::QueueShuffle(path) => {
+            Self::PlayFrom(index) => {
+                s.write_all(&[BYTE_PLAY_FROM])?;
+                index.to_bytes(s)?;
+            }
+            Self::QueueShuffle(path, weight, seed) => {
                 s.write_all(&[BYTE_QUEUE_ACTION])?;
                 s.write_all(&[SUBBYTE_ACTION_SHUFFLE])?;
                 path.to_bytes(s)?;
+                weight.to_bytes(s)?;
+                seed.to_bytes(s)?;
             }
-            Self::QueueSetShuffle(path, map) => {
+            Self::QueueSetShuffle(path, map, seed, weight) => {
                 s.write_all(&[BYTE_QUEUE_ACTION])?;
                 s.write_all(&[SUBBYTE_ACTION_SET_SHUFFLE])?;
                 path.to_bytes(s)?;
                 map.to_bytes(s)?;
+                seed.to_bytes(s)?;
+                weight.to_bytes(s)?;
             }
             Self::QueueUnshuffle(path) => {
                 s.write_all(&[BYTE_QUEUE_ACTION])?;
                 s.write_all(&[SUBBYTE_ACTION_UNSHUFFLE])?;
                 path.to_bytes(s)?;
             }
+            Self::QueueMoveMultiple(a, b) => {
+                s.write_all(&[BYTE_QUEUE_MOVE_MULTIPLE])?;
+                a.to_bytes(s)?;
+                b.to_bytes(s)?;
+            }
+            Self::QueueSetRepeat(mode) => {
+                s.write_all(&[BYTE_QUEUE_SET_REPEAT])?;
+                mode.to_bytes(s)?;
+            }
+            Self::SetCacheConfig {
+                songs,
+                min_mib,
+                max_mib,
+            } => {
+                s.write_all(&[BYTE_SET_CACHE_CONFIG])?;
+                songs.to_bytes(s)?;
+                min_mib.to_bytes(s)?;
+                max_mib.to_bytes(s)?;
+            }
+            Self::SetCachePinned(song, pinned) => {
+                s.write_all(&[BYTE_SET_CACHE_PINNED])?;
+                song.to_bytes(s)?;
+                pinned.to_bytes(s)?;
+            }
             Self::AddSong(song, req) => {
                 s.write_all(&[BYTE_LIB_ADD])?;
                 s.write_all(&[SUBBYTE_SONG])?;
@@ -759,11 +1059,51 @@ impl ToFromBytes for Action {
                 id.to_bytes(s)?;
                 key.to_bytes(s)?;
             }
+            Self::SetSongRating(id, rating) => {
+                s.write_all(&[BYTE_LIB_TAG])?;
+                s.write_all(&[SUBBYTE_TAG_SONG_RATING])?;
+                id.to_bytes(s)?;
+                rating.to_bytes(s)?;
+            }
+            Self::SetSongGain(id, gain) => {
+                s.write_all(&[BYTE_LIB_TAG])?;
+                s.write_all(&[SUBBYTE_TAG_SONG_GAIN])?;
+                id.to_bytes(s)?;
+                gain.to_bytes(s)?;
+            }
+            Self::SetArtistAliases(id, aliases) => {
+                s.write_all(&[BYTE_LIB_TAG])?;
+                s.write_all(&[SUBBYTE_TAG_ARTIST_ALIASES])?;
+                id.to_bytes(s)?;
+                aliases.to_bytes(s)?;
+            }
+            Self::SavePlaylist(name, queue) => {
+                s.write_all(&[BYTE_PLAYLIST])?;
+                s.write_all(&[SUBBYTE_PLAYLIST_SAVE])?;
+                name.to_bytes(s)?;
+                queue.to_bytes(s)?;
+            }
+            Self::RenamePlaylist(name, new_name) => {
+                s.write_all(&[BYTE_PLAYLIST])?;
+                s.write_all(&[SUBBYTE_PLAYLIST_RENAME])?;
+                name.to_bytes(s)?;
+                new_name.to_bytes(s)?;
+            }
+            Self::RemovePlaylist(name) => {
+                s.write_all(&[BYTE_PLAYLIST])?;
+                s.write_all(&[SUBBYTE_PLAYLIST_REMOVE])?;
+                name.to_bytes(s)?;
+            }
             Self::SetSongDuration(i, d) => {
                 s.write_all(&[BYTE_SET_SONG_DURATION])?;
                 i.to_bytes(s)?;
                 d.to_bytes(s)?;
             }
+            Self::SongPlayed(i, t) => {
+                s.write_all(&[BYTE_SONG_PLAYED])?;
+                i.to_bytes(s)?;
+                t.to_bytes(s)?;
+            }
             Self::Multiple(actions) => {
                 s.write_all(&[BYTE_MULTIPLE])?;
                 actions.to_bytes(s)?;
@@ -772,6 +1112,7 @@ impl ToFromBytes for Action {
                 s.write_all(&[BYTE_INIT_COMPLETE])?;
             }
             Self::Save => s.write_all(&[BYTE_SAVE])?,
+            Self::ReloadDatabase => s.write_all(&[BYTE_RELOAD_DATABASE])?,
             Self::ErrorInfo(t, d) => {
                 s.write_all(&[BYTE_ERRORINFO])?;
                 t.to_bytes(s)?;
@@ -798,19 +1139,44 @@ impl ToFromBytes for Action {
             BYTE_PAUSE => Self::Pause,
             BYTE_STOP => Self::Stop,
             BYTE_NEXT_SONG => Self::NextSong,
+            BYTE_PREVIOUS_SONG => Self::PreviousSong,
+            BYTE_STOP_AFTER_CURRENT_SONG => Self::StopAfterCurrentSong(from_bytes!()),
+            BYTE_SET_VOLUME => Self::SetVolume(from_bytes!()),
+            BYTE_SET_MUTED => Self::SetMuted(from_bytes!()),
+            BYTE_SET_CURRENT_SONG_POSITION => Self::SetCurrentSongPosition(from_bytes!()),
+            BYTE_PLAYBACK_POSITION => Self::PlaybackPosition(from_bytes!()),
+            BYTE_SET_CURRENT_SONG_FORMAT => Self::SetCurrentSongFormat(from_bytes!()),
             BYTE_SYNC_DATABASE => Self::SyncDatabase(from_bytes!(), from_bytes!(), from_bytes!()),
+            BYTE_SET_CACHE_CONFIG => Self::SetCacheConfig {
+                songs: from_bytes!(),
+                min_mib: from_bytes!(),
+                max_mib: from_bytes!(),
+            },
+            BYTE_SET_CACHE_PINNED => Self::SetCachePinned(from_bytes!(), from_bytes!()),
             BYTE_QUEUE_UPDATE => Self::QueueUpdate(from_bytes!(), from_bytes!(), from_bytes!()),
             BYTE_QUEUE_ADD => Self::QueueAdd(from_bytes!(), from_bytes!(), from_bytes!()),
+            BYTE_QUEUE_REPLACE => Self::QueueReplace(from_bytes!(), from_bytes!()),
+            BYTE_QUEUE_INSERT_AFTER_CURRENT => Self::QueueInsertAfterCurrent(from_bytes!()),
             BYTE_QUEUE_INSERT => {
                 Self::QueueInsert(from_bytes!(), from_bytes!(), from_bytes!(), from_bytes!())
             }
             BYTE_QUEUE_REMOVE => Self::QueueRemove(from_bytes!()),
             BYTE_QUEUE_MOVE => Self::QueueMove(from_bytes!(), from_bytes!()),
             BYTE_QUEUE_MOVE_INTO => Self::QueueMoveInto(from_bytes!(), from_bytes!()),
+            BYTE_QUEUE_MOVE_MULTIPLE => Self::QueueMoveMultiple(from_bytes!(), from_bytes!()),
             BYTE_QUEUE_GOTO => Self::QueueGoto(from_bytes!()),
+            BYTE_PLAY_FROM => Self::PlayFrom(from_bytes!()),
+            BYTE_QUEUE_SET_REPEAT => Self::QueueSetRepeat(from_bytes!()),
             BYTE_QUEUE_ACTION => match s.read_byte()? {
-                SUBBYTE_ACTION_SHUFFLE => Self::QueueShuffle(from_bytes!()),
-                SUBBYTE_ACTION_SET_SHUFFLE => Self::QueueSetShuffle(from_bytes!(), from_bytes!()),
+                SUBBYTE_ACTION_SHUFFLE => {
+                    Self::QueueShuffle(from_bytes!(), from_bytes!(), from_bytes!())
+                }
+                SUBBYTE_ACTION_SET_SHUFFLE => Self::QueueSetShuffle(
+                    from_bytes!(),
+                    from_bytes!(),
+                    from_bytes!(),
+                    from_bytes!(),
+                ),
                 SUBBYTE_ACTION_UNSHUFFLE => Self::QueueUnshuffle(from_bytes!()),
                 _ => {
                     eprintln!(
@@ -886,6 +1252,9 @@ impl ToFromBytes for Action {
                 SUBBYTE_TAG_ARTIST_PROPERTY_UNSET => {
                     Self::TagArtistPropertyUnset(from_bytes!(), from_bytes!())
                 }
+                SUBBYTE_TAG_SONG_RATING => Self::SetSongRating(from_bytes!(), from_bytes!()),
+                SUBBYTE_TAG_SONG_GAIN => Self::SetSongGain(from_bytes!(), from_bytes!()),
+                SUBBYTE_TAG_ARTIST_ALIASES => Self::SetArtistAliases(from_bytes!(), from_bytes!()),
                 _ => {
                     eprintln!(
                         "[{}] unexpected byte when reading command:libTag; stopping playback.",
@@ -894,10 +1263,24 @@ impl ToFromBytes for Action {
                     Self::Stop
                 }
             },
+            BYTE_PLAYLIST => match s.read_byte()? {
+                SUBBYTE_PLAYLIST_SAVE => Self::SavePlaylist(from_bytes!(), from_bytes!()),
+                SUBBYTE_PLAYLIST_RENAME => Self::RenamePlaylist(from_bytes!(), from_bytes!()),
+                SUBBYTE_PLAYLIST_REMOVE => Self::RemovePlaylist(from_bytes!()),
+                _ => {
+                    eprintln!(
+                        "[{}] unexpected byte when reading command:playlist; stopping playback.",
+                        "WARN".yellow()
+                    );
+                    Self::Stop
+                }
+            },
             BYTE_SET_SONG_DURATION => Self::SetSongDuration(from_bytes!(), from_bytes!()),
+            BYTE_SONG_PLAYED => Self::SongPlayed(from_bytes!(), from_bytes!()),
             BYTE_MULTIPLE => Self::Multiple(from_bytes!()),
             BYTE_INIT_COMPLETE => Self::InitComplete,
             BYTE_SAVE => Self::Save,
+            BYTE_RELOAD_DATABASE => Self::ReloadDatabase,
             BYTE_ERRORINFO => Self::ErrorInfo(from_bytes!(), from_bytes!()),
             BYTE_DENIED => Self::Denied(from_bytes!()),
             _ => {
@@ -924,24 +1307,69 @@ impl<T: Read> ReadByte for T {
 
 #[test]
 fn test_to_from_bytes() {
-    use crate::data::queue::QueueContent;
+    use crate::data::queue::{QueueContent, QueueFolder, QueuePlaylist, RepeatMode, ShuffleWeight};
     use std::io::Cursor;
     for v in [
         Action::Resume,
         Action::Pause,
         Action::Stop,
         Action::NextSong,
+        Action::PreviousSong,
+        Action::StopAfterCurrentSong(true),
+        Action::StopAfterCurrentSong(false),
+        Action::SetVolume(0.75),
+        Action::SetMuted(true),
         Action::SyncDatabase(vec![], vec![], vec![]),
         Action::QueueUpdate(vec![], QueueContent::Song(12).into(), Req::none()),
+        Action::QueueUpdate(
+            vec![],
+            QueueContent::Playlist(QueuePlaylist {
+                index: 1,
+                name: "playlist".to_owned(),
+                songs: vec![3, 7, 12],
+            })
+            .into(),
+            Req::none(),
+        ),
         Action::QueueAdd(vec![], vec![], Req::none()),
+        Action::QueueReplace(QueueContent::Folder(QueueFolder::default()).into(), true),
+        Action::QueueInsertAfterCurrent(vec![QueueContent::Song(3).into()]),
         Action::QueueInsert(vec![], 5, vec![], Req::none()),
         Action::QueueRemove(vec![]),
         Action::QueueMove(vec![], vec![]),
         Action::QueueMoveInto(vec![], vec![]),
+        Action::QueueMoveMultiple(vec![vec![0, 1], vec![2]], vec![3, 0]),
         Action::QueueGoto(vec![]),
-        Action::QueueShuffle(vec![]),
-        Action::QueueSetShuffle(vec![], vec![]),
+        Action::PlayFrom(vec![1, 0]),
+        Action::QueueShuffle(vec![], ShuffleWeight::Uniform, None),
+        Action::QueueShuffle(
+            vec![1, 2],
+            ShuffleWeight::Tag("Rating=".to_owned()),
+            Some(42),
+        ),
+        Action::QueueSetShuffle(vec![], vec![], None, ShuffleWeight::Uniform),
+        Action::QueueSetShuffle(
+            vec![0],
+            vec![1, 0],
+            Some(42),
+            ShuffleWeight::LeastRecentlyPlayed,
+        ),
         Action::QueueUnshuffle(vec![]),
+        Action::QueueSetRepeat(RepeatMode::Off),
+        Action::QueueSetRepeat(RepeatMode::All),
+        Action::QueueSetRepeat(RepeatMode::One),
+        Action::SetCacheConfig {
+            songs: 20,
+            min_mib: 1024,
+            max_mib: 2048,
+        },
+        Action::SetCurrentSongPosition(83_500),
+        Action::PlaybackPosition(12_345),
+        Action::SetCurrentSongFormat(Some((44100, 2))),
+        Action::SetCurrentSongFormat(None),
+        Action::SetCachePinned(5, true),
+        Action::SetCachePinned(5, false),
+        Action::SongPlayed(7, 1_700_000_000_000),
         // Action::AddSong(Song, Req),
         // Action::AddAlbum(Album, Req),
         // Action::AddArtist(Artist, Req),
@@ -965,8 +1393,28 @@ fn test_to_from_bytes() {
         // Action::TagAlbumPropertyUnset(AlbumId, String),
         // Action::TagArtistPropertySet(ArtistId, String, String),
         // Action::TagArtistPropertyUnset(ArtistId, String),
+        Action::SetSongRating(3, Some(4)),
+        Action::SetSongRating(3, None),
+        Action::SetSongGain(3, -4.5),
+        Action::SetArtistAliases(2, vec![format!("Beatles"), format!("beatles")]),
+        Action::SetArtistAliases(2, vec![]),
+        Action::SavePlaylist(
+            "playlist".to_owned(),
+            QueueContent::Folder(QueueFolder {
+                index: 0,
+                content: vec![QueueContent::Song(4).into(), QueueContent::Song(2).into()],
+                name: "playlist".to_owned(),
+                order: None,
+                seed: None,
+                weight: ShuffleWeight::Uniform,
+            })
+            .into(),
+        ),
+        Action::RenamePlaylist("playlist".to_owned(), "renamed".to_owned()),
+        Action::RemovePlaylist("renamed".to_owned()),
         Action::InitComplete,
         Action::Save,
+        Action::ReloadDatabase,
         Action::ErrorInfo(format!("some error"), format!("with a message")),
         Action::Denied(Req::none()),
     ] {
@@ -1,7 +1,41 @@
 #![cfg(test)]
-use std::{assert_eq, path::PathBuf};
+use std::{
+    assert_eq,
+    ops::Deref,
+    path::{Path, PathBuf},
+};
 
-use crate::load::ToFromBytes;
+use crate::{data::database::Database, load::ToFromBytes};
+
+/// A new, empty temporary directory for a test, which is removed again when this is dropped.
+/// `name` has to be unique, because tests run in parallel.
+pub struct TestDir(PathBuf);
+impl TestDir {
+    pub fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("musicdb-test-{name}-{}", std::process::id()));
+        _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+}
+impl Deref for TestDir {
+    type Target = Path;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+/// An empty database in a new `TestDir`, which is both its db_dir and its lib_dir.
+/// Keep the `TestDir` until the test is done.
+pub fn test_db(name: &str) -> (Database, TestDir) {
+    let dir = TestDir::new(name);
+    let db = Database::new_empty_in_dir(dir.to_path_buf(), dir.to_path_buf());
+    (db, dir)
+}
 
 #[test]
 fn string() {
@@ -15,7 +15,7 @@ use musicdb_lib::{
         album::Album,
         artist::Artist,
         database::Database,
-        queue::{Queue, QueueContent, QueueFolder},
+        queue::{Queue, QueueContent, QueueFolder, RepeatMode},
         song::Song,
     },
     server::Command,
@@ -71,19 +71,27 @@ pub fn add(
                     handle(&handler_next_song, move || (Data::empty_tuple(), ()));
                     handle(&handler_queue_changed, move || (Data::empty_tuple(), ()));
                 }
+                Command::PreviousSong => {
+                    handle(&handler_queue_changed, move || (Data::empty_tuple(), ()));
+                }
                 Command::SyncDatabase(..) => {
                     handle(&handler_library_changed, move || (Data::empty_tuple(), ()));
                 }
                 Command::QueueUpdate(..)
                 | Command::QueueAdd(..)
+                | Command::QueueReplace(..)
                 | Command::QueueInsert(..)
+                | Command::QueueInsertAfterCurrent(..)
                 | Command::QueueRemove(..)
                 | Command::QueueMove(..)
                 | Command::QueueMoveInto(..)
+                | Command::QueueMoveMultiple(..)
                 | Command::QueueGoto(..)
+                | Command::PlayFrom(..)
                 | Command::QueueShuffle(..)
                 | Command::QueueSetShuffle(..)
-                | Command::QueueUnshuffle(..) => {
+                | Command::QueueUnshuffle(..)
+                | Command::QueueSetRepeat(..) => {
                     handle(&handler_queue_changed, move || (Data::empty_tuple(), ()));
                 }
                 Command::AddSong(_)
@@ -98,7 +106,7 @@ pub fn add(
                 | Command::RemoveArtist(_) => {
                     handle(&handler_library_changed, move || (Data::empty_tuple(), ()));
                 }
-                Command::SetSongDuration(..) => {
+                Command::SetSongDuration(..) | Command::SongPlayed(..) => {
                     handle(&handler_library_changed, move || (Data::empty_tuple(), ()));
                 }
                 Command::TagSongFlagSet(..)
@@ -112,11 +120,21 @@ pub fn add(
                 | Command::TagAlbumPropertySet(..)
                 | Command::TagAlbumPropertyUnset(..)
                 | Command::TagArtistPropertySet(..)
-                | Command::TagArtistPropertyUnset(..) => {
+                | Command::TagArtistPropertyUnset(..)
+                | Command::SetSongRating(..)
+                | Command::SetSongGain(..)
+                | Command::SetArtistAliases(..)
+                | Command::SavePlaylist(..)
+                | Command::RenamePlaylist(..)
+                | Command::RemovePlaylist(..) => {
                     handle(&handler_library_changed, move || (Data::empty_tuple(), ()));
                 }
                 Command::InitComplete => (),
-                Command::Save => (),
+                Command::Save | Command::ReloadDatabase => (),
+                Command::SetCurrentSongPosition(..)
+                | Command::PlaybackPosition(..)
+                | Command::SetCurrentSongFormat(..) => (),
+                Command::SetCacheConfig { .. } | Command::SetCachePinned(..) => (),
                 Command::ErrorInfo(title, body) => {
                     handle(&handler_notification_received, move || {
                         (
@@ -284,6 +302,25 @@ pub fn add(
             },
         ),
     )
+    .add_var(
+        "previous_song".to_owned(),
+        Function::new_generic(
+            |a, _| {
+                if a.is_included_in(&Type::empty_tuple()) {
+                    Ok(Type::empty_tuple())
+                } else {
+                    Err(format!("Function argument must be `()`.").into())
+                }
+            },
+            {
+                let cmd = Arc::clone(cmd);
+                move |_, _| {
+                    cmd(Command::PreviousSong);
+                    Ok(Data::empty_tuple())
+                }
+            },
+        ),
+    )
     .add_var(
         "get_playing".to_owned(),
         Function::new_generic(
@@ -396,6 +433,40 @@ pub fn add(
             },
         ),
     )
+    .add_var(
+        "queue_set_repeat".to_owned(),
+        Function::new_generic(
+            |a, _| {
+                if a.is_included_in_single(&data::string::StringT) {
+                    Ok(Type::empty_tuple())
+                } else {
+                    Err(format!(
+                        "Function argument must be `String` (\"off\", \"all\" or \"one\")."
+                    )
+                    .into())
+                }
+            },
+            {
+                let cmd = Arc::clone(cmd);
+                move |a, _| {
+                    let mode = match a
+                        .get()
+                        .as_any()
+                        .downcast_ref::<data::string::String>()
+                        .unwrap()
+                        .0
+                        .as_str()
+                    {
+                        "all" => RepeatMode::All,
+                        "one" => RepeatMode::One,
+                        _ => RepeatMode::Off,
+                    };
+                    cmd(Command::QueueSetRepeat(mode));
+                    Ok(Data::empty_tuple())
+                }
+            },
+        ),
+    )
     .add_var(
         "queue_clear".to_owned(),
         Function::new_generic(
@@ -1066,6 +1137,40 @@ fn gen_queue_elem(queue_elem: &Queue, i: DisplayInfo) -> Data {
                     ),
                 ])),
             ),
+            QueueContent::Playlist(playlist) => (
+                i.object_fields.get_or_add_field("playlist"),
+                Data::new(data::object::Object::new(vec![
+                    (
+                        i.object_fields.get_or_add_field("index"),
+                        Data::new(data::int::Int(playlist.index as _)),
+                    ),
+                    (
+                        i.object_fields.get_or_add_field("length"),
+                        Data::new(data::int::Int(playlist.songs.len() as _)),
+                    ),
+                    (
+                        i.object_fields.get_or_add_field("name"),
+                        Data::new(data::string::String(playlist.name.clone())),
+                    ),
+                ])),
+            ),
+            QueueContent::Conditional(conditional) => (
+                i.object_fields.get_or_add_field("conditional"),
+                Data::new(data::object::Object::new(vec![
+                    (
+                        i.object_fields.get_or_add_field("name"),
+                        Data::new(data::string::String(conditional.name.clone())),
+                    ),
+                    (
+                        i.object_fields.get_or_add_field("count"),
+                        Data::new(data::int::Int(conditional.count as _)),
+                    ),
+                    (
+                        i.object_fields.get_or_add_field("random"),
+                        Data::new(data::bool::Bool(conditional.random)),
+                    ),
+                ])),
+            ),
         },
     ]))
 }
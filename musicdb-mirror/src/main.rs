@@ -0,0 +1,247 @@
+use std::{
+    collections::BTreeSet,
+    io::{BufReader, Write},
+    net::{SocketAddr, TcpStream},
+    process::exit,
+};
+
+use clap::Parser;
+use musicdb_lib::{
+    data::{
+        database::{ClientIo, Database},
+        CoverId,
+    },
+    load::ToFromBytes,
+    server::{get, Action, Command},
+};
+
+/// Keeps the library (artists, albums, songs, tags) of a destination server in sync with a source server,
+/// for example to maintain a backup or a replica.
+/// Playlists are mirrored too, playback and the queue are not.
+///
+/// Song files and covers are not copied, so the destination's library directory
+/// should contain the same files as the source's (for example using rsync).
+#[derive(Parser)]
+struct Args {
+    /// the server whose library should be mirrored
+    source: SocketAddr,
+    /// the server which will be changed to match the source
+    destination: SocketAddr,
+    /// sync once, then exit instead of mirroring changes
+    #[arg(long)]
+    once: bool,
+    /// compare all covers used in the source's database with the destination's covers
+    /// (downloads every cover from both servers)
+    #[arg(long)]
+    check_covers: bool,
+}
+
+fn main() {
+    let args = Args::parse();
+    eprintln!("Connecting to source {}...", args.source);
+    let mut src_con = TcpStream::connect(args.source).unwrap();
+    writeln!(src_con, "main").unwrap();
+    let mut src_db = Database::new_clientside();
+    receive_until_init(&mut src_db, &mut src_con);
+    eprintln!("Connecting to destination {}...", args.destination);
+    let mut dst_con = TcpStream::connect(args.destination).unwrap();
+    writeln!(dst_con, "main").unwrap();
+    let mut dst_db = Database::new_clientside();
+    receive_until_init(&mut dst_db, &mut dst_con);
+
+    if args.check_covers {
+        check_covers(&src_db, &args.source, &args.destination);
+    }
+
+    // after this, both servers have the same ids for everything,
+    // and because new ids are always the lowest unused ones,
+    // replaying `Add*` actions in order keeps them the same.
+    if src_db.artists() == dst_db.artists()
+        && src_db.albums() == dst_db.albums()
+        && src_db.songs() == dst_db.songs()
+    {
+        eprintln!("Destination is already up to date.");
+    } else {
+        eprintln!(
+            "Syncing {} artists, {} albums and {} songs...",
+            src_db.artists().len(),
+            src_db.albums().len(),
+            src_db.songs().len()
+        );
+        send(
+            &mut dst_con,
+            Action::SyncDatabase(
+                src_db.artists().values().cloned().collect(),
+                src_db.albums().values().cloned().collect(),
+                src_db.songs().values().cloned().collect(),
+            ),
+        );
+    }
+    if args.once {
+        dst_con.flush().unwrap();
+        return;
+    }
+
+    // the destination sends us all of its updates, which we have to read
+    // so it doesn't block, but we only care about denials.
+    let mut dst_recv = dst_con.try_clone().unwrap();
+    std::thread::spawn(move || loop {
+        match Command::from_bytes(&mut dst_recv) {
+            Ok(command) => {
+                if let Action::Denied(_) = command.action {
+                    eprintln!("[warn] the destination denied an action, it may be out of sync now");
+                }
+            }
+            Err(e) => {
+                eprintln!("[err] lost connection to the destination: {e}");
+                exit(1);
+            }
+        }
+    });
+
+    eprintln!("Mirroring changes...");
+    loop {
+        let command = match Command::from_bytes(&mut src_con) {
+            Ok(v) => v,
+            Err(e) => {
+                eprintln!("[err] lost connection to the source: {e}");
+                exit(1);
+            }
+        };
+        let action = src_db.seq.recv(command);
+        if let Some(action) = library_action(action.clone()) {
+            send(&mut dst_con, action);
+        }
+        src_db.apply_action_unchecked_seq(action, None);
+    }
+}
+
+/// Like the server's remote-source thread, only keeps actions which change the library.
+fn library_action(action: Action) -> Option<Action> {
+    use Action::*;
+    match action {
+        SyncDatabase(..)
+        | AddSong(..)
+        | AddAlbum(..)
+        | AddArtist(..)
+        | AddCover(..)
+        | ModifySong(..)
+        | ModifyAlbum(..)
+        | ModifyArtist(..)
+        | RemoveSong(..)
+        | RemoveAlbum(..)
+        | RemoveArtist(..)
+        | SetSongDuration(..)
+        | SongPlayed(..)
+        | TagSongFlagSet(..)
+        | TagSongFlagUnset(..)
+        | TagAlbumFlagSet(..)
+        | TagAlbumFlagUnset(..)
+        | TagArtistFlagSet(..)
+        | TagArtistFlagUnset(..)
+        | TagSongPropertySet(..)
+        | TagSongPropertyUnset(..)
+        | TagAlbumPropertySet(..)
+        | TagAlbumPropertyUnset(..)
+        | TagArtistPropertySet(..)
+        | TagArtistPropertyUnset(..)
+        | SetSongRating(..)
+        | SetSongGain(..)
+        | SetArtistAliases(..)
+        | SavePlaylist(..)
+        | RenamePlaylist(..)
+        | RemovePlaylist(..) => Some(action),
+        Multiple(actions) => {
+            let actions = actions
+                .into_iter()
+                .filter_map(library_action)
+                .collect::<Vec<_>>();
+            if actions.is_empty() {
+                None
+            } else {
+                Some(Multiple(actions))
+            }
+        }
+        // playback, queue, and things which only concern this connection
+        Resume
+        | Pause
+        | Stop
+        | NextSong
+        | PreviousSong
+        | StopAfterCurrentSong(..)
+        | SetVolume(..)
+        | SetMuted(..)
+        | SetCurrentSongPosition(..)
+        | PlaybackPosition(..)
+        | SetCurrentSongFormat(..)
+        | SetCacheConfig { .. }
+        | SetCachePinned(..)
+        | QueueUpdate(..)
+        | QueueAdd(..)
+        | QueueReplace(..)
+        | QueueInsert(..)
+        | QueueInsertAfterCurrent(..)
+        | QueueRemove(..)
+        | QueueMove(..)
+        | QueueMoveInto(..)
+        | QueueMoveMultiple(..)
+        | QueueGoto(..)
+        | PlayFrom(..)
+        | QueueShuffle(..)
+        | QueueSetShuffle(..)
+        | QueueUnshuffle(..)
+        | QueueSetRepeat(..)
+        | InitComplete
+        | Save
+        | ReloadDatabase
+        | ErrorInfo(..)
+        | Denied(..) => None,
+    }
+}
+
+/// Covers aren't part of `SyncDatabase`, so we can't fix them, but we can at least warn.
+fn check_covers(src_db: &Database, src: &SocketAddr, dst: &SocketAddr) {
+    let covers = src_db
+        .artists()
+        .values()
+        .filter_map(|v| v.cover)
+        .chain(src_db.albums().values().filter_map(|v| v.cover))
+        .chain(src_db.songs().values().filter_map(|v| v.cover))
+        .collect::<BTreeSet<CoverId>>();
+    eprintln!("Comparing {} covers...", covers.len());
+    let mut src = get_client(src);
+    let mut dst = get_client(dst);
+    let mut bad = 0;
+    for id in covers {
+        let src_bytes = src.cover_bytes(id).unwrap();
+        let dst_bytes = dst.cover_bytes(id).unwrap();
+        match (src_bytes, dst_bytes) {
+            (Ok(a), Ok(b)) if a == b => continue,
+            (Ok(_), Ok(_)) => eprintln!("[warn] cover {id} is different on the destination"),
+            (Ok(_), Err(e)) => eprintln!(
+                "[warn] cover {id} is missing on the destination: {}",
+                e.trim()
+            ),
+            (Err(_), _) => continue,
+        }
+        bad += 1;
+    }
+    if bad > 0 {
+        eprintln!("[warn] {bad} covers don't match, add them on the destination in the same order as on the source.");
+    }
+}
+
+fn get_client(addr: &SocketAddr) -> get::Client<Box<dyn ClientIo>> {
+    let con: Box<dyn ClientIo> = Box::new(TcpStream::connect(addr).unwrap());
+    get::Client::new(BufReader::new(con)).unwrap()
+}
+
+fn send(con: &mut TcpStream, action: Action) {
+    action.cmd(0xFFu8).to_bytes(con).unwrap();
+}
+fn receive_until_init(db: &mut Database, con: &mut TcpStream) {
+    while !db.is_client_init() {
+        let action = db.seq.recv(Command::from_bytes(con).unwrap());
+        db.apply_action_unchecked_seq(action, None);
+    }
+}
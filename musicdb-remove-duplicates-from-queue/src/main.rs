@@ -1,61 +1,76 @@
-use std::{collections::HashSet, io::Write, net::TcpStream};
+use std::{io::Write, net::TcpStream, process::exit};
 
 use musicdb_lib::{
-    data::{
-        database::Database,
-        queue::{Queue, QueueContent},
-        SongId,
-    },
+    data::{database::Database, queue::QueueContent},
     load::ToFromBytes,
     server::{Action, Command},
 };
 
 fn main() {
-    let mut con = TcpStream::connect(
-        std::env::args()
-            .nth(1)
-            .expect("required argument: server address and port"),
-    )
-    .unwrap();
+    let mut addr = None;
+    let mut keep_last = false;
+    let mut dry_run = false;
+    let mut folder = vec![];
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            // remove earlier occurrences instead of later ones
+            "--keep-last" => keep_last = true,
+            // only print what would be removed
+            "--dry-run" => dry_run = true,
+            // only remove duplicates inside this queue folder, given as its index path, like `1,0`
+            "--folder" => {
+                let path = args.next().expect("--folder requires a path like `1,0`");
+                folder = path
+                    .split(',')
+                    .map(|i| i.trim().parse::<usize>().expect("invalid --folder path"))
+                    .collect();
+            }
+            _ if addr.is_none() => addr = Some(arg),
+            _ => panic!("unexpected argument {arg:?}"),
+        }
+    }
+    let mut con =
+        TcpStream::connect(addr.expect(
+            "required argument: server address and port (optional: --keep-last, --dry-run, --folder <path>)",
+        ))
+        .unwrap();
     writeln!(con, "main").unwrap();
     let mut db = Database::new_clientside();
     while !db.is_client_init() {
         db.apply_action_unchecked_seq(Command::from_bytes(&mut con).unwrap().action, None);
     }
-    let mut actions = vec![];
-    rev_actions(&mut actions, &db.queue, &mut vec![], &mut HashSet::new());
+    let paths = match db.queue.get_item_at_index(&folder, 0) {
+        Some(queue) if !matches!(queue.content(), QueueContent::Song(_)) => queue
+            .find_duplicate_song_paths(keep_last)
+            .into_iter()
+            .map(|path| folder.iter().copied().chain(path).collect::<Vec<_>>())
+            .collect::<Vec<_>>(),
+        _ => {
+            eprintln!("There is no folder at {folder:?} in the queue");
+            exit(1);
+        }
+    };
+    if dry_run {
+        for path in &paths {
+            match db.queue.get_item_at_index(path, 0).map(|v| v.content()) {
+                Some(QueueContent::Song(id)) => match db.get_song(id) {
+                    Some(song) => println!("{path:?}: {song}"),
+                    None => println!("{path:?}: song {id}"),
+                },
+                _ => println!("{path:?}"),
+            }
+        }
+        eprintln!("Would remove {} queue elements", paths.len());
+        return;
+    }
+    let actions = paths
+        .into_iter()
+        .map(Action::QueueRemove)
+        .collect::<Vec<_>>();
     eprintln!("Removing {} queue elements", actions.len());
     db.seq
         .pack(Action::Multiple(actions))
         .to_bytes(&mut con)
         .unwrap();
 }
-
-fn rev_actions(
-    actions: &mut Vec<Action>,
-    queue: &Queue,
-    path: &mut Vec<usize>,
-    seen: &mut HashSet<SongId>,
-) {
-    match queue.content() {
-        QueueContent::Song(id) => {
-            if seen.contains(id) {
-                actions.push(Action::QueueRemove(path.clone()));
-            } else {
-                seen.insert(*id);
-            }
-        }
-        QueueContent::Folder(folder) => {
-            for (i, queue) in folder.iter().enumerate() {
-                path.push(i);
-                rev_actions(actions, queue, path, seen);
-                path.pop();
-            }
-        }
-        QueueContent::Loop(_, _, inner) => {
-            path.push(0);
-            rev_actions(actions, &*inner, path, seen);
-            path.pop();
-        }
-    }
-}
@@ -1,3 +1,5 @@
+#[cfg(feature = "watch")]
+mod watch;
 #[cfg(feature = "website")]
 mod web;
 
@@ -7,12 +9,16 @@ use std::{
     path::PathBuf,
     process::exit,
     sync::{Arc, Mutex},
+    time::Duration,
 };
 
 use clap::{Parser, Subcommand};
-use musicdb_lib::{load::ToFromBytes, server::run_server_caching_thread_opt};
+use musicdb_lib::{
+    load::ToFromBytes,
+    server::{list_audio_devices, run_server_caching_thread_opt, PlayerConfig},
+};
 
-use musicdb_lib::data::database::Database;
+use musicdb_lib::data::{database::Database, queue::QueueLimitPolicy, song::ReplayGainMode};
 
 #[derive(Parser, Debug)]
 struct Args {
@@ -20,9 +26,13 @@ struct Args {
     #[arg(long)]
     tcp: Option<SocketAddr>,
     /// optional address on which to start a website which can be used on devices without `musicdb-client` to control playback.
-    /// requires the `assets/` folder to be present!
+    /// the website is part of the binary, no other files are needed.
     #[arg(long)]
     web: Option<SocketAddr>,
+    /// if set (as `user:password`), the website's controls require HTTP basic auth with these credentials.
+    /// viewing the website is always possible.
+    #[arg(long, value_name = "user:password")]
+    web_auth: Option<String>,
     /// play audio instead of acting like a server
     #[arg(long)]
     play_audio: bool,
@@ -45,6 +55,62 @@ struct Args {
     #[arg(long, value_name = "number_of_songs", default_value_t = 10)]
     advanced_cache_song_lookahead_limit: u32,
 
+    /// Autosave the dbfile once the oldest unsaved change is at least this many seconds old...
+    #[arg(long, value_name = "seconds", default_value_t = 60.0)]
+    autosave_max_delay: f64,
+    /// ...and nothing was modified for this many seconds. Use `0` for both to save after every change.
+    #[arg(long, value_name = "seconds", default_value_t = 5.0)]
+    autosave_quiet_period: f64,
+
+    /// With --play-audio, fade in for this many milliseconds when resuming, and fade out when pausing or stopping. Use `0` to disable fading.
+    #[arg(long, value_name = "milliseconds", default_value_t = 150)]
+    fade_millis: u64,
+    /// With --play-audio, adjust each song's volume using its `ReplayGain...Gain=` tags: `track` uses the track gain,
+    /// `album` uses the album gain (or the track gain if a song has no album gain), `off` ignores the tags.
+    #[arg(long, value_name = "off|track|album", default_value = "off")]
+    replaygain: ReplayGainMode,
+    /// With --play-audio, skip songs whose file can't be loaded, even while playback is paused
+    /// (until too many were skipped in a row, then playback is paused).
+    /// By default, such songs are only skipped while playing, and each one shows an error.
+    #[arg(long)]
+    skip_unplayable: bool,
+    /// With --play-audio, play on the output device with this name instead of the default device.
+    /// See `--list-audio-devices` for the available names.
+    #[arg(long, value_name = "name")]
+    audio_device: Option<String>,
+    /// Don't start a server. Instead, list the names of the output devices which can be used with `--audio-device`, then exit.
+    /// The source is not loaded, so something like `local . .` is enough.
+    #[arg(long)]
+    list_audio_devices: bool,
+
+    /// Don't let the queue grow beyond this many songs, for example if a script or an auto-queue keeps adding songs.
+    /// By default, the queue size is unlimited.
+    #[arg(long, value_name = "number_of_songs")]
+    queue_limit: Option<usize>,
+    /// Only does something if `--queue-limit` is used. `reject` denies adding songs to a full queue,
+    /// `drop-front` removes songs which were already played from the start of the queue to make room
+    /// (and denies adding songs if that isn't enough).
+    #[arg(long, value_name = "reject|drop-front", default_value = "reject")]
+    queue_limit_policy: QueueLimitPolicy,
+
+    /// Don't start a server. Instead, go through the queue (restored from the last time the server was running) once,
+    /// as fast as possible, write all songs to this .wav file, then exit.
+    #[cfg(feature = "render")]
+    #[arg(long, value_name = "output.wav")]
+    render: Option<PathBuf>,
+
+    /// Don't start a server. Instead, write the library (artists, albums, songs, covers) to this file as json, then exit.
+    /// Use `-` to write to stdout.
+    #[cfg(feature = "json")]
+    #[arg(long, value_name = "library.json")]
+    export_json: Option<PathBuf>,
+
+    /// Don't start a server. Instead, list groups of songs which are probably the same track (added more than once, for example from different rips),
+    /// because their title and artist are nearly identical and their durations differ by at most this many milliseconds, then exit.
+    /// Nothing is removed or merged.
+    #[arg(long, value_name = "tolerance_in_milliseconds")]
+    find_similar_songs: Option<u64>,
+
     // db and song file source
     #[command(subcommand)]
     source: Source,
@@ -61,6 +127,13 @@ enum Source {
         /// skip reading the dbfile (because it doesn't exist yet)
         #[arg(long)]
         init: bool,
+        /// before starting, remove covers which no song, album or artist uses anymore from the dbfile.
+        /// the image files themselves are not deleted.
+        #[arg(long)]
+        remove_orphaned_covers: bool,
+        /// watch the lib_dir for changes and add, update or remove songs when song files are added, changed or removed
+        #[arg(long)]
+        watch: bool,
     },
     Remote {
         /// The address of another musicdb-server from where to load the songs
@@ -104,14 +177,34 @@ enum Source {
 fn main() {
     // parse args
     let args = Args::parse();
+    if args.list_audio_devices {
+        match list_audio_devices() {
+            Some(devices) => {
+                for device in devices {
+                    println!("{device}");
+                }
+            }
+            None => {
+                eprintln!("The player backend this server was compiled with always uses the default output device.");
+                exit(1);
+            }
+        }
+        return;
+    }
     let mut remote_source_addr = None;
+    let mut watch_dir = None;
     let mut database = match args.source {
         Source::Local {
             db_dir,
             lib_dir,
             init,
+            remove_orphaned_covers,
+            watch,
         } => {
-            if init {
+            if watch {
+                watch_dir = Some(lib_dir.clone());
+            }
+            let mut db = if init {
                 Database::new_empty_in_dir(db_dir, lib_dir)
             } else {
                 match Database::load_database_from_dir(db_dir.clone(), lib_dir.clone()) {
@@ -124,7 +217,19 @@ fn main() {
                         exit(1);
                     }
                 }
+            };
+            // no clients are connected yet, so changing cover ids is safe
+            if remove_orphaned_covers {
+                let removed = db.remove_orphaned_covers();
+                eprintln!("Removed {removed} unused covers.");
+                if removed > 0 {
+                    if let Err(e) = db.save_database(None) {
+                        eprintln!("Couldn't save database: {e}");
+                        exit(1);
+                    }
+                }
             }
+            db
         }
         Source::Remote { addr } => {
             let mut db = Database::new_clientside();
@@ -139,8 +244,58 @@ fn main() {
         }
     };
     database.custom_files = args.custom_files;
+    database.fade_millis = args.fade_millis;
+    database.queue_limit = args.queue_limit;
+    database.queue_limit_policy = args.queue_limit_policy;
+    match (
+        Duration::try_from_secs_f64(args.autosave_max_delay),
+        Duration::try_from_secs_f64(args.autosave_quiet_period),
+    ) {
+        (Ok(max_delay), Ok(quiet_period)) => {
+            database.autosave_max_delay = max_delay;
+            database.autosave_quiet_period = quiet_period;
+        }
+        _ => {
+            eprintln!("--autosave-max-delay and --autosave-quiet-period must be positive numbers of seconds.");
+            exit(1);
+        }
+    }
+    #[cfg(feature = "json")]
+    if let Some(output) = args.export_json {
+        if remote_source_addr.is_some() {
+            eprintln!("Can't export the library of a remote server, use the remote server's --export-json instead.");
+            exit(1);
+        }
+        export_json(&database, &output);
+        return;
+    }
+    if let Some(tolerance) = args.find_similar_songs {
+        if remote_source_addr.is_some() {
+            eprintln!("Can't search the library of a remote server, use the remote server's --find-similar-songs instead.");
+            exit(1);
+        }
+        find_similar_songs(&database, tolerance);
+        return;
+    }
+    #[cfg(feature = "render")]
+    if let Some(output) = args.render {
+        render(database, output);
+        return;
+    }
     // database can be shared by multiple threads using Arc<Mutex<_>>
     let database = Arc::new(Mutex::new(database));
+    if let Some(lib_dir) = watch_dir {
+        #[cfg(not(feature = "watch"))]
+        {
+            let _ = lib_dir;
+            eprintln!(
+                "--watch requires the 'watch' feature to be enabled when compiling the server!"
+            );
+            exit(80);
+        }
+        #[cfg(feature = "watch")]
+        watch::watch(Arc::clone(&database), lib_dir);
+    }
     // thread to communicate with the remote server
     if let Some(addr) = remote_source_addr {
         let database = Arc::clone(&database);
@@ -152,11 +307,40 @@ fn main() {
                 use musicdb_lib::server::Action::{self, *};
                 fn sanitize_actions(action: Action) -> Option<Action> {
                     match action {
-                        // ignore playback and queue commands, and denials
-                        Resume | Pause | Stop | NextSong | QueueUpdate(..) | QueueAdd(..)
-                        | QueueInsert(..) | QueueRemove(..) | QueueMove(..) | QueueMoveInto(..)
-                        | QueueGoto(..) | QueueShuffle(..) | QueueSetShuffle(..)
-                        | QueueUnshuffle(..) | Denied(..) => None,
+                        // ignore playback, queue and playlist commands, and denials
+                        Resume
+                        | Pause
+                        | Stop
+                        | NextSong
+                        | PreviousSong
+                        | StopAfterCurrentSong(..)
+                        | SetVolume(..)
+                        | SetMuted(..)
+                        | SetCurrentSongPosition(..)
+                        | PlaybackPosition(..)
+                        | SetCurrentSongFormat(..)
+                        | SetCacheConfig { .. }
+                        | SetCachePinned(..)
+                        | QueueUpdate(..)
+                        | QueueAdd(..)
+                        | QueueReplace(..)
+                        | QueueInsert(..)
+                        | QueueInsertAfterCurrent(..)
+                        | QueueRemove(..)
+                        | QueueMove(..)
+                        | QueueMoveInto(..)
+                        | QueueMoveMultiple(..)
+                        | QueueGoto(..)
+                        | PlayFrom(..)
+                        | QueueShuffle(..)
+                        | QueueSetShuffle(..)
+                        | QueueUnshuffle(..)
+                        | QueueSetRepeat(..)
+                        | SavePlaylist(..)
+                        | RenamePlaylist(..)
+                        | RemovePlaylist(..)
+                        | ReloadDatabase
+                        | Denied(..) => None,
                         SyncDatabase(..)
                         | AddSong(..)
                         | AddAlbum(..)
@@ -169,6 +353,7 @@ fn main() {
                         | RemoveArtist(..)
                         | ModifyArtist(..)
                         | SetSongDuration(..)
+                        | SongPlayed(..)
                         | TagSongFlagSet(..)
                         | TagSongFlagUnset(..)
                         | TagAlbumFlagSet(..)
@@ -181,6 +366,9 @@ fn main() {
                         | TagAlbumPropertyUnset(..)
                         | TagArtistPropertySet(..)
                         | TagArtistPropertyUnset(..)
+                        | SetSongRating(..)
+                        | SetSongGain(..)
+                        | SetArtistAliases(..)
                         | InitComplete
                         | Save
                         | ErrorInfo(..) => Some(action),
@@ -210,6 +398,11 @@ fn main() {
         let mem_min = args.advanced_cache_min_mem;
         let cache_limit = args.advanced_cache_song_lookahead_limit;
         let args_tcp = args.tcp;
+        let player_config = PlayerConfig {
+            replaygain_mode: args.replaygain,
+            skip_unplayable: args.skip_unplayable,
+            audio_device: args.audio_device,
+        };
         let run_server = move |database, sender_sender| {
             run_server_caching_thread_opt(
                 database,
@@ -224,12 +417,13 @@ fn main() {
                     ) as _
                 }),
                 args.play_audio,
+                player_config,
             );
         };
         if let Some(addr) = &args.web {
             #[cfg(not(feature = "website"))]
             {
-                let _ = addr;
+                let _ = (addr, &args.web_auth);
                 eprintln!("Website support requires the 'website' feature to be enabled when compiling the server!");
                 std::process::exit(80);
             }
@@ -245,7 +439,7 @@ fn main() {
                     .enable_all()
                     .build()
                     .unwrap()
-                    .block_on(web::main(db, sender, *addr));
+                    .block_on(web::main(db, sender, *addr, args.web_auth));
             }
         } else {
             run_server(database, None);
@@ -254,3 +448,98 @@ fn main() {
         eprintln!("nothing to do, not starting the server.");
     }
 }
+
+#[cfg(feature = "json")]
+fn export_json(database: &Database, output: &PathBuf) {
+    let result = if output.as_os_str() == "-" {
+        database.export_json_to(std::io::stdout().lock())
+    } else {
+        match std::fs::File::create(output) {
+            Ok(file) => database.export_json_to(std::io::BufWriter::new(file)),
+            Err(e) => {
+                eprintln!("Couldn't create output file {output:?}: {e}");
+                exit(1);
+            }
+        }
+    };
+    if let Err(e) = result {
+        eprintln!("Couldn't write {output:?}: {e}");
+        exit(1);
+    }
+    eprintln!(
+        "Exported {} artists, {} albums and {} songs.",
+        database.artists().len(),
+        database.albums().len(),
+        database.songs().len()
+    );
+}
+
+fn find_similar_songs(database: &Database, tolerance: u64) {
+    let groups = database.find_similar_songs(tolerance);
+    for group in &groups {
+        println!();
+        for id in group {
+            let Some(song) = database.get_song(id) else {
+                continue;
+            };
+            let artist = database
+                .artists()
+                .get(&song.artist)
+                .map(|artist| artist.name.as_str())
+                .unwrap_or("?");
+            let album = song
+                .album
+                .and_then(|id| database.albums().get(&id))
+                .map(|album| album.name.as_str())
+                .unwrap_or("-");
+            println!(
+                "#{id}  {} - {} [{album}] ({:.1}s)  {}",
+                artist,
+                song.title,
+                song.duration_millis as f64 / 1000.0,
+                song.location.rel_path.to_string_lossy()
+            );
+        }
+    }
+    eprintln!(
+        "Found {} groups of similar songs ({} songs in total).",
+        groups.len(),
+        groups.iter().map(|group| group.len()).sum::<usize>()
+    );
+}
+
+#[cfg(feature = "render")]
+fn render(mut database: Database, output: PathBuf) {
+    use musicdb_lib::{
+        data::queue::RepeatMode,
+        player::{render::PlayerBackendRender, Player},
+    };
+    database.load_playback_state();
+    if database.queue.get_current_song().is_none() {
+        eprintln!("Can't render: the queue is empty.");
+        exit(1);
+    }
+    // the queue has to end at some point, or the output file would keep growing until the disk is full
+    if database.queue.has_infinite_loop() {
+        eprintln!("Can't render: the queue contains a loop which repeats forever.");
+        exit(1);
+    }
+    database.repeat = RepeatMode::Off;
+    let mut player = match PlayerBackendRender::new(&output) {
+        Ok(backend) => Player::new(backend),
+        Err(e) => {
+            eprintln!("Couldn't create output file {output:?}: {e}");
+            exit(1);
+        }
+    };
+    database.playing = true;
+    // `NextSong` pauses once the end of the queue is reached
+    while database.playing && database.queue.get_current_song().is_some() {
+        player.update(&mut database);
+    }
+    if let Err(e) = player.backend.finish() {
+        eprintln!("Couldn't finish writing {output:?}: {e}");
+        exit(1);
+    }
+    eprintln!("Done, saved to {output:?}.");
+}
@@ -0,0 +1,259 @@
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc, Mutex},
+    time::Duration,
+};
+
+use musicdb_lib::{
+    data::{
+        album::Album,
+        artist::Artist,
+        database::{Cover, Database},
+        metadata::{find_cover, SongMetadata, FILE_TAGS},
+        song::Song,
+        AlbumId, ArtistId, DatabaseLocation, GeneralData,
+    },
+    server::{Action, Req},
+};
+use notify::{EventKind, RecursiveMode, Watcher};
+
+/// changes are only handled once nothing happened for this long,
+/// so copying an album doesn't cause one update per file (or multiple per file, if it's big)
+const DEBOUNCE: Duration = Duration::from_secs(2);
+
+/// Starts a thread which watches `lib_dir` and adds, updates, or removes songs
+/// whenever song files are added, changed, or removed, like running filldb for only the changed files.
+/// Renaming or moving a file removes the old song and adds a new one.
+pub fn watch(database: Arc<Mutex<Database>>, lib_dir: PathBuf) {
+    std::thread::spawn(move || {
+        // events contain absolute paths
+        let lib_dir = lib_dir.canonicalize().unwrap_or(lib_dir);
+        let (sender, receiver) = mpsc::channel();
+        let mut watcher = match notify::recommended_watcher(sender) {
+            Ok(v) => v,
+            Err(e) => {
+                eprintln!("[watch] Couldn't watch the lib_dir: {e}");
+                return;
+            }
+        };
+        if let Err(e) = watcher.watch(&lib_dir, RecursiveMode::Recursive) {
+            eprintln!("[watch] Couldn't watch {lib_dir:?}: {e}");
+            return;
+        }
+        eprintln!("[watch] watching {lib_dir:?} for changes");
+        let mut changed = BTreeSet::new();
+        loop {
+            let event = if changed.is_empty() {
+                receiver.recv().map_err(|_| ())
+            } else {
+                match receiver.recv_timeout(DEBOUNCE) {
+                    Ok(v) => Ok(v),
+                    Err(mpsc::RecvTimeoutError::Timeout) => {
+                        update(&database, &lib_dir, std::mem::take(&mut changed));
+                        continue;
+                    }
+                    Err(mpsc::RecvTimeoutError::Disconnected) => Err(()),
+                }
+            };
+            match event {
+                // the server reading song files shouldn't cause updates
+                Ok(Ok(event)) if matches!(event.kind, EventKind::Access(_)) => {}
+                Ok(Ok(event)) => changed.extend(event.paths),
+                Ok(Err(e)) => eprintln!("[watch] {e}"),
+                Err(()) => {
+                    eprintln!("[watch] stopped watching {lib_dir:?}");
+                    return;
+                }
+            }
+        }
+    });
+}
+
+fn update(database: &Mutex<Database>, lib_dir: &Path, changed: BTreeSet<PathBuf>) {
+    // paths relative to lib_dir, because that's what the database uses
+    let changed = changed
+        .iter()
+        .filter_map(|path| Some(path.strip_prefix(lib_dir).ok()?.to_path_buf()))
+        .collect::<BTreeSet<_>>();
+    // read tags before locking the database, because this can be slow
+    let mut files = vec![];
+    for path in &changed {
+        find_song_files(lib_dir, path, &mut files);
+    }
+    let mut songs = files
+        .into_iter()
+        .filter_map(
+            |path| match SongMetadata::read(lib_dir, path.clone(), false) {
+                Ok(v) => Some(v),
+                Err(e) => {
+                    eprintln!("[watch] error reading id3 tag of {path:?}: {e}, skipping");
+                    None
+                }
+            },
+        )
+        .collect::<Vec<_>>();
+    // like filldb, so albums are in the right order
+    songs.sort_by(|a, b| (a.disc, a.track, &a.rel_path).cmp(&(b.disc, b.track, &b.rel_path)));
+
+    let mut db = database.lock().unwrap();
+    let removed = db
+        .songs()
+        .values()
+        .filter(|song| {
+            let path = &song.location.rel_path;
+            changed.iter().any(|c| path.starts_with(c)) && !lib_dir.join(path).is_file()
+        })
+        .map(|song| song.id)
+        .collect::<Vec<_>>();
+    for id in removed {
+        if let Some(song) = db.get_song(&id) {
+            eprintln!("[watch] removing {:?}", song.location.rel_path);
+        }
+        db.apply_action_unchecked_seq(Action::RemoveSong(id), None);
+    }
+    for new in songs {
+        let existing = db
+            .songs()
+            .values()
+            .find(|song| song.location.rel_path == new.rel_path)
+            .cloned();
+        if let Some(mut song) = existing {
+            if song.file_size == new.file_size
+                && song.file_last_modified_unix_timestamp == new.last_modified
+            {
+                continue;
+            }
+            eprintln!("[watch] updating {:?}", new.rel_path);
+            let artist = artist_id(&mut db, &new.artist);
+            song.album = new
+                .album
+                .as_ref()
+                .map(|album| album_id(&mut db, lib_dir, artist, album, &new.rel_path));
+            song.artist = artist;
+            song.title = new.title;
+            song.file_size = new.file_size;
+            song.file_last_modified_unix_timestamp = new.last_modified;
+            song.duration_millis = new.duration_millis;
+            // keep tags added by users
+            song.general
+                .tags
+                .retain(|tag| !FILE_TAGS.iter().any(|t| tag.starts_with(t)));
+            song.general.tags.extend(new.general.tags);
+            db.apply_action_unchecked_seq(Action::ModifySong(song, Req::none()), None);
+        } else {
+            eprintln!("[watch] adding {:?}", new.rel_path);
+            let artist = artist_id(&mut db, &new.artist);
+            let album = new
+                .album
+                .as_ref()
+                .map(|album| album_id(&mut db, lib_dir, artist, album, &new.rel_path));
+            let song = Song::new(
+                DatabaseLocation {
+                    rel_path: new.rel_path,
+                },
+                new.last_modified,
+                new.title,
+                album,
+                artist,
+                vec![],
+                None,
+                new.file_size,
+                new.duration_millis,
+                new.general,
+            );
+            db.apply_action_unchecked_seq(Action::AddSong(song, Req::none()), None);
+        }
+    }
+}
+
+/// the mp3 files at `rel_path`, which may be a file or a directory
+fn find_song_files(lib_dir: &Path, rel_path: &Path, out: &mut Vec<PathBuf>) {
+    let path = lib_dir.join(rel_path);
+    if path.is_dir() {
+        if let Ok(entries) = fs::read_dir(&path) {
+            for entry in entries.filter_map(|e| e.ok()) {
+                find_song_files(lib_dir, &rel_path.join(entry.file_name()), out);
+            }
+        }
+    } else if path.is_file()
+        && path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("mp3"))
+    {
+        out.push(rel_path.to_path_buf());
+    }
+}
+
+/// the artist with this name, which is added if it doesn't exist
+fn artist_id(db: &mut Database, name: &str) -> ArtistId {
+    if let Some(id) = db.find_artist_by_name(name) {
+        return id;
+    }
+    db.apply_action_unchecked_seq(
+        Action::AddArtist(
+            Artist {
+                id: 0,
+                name: name.to_owned(),
+                aliases: vec![],
+                cover: None,
+                albums: vec![],
+                singles: vec![],
+                general: GeneralData::default(),
+            },
+            Req::none(),
+        ),
+        None,
+    );
+    db.find_artist_by_name(name).unwrap()
+}
+
+/// the album with this name, which is added (with the largest image in the song's directory as its cover) if it doesn't exist
+fn album_id(
+    db: &mut Database,
+    lib_dir: &Path,
+    artist: ArtistId,
+    name: &str,
+    song_path: &Path,
+) -> AlbumId {
+    if let Some(id) = db.find_album_by_name(artist, name) {
+        return id;
+    }
+    let cover = song_path
+        .parent()
+        .and_then(|dir| find_cover(lib_dir, dir))
+        .map(|path| {
+            if let Some(id) = db.find_cover_by_path(&path) {
+                return id;
+            }
+            db.apply_action_unchecked_seq(
+                Action::AddCover(
+                    Cover {
+                        location: DatabaseLocation {
+                            rel_path: path.clone(),
+                        },
+                        data: Arc::new(Mutex::new((false, None))),
+                    },
+                    Req::none(),
+                ),
+                None,
+            );
+            db.find_cover_by_path(&path).unwrap()
+        });
+    db.apply_action_unchecked_seq(
+        Action::AddAlbum(
+            Album {
+                id: 0,
+                name: name.to_owned(),
+                artist,
+                cover,
+                songs: vec![],
+                general: GeneralData::default(),
+            },
+            Req::none(),
+        ),
+        None,
+    );
+    db.find_album_by_name(artist, name).unwrap()
+}
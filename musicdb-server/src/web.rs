@@ -1,15 +1,34 @@
+use std::io::{self, Cursor};
 use std::net::SocketAddr;
+use std::ops::Bound;
+use std::pin::Pin;
 use std::sync::{mpsc, Arc, Mutex};
+#[cfg(feature = "json")]
+use std::time::Instant;
 
 use musicdb_lib::data::album::Album;
 use musicdb_lib::data::artist::Artist;
-use musicdb_lib::data::database::Database;
-use musicdb_lib::data::queue::{Queue, QueueContent, QueueFolder};
+use musicdb_lib::data::database::{Database, UpdateEndpoint};
+use musicdb_lib::data::queue::{Queue, QueueContent, QueueFolder, ShuffleWeight};
 use musicdb_lib::data::song::Song;
-use musicdb_lib::data::SongId;
+use musicdb_lib::data::{CoverId, SongId};
+use musicdb_lib::load::ToFromBytes;
 use musicdb_lib::server::{Action, Command, Req};
+use rocket::data::{IoHandler, IoStream};
+use rocket::futures::{SinkExt, StreamExt};
+use rocket::http::{ContentType, Status};
+use rocket::request::{FromRequest, Outcome};
 use rocket::response::content::RawHtml;
-use rocket::{get, routes, Config, State};
+#[cfg(feature = "json")]
+use rocket::response::content::RawJson;
+use rocket::response::{self, Responder};
+use rocket::{catchers, get, post, routes, Config, Request, Response, State};
+#[cfg(feature = "json")]
+use serde::Serialize;
+use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+use tokio_tungstenite::tungstenite::protocol::Role;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
 
 /*
 
@@ -41,6 +60,45 @@ const HTML_END: &'static str = "</body></html>";
 struct Data {
     db: Arc<Mutex<Database>>,
     command_sender: mpsc::Sender<(Command, Option<u64>)>,
+    /// the expected `Authorization` header, if `--web-auth` was used
+    auth: Option<String>,
+}
+
+/// Request guard for everything which changes the server's state.
+/// Fails with `401 Unauthorized` if `--web-auth` was used and the request doesn't have the correct credentials.
+struct Authorized;
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Authorized {
+    type Error = ();
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, ()> {
+        let expected = match req.rocket().state::<Data>() {
+            Some(Data { auth: None, .. }) => return Outcome::Success(Self),
+            Some(Data {
+                auth: Some(expected),
+                ..
+            }) => expected,
+            None => return Outcome::Error((Status::InternalServerError, ())),
+        };
+        if req.headers().get_one("Authorization") == Some(expected.as_str()) {
+            Outcome::Success(Self)
+        } else {
+            Outcome::Error((Status::Unauthorized, ()))
+        }
+    }
+}
+/// asks the browser for the credentials
+#[rocket::catch(401)]
+fn unauthorized() -> Unauthorized {
+    Unauthorized
+}
+struct Unauthorized;
+impl<'r> Responder<'r, 'static> for Unauthorized {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        Response::build()
+            .status(Status::Unauthorized)
+            .raw_header("WWW-Authenticate", "Basic realm=\"musicdb\"")
+            .ok()
+    }
 }
 
 #[get("/")]
@@ -82,6 +140,9 @@ async function performSearch() {
 async function addSong(id) {
     await fetch("/add-song/" + id);
 }
+async function playNext(id) {
+    await fetch("/play-next/" + id);
+}
 </script>"#;
     let script2 = r#"<script>
 const searchDiv = document.getElementById("searchDiv");
@@ -112,7 +173,7 @@ async function runLoop() {
     }
 }
 runLoop();</script>"#;
-    let buttons = "<button onclick=\"fetch('/play')\">play</button><button onclick=\"fetch('/pause')\">pause</button><button onclick=\"fetch('/stop')\">stop</button><button onclick=\"fetch('/skip')\">skip</button><button onclick=\"fetch('/clear-queue')\">clear queue</button>";
+    let buttons = "<button onclick=\"fetch('/play')\">play</button><button onclick=\"fetch('/pause')\">pause</button><button onclick=\"fetch('/stop')\">stop</button><button onclick=\"fetch('/previous')\">previous</button><button onclick=\"fetch('/skip')\">skip</button><button onclick=\"fetch('/mute')\">mute</button><button onclick=\"fetch('/unmute')\">unmute</button><button onclick=\"fetch('/clear-queue')\">clear queue</button>";
     let search = "<input id=\"searchFieldArtist\" placeholder=\"artist\"><input id=\"searchFieldAlbum\" placeholder=\"album\"><input id=\"searchFieldTitle\" placeholder=\"title\">
 <button onclick=\"performSearch()\">search</button><div id=\"searchResultDiv\"></div>";
     let db = data.db.lock().unwrap();
@@ -138,8 +199,24 @@ fn queue_html(data: &State<Data>) -> RawHtml<String> {
 }
 fn gen_now_playing(db: &Database) -> String {
     if let Some(current_song) = db.queue.get_current_song().and_then(|id| db.get_song(id)) {
+        let format = [
+            current_song.codec.clone(),
+            current_song.bitrate.map(|v| format!("{v} kbit/s")),
+            db.current_song_format_string(),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+        let format = if format.is_empty() {
+            String::new()
+        } else {
+            format!(
+                "<small>{}</small>",
+                html_escape::encode_text(&format.join(" · "))
+            )
+        };
         format!(
-            "<h1>Now Playing</h1><h4>{}</h4>",
+            "<h1>Now Playing</h1><h4>{}</h4>{format}",
             html_escape::encode_safe(&current_song.title),
         )
     } else {
@@ -251,11 +328,53 @@ fn gen_queue_html_impl(
             }
             path.pop();
         }
+        QueueContent::Playlist(p) => {
+            str.push_str(&format!("[{}/{}] ", p.index + 1, p.songs.len()));
+            if active_highlight {
+                str.push_str("<b>");
+            }
+            str.push_str(&html_escape::encode_text(&p.name));
+            if active_highlight {
+                str.push_str("</b>");
+            }
+            str.push_str(" <small><small>playlist</small></small>");
+            str.push_str("<ol>");
+            for (i, id) in p.songs.iter().enumerate() {
+                str.push_str("<li>");
+                if !path.is_empty() {
+                    path.push('_');
+                }
+                path.push_str(&format!("{i}"));
+                gen_queue_html_impl(
+                    &QueueContent::Song(*id).into(),
+                    str,
+                    db,
+                    active_highlight && i == p.index,
+                    path,
+                );
+                while !(path.is_empty() || path.ends_with('_')) {
+                    path.pop();
+                }
+                path.pop();
+                str.push_str("</li>");
+            }
+            str.push_str("</ol>");
+        }
+        QueueContent::Conditional(c) => {
+            if active_highlight {
+                str.push_str("<b>");
+            }
+            str.push_str(&html_escape::encode_text(&c.name));
+            if active_highlight {
+                str.push_str("</b>");
+            }
+            str.push_str(" <small><small>conditional</small></small>");
+        }
     }
 }
 
 #[get("/queue-remove/<path>")]
-fn queue_remove(data: &State<Data>, path: &str) {
+fn queue_remove(data: &State<Data>, _auth: Authorized, path: &str) {
     if let Some(path) = path.split('_').map(|v| v.parse().ok()).collect() {
         data.command_sender
             .send((Action::QueueRemove(path).cmd(0xFFu8), None))
@@ -263,7 +382,7 @@ fn queue_remove(data: &State<Data>, path: &str) {
     }
 }
 #[get("/queue-goto/<path>")]
-fn queue_goto(data: &State<Data>, path: &str) {
+fn queue_goto(data: &State<Data>, _auth: Authorized, path: &str) {
     if let Some(path) = path.split('_').map(|v| v.parse().ok()).collect() {
         data.command_sender
             .send((Action::QueueGoto(path).cmd(0xFFu8), None))
@@ -272,31 +391,49 @@ fn queue_goto(data: &State<Data>, path: &str) {
 }
 
 #[get("/play")]
-fn play(data: &State<Data>) {
+fn play(data: &State<Data>, _auth: Authorized) {
     data.command_sender
         .send((Action::Resume.cmd(0xFFu8), None))
         .unwrap();
 }
 #[get("/pause")]
-fn pause(data: &State<Data>) {
+fn pause(data: &State<Data>, _auth: Authorized) {
     data.command_sender
         .send((Action::Pause.cmd(0xFFu8), None))
         .unwrap();
 }
 #[get("/stop")]
-fn stop(data: &State<Data>) {
+fn stop(data: &State<Data>, _auth: Authorized) {
     data.command_sender
         .send((Action::Stop.cmd(0xFFu8), None))
         .unwrap();
 }
 #[get("/skip")]
-fn skip(data: &State<Data>) {
+fn skip(data: &State<Data>, _auth: Authorized) {
     data.command_sender
         .send((Action::NextSong.cmd(0xFFu8), None))
         .unwrap();
 }
+#[get("/previous")]
+fn previous(data: &State<Data>, _auth: Authorized) {
+    data.command_sender
+        .send((Action::PreviousSong.cmd(0xFFu8), None))
+        .unwrap();
+}
+#[get("/mute")]
+fn mute(data: &State<Data>, _auth: Authorized) {
+    data.command_sender
+        .send((Action::SetMuted(true).cmd(0xFFu8), None))
+        .unwrap();
+}
+#[get("/unmute")]
+fn unmute(data: &State<Data>, _auth: Authorized) {
+    data.command_sender
+        .send((Action::SetMuted(false).cmd(0xFFu8), None))
+        .unwrap();
+}
 #[get("/clear-queue")]
-fn clear_queue(data: &State<Data>) {
+fn clear_queue(data: &State<Data>, _auth: Authorized) {
     data.command_sender
         .send((
             Action::QueueUpdate(
@@ -306,6 +443,8 @@ fn clear_queue(data: &State<Data>) {
                     content: vec![],
                     name: String::new(),
                     order: None,
+                    seed: None,
+                    weight: ShuffleWeight::Uniform,
                 })
                 .into(),
                 Req::none(),
@@ -317,7 +456,7 @@ fn clear_queue(data: &State<Data>) {
 }
 
 #[get("/add-song/<id>")]
-fn add_song(data: &State<Data>, id: SongId) {
+fn add_song(data: &State<Data>, _auth: Authorized, id: SongId) {
     data.command_sender
         .send((
             Action::QueueAdd(vec![], vec![QueueContent::Song(id).into()], Req::none()).cmd(0xFFu8),
@@ -326,6 +465,14 @@ fn add_song(data: &State<Data>, id: SongId) {
         .unwrap();
 }
 
+#[get("/play-next/<id>")]
+fn play_next(data: &State<Data>, _auth: Authorized, id: SongId) {
+    send(
+        data,
+        Action::QueueInsertAfterCurrent(vec![QueueContent::Song(id).into()]),
+    );
+}
+
 #[get("/search?<artist>&<album>&<title>&<artist_tags>&<album_tags>&<song_tags>")]
 fn search(
     data: &State<Data>,
@@ -540,23 +687,468 @@ fn search(
         out.push_str(&format!("{}", song.id));
         out.push_str("')\">");
         out.push_str(&song.title);
-        out.push_str("</button><br>");
+        out.push_str("</button><button onclick=\"playNext('");
+        out.push_str(&format!("{}", song.id));
+        out.push_str("')\">next</button><br>");
     }
     RawHtml(out)
 }
 
+#[post("/api/play")]
+fn api_play(data: &State<Data>, _auth: Authorized) {
+    send(data, Action::Resume);
+}
+#[post("/api/pause")]
+fn api_pause(data: &State<Data>, _auth: Authorized) {
+    send(data, Action::Pause);
+}
+#[post("/api/next")]
+fn api_next(data: &State<Data>, _auth: Authorized) {
+    send(data, Action::NextSong);
+}
+#[post("/api/previous")]
+fn api_previous(data: &State<Data>, _auth: Authorized) {
+    send(data, Action::PreviousSong);
+}
+/// `path` is the queue element's path, separated by `_`, like in `/queue-goto/<path>`
+#[post("/api/play-from?<path>")]
+fn api_play_from(data: &State<Data>, path: &str, _auth: Authorized) {
+    if let Some(path) = path.split('_').map(|v| v.parse().ok()).collect() {
+        send(data, Action::PlayFrom(path));
+    }
+}
+#[post("/api/seek?<ms>")]
+fn api_seek(data: &State<Data>, ms: u64, _auth: Authorized) {
+    send(data, Action::SetCurrentSongPosition(ms));
+}
+#[post("/api/volume?<v>")]
+fn api_volume(data: &State<Data>, v: f32, _auth: Authorized) {
+    send(data, Action::SetVolume(v.max(0.0)));
+}
+/// after rewriting the dbfile, for example with `musicdb-filldb --update`
+#[post("/api/reload-database")]
+fn api_reload_database(data: &State<Data>, _auth: Authorized) {
+    send(data, Action::ReloadDatabase);
+}
+fn send(data: &Data, action: Action) {
+    data.command_sender
+        .send((action.cmd(0xFFu8), None))
+        .unwrap();
+}
+
+/// The cover's image. Covers don't change, so browsers are told to cache them forever.
+#[get("/cover/<id>")]
+async fn cover(data: &State<Data>, id: CoverId, if_none_match: IfNoneMatch) -> Option<CoverFile> {
+    let db = Arc::clone(&data.db);
+    // reading the file (or getting it from the remote server) may take a while
+    let (bytes, content_type) = tokio::task::spawn_blocking(move || {
+        let db = db.lock().unwrap();
+        let cover = db.covers().get(&id)?;
+        let content_type = cover
+            .location
+            .rel_path
+            .extension()
+            .and_then(|ext| ContentType::from_extension(&ext.to_string_lossy()))
+            .unwrap_or(ContentType::Binary);
+        let bytes = if let Some(get_con) = &db.remote_server_as_song_file_source {
+            get_con.lock().unwrap().cover_bytes(id).ok()?.ok()?
+        } else {
+            cover.get_bytes_from_file(|p| db.get_path(p), |bytes| bytes.clone())?
+        };
+        Some((bytes, content_type))
+    })
+    .await
+    .ok()??;
+    let etag = {
+        use std::hash::{Hash, Hasher};
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        bytes.hash(&mut hasher);
+        format!("\"{id}-{:x}\"", hasher.finish())
+    };
+    Some(CoverFile {
+        not_modified: if_none_match.0.is_some_and(|v| v == etag),
+        bytes,
+        content_type,
+        etag,
+    })
+}
+struct IfNoneMatch(Option<String>);
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for IfNoneMatch {
+    type Error = ();
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, ()> {
+        Outcome::Success(Self(
+            req.headers().get_one("If-None-Match").map(|v| v.to_owned()),
+        ))
+    }
+}
+struct CoverFile {
+    bytes: Vec<u8>,
+    content_type: ContentType,
+    etag: String,
+    not_modified: bool,
+}
+impl<'r> Responder<'r, 'static> for CoverFile {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        let mut response = Response::build();
+        response
+            .raw_header("ETag", self.etag)
+            .raw_header("Cache-Control", "public, max-age=31536000, immutable");
+        if self.not_modified {
+            response.status(Status::NotModified);
+        } else {
+            response
+                .header(self.content_type)
+                .sized_body(self.bytes.len(), Cursor::new(self.bytes));
+        }
+        response.ok()
+    }
+}
+
+#[cfg(feature = "json")]
+/// The current song (or `null`), the estimated playback position and the decoded format, as JSON
+#[get("/api/now-playing")]
+fn api_now_playing(data: &State<Data>) -> RawJson<String> {
+    let db = data.db.lock().unwrap();
+    let song = db
+        .queue
+        .get_current_song()
+        .and_then(|id| db.get_song(id))
+        .map(|song| JsonSong::new(song, &db));
+    // the web server only knows the position from the last `SetCurrentSongPosition` or `PlaybackPosition`
+    let position_millis = song.as_ref().and(db.current_song_position).map(|(pos, t)| {
+        if db.playing {
+            pos + Instant::now().saturating_duration_since(t).as_millis() as u64
+        } else {
+            pos
+        }
+    });
+    RawJson(
+        serde_json::to_string(&JsonNowPlaying {
+            playing: db.playing,
+            song,
+            position_millis,
+            sample_rate: db.current_song_format.map(|v| v.0),
+            channels: db.current_song_format.map(|v| v.1),
+        })
+        .unwrap(),
+    )
+}
+#[cfg(feature = "json")]
+/// All songs whose title matches the regex `q` (case-insensitive, like the gui's search), as JSON
+#[get("/api/search?<q>")]
+fn api_search(data: &State<Data>, q: &str) -> Result<RawJson<String>, (Status, String)> {
+    let regex = regex::RegexBuilder::new(q)
+        .unicode(true)
+        .case_insensitive(true)
+        .build()
+        .map_err(|e| (Status::BadRequest, e.to_string()))?;
+    let db = data.db.lock().unwrap();
+    let songs: Vec<_> = db
+        .songs()
+        .values()
+        .filter(|song| regex.is_match(&song.title))
+        .map(|song| JsonSong::new(song, &db))
+        .collect();
+    Ok(RawJson(serde_json::to_string(&songs).unwrap()))
+}
+#[cfg(feature = "json")]
+/// Totals for the library (counts, duration, file size) and how many songs have each flag or property, as JSON
+#[get("/api/stats")]
+fn api_stats(data: &State<Data>) -> RawJson<String> {
+    let stats = data.db.lock().unwrap().statistics();
+    RawJson(
+        serde_json::json!({
+            "songs": stats.songs,
+            "albums": stats.albums,
+            "artists": stats.artists,
+            "covers": stats.covers,
+            "duration_millis": stats.duration_millis,
+            "file_size": stats.file_size,
+            "singles": stats.singles,
+            "songs_without_duration": stats.songs_without_duration,
+            "flags": stats.flags,
+            "properties": stats.properties,
+        })
+        .to_string(),
+    )
+}
+
+#[cfg(feature = "json")]
+#[derive(Serialize)]
+struct JsonNowPlaying<'a> {
+    playing: bool,
+    song: Option<JsonSong<'a>>,
+    position_millis: Option<u64>,
+    /// as decoded by the server, only known if it plays audio
+    sample_rate: Option<u32>,
+    channels: Option<u16>,
+}
+#[cfg(feature = "json")]
+#[derive(Serialize)]
+struct JsonSong<'a> {
+    id: SongId,
+    title: &'a str,
+    duration_millis: u64,
+    bitrate: Option<u32>,
+    codec: Option<&'a str>,
+    artist: Option<JsonNamed<'a>>,
+    album: Option<JsonNamed<'a>>,
+    cover: Option<CoverId>,
+}
+#[cfg(feature = "json")]
+#[derive(Serialize)]
+struct JsonNamed<'a> {
+    id: u64,
+    name: &'a str,
+}
+#[cfg(feature = "json")]
+impl<'a> JsonSong<'a> {
+    fn new(song: &'a Song, db: &'a Database) -> Self {
+        let album = song.album.and_then(|id| db.albums().get(&id));
+        Self {
+            id: song.id,
+            title: &song.title,
+            duration_millis: song.duration_millis,
+            bitrate: song.bitrate,
+            codec: song.codec.as_deref(),
+            artist: db.artists().get(&song.artist).map(|artist| JsonNamed {
+                id: artist.id,
+                name: &artist.name,
+            }),
+            album: album.map(|album| JsonNamed {
+                id: album.id,
+                name: &album.name,
+            }),
+            cover: song.cover.or_else(|| album.and_then(|album| album.cover)),
+        }
+    }
+}
+
+/// The song's file. Supports `Range` requests (one range per request), which browsers use for seeking.
+#[get("/song/<id>")]
+async fn song(data: &State<Data>, id: SongId, range: RangeHeader) -> Option<SongFile> {
+    let (cached_data, content_type) = {
+        let db = data.db.lock().unwrap();
+        let song = db.get_song(&id)?;
+        song.cached_data().cache_data_start_thread(&db, song);
+        let content_type = song
+            .location
+            .rel_path
+            .extension()
+            .and_then(|ext| ContentType::from_extension(&ext.to_string_lossy()))
+            .unwrap_or(ContentType::Binary);
+        (song.cached_data().clone(), content_type)
+    };
+    // loads the song from disk or the remote server if it isn't cached already
+    let bytes = tokio::task::spawn_blocking(move || cached_data.cached_data_await())
+        .await
+        .ok()??;
+    Some(SongFile {
+        range: range.0.map(|range| satisfiable_range(range, bytes.len())),
+        bytes,
+        content_type,
+    })
+}
+
+/// the `Range` header, if it contains exactly one range
+struct RangeHeader(Option<(Bound<u64>, Bound<u64>)>);
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for RangeHeader {
+    type Error = ();
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, ()> {
+        let range = req
+            .headers()
+            .get_one("Range")
+            .and_then(|v| headers::HeaderValue::from_str(v).ok())
+            .and_then(|v| {
+                <headers::Range as headers::Header>::decode(&mut std::iter::once(&v)).ok()
+            })
+            .and_then(|range| {
+                let mut ranges = range.iter();
+                match (ranges.next(), ranges.next()) {
+                    (Some(range), None) => Some(range),
+                    // serving the whole file is allowed if we don't want to handle multiple ranges
+                    _ => None,
+                }
+            });
+        Outcome::Success(Self(range))
+    }
+}
+
+/// the (inclusive) range of bytes to send, or `None` if the range can't be satisfied
+fn satisfiable_range(range: (Bound<u64>, Bound<u64>), len: usize) -> Option<(usize, usize)> {
+    let len = len as u64;
+    let (start, end) = match range {
+        (Bound::Included(start), Bound::Included(end)) => (start, end.min(len.saturating_sub(1))),
+        (Bound::Included(start), Bound::Unbounded) => (start, len.saturating_sub(1)),
+        // `bytes=-n` means the last n bytes
+        (Bound::Unbounded, Bound::Included(n)) if n > 0 => {
+            (len.saturating_sub(n), len.saturating_sub(1))
+        }
+        _ => return None,
+    };
+    if start <= end && end < len {
+        Some((start as usize, end as usize))
+    } else {
+        None
+    }
+}
+
+struct SongFile {
+    bytes: Arc<Vec<u8>>,
+    /// `None` if there was no (usable) `Range` header, `Some(None)` if the range can't be satisfied
+    range: Option<Option<(usize, usize)>>,
+    content_type: ContentType,
+}
+/// a part of a song file, which can be sent without copying the data
+struct SongBytes(Arc<Vec<u8>>, usize, usize);
+impl AsRef<[u8]> for SongBytes {
+    fn as_ref(&self) -> &[u8] {
+        &self.0[self.1..self.2]
+    }
+}
+impl<'r> Responder<'r, 'static> for SongFile {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        let len = self.bytes.len();
+        let mut response = Response::build();
+        response
+            .header(self.content_type)
+            .raw_header("Accept-Ranges", "bytes");
+        match self.range {
+            None => response.sized_body(len, Cursor::new(SongBytes(self.bytes, 0, len))),
+            Some(Some((start, end))) => response
+                .status(Status::PartialContent)
+                .raw_header("Content-Range", format!("bytes {start}-{end}/{len}"))
+                .sized_body(
+                    end + 1 - start,
+                    Cursor::new(SongBytes(self.bytes, start, end + 1)),
+                ),
+            Some(None) => response
+                .status(Status::RangeNotSatisfiable)
+                .raw_header("Content-Range", format!("bytes */{len}")),
+        };
+        response.ok()
+    }
+}
+
+/// Like a `main` tcp connection, but over a websocket:
+/// The first (binary) message contains the commands which sync the database,
+/// every following message contains one command (`Command::to_bytes_vec()`).
+/// Commands sent by the browser (one per binary message) are applied like commands from tcp clients.
+#[get("/ws")]
+fn ws(data: &State<Data>, key: WebSocketKey, _auth: Authorized) -> WebSocket {
+    WebSocket {
+        accept: derive_accept_key(key.0.as_bytes()),
+        db: Arc::clone(&data.db),
+        command_sender: data.command_sender.clone(),
+    }
+}
+
+struct WebSocketKey(String);
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for WebSocketKey {
+    type Error = ();
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, ()> {
+        match req.headers().get_one("Sec-WebSocket-Key") {
+            Some(key) if req.headers().get_one("Upgrade").is_some() => {
+                Outcome::Success(Self(key.to_owned()))
+            }
+            _ => Outcome::Error((Status::UpgradeRequired, ())),
+        }
+    }
+}
+
+struct WebSocket {
+    accept: String,
+    db: Arc<Mutex<Database>>,
+    command_sender: mpsc::Sender<(Command, Option<u64>)>,
+}
+impl<'r> Responder<'r, 'static> for WebSocket {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        Response::build()
+            .status(Status::SwitchingProtocols)
+            .raw_header("Sec-WebSocket-Accept", self.accept.clone())
+            .upgrade("websocket", self)
+            .ok()
+    }
+}
+#[rocket::async_trait]
+impl IoHandler for WebSocket {
+    async fn io(self: Pin<Box<Self>>, io: IoStream) -> io::Result<()> {
+        let (mut sink, mut stream) = WebSocketStream::from_raw_socket(io, Role::Server, None)
+            .await
+            .split();
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        // sync database and register the connection, like `handle_one_connection_as_main`
+        let udepid = {
+            let mut db = self.db.lock().unwrap();
+            let mut init = Vec::new();
+            db.init_connection(&mut init)?;
+            _ = sender.send(init);
+            let udepid = db.update_endpoints_id;
+            db.update_endpoints_id += 1;
+            db.update_endpoints.push((
+                udepid,
+                UpdateEndpoint::CustomBytes(Box::new(move |bytes| {
+                    _ = sender.send(bytes.to_vec());
+                })),
+            ));
+            udepid
+        };
+        let writer = tokio::spawn(async move {
+            while let Some(bytes) = receiver.recv().await {
+                if sink.send(Message::Binary(bytes)).await.is_err() {
+                    break;
+                }
+            }
+        });
+        while let Some(Ok(message)) = stream.next().await {
+            match message {
+                Message::Binary(bytes) => match Command::from_bytes(&mut bytes.as_slice()) {
+                    Ok(command) => _ = self.command_sender.send((command, Some(udepid))),
+                    Err(e) => eprintln!("[WARN] Received invalid command via websocket: {e}"),
+                },
+                Message::Close(_) => break,
+                _ => {}
+            }
+        }
+        // the connection was closed, stop sending updates to it
+        self.db
+            .lock()
+            .unwrap()
+            .update_endpoints
+            .retain(|(id, _)| *id != udepid);
+        writer.abort();
+        Ok(())
+    }
+}
+
 pub async fn main(
     db: Arc<Mutex<Database>>,
     command_sender: mpsc::Sender<(Command, Option<u64>)>,
     addr: SocketAddr,
+    auth: Option<String>,
 ) {
-    rocket::build()
+    let auth = auth.map(|auth| {
+        use base64::Engine;
+        format!(
+            "Basic {}",
+            base64::engine::general_purpose::STANDARD.encode(auth)
+        )
+    });
+    let rocket = rocket::build()
         .configure(Config {
             address: addr.ip(),
             port: addr.port(),
             ..Default::default()
         })
-        .manage(Data { db, command_sender })
+        .manage(Data {
+            db,
+            command_sender,
+            auth,
+        })
+        .register("/", catchers![unauthorized])
         .mount(
             "/",
             routes![
@@ -565,16 +1157,31 @@ pub async fn main(
                 pause,
                 stop,
                 skip,
+                previous,
+                mute,
+                unmute,
                 clear_queue,
                 queue_goto,
                 queue_remove,
                 add_song,
+                play_next,
                 search,
                 now_playing_html,
-                queue_html
+                queue_html,
+                song,
+                cover,
+                api_play,
+                api_pause,
+                api_next,
+                api_previous,
+                api_play_from,
+                api_seek,
+                api_volume,
+                api_reload_database,
+                ws
             ],
-        )
-        .launch()
-        .await
-        .unwrap();
+        );
+    #[cfg(feature = "json")]
+    let rocket = rocket.mount("/", routes![api_now_playing, api_search, api_stats]);
+    rocket.launch().await.unwrap();
 }